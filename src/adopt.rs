@@ -0,0 +1,378 @@
+//! Adopt
+//!
+//! Registers binaries hoist did not build itself, such as those installed by
+//! `cargo install`, or any executable on `PATH`, from scripts to downloaded
+//! tools, so the registry indexes every tool in use.
+//!
+//! Adopted binaries are registered where they are installed rather than
+//! copied into the content store: their installer keeps managing them.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Registration, RegistrationSource};
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// A package installed by `cargo install`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledPackage {
+    /// The package name
+    pub name: String,
+    /// The installed version
+    pub version: String,
+    /// The package source, e.g. `registry+https://github.com/rust-lang/crates.io-index`
+    pub source: String,
+    /// The binaries the package installed
+    pub bins: Vec<String>,
+    /// The cargo profile the package was built with, if recorded
+    pub profile: Option<String>,
+    /// The target triple the package was built for, if recorded
+    pub target: Option<String>,
+}
+
+impl InstalledPackage {
+    /// Parses a `<name> <version> (<source>)` package id, as cargo keys its
+    /// install tracking files by.
+    fn from_id(id: &str, bins: Vec<String>) -> Option<Self> {
+        let (name, rest) = id.split_once(' ')?;
+        let (version, source) = rest.split_once(' ')?;
+        let source = source.strip_prefix('(')?.strip_suffix(')')?;
+        Some(Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            source: source.to_string(),
+            bins,
+            profile: None,
+            target: None,
+        })
+    }
+
+    /// The project directory of a package installed with `--path`.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.source
+            .strip_prefix("path+file://")
+            .map(|p| PathBuf::from(p.split(['?', '#']).next().unwrap_or(p)))
+    }
+
+    /// The repository url of a package installed with `--git`.
+    pub fn repository(&self) -> Option<String> {
+        let url = self.source.strip_prefix("git+")?;
+        Some(url.split(['?', '#']).next().unwrap_or(url).to_string())
+    }
+}
+
+/// An install in `.crates2.json`.
+#[derive(Debug, Deserialize)]
+struct Install {
+    #[serde(default)]
+    bins: Vec<String>,
+    profile: Option<String>,
+    target: Option<String>,
+}
+
+/// The `.crates2.json` install tracking file.
+#[derive(Debug, Deserialize)]
+struct Crates2 {
+    #[serde(default)]
+    installs: BTreeMap<String, Install>,
+}
+
+/// The `.crates.toml` install tracking file, written by older cargos and
+/// still kept alongside `.crates2.json`.
+#[derive(Debug, Deserialize)]
+struct CratesToml {
+    #[serde(default)]
+    v1: BTreeMap<String, Vec<String>>,
+}
+
+/// Reads the packages `cargo install` tracks in `cargo_home`, preferring
+/// `.crates2.json` and falling back to `.crates.toml`.
+pub fn installed_packages(cargo_home: &Path) -> Result<Vec<InstalledPackage>> {
+    let crates2 = cargo_home.join(".crates2.json");
+    let crates = cargo_home.join(".crates.toml");
+    let packages: Vec<_> = if crates2.exists() {
+        let tracked: Crates2 = crate::json::from_str(&std::fs::read_to_string(&crates2)?)?;
+        tracked
+            .installs
+            .into_iter()
+            .filter_map(|(id, install)| {
+                Some(InstalledPackage {
+                    profile: install.profile,
+                    target: install.target,
+                    ..InstalledPackage::from_id(&id, install.bins)?
+                })
+            })
+            .collect()
+    } else if crates.exists() {
+        let tracked: CratesToml = toml::from_str(&std::fs::read_to_string(&crates)?)?;
+        tracked
+            .v1
+            .into_iter()
+            .filter_map(|(id, bins)| InstalledPackage::from_id(&id, bins))
+            .collect()
+    } else {
+        anyhow::bail!(
+            "No cargo install tracking files found in {}",
+            cargo_home.display()
+        );
+    };
+    Ok(packages)
+}
+
+/// Returns the registry entries for the binaries of `packages` installed in
+/// `bin_dir`, skipping those no longer on disk.
+pub fn installed_binaries(packages: &[InstalledPackage], bin_dir: &Path) -> Vec<HoistedBinary> {
+    let mut binaries = vec![];
+    for package in packages {
+        let project = package.path().unwrap_or_else(|| bin_dir.to_path_buf());
+        for bin in &package.bins {
+            let location = bin_dir.join(bin);
+            if !location.is_file() {
+                tracing::warn!(
+                    "{} of {} is missing from {}",
+                    bin,
+                    package.name,
+                    bin_dir.display()
+                );
+                continue;
+            }
+            let name = bin
+                .strip_suffix(std::env::consts::EXE_SUFFIX)
+                .unwrap_or(bin);
+            let mut binary = HoistedBinary {
+                version: Some(package.version.clone()),
+                profile: package.profile.clone(),
+                repository: package.repository(),
+                registration: Some(Registration::now(
+                    RegistrationSource::Adopt,
+                    project.clone(),
+                )),
+                ..HoistedBinary::new(name, location)
+            };
+            binary.inspect_linkage();
+            binary.record_size();
+            binaries.push(binary);
+        }
+    }
+    binaries
+}
+
+/// Registers the binaries `cargo install` installed into the cargo home.
+#[instrument(skip(ctx))]
+pub fn adopt_cargo_install(ctx: &HoistContext, quiet: bool) -> Result<()> {
+    let cargo_home = ctx.cargo_home();
+    let packages = installed_packages(&cargo_home)?;
+    let binaries = installed_binaries(&packages, &cargo_home.join("bin"));
+    if binaries.is_empty() {
+        tracing::warn!("No installed binaries found in {}", cargo_home.display());
+        return Ok(());
+    }
+    adopt(ctx, binaries, quiet)
+}
+
+/// Returns the first executable named `name` in the directories of `path`.
+pub fn find_on_path(name: &str, path: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|file| crate::executables::exec_path(file).is_ok())
+}
+
+/// Returns a registry entry for the executable at `location`, named after
+/// its file name and registered from its directory.
+pub fn executable(location: &Path) -> Result<HoistedBinary> {
+    let name = crate::executables::exec_path(location)?;
+    let project = location.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut binary = HoistedBinary {
+        registration: Some(Registration::now(RegistrationSource::Adopt, project)),
+        ..HoistedBinary::new(name, location.to_path_buf())
+    };
+    binary.inspect_linkage();
+    binary.record_size();
+    Ok(binary)
+}
+
+/// Returns the executables in the directories of `path` under `home`,
+/// skipping the system directories and the hoist directory `hoist_dir`.
+/// Of executables with the same name, the one `PATH` resolves to is kept.
+pub fn scan_path(path: &OsStr, home: &Path, hoist_dir: &Path) -> Vec<HoistedBinary> {
+    let mut names = HashSet::new();
+    let mut binaries = vec![];
+    for dir in std::env::split_paths(path) {
+        if !dir.starts_with(home) || dir.starts_with(hoist_dir) {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut files: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        files.sort();
+        for file in files {
+            if let Ok(binary) = executable(&file) {
+                if names.insert(binary.name.clone()) {
+                    binaries.push(binary);
+                }
+            }
+        }
+    }
+    binaries
+}
+
+/// Registers the executables named by `targets`, paths or names looked up
+/// on `PATH`, and with `scan`, every executable on `PATH` under the home
+/// directory. Executables already registered at their location are left
+/// as they are.
+#[instrument(skip(ctx))]
+pub fn adopt_executables(
+    ctx: &HoistContext,
+    targets: Vec<String>,
+    scan: bool,
+    quiet: bool,
+) -> Result<()> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let mut binaries = vec![];
+    for target in targets {
+        let location = match target.contains(std::path::MAIN_SEPARATOR) {
+            true => ctx.resolve(Path::new(&target)),
+            false => find_on_path(&target, &path)
+                .ok_or_else(|| anyhow::anyhow!("Failed to find {} on PATH", target))?,
+        };
+        binaries.push(executable(&location)?);
+    }
+    if scan {
+        binaries.extend(scan_path(&path, &ctx.home, &ctx.hoist_dir()));
+    }
+    let registry = HoistRegistry::load(ctx).unwrap_or_default();
+    binaries.retain(|b| !registry.binaries.contains(b));
+    if binaries.is_empty() {
+        tracing::warn!("No new executables to adopt");
+        return Ok(());
+    }
+    adopt(ctx, binaries, quiet)
+}
+
+/// Registers adopted binaries, printing each unless `quiet` is set.
+pub fn adopt(ctx: &HoistContext, binaries: Vec<HoistedBinary>, quiet: bool) -> Result<()> {
+    HoistRegistry::create_registry_with(ctx, quiet)?;
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    for binary in &binaries {
+        if !quiet {
+            HoistRegistry::print_color("Adopted ", Color::Green, false)?;
+            HoistRegistry::print_color(&binary.display_name(), Color::Magenta, false)?;
+            HoistRegistry::print_color(
+                &format!(" ({})", binary.location.display()),
+                Color::Cyan,
+                true,
+            )?;
+        }
+        registry.insert(binary.clone());
+    }
+    registry.save(ctx)?;
+    #[cfg(feature = "server")]
+    crate::metrics::METRICS.record_registrations(binaries.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adopt_cargo_install() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cargo_home = tempdir.path().join(".cargo");
+        std::fs::create_dir_all(cargo_home.join("bin")).unwrap();
+        assert!(installed_packages(&cargo_home).is_err());
+
+        std::fs::write(
+            cargo_home.join(".crates.toml"),
+            "[v1]\n\"ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)\" = [\"rg\"]\n",
+        )
+        .unwrap();
+        let packages = installed_packages(&cargo_home).unwrap();
+        assert_eq!(packages[0].name, "ripgrep");
+        assert_eq!(packages[0].bins, vec!["rg"]);
+        assert_eq!(packages[0].profile, None);
+
+        std::fs::write(
+            cargo_home.join(".crates2.json"),
+            r#"{"installs": {
+                "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+                    "bins": ["rg"], "profile": "release", "target": "x86_64-unknown-linux-gnu"
+                },
+                "tool 0.1.0 (git+https://github.com/o/tool?branch=main#abc)": {"bins": ["tool", "gone"]},
+                "local 0.2.0 (path+file:///src/local)": {"bins": ["local"]}
+            }}"#,
+        )
+        .unwrap();
+        let packages = installed_packages(&cargo_home).unwrap();
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].path(), Some(PathBuf::from("/src/local")));
+        assert_eq!(
+            packages[2].repository().as_deref(),
+            Some("https://github.com/o/tool")
+        );
+
+        for bin in ["rg", "tool", "local"] {
+            std::fs::write(cargo_home.join("bin").join(bin), "").unwrap();
+        }
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        adopt(
+            &ctx,
+            installed_binaries(&packages, &cargo_home.join("bin")),
+            true,
+        )
+        .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(registry.binaries.len(), 3);
+        let rg = registry.latest("rg").unwrap();
+        assert_eq!(rg.version.as_deref(), Some("14.1.0"));
+        assert_eq!(rg.profile.as_deref(), Some("release"));
+        assert_eq!(rg.location, cargo_home.join("bin/rg"));
+        assert_eq!(
+            registry.latest("local").unwrap().project(),
+            Some(PathBuf::from("/src/local"))
+        );
+    }
+
+    #[test]
+    fn test_adopt_executables() {
+        use std::os::unix::fs::PermissionsExt;
+        let tempdir = tempfile::tempdir().unwrap();
+        let home = tempdir.path().join("home");
+        let outside = tempdir.path().join("usr/bin");
+        let (first, second) = (home.join(".local/bin"), home.join("go/bin"));
+        for dir in [&outside, &first, &second, &home.join(".hoist/bin")] {
+            std::fs::create_dir_all(dir).unwrap();
+            for name in ["tool", "other"] {
+                let file = dir.join(name);
+                std::fs::write(&file, "#!/bin/sh\n").unwrap();
+                std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+            }
+        }
+        std::fs::set_permissions(first.join("other"), std::fs::Permissions::from_mode(0o644))
+            .unwrap();
+        let path =
+            std::env::join_paths([&outside, &first, &second, &home.join(".hoist/bin")]).unwrap();
+
+        assert_eq!(find_on_path("tool", &path), Some(outside.join("tool")));
+        assert_eq!(find_on_path("missing", &path), None);
+        assert!(executable(&first.join("other")).is_err());
+
+        let found: Vec<_> = scan_path(&path, &home, &home.join(".hoist"))
+            .into_iter()
+            .map(|b| b.location)
+            .collect();
+        assert_eq!(found, vec![first.join("tool"), second.join("other")]);
+        let tool = executable(&first.join("tool")).unwrap();
+        assert_eq!(tool.name, "tool");
+        assert_eq!(tool.project(), Some(first.clone()));
+        assert_eq!(tool.size, Some(10));
+    }
+}
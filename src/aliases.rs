@@ -0,0 +1,244 @@
+//! Aliases
+//!
+//! Renaming registered binaries, and the alternative names
+//! `cargo hoist alias` gives them.
+
+use anyhow::Result;
+use std::path::Path;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::{is_valid_name, HoistRegistry};
+
+impl HoistRegistry {
+    /// Renames the builds of `old`, or only those registered from projects
+    /// under `project`, to `new`, returning the renamed entries. `new` must
+    /// not name a registered binary or alias. The aliases of `old` follow it
+    /// if every build was renamed.
+    pub fn rename(
+        &mut self,
+        old: &str,
+        new: &str,
+        project: Option<&Path>,
+    ) -> Result<Vec<HoistedBinary>> {
+        let old = self.resolve_alias(old);
+        if !is_valid_name(new) {
+            anyhow::bail!("Invalid binary name {:?}", new);
+        }
+        if self.aliases.contains_key(new) || self.binaries.iter().any(|b| b.name == new) {
+            anyhow::bail!("{} already names a registered binary or alias", new);
+        }
+        let selected: Vec<_> = self
+            .binaries
+            .iter()
+            .filter(|b| b.name == old)
+            .filter(|b| project.is_none_or(|p| b.project().is_some_and(|bp| bp.starts_with(p))))
+            .cloned()
+            .collect();
+        if selected.is_empty() {
+            anyhow::bail!("Failed to find binary {} in hoist registry", old);
+        }
+        let mut renamed = vec![];
+        for b in selected {
+            self.binaries.remove(&b);
+            let b = HoistedBinary {
+                name: new.to_string(),
+                ..b
+            };
+            self.binaries.insert(b.clone());
+            renamed.push(b);
+        }
+        if !self.binaries.iter().any(|b| b.name == old) {
+            for target in self.aliases.values_mut().filter(|t| **t == old) {
+                *target = new.to_string();
+            }
+        }
+        renamed.sort_by(|a, b| a.location.cmp(&b.location));
+        Ok(renamed)
+    }
+
+    /// Renames a registered binary, see [HoistRegistry::rename]. With
+    /// `store`, the stored copies of the renamed builds are renamed too, so
+    /// they run as `new`.
+    ///
+    /// Later registrations of the same builds register them under their
+    /// original name again.
+    #[instrument(skip(ctx))]
+    pub fn rename_binary(
+        ctx: &HoistContext,
+        old: &str,
+        new: &str,
+        project: Option<&Path>,
+        store: bool,
+        quiet: bool,
+    ) -> Result<()> {
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let _lock = HoistRegistry::lock(ctx)?;
+        let mut registry = HoistRegistry::load(ctx)?;
+        let project = project.map(|p| {
+            let p = ctx.resolve(p);
+            p.canonicalize().unwrap_or(p)
+        });
+        let renamed = registry.rename(old, new, project.as_deref())?;
+        for b in &renamed {
+            let mut b = b.clone();
+            if store {
+                registry.binaries.remove(&b);
+                crate::content::rename(ctx, &mut b, new)?;
+                registry.binaries.insert(b.clone());
+            }
+            if !quiet {
+                HoistRegistry::print_color("Renamed ", Color::Green, false)?;
+                HoistRegistry::print_color(&format!("{} -> {}", old, new), Color::Magenta, false)?;
+                HoistRegistry::print_color(
+                    &format!(" ({})", b.location.display()),
+                    Color::Cyan,
+                    true,
+                )?;
+            }
+        }
+        registry.save(ctx)
+    }
+
+    /// Aliases a registered binary, so that `hoist <alias>` resolves to it.
+    ///
+    /// Aliases are stored by binary name, so they survive re-registration.
+    /// Re-aliasing an existing alias points it at the new binary.
+    #[instrument(skip(ctx))]
+    pub fn alias(ctx: &HoistContext, alias: &str, binary: &str, quiet: bool) -> Result<()> {
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let _lock = HoistRegistry::lock(ctx)?;
+        let mut registry = HoistRegistry::load(ctx)?;
+        let binary = registry.resolve_alias(binary);
+        if !is_valid_name(alias) {
+            anyhow::bail!("Invalid alias {:?}", alias);
+        }
+        if !registry.binaries.iter().any(|b| b.name == binary) {
+            anyhow::bail!("Failed to find binary {} in hoist registry", binary);
+        }
+        if alias == binary || registry.binaries.iter().any(|b| b.name == alias) {
+            anyhow::bail!("{} is already the name of a registered binary", alias);
+        }
+        registry.aliases.insert(alias.to_string(), binary.clone());
+        registry.save(ctx)?;
+        if !quiet {
+            HoistRegistry::print_color(&format!("{} -> ", alias), Color::Blue, false)?;
+            HoistRegistry::print_color(&binary, Color::Magenta, true)?;
+        }
+        Ok(())
+    }
+
+    /// Removes an alias.
+    #[instrument(skip(ctx))]
+    pub fn unalias(ctx: &HoistContext, alias: &str, quiet: bool) -> Result<()> {
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let _lock = HoistRegistry::lock(ctx)?;
+        let mut registry = HoistRegistry::load(ctx)?;
+        if registry.aliases.remove(alias).is_none() {
+            anyhow::bail!("{} is not an alias", alias);
+        }
+        registry.save(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{Registration, RegistrationSource};
+    use crate::hoist::HoistOptions;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+
+    #[test]
+    fn test_rename() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join(".bashrc"), "").unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let mut registry = HoistRegistry::default();
+        for project in ["a", "b"] {
+            let build = tempdir.path().join(project).join("cli");
+            std::fs::create_dir_all(build.parent().unwrap()).unwrap();
+            std::fs::write(&build, project).unwrap();
+            let mut binary = HoistedBinary {
+                registration: Some(Registration::now(
+                    RegistrationSource::Manual,
+                    tempdir.path().join(project),
+                )),
+                tags: vec!["tooling".to_string()],
+                ..HoistedBinary::new("cli", build)
+            };
+            crate::content::store(&ctx, &mut binary).unwrap();
+            registry.insert(binary);
+        }
+        registry.aliases.insert("c".to_string(), "cli".to_string());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        registry.save(&ctx).unwrap();
+
+        let rename = |old: &str, new: &str, project: Option<&str>| {
+            let project = project.map(|p| tempdir.path().join(p));
+            HoistRegistry::rename_binary(&ctx, old, new, project.as_deref(), true, true)
+        };
+        rename("c", "b-cli", Some("b")).unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let renamed = registry.latest("b-cli").unwrap();
+        assert_eq!(renamed.location.file_name().unwrap(), "b-cli");
+        assert_eq!(std::fs::read_to_string(&renamed.location).unwrap(), "b");
+        assert!(renamed.has_tag("tooling"));
+        assert_eq!(registry.aliases["c"], "cli");
+
+        assert!(rename("cli", "b-cli", None).is_err());
+        assert!(rename("cli", "c", None).is_err());
+        assert!(rename("cli", "bad name", None).is_err());
+        assert!(rename("missing", "other", None).is_err());
+        rename("cli", "a-cli", None).unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert!(registry.latest("cli").is_none());
+        assert_eq!(registry.aliases["c"], "a-cli");
+    }
+
+    #[test]
+    fn test_aliases() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_aliases");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        HoistRegistry::alias(&ctx, "b1", "binary1", false).unwrap();
+        assert!(HoistRegistry::alias(&ctx, "b1", "missing", false).is_err());
+        assert!(HoistRegistry::alias(&ctx, "binary2", "binary1", false).is_err());
+        assert!(HoistRegistry::alias(&ctx, "a/b", "binary1", false).is_err());
+
+        // Aliases survive re-registration.
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(registry.resolve_alias("b1"), "binary1");
+        assert_eq!(registry.resolve_alias("b1@0.1.0"), "binary1@0.1.0");
+        assert_eq!(registry.resolve_alias("binary2"), "binary2");
+        assert_eq!(registry.aliases_of("binary1"), vec!["b1"]);
+
+        HoistRegistry::hoist_with(&ctx, vec!["b1".to_string()], HoistOptions::default(), false)
+            .unwrap();
+        assert!(test_tempdir.join("binary1").exists());
+        assert!(!test_tempdir.join("b1").exists());
+
+        HoistRegistry::unalias(&ctx, "b1", false).unwrap();
+        assert!(HoistRegistry::unalias(&ctx, "b1", false).is_err());
+        assert!(HoistRegistry::load(&ctx).unwrap().aliases.is_empty());
+    }
+}
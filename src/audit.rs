@@ -0,0 +1,354 @@
+//! Audit
+//!
+//! Checks the recorded `Cargo.lock` snapshots of registered binaries against
+//! a local checkout of the [RustSec advisory database](https://github.com/rustsec/advisory-db).
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::registry::HoistRegistry;
+use crate::semver::{Version, VersionReq};
+
+/// The upstream advisory database repository.
+pub const ADVISORY_DB_URL: &str = "https://github.com/rustsec/advisory-db";
+
+/// A RustSec security advisory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advisory {
+    /// The advisory id, e.g. `RUSTSEC-2021-0001`
+    pub id: String,
+    /// The affected crate
+    pub package: String,
+    /// The advisory title
+    pub title: String,
+    /// The informational category, e.g. `unmaintained`, for non-vulnerability advisories
+    pub informational: Option<String>,
+    /// The versions containing a fix
+    pub patched: Vec<VersionReq>,
+    /// The versions never affected
+    pub unaffected: Vec<VersionReq>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryMetadata,
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    informational: Option<String>,
+    withdrawn: Option<toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+impl Advisory {
+    /// Parses an advisory markdown file with a fenced toml front matter block.
+    /// Returns [None] for withdrawn advisories.
+    pub fn parse(contents: &str) -> Result<Option<Self>> {
+        let start = contents
+            .find("```toml")
+            .ok_or(anyhow::anyhow!("advisory is missing its toml front matter"))?;
+        let body = &contents[start + "```toml".len()..];
+        let end = body
+            .find("```")
+            .ok_or(anyhow::anyhow!("advisory front matter is not terminated"))?;
+        let file: AdvisoryFile = toml::from_str(&body[..end])?;
+        if file.advisory.withdrawn.is_some() {
+            return Ok(None);
+        }
+        let title = body[end + 3..]
+            .lines()
+            .find_map(|l| l.strip_prefix("# "))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let reqs = |reqs: &[String]| {
+            reqs.iter()
+                .map(|r| VersionReq::parse(r))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Some(Self {
+            id: file.advisory.id,
+            package: file.advisory.package,
+            title,
+            informational: file.advisory.informational,
+            patched: reqs(&file.versions.patched)?,
+            unaffected: reqs(&file.versions.unaffected)?,
+        }))
+    }
+
+    /// Returns if the advisory applies to the given version of its crate.
+    pub fn affects(&self, version: &Version) -> bool {
+        !self
+            .patched
+            .iter()
+            .chain(&self.unaffected)
+            .any(|r| r.matches(version))
+    }
+}
+
+/// A locked package matched by an advisory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding<'a> {
+    /// The affected package
+    pub package: &'a LockedPackage,
+    /// The matching advisory
+    pub advisory: &'a Advisory,
+}
+
+/// The default advisory database checkout, shared with `cargo audit`.
+pub fn default_db(ctx: &HoistContext) -> PathBuf {
+    ctx.cargo_home().join("advisory-db")
+}
+
+/// Clones the advisory database into `db`, or fast-forwards an existing checkout.
+#[instrument]
+pub fn fetch(db: &Path) -> Result<()> {
+    let mut git = std::process::Command::new("git");
+    match db.join(".git").exists() {
+        true => git.arg("-C").arg(db).args(["pull", "--ff-only", "--quiet"]),
+        false => git
+            .args(["clone", "--depth", "1", "--quiet", ADVISORY_DB_URL])
+            .arg(db),
+    };
+    let output = git.output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to fetch the advisory database: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Loads every advisory under the database's `crates/` directory for the given crates.
+#[instrument(skip(crates))]
+pub fn load(db: &Path, crates: &[&str]) -> Result<Vec<Advisory>> {
+    let dir = db.join("crates");
+    if !dir.is_dir() {
+        anyhow::bail!(
+            "No advisory database found at {}, run `cargo hoist audit --fetch` to download it",
+            db.display()
+        );
+    }
+    let mut advisories = vec![];
+    for name in crates {
+        let Ok(entries) = std::fs::read_dir(dir.join(name)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "md") {
+                let contents = std::fs::read_to_string(&path)?;
+                match Advisory::parse(&contents) {
+                    Ok(Some(a)) => advisories.push(a),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Skipping advisory {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+    advisories.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(advisories)
+}
+
+/// Returns the advisories matching any of the locked packages.
+pub fn check<'a>(packages: &[&'a LockedPackage], advisories: &'a [Advisory]) -> Vec<Finding<'a>> {
+    let mut findings = vec![];
+    for package in packages.iter().filter(|p| p.source.is_some()) {
+        let Ok(version) = Version::parse(&package.version) else {
+            continue;
+        };
+        for advisory in advisories
+            .iter()
+            .filter(|a| a.package == package.name && a.affects(&version))
+        {
+            findings.push(Finding { package, advisory });
+        }
+    }
+    findings
+}
+
+/// Audits the lockfile snapshot of every registered binary, or only the
+/// named `binaries`, against the advisory database.
+///
+/// Fails if any binary was built with a vulnerable dependency.
+/// Informational advisories, such as unmaintained crates, are reported as warnings.
+#[instrument(skip(ctx, binaries, db))]
+pub fn audit(
+    ctx: &HoistContext,
+    binaries: Vec<String>,
+    db: Option<PathBuf>,
+    fetch_db: bool,
+    quiet: bool,
+) -> Result<()> {
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let registry = HoistRegistry::load(ctx)?;
+    let db = match db {
+        Some(db) => db,
+        None => default_db(ctx),
+    };
+    if fetch_db {
+        fetch(&db)?;
+    }
+
+    let mut entries: Vec<_> = registry
+        .binaries
+        .iter()
+        .filter(|b| binaries.is_empty() || binaries.contains(&b.name))
+        .collect();
+    entries.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+
+    let mut vulnerable = 0;
+    let mut skipped = 0;
+    for binary in entries {
+        let Some(digest) = binary
+            .registration
+            .as_ref()
+            .and_then(|r| r.lockfile.as_ref())
+        else {
+            skipped += 1;
+            continue;
+        };
+        let lockfile = Lockfile::parse(&crate::lockfile::load(ctx, digest)?)?;
+        let packages = lockfile.reachable(&binary.name);
+        let crates: Vec<_> = packages.iter().map(|p| p.name.as_str()).collect();
+        let advisories = load(&db, &crates)?;
+        let findings = check(&packages, &advisories);
+        if findings.is_empty() {
+            continue;
+        }
+
+        let vulnerabilities = findings
+            .iter()
+            .filter(|f| f.advisory.informational.is_none())
+            .count();
+        if vulnerabilities > 0 {
+            vulnerable += 1;
+        }
+        if quiet {
+            continue;
+        }
+        HoistRegistry::print_color(&format!("{}: ", binary.name), Color::Blue, false)?;
+        HoistRegistry::print_color(&binary.location.display().to_string(), Color::Cyan, true)?;
+        for f in &findings {
+            let (color, label) = match &f.advisory.informational {
+                Some(kind) => (Color::Yellow, format!("warning ({})", kind)),
+                None => (Color::Red, "vulnerable".to_string()),
+            };
+            HoistRegistry::print_color(
+                &format!(
+                    "  {} {} {} {}: {}",
+                    label, f.advisory.id, f.package.name, f.package.version, f.advisory.title
+                ),
+                color,
+                true,
+            )?;
+        }
+        if vulnerabilities > 0 {
+            HoistRegistry::print_color(
+                &format!(
+                    "  update the affected dependencies in its project, then run `cargo hoist upgrade {}`",
+                    binary.name
+                ),
+                Color::White,
+                true,
+            )?;
+        }
+    }
+
+    if skipped > 0 && !quiet {
+        HoistRegistry::print_color(
+            &format!(
+                "{} binaries have no Cargo.lock snapshot, re-register them to audit",
+                skipped
+            ),
+            Color::Yellow,
+            true,
+        )?;
+    }
+    match vulnerable {
+        0 => Ok(()),
+        n => anyhow::bail!("{} binaries were built with vulnerable dependencies", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0001"
+package = "serde"
+date = "2020-01-01"
+
+[versions]
+patched = [">= 1.0.5"]
+unaffected = ["< 1.0.0"]
+```
+
+# Use after free in serde
+
+Details.
+"#;
+
+    #[test]
+    fn test_parse_advisory() {
+        let advisory = Advisory::parse(ADVISORY).unwrap().unwrap();
+        assert_eq!(advisory.id, "RUSTSEC-2020-0001");
+        assert_eq!(advisory.title, "Use after free in serde");
+        assert!(advisory.affects(&Version::parse("1.0.4").unwrap()));
+        assert!(!advisory.affects(&Version::parse("1.0.5").unwrap()));
+        assert!(!advisory.affects(&Version::parse("0.9.0").unwrap()));
+
+        let withdrawn = ADVISORY.replace("date =", "withdrawn = \"2020-02-01\"\ndate =");
+        assert_eq!(Advisory::parse(&withdrawn).unwrap(), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let db = tempdir.path();
+        std::fs::create_dir_all(db.join("crates/serde")).unwrap();
+        std::fs::write(db.join("crates/serde/RUSTSEC-2020-0001.md"), ADVISORY).unwrap();
+        let lockfile = Lockfile::parse(
+            r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["serde"]
+
+[[package]]
+name = "serde"
+version = "1.0.4"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        let packages = lockfile.reachable("app");
+        let advisories = load(db, &["app", "serde"]).unwrap();
+        let findings = check(&packages, &advisories);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].package.name, "serde");
+        assert_eq!(findings[0].advisory.id, "RUSTSEC-2020-0001");
+
+        assert!(load(&db.join("missing"), &["serde"]).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Backup
+//!
+//! Timestamped snapshots of the registry under `~/.hoist/backups/`.
+//!
+//! Snapshots are written as registry TOML whichever store backs the registry,
+//! so `restore` can also move a registry between stores or machines.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use termcolor::Color;
+
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// The number of snapshots kept by default, older ones being rotated out.
+pub const KEEP: usize = 10;
+
+/// The snapshot file name for `at`, e.g. `registry-20231014T093000.123Z.toml`,
+/// which sorts chronologically.
+fn file_name(at: SystemTime) -> String {
+    let millis = at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_millis();
+    let stamp: String = crate::time::to_rfc3339(at)
+        .trim_end_matches('Z')
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    format!("registry-{}.{:03}Z.toml", stamp, millis)
+}
+
+/// Lists the snapshots in the backups directory, oldest first.
+pub fn list(ctx: &HoistContext) -> Result<Vec<PathBuf>> {
+    let dir = ctx.backups_dir();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut backups = std::fs::read_dir(dir)?
+        .map(|e| Ok(e?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    backups.retain(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("registry-") && n.ends_with(".toml"))
+    });
+    backups.sort();
+    Ok(backups)
+}
+
+/// Writes a snapshot of the registry, then removes all but the newest `keep`
+/// snapshots. Returns the snapshot path.
+pub fn backup(ctx: &HoistContext, keep: usize) -> Result<PathBuf> {
+    let registry = HoistRegistry::load(ctx)?;
+    let dir = ctx.backups_dir();
+    std::fs::create_dir_all(&dir)?;
+    let mut at = SystemTime::now();
+    let mut path = dir.join(file_name(at));
+    while path.exists() {
+        at += Duration::from_millis(1);
+        path = dir.join(file_name(at));
+    }
+    std::fs::write(&path, toml::to_string(&registry)?)?;
+    let backups = list(ctx)?;
+    for old in &backups[..backups.len().saturating_sub(keep.max(1))] {
+        tracing::info!("Rotating out backup {}", old.display());
+        std::fs::remove_file(old)?;
+    }
+    Ok(path)
+}
+
+/// Restores the registry from the snapshot at `from`, or the newest snapshot.
+pub fn restore(ctx: &HoistContext, from: Option<&Path>, quiet: bool) -> Result<()> {
+    let path = match from {
+        Some(from) => ctx.resolve(from),
+        None => match list(ctx)?.pop() {
+            Some(latest) => latest,
+            None => anyhow::bail!("No backups found in {}", ctx.backups_dir().display()),
+        },
+    };
+    let registry = crate::migrate::parse(&std::fs::read_to_string(&path)?)?;
+    HoistRegistry::create_registry_with(ctx, quiet)?;
+    let _lock = HoistRegistry::lock(ctx)?;
+    registry.save(ctx)?;
+    if !quiet {
+        HoistRegistry::print_color(
+            &format!("Restored {} binaries from ", registry.binaries.len()),
+            Color::Green,
+            false,
+        )?;
+        HoistRegistry::print_color(&path.display().to_string(), Color::Cyan, true)?;
+    }
+    Ok(())
+}
+
+/// Backs up the registry, or lists the snapshots when `list_only` is set.
+pub fn run(ctx: &HoistContext, list_only: bool, keep: usize, quiet: bool) -> Result<()> {
+    if list_only {
+        for backup in list(ctx)? {
+            println!("{}", backup.display());
+        }
+        return Ok(());
+    }
+    HoistRegistry::create_registry_with(ctx, quiet)?;
+    let path = backup(ctx, keep)?;
+    if !quiet {
+        HoistRegistry::print_color("Backed up the registry to ", Color::Green, false)?;
+        HoistRegistry::print_color(&path.display().to_string(), Color::Cyan, true)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::HoistedBinary;
+
+    #[test]
+    fn test_file_name() {
+        let at = crate::time::from_unix(1_697_275_800) + Duration::from_millis(42);
+        assert_eq!(file_name(at), "registry-20231014T093000.042Z.toml");
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        assert!(restore(&ctx, None, true).is_err());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        registry.save(&ctx).unwrap();
+
+        let first = backup(&ctx, 2).unwrap();
+        HoistRegistry::default().save(&ctx).unwrap();
+        backup(&ctx, 2).unwrap();
+        backup(&ctx, 2).unwrap();
+        assert_eq!(list(&ctx).unwrap().len(), 2);
+        assert!(!first.exists());
+
+        restore(&ctx, None, true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), HoistRegistry::default());
+        let snapshot = tempdir.path().join("snapshot.toml");
+        std::fs::write(&snapshot, toml::to_string(&registry).unwrap()).unwrap();
+        restore(&ctx, Some(Path::new("snapshot.toml")), true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), registry);
+    }
+}
@@ -3,17 +3,334 @@
 //! Core logic for working with hoisted binaries.
 
 use anyhow::Result;
+use indicatif::ProgressBar;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tracing::instrument;
 
+use crate::context::HoistContext;
+use crate::progress::Unit;
+
+/// How [HoistedBinary::hoist_to_dir] places a binary into a directory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyStrategy {
+    /// Copy the registered binary
+    #[default]
+    Copy,
+    /// Hard link the registered binary, which must be on the same filesystem.
+    /// Writing to the hoisted file in place changes the registered binary too
+    Hardlink,
+    /// Clone the registered binary's extents on copy-on-write filesystems,
+    /// e.g. btrfs, XFS or APFS, so the copy shares its disk space until
+    /// either is modified
+    Reflink,
+    /// Symlink to the registered binary, so large binaries cost no disk space
+    /// and follow the stored copy
+    Symlink,
+}
+
+/// What metadata of a binary [HoistedBinary::copy_to_dir_with] keeps on the
+/// copy, beyond its `rwx` permission bits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Preserve {
+    /// Nothing else
+    None,
+    /// The access and modification times
+    #[default]
+    Times,
+    /// The access and modification times and the setuid, setgid and sticky
+    /// bits
+    All,
+}
+
+impl CopyStrategy {
+    /// Returns if the strategy is [CopyStrategy::Copy].
+    pub fn is_copy(&self) -> bool {
+        *self == CopyStrategy::Copy
+    }
+}
+
+impl std::fmt::Display for CopyStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CopyStrategy::Copy => write!(f, "copy"),
+            CopyStrategy::Hardlink => write!(f, "hardlink"),
+            CopyStrategy::Reflink => write!(f, "reflink"),
+            CopyStrategy::Symlink => write!(f, "symlink"),
+        }
+    }
+}
+
+/// The kind of cargo artifact a binary was built as.
+#[derive(
+    Debug, Default, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// A `[[bin]]` target
+    #[default]
+    Bin,
+    /// A test harness
+    Test,
+    /// A bench harness
+    Bench,
+    /// An example
+    Example,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl ArtifactKind {
+    /// Returns if the artifact kind is [ArtifactKind::Bin].
+    pub fn is_bin(&self) -> bool {
+        *self == ArtifactKind::Bin
+    }
+}
+
+/// How a binary came to be registered.
+#[derive(Debug, Default, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationSource {
+    /// Registered by the pre-cargo shell hook
+    Hook,
+    /// Registered by running `cargo hoist register`
+    #[default]
+    Manual,
+    /// Uploaded to a `cargo hoist server`
+    Upload,
+    /// Adopted by `cargo hoist adopt` from another installer
+    Adopt,
+    /// Unpacked from a bundle by `cargo hoist unbundle`
+    Bundle,
+    /// Downloaded from a `cargo hoist server` by `cargo hoist pull`
+    Pull,
+}
+
+impl std::fmt::Display for RegistrationSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationSource::Hook => write!(f, "hook"),
+            RegistrationSource::Manual => write!(f, "manual"),
+            RegistrationSource::Upload => write!(f, "upload"),
+            RegistrationSource::Adopt => write!(f, "adopt"),
+            RegistrationSource::Bundle => write!(f, "bundle"),
+            RegistrationSource::Pull => write!(f, "pull"),
+        }
+    }
+}
+
+/// The provenance of a registered binary.
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Registration {
+    /// How the binary was last registered
+    pub source: RegistrationSource,
+    /// The project directory the binary was registered from
+    pub project: PathBuf,
+    /// When the binary was last registered, in seconds since the unix epoch
+    pub at: u64,
+    /// The sha256 digest of the `Cargo.lock` snapshot the binary was built against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lockfile: Option<String>,
+    /// The git commit the project was checked out at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Whether the project's git working tree had uncommitted changes
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dirty: bool,
+}
+
+impl Registration {
+    /// Creates a registration from `project` stamped with the current time.
+    pub fn now(source: RegistrationSource, project: PathBuf) -> Self {
+        Self {
+            source,
+            project,
+            at: crate::time::unix_now(),
+            lockfile: None,
+            commit: None,
+            dirty: false,
+        }
+    }
+
+    /// Creates a registration as [Registration::now], snapshotting the
+    /// project's `Cargo.lock` into the context's lockfile store and recording
+    /// its git revision.
+    pub fn snapshot(ctx: &HoistContext, source: RegistrationSource, project: PathBuf) -> Self {
+        let lockfile = match crate::lockfile::snapshot(ctx, &project) {
+            Ok(l) => l,
+            Err(e) => {
+                tracing::warn!("Failed to snapshot Cargo.lock: {}", e);
+                None
+            }
+        };
+        let revision = crate::git::revision(&project);
+        Self {
+            lockfile,
+            commit: revision.as_ref().map(|r| r.commit.clone()),
+            dirty: revision.is_some_and(|r| r.dirty),
+            ..Self::now(source, project)
+        }
+    }
+
+    /// The abbreviated git commit the binary was built from, suffixed with
+    /// `-dirty` if the working tree had uncommitted changes.
+    pub fn revision(&self) -> Option<String> {
+        self.commit.as_ref().map(|c| match self.dirty {
+            true => format!("{:.12}-dirty", c),
+            false => format!("{:.12}", c),
+        })
+    }
+}
+
+/// A summary of the differences between an existing file and the binary
+/// about to replace it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeSummary {
+    /// The version of the existing file, as recorded when it was hoisted, and
+    /// of the new binary
+    pub version: (Option<String>, Option<String>),
+    /// The size of the existing file and the new binary
+    pub size: (u64, u64),
+    /// The sha256 digest of the existing file and the new binary
+    pub hash: (String, String),
+    /// The last modification time of the existing file and the new binary
+    pub modified: (Option<std::time::SystemTime>, Option<std::time::SystemTime>),
+}
+
+impl ChangeSummary {
+    /// Returns if the existing file is byte-identical to the new binary.
+    pub fn is_unchanged(&self) -> bool {
+        self.hash.0 == self.hash.1
+    }
+}
+
+impl std::fmt::Display for ChangeSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |t: Option<std::time::SystemTime>| {
+            t.map(crate::time::to_rfc3339)
+                .unwrap_or_else(|| "unknown".to_string())
+        };
+        if self.version.0.is_some() || self.version.1.is_some() {
+            let version = |v: &Option<String>| v.as_deref().unwrap_or("unknown").to_string();
+            writeln!(
+                f,
+                "  version: {} -> {}",
+                version(&self.version.0),
+                version(&self.version.1)
+            )?;
+        }
+        writeln!(
+            f,
+            "  size:    {} -> {}",
+            crate::utils::format_bytes(self.size.0),
+            crate::utils::format_bytes(self.size.1)
+        )?;
+        writeln!(f, "  hash:    {:.12} -> {:.12}", self.hash.0, self.hash.1)?;
+        write!(
+            f,
+            "  built:   {} -> {}",
+            time(self.modified.0),
+            time(self.modified.1)
+        )
+    }
+}
+
 /// Binary Metadata Object
-#[derive(Debug, Default, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+///
+/// A binary is identified by its name and location; the remaining fields are
+/// metadata that is refreshed each time the binary is registered.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct HoistedBinary {
     /// The binary name
     pub name: String,
     /// The binary location
     pub location: PathBuf,
+    /// The path the binary was built at, when `location` is its copy in the
+    /// content store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<PathBuf>,
+    /// The kind of artifact the binary was built as
+    #[serde(default, skip_serializing_if = "ArtifactKind::is_bin")]
+    pub kind: ArtifactKind,
+    /// The version of the package the binary was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The cargo profile the binary was built with, e.g. `release`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// The target triple of a cross-compiled build, e.g. `x86_64-unknown-linux-musl`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triple: Option<String>,
+    /// The cargo features the binary was built with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// The version of rustc the binary was built with, e.g.
+    /// `1.80.0 (051478957 2024-07-21)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc: Option<String>,
+    /// The SPDX license expression of the package the binary was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// The description of the package the binary was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The source repository url of the package the binary was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// The homepage url of the package the binary was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    /// User-assigned tags grouping binaries by purpose, e.g. `tooling`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether the binary is pinned, so `prune`, `gc` and `nuke` keep it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// How long the binary is kept after it was last used, in seconds,
+    /// before it expires, overriding the `ttl` of the user config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+    /// The libc the binary links against, for Linux ELF binaries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub libc: Option<crate::elf::LibcFlavor>,
+    /// The newest glibc symbol version the binary requires, e.g. `2.34`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_glibc: Option<String>,
+    /// The size of the binary in bytes when it was registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// When the binary was last hoisted or executed through hoist, in seconds
+    /// since the unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_hoisted_at: Option<u64>,
+    /// How many times the binary was hoisted
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hoist_count: u64,
+    /// Where and how the binary was registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration: Option<Registration>,
+    /// The other paths byte-identical builds of the binary were registered
+    /// from, collapsed into this entry
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<PathBuf>,
+}
+
+impl PartialEq for HoistedBinary {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.location == other.location
+    }
+}
+
+impl Eq for HoistedBinary {}
+
+impl std::hash::Hash for HoistedBinary {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.location.hash(state);
+    }
 }
 
 impl HoistedBinary {
@@ -23,19 +340,390 @@ impl HoistedBinary {
         Self {
             name: name.into(),
             location,
+            origin: None,
+            kind: ArtifactKind::Bin,
+            version: None,
+            profile: None,
+            triple: None,
+            features: vec![],
+            rustc: None,
+            license: None,
+            description: None,
+            repository: None,
+            homepage: None,
+            tags: vec![],
+            pinned: false,
+            ttl: None,
+            libc: None,
+            min_glibc: None,
+            size: None,
+            last_hoisted_at: None,
+            hoist_count: 0,
+            registration: None,
+            sources: vec![],
+        }
+    }
+
+    /// Returns if the binary is selected by `spec`, a binary name optionally
+    /// followed by `@<version>`, e.g. `hoist@0.1.11`.
+    pub fn matches(&self, spec: &str) -> bool {
+        match spec.split_once('@') {
+            Some((name, version)) => {
+                self.name == name
+                    && self.version.as_deref() == Some(version.trim_start_matches('v'))
+            }
+            None => self.name == spec,
+        }
+    }
+
+    /// The binary name, suffixed with `@<version>` if its version is known.
+    pub fn display_name(&self) -> String {
+        match &self.version {
+            Some(v) => format!("{}@{}", self.name, v),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Records how the build at the binary's location was compiled: the
+    /// features from cargo's fingerprint of it, and the `rustc` version.
+    pub fn record_compilation(&mut self, rustc: Option<&str>) {
+        self.features =
+            crate::fingerprint::features(&self.location, &self.name, self.kind).unwrap_or_default();
+        self.rustc = rustc.map(str::to_string);
+    }
+
+    /// Records the size of the file at the binary's location.
+    pub fn record_size(&mut self) {
+        self.size = std::fs::metadata(&self.location).ok().map(|m| m.len());
+    }
+
+    /// Records the libc linkage read from the binary at its location.
+    pub fn inspect_linkage(&mut self) {
+        let linkage = crate::elf::inspect(&self.location);
+        self.libc = linkage.as_ref().map(|l| l.libc);
+        self.min_glibc = linkage.and_then(|l| l.min_glibc);
+    }
+
+    /// The metadata fields matched by a full-metadata search, besides the name.
+    pub fn search_fields(&self) -> Vec<String> {
+        let mut fields = vec![self.location.display().to_string()];
+        fields.extend(self.license.clone());
+        fields.extend(self.description.clone());
+        fields.extend(self.tags.iter().cloned());
+        fields.extend(self.project().map(|p| p.display().to_string()));
+        fields
+    }
+
+    /// The path the binary was built at, its origin if it was copied into the
+    /// content store and its location otherwise.
+    pub fn built_path(&self) -> &Path {
+        self.origin.as_deref().unwrap_or(&self.location)
+    }
+
+    /// When the binary was registered, in seconds since the unix epoch.
+    pub fn registered_at(&self) -> Option<u64> {
+        self.registration.as_ref().map(|r| r.at)
+    }
+
+    /// When the binary was last hoisted, executed or registered, in seconds
+    /// since the unix epoch.
+    pub fn last_used_at(&self) -> Option<u64> {
+        self.last_hoisted_at.max(self.registered_at())
+    }
+
+    /// When the binary expires, in seconds since the unix epoch: its own
+    /// [HoistedBinary::ttl], or else `default`, after it was last used.
+    /// Pinned binaries and binaries of unknown age never expire.
+    pub fn expires_at(&self, default: Option<std::time::Duration>) -> Option<u64> {
+        if self.pinned {
+            return None;
+        }
+        let ttl = self.ttl.or(default.map(|d| d.as_secs()))?;
+        Some(self.last_used_at()?.saturating_add(ttl))
+    }
+
+    /// Returns if the binary expired by `now`, see [HoistedBinary::expires_at].
+    pub fn is_expired(&self, default: Option<std::time::Duration>, now: u64) -> bool {
+        self.expires_at(default).is_some_and(|at| at <= now)
+    }
+
+    /// Returns if the registered location no longer holds an executable file,
+    /// e.g. after a `cargo clean`.
+    pub fn is_stale(&self) -> bool {
+        crate::executables::exec_path(&self.location).is_err()
+    }
+
+    /// Records the build path and sources of `other`, a byte-identical build,
+    /// as sources of this binary.
+    pub fn add_sources(&mut self, other: &HoistedBinary) {
+        let paths =
+            std::iter::once(other.built_path()).chain(other.sources.iter().map(PathBuf::as_path));
+        for path in paths {
+            if path != self.built_path() && !self.sources.iter().any(|s| s == path) {
+                self.sources.push(path.to_path_buf());
+            }
+        }
+    }
+
+    /// Returns if the binary is tagged with `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Returns if the binary's license expression names the license `id`,
+    /// compared case-insensitively, e.g. `mit` matches `MIT OR Apache-2.0`.
+    pub fn has_license(&self, id: &str) -> bool {
+        self.license.as_deref().is_some_and(|l| {
+            l.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '/')
+                .any(|t| t.eq_ignore_ascii_case(id))
+        })
+    }
+
+    /// The project directory the binary was built in.
+    ///
+    /// Falls back to the parent of the nearest `target` directory for
+    /// binaries registered before provenance was recorded.
+    pub fn project(&self) -> Option<PathBuf> {
+        if let Some(r) = &self.registration {
+            return Some(r.project.clone());
+        }
+        self.built_path()
+            .ancestors()
+            .find(|a| a.file_name().is_some_and(|n| n == "target"))
+            .and_then(|t| t.parent())
+            .map(Path::to_path_buf)
+    }
+
+    /// Returns if the binary was registered or rebuilt at or after `since`.
+    pub fn changed_since(&self, since: std::time::SystemTime) -> bool {
+        let registered = self
+            .registration
+            .as_ref()
+            .map(|r| crate::time::from_unix(r.at));
+        let built = std::fs::metadata(&self.location)
+            .and_then(|m| m.modified())
+            .ok();
+        registered.max(built).is_some_and(|t| t >= since)
+    }
+
+    /// Summarizes what changes if the binary replaces the file of the same name
+    /// in the specified directory, [`dir`]. Returns [None] if no such file exists.
+    ///
+    /// The version of the existing file is read from the hoist manifest of
+    /// [`dir`], so it is only known for files `hoist` placed there.
+    #[instrument]
+    pub fn change_summary(&self, dir: &Path) -> Result<Option<ChangeSummary>> {
+        let existing = dir.join(&self.name);
+        if !existing.is_file() {
+            return Ok(None);
+        }
+        let old_version = crate::hoisted::HoistManifest::load(dir)
+            .ok()
+            .and_then(|m| m.files.into_iter().find(|f| f.name == self.name))
+            .and_then(|f| f.version);
+        let old = std::fs::metadata(&existing)?;
+        let new = std::fs::metadata(&self.location)?;
+        let algo = crate::hash::HashAlgorithm::Sha256;
+        Ok(Some(ChangeSummary {
+            version: (old_version, self.version.clone()),
+            size: (old.len(), new.len()),
+            hash: (
+                crate::hash::hash_file(&existing, algo)?,
+                crate::hash::hash_file(&self.location, algo)?,
+            ),
+            modified: (old.modified().ok(), new.modified().ok()),
+        }))
+    }
+
+    /// Returns if the file of the same name in the specified directory,
+    /// [`dir`], already is what placing the binary with `strategy` produces:
+    /// a symlink to it, a hard link of it, or a file with the same contents.
+    ///
+    /// Files of the same size and modification time are taken to have the
+    /// same contents, which are only hashed otherwise.
+    #[instrument]
+    pub fn is_up_to_date(&self, dir: &Path, strategy: CopyStrategy) -> Result<bool> {
+        let existing = dir.join(&self.name);
+        let Ok(old) = existing.symlink_metadata() else {
+            return Ok(false);
+        };
+        let algo = crate::hash::HashAlgorithm::Sha256;
+        // Compressed copies are always decompressed into the directory.
+        if crate::content::is_compressed(&self.location) {
+            let digest = crate::content::digest(&self.location);
+            return Ok(!old.file_type().is_symlink()
+                && digest == Some(crate::hash::hash_file(&existing, algo)?.as_str()));
+        }
+        if strategy == CopyStrategy::Symlink {
+            return Ok(std::fs::read_link(&existing).is_ok_and(|t| t == self.location));
         }
+        // A copy that lost its executable bits is placed again.
+        use std::os::unix::fs::PermissionsExt;
+        if old.file_type().is_symlink() || old.permissions().mode() & 0o111 == 0 {
+            return Ok(false);
+        }
+        let new = std::fs::metadata(&self.location)?;
+        if strategy == CopyStrategy::Hardlink {
+            use std::os::unix::fs::MetadataExt;
+            return Ok(old.dev() == new.dev() && old.ino() == new.ino());
+        }
+        if old.len() != new.len() {
+            return Ok(false);
+        }
+        if let (Ok(a), Ok(b)) = (old.modified(), new.modified()) {
+            if a == b {
+                return Ok(true);
+            }
+        }
+        Ok(crate::hash::hash_file(&existing, algo)?
+            == crate::hash::hash_file(&self.location, algo)?)
     }
 
-    /// Copies the binary to the specified directory, [`dir`].
+    /// Copies the binary to the specified directory, [`dir`], preserving its
+    /// `rwx` permission bits and modification time.
     #[instrument]
     pub fn copy_to_dir(&self, dir: &Path) -> Result<()> {
+        self.copy_to_dir_with(dir, Preserve::default())
+    }
+
+    /// Copies the binary to the specified directory, [`dir`], showing a
+    /// progress bar for large binaries. The copy gets the `rwx` permission
+    /// bits of the binary, and what else of its metadata `preserve` selects.
+    /// Its setuid, setgid and sticky bits are only kept with [Preserve::All].
+    #[instrument]
+    pub fn copy_to_dir_with(&self, dir: &Path, preserve: Preserve) -> Result<()> {
         let binary_path = dir.join(&self.name);
         tracing::debug!("Copying binary to current directory: {:?}", binary_path);
-        std::fs::copy(&self.location, binary_path)?;
+        // Copying onto a link from an earlier hoist would write through it.
+        remove_existing(&binary_path)?;
+        let meta = std::fs::metadata(&self.location)?;
+        let mut dst = std::fs::File::create(&binary_path)?;
+        if crate::content::is_compressed(&self.location) {
+            crate::content::decompress_to(&self.location, &dst)?;
+        } else {
+            let mut src = std::fs::File::open(&self.location)?;
+            let progress = match meta.len() >= crate::progress::LARGE_FILE {
+                true => {
+                    crate::progress::bar(format!("Copying {}", self.name), meta.len(), Unit::Bytes)
+                }
+                false => ProgressBar::hidden(),
+            };
+            std::io::copy(&mut progress.wrap_read(&mut src), &mut dst)?;
+            progress.finish_and_clear();
+        }
+        if preserve != Preserve::None {
+            // The times are set through the open file, as the restored mode
+            // may not be writable.
+            dst.set_times(
+                std::fs::FileTimes::new()
+                    .set_accessed(meta.accessed()?)
+                    .set_modified(meta.modified()?),
+            )?;
+        }
+        let mut permissions = meta.permissions();
+        if preserve != Preserve::All {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(permissions.mode() & 0o777);
+        }
+        dst.set_permissions(permissions)?;
+        Ok(())
+    }
+
+    /// Links the binary into the specified directory, [`dir`], with a
+    /// [CopyStrategy] other than [CopyStrategy::Copy], replacing any file of
+    /// the same name.
+    #[instrument]
+    pub fn link_to_dir(&self, dir: &Path, strategy: CopyStrategy) -> Result<()> {
+        let link_path = dir.join(&self.name);
+        tracing::debug!("Linking binary into directory: {:?}", link_path);
+        remove_existing(&link_path)?;
+        match strategy {
+            CopyStrategy::Copy => std::fs::copy(&self.location, &link_path).map(|_| ())?,
+            CopyStrategy::Hardlink => std::fs::hard_link(&self.location, &link_path)?,
+            CopyStrategy::Reflink => reflink(&self.location, &link_path)?,
+            CopyStrategy::Symlink => std::os::unix::fs::symlink(&self.location, &link_path)?,
+        }
+        Ok(())
+    }
+
+    /// Places the binary into the specified directory, [`dir`], with the
+    /// given [CopyStrategy], returning the strategy used. Copies keep the
+    /// metadata of the binary that `preserve` selects.
+    ///
+    /// Falls back to copying the binary where the link can't be created:
+    /// hard links across filesystems and reflinks on filesystems without
+    /// copy-on-write. Compressed copies in the content store are always
+    /// decompressed into the directory.
+    ///
+    /// Placed files that aren't executable get their executable bits back,
+    /// see [crate::executables::ensure_executable].
+    #[instrument]
+    pub fn hoist_to_dir(
+        &self,
+        dir: &Path,
+        strategy: CopyStrategy,
+        preserve: Preserve,
+    ) -> Result<CopyStrategy> {
+        let linked = !strategy.is_copy()
+            && !crate::content::is_compressed(&self.location)
+            && match self.link_to_dir(dir, strategy) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::debug!("Failed to {} {}, copying it: {}", strategy, self.name, e);
+                    false
+                }
+            };
+        let used = match linked {
+            true => strategy,
+            false => {
+                self.copy_to_dir_with(dir, preserve)?;
+                CopyStrategy::Copy
+            }
+        };
+        // A symlink runs with the mode of the registered binary.
+        if used != CopyStrategy::Symlink
+            && crate::executables::ensure_executable(&dir.join(&self.name))?
+        {
+            tracing::warn!("Restored the executable bits of {}", self.name);
+        }
+        Ok(used)
+    }
+
+    /// Writes a shim script to the specified directory, [`dir`], that runs the
+    /// binary through `cargo hoist exec`.
+    ///
+    /// Running through the shim lets cargo-hoist notice when a newer build of
+    /// the binary has been registered.
+    #[instrument]
+    pub fn shim_to_dir(&self, dir: &Path) -> Result<()> {
+        use std::os::unix::fs::OpenOptionsExt;
+        let shim_path = dir.join(&self.name);
+        tracing::debug!("Writing shim to directory: {:?}", shim_path);
+        let shim = format!(
+            "#!/bin/sh\n# Generated by cargo-hoist.\nexec \"${{CARGO_HOIST:-cargo-hoist}}\" hoist exec --from {} {} -- \"$@\"\n",
+            crate::session::shell_quote(&self.location.display().to_string()),
+            crate::session::shell_quote(&self.name),
+        );
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(shim_path)?;
+        std::io::Write::write_all(&mut file, shim.as_bytes())?;
         Ok(())
     }
 
+    /// Writes a shim script for the binary to the current directory.
+    #[deprecated(note = "depends on the process working directory, use `shim_to_dir`")]
+    #[instrument]
+    pub fn shim_to_current_dir(&self) -> Result<()> {
+        let current_dir = std::env::current_dir()?;
+        self.shim_to_dir(&current_dir)
+    }
+
     /// Copies the binary to the current directory.
+    #[deprecated(note = "depends on the process working directory, use `copy_to_dir`")]
     #[instrument]
     pub fn copy_to_current_dir(&self) -> Result<()> {
         let current_dir = std::env::current_dir()?;
@@ -43,6 +731,53 @@ impl HoistedBinary {
     }
 }
 
+/// Removes the file or link at `path`, if any.
+fn remove_existing(path: &Path) -> std::io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(_) => std::fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Clones the extents of `src` into a new file at `dst` with the `FICLONE`
+/// ioctl, which fails on filesystems without copy-on-write.
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let from = std::fs::File::open(src)?;
+    let to = std::fs::File::create(dst)?;
+    if unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) } != 0 {
+        let err = std::io::Error::last_os_error();
+        drop(to);
+        let _ = std::fs::remove_file(dst);
+        return Err(err);
+    }
+    to.set_permissions(from.metadata()?.permissions())
+}
+
+/// Clones `src` to `dst` with `clonefile`, which fails outside APFS.
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dst: &Path) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let cstr = |p: &Path| {
+        std::ffi::CString::new(p.as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+    };
+    let (src, dst) = (cstr(src)?, cstr(dst)?);
+    match unsafe { libc::clonefile(src.as_ptr(), dst.as_ptr(), 0) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dst: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reflinks are not supported on this platform",
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,10 +788,10 @@ mod tests {
     fn setup_test(tempdir: &TempDir, t: &str) -> PathBuf {
         let test_dir = tempdir.path().join(t);
         std::fs::create_dir(&test_dir).unwrap();
-        std::env::set_current_dir(&test_dir).unwrap();
         test_dir
     }
 
+    #[allow(clippy::suspicious_open_options, clippy::vec_init_then_push)]
     fn create_binaries(p: &Path) -> Vec<HoistedBinary> {
         let target_dir = p.join("target/release/");
         std::fs::create_dir_all(&target_dir).unwrap();
@@ -83,11 +818,10 @@ mod tests {
     }
 
     #[test]
-    #[serial]
     fn test_copy_to_dir() {
         let tempdir = tempfile::tempdir().unwrap();
         let test_dir = setup_test(&tempdir, "test_copy_to_dir");
-        let dest = std::env::current_dir().unwrap().join("dest");
+        let dest = test_dir.join("dest");
         std::fs::create_dir_all(&dest).unwrap();
         let bins = create_binaries(&test_dir);
         for b in bins {
@@ -97,16 +831,198 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_copy_preserves_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_copy_preserves_metadata");
+        let dest = test_dir.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let b = create_binaries(&test_dir).remove(0);
+        let day_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(86400);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&b.location)
+            .unwrap();
+        file.set_modified(day_ago).unwrap();
+        file.set_permissions(std::fs::Permissions::from_mode(0o4550))
+            .unwrap();
+
+        b.copy_to_dir(&dest).unwrap();
+        let copied = std::fs::metadata(dest.join(&b.name)).unwrap();
+        assert_eq!(copied.permissions().mode() & 0o7777, 0o550);
+        assert_eq!(copied.modified().unwrap(), day_ago);
+
+        b.copy_to_dir_with(&dest, Preserve::All).unwrap();
+        let copied = std::fs::metadata(dest.join(&b.name)).unwrap();
+        assert_eq!(copied.permissions().mode() & 0o7777, 0o4550);
+        assert_eq!(copied.modified().unwrap(), day_ago);
+
+        b.copy_to_dir_with(&dest, Preserve::None).unwrap();
+        let copied = std::fs::metadata(dest.join(&b.name)).unwrap();
+        assert_eq!(copied.permissions().mode() & 0o7777, 0o550);
+        assert_ne!(copied.modified().unwrap(), day_ago);
+    }
+
+    #[test]
+    fn test_hoist_to_dir() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_hoist_to_dir");
+        let dest = test_dir.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let b = create_binaries(&test_dir).remove(0);
+        std::fs::write(&b.location, "registered").unwrap();
+        let hoisted = dest.join(&b.name);
+
+        let used = b
+            .hoist_to_dir(&dest, CopyStrategy::Symlink, Preserve::Times)
+            .unwrap();
+        assert_eq!(used, CopyStrategy::Symlink);
+        assert_eq!(std::fs::read_link(&hoisted).unwrap(), b.location);
+        assert_eq!(
+            b.hoist_to_dir(&dest, CopyStrategy::Symlink, Preserve::Times)
+                .unwrap(),
+            CopyStrategy::Symlink
+        );
+
+        // Copying over the symlink replaces it rather than writing through it.
+        assert_eq!(
+            b.hoist_to_dir(&dest, CopyStrategy::Copy, Preserve::Times)
+                .unwrap(),
+            CopyStrategy::Copy
+        );
+        assert!(!hoisted.is_symlink());
+        std::fs::write(&hoisted, "edited").unwrap();
+        assert_eq!(std::fs::read_to_string(&b.location).unwrap(), "registered");
+
+        use std::os::unix::fs::MetadataExt;
+        let inode = |p: &Path| std::fs::metadata(p).unwrap().ino();
+        assert_eq!(
+            b.hoist_to_dir(&dest, CopyStrategy::Hardlink, Preserve::Times)
+                .unwrap(),
+            CopyStrategy::Hardlink
+        );
+        assert_eq!(inode(&hoisted), inode(&b.location));
+        b.copy_to_dir(&dest).unwrap();
+        assert_ne!(inode(&hoisted), inode(&b.location));
+
+        // Copies get their executable bits back.
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&b.location, std::fs::Permissions::from_mode(0o644)).unwrap();
+        b.hoist_to_dir(&dest, CopyStrategy::Copy, Preserve::Times)
+            .unwrap();
+        let mode = std::fs::metadata(&hoisted).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        std::fs::set_permissions(&b.location, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Reflinks fall back to a copy on filesystems without copy-on-write.
+        let used = b
+            .hoist_to_dir(&dest, CopyStrategy::Reflink, Preserve::Times)
+            .unwrap();
+        assert!(matches!(used, CopyStrategy::Reflink | CopyStrategy::Copy));
+        assert_eq!(std::fs::read_to_string(&hoisted).unwrap(), "registered");
+        assert_ne!(inode(&hoisted), inode(&b.location));
+    }
+
+    #[test]
+    fn test_is_up_to_date() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_is_up_to_date");
+        let dest = test_dir.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let b = create_binaries(&test_dir).remove(0);
+        std::fs::write(&b.location, "registered").unwrap();
+        assert!(!b.is_up_to_date(&dest, CopyStrategy::Copy).unwrap());
+
+        b.copy_to_dir(&dest).unwrap();
+        assert!(b.is_up_to_date(&dest, CopyStrategy::Copy).unwrap());
+        assert!(!b.is_up_to_date(&dest, CopyStrategy::Symlink).unwrap());
+        assert!(!b.is_up_to_date(&dest, CopyStrategy::Hardlink).unwrap());
+        std::fs::write(dest.join(&b.name), "edited!!!!").unwrap();
+        assert!(!b.is_up_to_date(&dest, CopyStrategy::Copy).unwrap());
+
+        b.link_to_dir(&dest, CopyStrategy::Symlink).unwrap();
+        assert!(b.is_up_to_date(&dest, CopyStrategy::Symlink).unwrap());
+        assert!(!b.is_up_to_date(&dest, CopyStrategy::Copy).unwrap());
+        b.link_to_dir(&dest, CopyStrategy::Hardlink).unwrap();
+        assert!(b.is_up_to_date(&dest, CopyStrategy::Hardlink).unwrap());
+    }
+
+    #[test]
+    fn test_change_summary() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_change_summary");
+        let bins = create_binaries(&test_dir);
+        let b = &bins[0];
+        assert!(b.change_summary(&test_dir).unwrap().is_none());
+
+        b.copy_to_dir(&test_dir).unwrap();
+        let summary = b.change_summary(&test_dir).unwrap().unwrap();
+        assert!(summary.is_unchanged());
+
+        std::fs::write(&b.location, b"rebuilt").unwrap();
+        let summary = b.change_summary(&test_dir).unwrap().unwrap();
+        assert!(!summary.is_unchanged());
+        assert_eq!(summary.size, (0, 7));
+        assert!(summary.to_string().contains("size:    0 B -> 7 B"));
+        assert!(!summary.to_string().contains("version:"));
+
+        let old = HoistedBinary {
+            version: Some("1.0.0".to_string()),
+            ..b.clone()
+        };
+        crate::hoisted::HoistManifest::record_in(
+            &test_dir,
+            vec![crate::hoisted::HoistedFile::new(&b.name, &old)],
+        );
+        let new = HoistedBinary {
+            version: Some("1.1.0".to_string()),
+            ..b.clone()
+        };
+        let summary = new.change_summary(&test_dir).unwrap().unwrap();
+        assert_eq!(
+            summary.version,
+            (Some("1.0.0".to_string()), Some("1.1.0".to_string()))
+        );
+        assert!(summary.to_string().contains("version: 1.0.0 -> 1.1.0"));
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_changed_since");
+        let bins = create_binaries(&test_dir);
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&bins[0].location)
+            .unwrap()
+            .set_modified(hour_ago - std::time::Duration::from_secs(3600))
+            .unwrap();
+        assert!(!bins[0].changed_since(hour_ago));
+        assert!(bins[1].changed_since(hour_ago));
+        let registered = HoistedBinary {
+            registration: Some(Registration::now(RegistrationSource::Hook, test_dir)),
+            ..bins[0].clone()
+        };
+        assert!(registered.changed_since(hour_ago));
+        assert!(!HoistedBinary::new("missing", "/missing".into()).changed_since(hour_ago));
+    }
+
     #[test]
     #[serial]
+    #[allow(deprecated)]
     fn test_copy_to_current_dir() {
+        let original_dir = std::env::current_dir().unwrap();
         let tempdir = tempfile::tempdir().unwrap();
         let test_dir = setup_test(&tempdir, "test_copy_to_current_dir");
+        std::env::set_current_dir(&test_dir).unwrap();
         let bins = create_binaries(&test_dir);
         for b in bins {
             assert!(!test_dir.join(&b.name).exists());
             b.copy_to_current_dir().unwrap();
             assert!(test_dir.join(&b.name).exists());
         }
+        std::env::set_current_dir(original_dir).unwrap();
     }
 }
@@ -0,0 +1,253 @@
+//! Bundles
+//!
+//! `cargo hoist bundle` packs registered binaries, with their registry
+//! entries, into a tar archive, e.g. `tools.tar.zst`, so locally built tools
+//! can be shipped to a teammate or an air-gapped machine.
+//! `cargo hoist unbundle` copies them into the content store there and
+//! registers them.
+//!
+//! A bundle holds each binary at `<sha256>/<name>`, as the content store
+//! does, next to a `bundle.toml` listing their registry entries. Archives
+//! are written and read with the `tar` command, which picks the compression
+//! from the archive's extension.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Preserve, Registration, RegistrationSource};
+use crate::context::HoistContext;
+use crate::export::ExportFormat;
+use crate::hash::HashAlgorithm;
+use crate::registry::HoistRegistry;
+
+/// The name of the file listing the bundled registry entries.
+const INDEX_FILE: &str = "bundle.toml";
+
+/// Runs `tar` with `args`.
+fn tar(args: &[&std::ffi::OsStr]) -> Result<()> {
+    let status = Command::new("tar")
+        .args(args)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to run tar: {}", e))?;
+    if !status.success() {
+        anyhow::bail!("tar exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Runs `f` with a new staging directory, removing it afterwards.
+fn with_staging<T>(f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let dir = std::env::temp_dir().join(format!("cargo-hoist-bundle-{}", std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    std::fs::create_dir_all(&dir)?;
+    let res = f(&dir);
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        tracing::warn!("Failed to remove {}: {}", dir.display(), e);
+    }
+    res
+}
+
+/// Packs the latest registered build of each named binary, glob pattern or
+/// `name@version` spec, along with its registry entry, into the archive at
+/// `output`.
+#[instrument(skip(ctx, names))]
+pub fn bundle(ctx: &HoistContext, names: Vec<String>, output: &Path, quiet: bool) -> Result<()> {
+    if names.is_empty() {
+        anyhow::bail!("Name the binaries to bundle");
+    }
+    let output = ctx.resolve(output);
+    let registry = HoistRegistry::load(ctx)?;
+    let mut selected: Vec<HoistedBinary> = Vec::with_capacity(names.len());
+    for spec in registry.expand_globs(&names)? {
+        let latest = registry
+            .binaries
+            .iter()
+            .filter(|b| b.matches(&spec) && b.location.exists())
+            .max_by_key(|b| crate::registry::recency(b))
+            .ok_or(anyhow::anyhow!("{} is not in the hoist registry", spec))?;
+        if !selected.contains(latest) {
+            selected.push(latest.clone());
+        }
+    }
+
+    with_staging(|staging| {
+        let mut index = HoistRegistry::default();
+        for b in &selected {
+            let digest = match crate::content::is_compressed(&b.location) {
+                true => crate::content::digest(&b.location).map(str::to_string),
+                false => None,
+            };
+            let digest = match digest {
+                Some(digest) => digest,
+                None => crate::hash::hash_file_mmap(&b.location, HashAlgorithm::Sha256)?,
+            };
+            let dir = staging.join(&digest);
+            std::fs::create_dir_all(&dir)?;
+            b.copy_to_dir_with(&dir, Preserve::Times)?;
+            index.binaries.insert(HoistedBinary {
+                location: Path::new(&digest).join(&b.name),
+                hoist_count: 0,
+                last_hoisted_at: None,
+                ..b.clone()
+            });
+        }
+        let index = crate::export::portable(&index, &ctx.home);
+        std::fs::write(
+            staging.join(INDEX_FILE),
+            crate::export::to_string(&index, ExportFormat::Toml)?,
+        )?;
+        tar(&[
+            "-a".as_ref(),
+            "-cf".as_ref(),
+            output.as_os_str(),
+            "-C".as_ref(),
+            staging.as_os_str(),
+            ".".as_ref(),
+        ])
+    })?;
+    if !quiet {
+        HoistRegistry::print_color(
+            &format!("Bundled {} binaries into ", selected.len()),
+            Color::Green,
+            false,
+        )?;
+        HoistRegistry::print_color(&output.display().to_string(), Color::Cyan, true)?;
+    }
+    Ok(())
+}
+
+/// Unpacks the archive at `path`, copying its binaries into the content
+/// store and registering them.
+///
+/// Fails, registering nothing, if a binary doesn't match the digest it is
+/// bundled under.
+#[instrument(skip(ctx))]
+pub fn unbundle(ctx: &HoistContext, path: &Path, quiet: bool) -> Result<()> {
+    let path = ctx.resolve(path);
+    if !path.is_file() {
+        anyhow::bail!("{} does not exist", path.display());
+    }
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let binaries = with_staging(|staging| {
+        tar(&[
+            "-xf".as_ref(),
+            path.as_os_str(),
+            "-C".as_ref(),
+            staging.as_os_str(),
+        ])?;
+        let index = staging.join(INDEX_FILE);
+        if !index.is_file() {
+            anyhow::bail!("{} is not a hoist bundle", path.display());
+        }
+        let contents = std::fs::read_to_string(&index)?;
+        let index = crate::export::from_str(&contents, ExportFormat::Toml)?;
+        let index = crate::export::localize(&index, &ctx.home);
+
+        let mut staged = Vec::with_capacity(index.binaries.len());
+        for b in index.binaries {
+            let mut parts = b.location.components();
+            let (Some(digest), Some(name), None) = (parts.next(), parts.next(), parts.next())
+            else {
+                anyhow::bail!("Invalid bundled location {}", b.location.display());
+            };
+            let (digest, name) = (digest.as_os_str(), name.as_os_str());
+            let file = staging.join(digest).join(name);
+            if name != b.name.as_str()
+                || *digest != *crate::hash::hash_file_mmap(&file, HashAlgorithm::Sha256)?
+            {
+                anyhow::bail!("{} does not match the digest it is bundled under", b.name);
+            }
+            staged.push((PathBuf::from(digest), file, b));
+        }
+
+        let mut binaries = Vec::with_capacity(staged.len());
+        for (digest, file, b) in staged {
+            let dir = crate::content::dir(ctx).join(digest);
+            std::fs::create_dir_all(&dir)?;
+            let location = dir.join(&b.name);
+            if !location.exists() {
+                HoistedBinary::new(b.name.clone(), file).copy_to_dir_with(&dir, Preserve::Times)?;
+            }
+            binaries.push(HoistedBinary {
+                location,
+                registration: Some(Registration::now(RegistrationSource::Bundle, path.clone())),
+                ..b
+            });
+        }
+        Ok(binaries)
+    })?;
+
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    for b in &binaries {
+        registry.insert(b.clone());
+    }
+    registry.save(ctx)?;
+    #[cfg(feature = "server")]
+    crate::metrics::METRICS.record_registrations(binaries.len());
+    if !quiet {
+        for b in &binaries {
+            HoistRegistry::print_color("Registered ", Color::Green, false)?;
+            HoistRegistry::print_color(&b.display_name(), Color::Magenta, true)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_and_unbundle() {
+        if Command::new("tar").arg("--version").output().is_err() {
+            return;
+        }
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path().join("a"), tempdir.path());
+        HoistRegistry::create_dir_with(&ctx, true).unwrap();
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let build = tempdir.path().join("a/project/target/release/tool");
+        std::fs::create_dir_all(build.parent().unwrap()).unwrap();
+        std::fs::write(&build, "#!/bin/sh\necho tool\n").unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.binaries.insert(HoistedBinary {
+            version: Some("1.0.0".to_string()),
+            hoist_count: 3,
+            ..HoistedBinary::new("tool", build)
+        });
+        registry.save(&ctx).unwrap();
+
+        let archive = tempdir.path().join("tools.tar");
+        assert!(bundle(&ctx, vec!["missing".to_string()], &archive, true).is_err());
+        bundle(&ctx, vec!["tool".to_string()], &archive, true).unwrap();
+
+        let other = HoistContext::new(tempdir.path().join("b"), tempdir.path());
+        std::fs::create_dir_all(other.hoist_dir()).unwrap();
+        std::fs::write(other.hook_identifier(), "").unwrap();
+        unbundle(&other, &archive, true).unwrap();
+        let registry = HoistRegistry::load(&other).unwrap();
+        let b = registry.binaries.iter().next().unwrap();
+        assert_eq!(b.name, "tool");
+        assert_eq!(b.version.as_deref(), Some("1.0.0"));
+        assert_eq!(b.hoist_count, 0);
+        assert!(b.location.starts_with(crate::content::dir(&other)));
+        assert_eq!(
+            std::fs::read_to_string(&b.location).unwrap(),
+            "#!/bin/sh\necho tool\n"
+        );
+        assert_eq!(
+            b.registration.as_ref().unwrap().source,
+            RegistrationSource::Bundle
+        );
+
+        let bogus = tempdir.path().join("bogus.tar");
+        std::fs::write(&bogus, "").unwrap();
+        assert!(unbundle(&other, &bogus, true).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Checksums
+//!
+//! Printing and verifying the checksums of registered binaries with
+//! `cargo hoist checksum`.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::registry::HoistRegistry;
+
+impl HoistRegistry {
+    /// Prints `hash  name  path` checksum lines for the given registered binaries,
+    /// or every registered binary if none are provided.
+    ///
+    /// When a `check` file is provided, each of its checksum lines is instead
+    /// verified against the current digest of the listed path.
+    #[instrument(skip(binaries))]
+    pub fn checksum(
+        ctx: &HoistContext,
+        binaries: Vec<String>,
+        algo: HashAlgorithm,
+        check: Option<PathBuf>,
+    ) -> Result<()> {
+        HoistRegistry::setup_with(ctx, false)?;
+        if let Some(check) = check {
+            return HoistRegistry::check_checksums(ctx, &check, &binaries, algo);
+        }
+
+        let registry = HoistRegistry::load(ctx)?;
+        if let Some(missing) = binaries
+            .iter()
+            .find(|name| !registry.binaries.iter().any(|b| &b.name == *name))
+        {
+            anyhow::bail!("Failed to find binary {} in hoist registry", missing);
+        }
+        let mut selected = registry
+            .binaries
+            .iter()
+            .filter(|b| binaries.is_empty() || binaries.contains(&b.name))
+            .collect::<Vec<_>>();
+        selected.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        let digests = crate::parallel::try_map(selected, |b| {
+            crate::content::hash_file(ctx, &b.location, algo).map(|d| (d, b))
+        })?;
+        for (digest, b) in digests {
+            println!("{}  {}  {}", digest, b.name, b.location.display());
+        }
+        Ok(())
+    }
+
+    /// Verifies `hash  name  path` checksum lines from the `check` file.
+    fn check_checksums(
+        ctx: &HoistContext,
+        check: &Path,
+        binaries: &[String],
+        algo: HashAlgorithm,
+    ) -> Result<()> {
+        let contents = std::fs::read_to_string(ctx.resolve(check))?;
+        let mut lines = vec![];
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let mut parts = line.splitn(3, "  ");
+            let (Some(expected), Some(name), Some(path)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                anyhow::bail!("Malformed checksum line: {}", line);
+            };
+            if binaries.is_empty() || binaries.iter().any(|b| b == name) {
+                lines.push((expected.trim(), name, path));
+            }
+        }
+        let results = crate::parallel::try_map(lines, |(expected, name, path)| {
+            let matches = crate::hash::hash_file(&ctx.resolve(Path::new(path)), algo)
+                .map(|actual| actual == expected)
+                .unwrap_or(false);
+            Ok((name, matches))
+        })?;
+
+        let mut failed = 0;
+        for (name, matches) in results {
+            HoistRegistry::print_color(&format!("{}: ", name), Color::Blue, false)?;
+            match matches {
+                true => HoistRegistry::print_color("OK", Color::Green, true)?,
+                false => {
+                    failed += 1;
+                    HoistRegistry::print_color("FAILED", Color::Red, true)?
+                }
+            }
+        }
+        if failed > 0 {
+            anyhow::bail!("{} checksum(s) did not match", failed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+
+    #[test]
+    fn test_checksum_check() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_checksum_check");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        HoistRegistry::checksum(
+            &ctx,
+            vec!["binary1".to_string()],
+            HashAlgorithm::Sha256,
+            None,
+        )
+        .unwrap();
+        assert!(HoistRegistry::checksum(
+            &ctx,
+            vec!["missing".to_string()],
+            HashAlgorithm::Sha256,
+            None
+        )
+        .is_err());
+
+        let binary1 = test_tempdir.join("target/release/binary1");
+        let digest = crate::hash::hash_file(&binary1, HashAlgorithm::Sha256).unwrap();
+        let check = test_tempdir.join("SHA256SUMS");
+        std::fs::write(
+            &check,
+            format!("{}  binary1  {}\n", digest, binary1.display()),
+        )
+        .unwrap();
+        HoistRegistry::checksum(&ctx, vec![], HashAlgorithm::Sha256, Some(check.clone())).unwrap();
+
+        std::fs::write(&binary1, b"tampered").unwrap();
+        assert!(HoistRegistry::checksum(&ctx, vec![], HashAlgorithm::Sha256, Some(check)).is_err());
+    }
+}
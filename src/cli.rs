@@ -1,8 +1,25 @@
 //! CLI Logic
 
+use crate::binaries::{CopyStrategy, RegistrationSource};
+use crate::completions::SpecFormat;
+use crate::config::DefaultAction;
+use crate::context::HoistContext;
+use crate::diff::DiffFormat;
+use crate::export::ExportFormat;
+use crate::gc::GcOptions;
+use crate::hash::HashAlgorithm;
+use crate::hoist::{HoistOptions, OverwritePolicy};
+use crate::install::InstallOptions;
+use crate::list::{ListOptions, ListSort};
+use crate::merge::MergeStrategy;
 use crate::registry::HoistRegistry;
+use crate::remove::{NukeOptions, RemoveOptions};
+use crate::sbom::SbomFormat;
+use crate::schema::SchemaTarget;
+use crate::shell::ShellType;
 use anyhow::Result;
-use clap::{ArgAction, Parser, Subcommand};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[clap(name = "cargo-hoist", author, bin_name = "cargo", version)]
@@ -13,7 +30,14 @@ enum Cargo {
 
 /// Command line arguments
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "When no subcommand is given, runs the `default-action` set in ~/.hoist/config.toml: \
+                  `install` (the default), `list` or `help`."
+)]
 pub struct Args {
     /// Global options
     #[clap(flatten)]
@@ -34,6 +58,28 @@ pub struct GlobalOpts {
     /// Suppresses standard output.
     #[arg(long, short)]
     pub quiet: bool,
+
+    /// Maximum number of worker threads for scanning, hashing and copying.
+    /// Defaults to the `jobs` config key, or the number of available cores.
+    #[arg(long, short)]
+    pub jobs: Option<usize>,
+
+    /// The directory hoist keeps its registry, config and content store in.
+    /// Defaults to `HOIST_HOME`, or `~/.hoist`.
+    #[arg(long, value_name = "DIR")]
+    pub hoist_dir: Option<PathBuf>,
+
+    /// Fails on a corrupt registry instead of salvaging its valid entries
+    /// and moving the broken file aside.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Prints the files `hoist`, `register`, `prune`, `gc`, `dedupe`,
+    /// `remove`, `store gc` and `nuke` would place or remove, and the registry
+    /// changes they would make, without making them. Commands that can't
+    /// preview their changes, e.g. `undo`, refuse to run with it.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
 }
 
 /// Subcommands
@@ -41,34 +87,809 @@ pub struct GlobalOpts {
 pub enum Command {
     /// Hoist dependencies
     Hoist {
-        /// An optional list of binaries to bring into scope from the hoist toml registry
+        /// An optional list of binaries to bring into scope from the hoist toml registry.
+        /// A `name@version` selects the builds of that package version, a
+        /// `name@requirement`, e.g. `'my-tool@^1.2'`, the highest registered
+        /// version satisfying it, and a glob pattern, e.g. `'op-*'`, every
+        /// registered binary it matches.
+        /// A `-` reads newline-separated names from stdin.
         bins: Option<Vec<String>>,
 
         /// Binary list flag. Merged ad de-duplicated with any binaries provided in the inline
         /// argument.
         #[clap(short, long)]
         binaries: Option<Vec<String>>,
+
+        /// Reads newline-separated binary names from stdin.
+        #[clap(long)]
+        stdin: bool,
+
+        /// Write shims that run the registered binaries instead of copying them.
+        /// Shims notify you when a newer build has been registered.
+        #[clap(long)]
+        shim: bool,
+
+        /// Only hoist builds cross-compiled for this target triple, from
+        /// `target/<triple>/<profile>`.
+        #[clap(long, value_name = "TRIPLE")]
+        target: Option<String>,
+
+        /// Hoists binaries that are not registered from this project's target
+        /// directory, instead of the current directory's.
+        #[clap(long, value_name = "PATH", conflicts_with = "previous")]
+        project: Option<PathBuf>,
+
+        /// Only hoist binaries with this tag. Hoists every tagged binary when
+        /// no binaries are named.
+        #[clap(long)]
+        tag: Option<String>,
+
+        /// Copy the build preceding the latest of each binary instead, as
+        /// `cargo hoist rollback` does.
+        #[clap(long, conflicts_with_all = ["shim", "target", "tag"])]
+        previous: bool,
+
+        /// Hoists the highest registered version of each named binary that
+        /// satisfies this semver requirement, e.g. `^1.2` or `>=1.0, <2`.
+        #[clap(long, value_name = "REQ", conflicts_with = "previous")]
+        version: Option<String>,
+
+        /// Hoists the most recently registered or built of the builds sharing
+        /// a name, instead of prompting for which to hoist.
+        #[clap(long, conflicts_with = "previous")]
+        latest: bool,
+
+        /// Hoists every registered binary.
+        #[clap(long, conflicts_with_all = ["bins", "binaries", "stdin", "tag", "previous"])]
+        all: bool,
+
+        /// Also hoist binaries that expired by their ttl.
+        #[clap(long)]
+        include_expired: bool,
+
+        /// Symlink to the registered binaries instead of copying them, as
+        /// `--strategy symlink` does.
+        #[clap(long, conflicts_with_all = ["shim", "previous", "strategy"])]
+        link: bool,
+
+        /// How to place the binaries: copy them, or hard link, reflink or
+        /// symlink them to save disk space, falling back to a copy where the
+        /// link can't be created. Defaults to the `strategy` of the user config.
+        #[clap(long, value_enum, conflicts_with_all = ["shim", "previous"])]
+        strategy: Option<CopyStrategy>,
+
+        /// Hoists into this directory, e.g. `./bin` or a container build
+        /// context, creating it if missing, instead of the current directory.
+        #[clap(long, value_name = "DIR")]
+        to: Option<PathBuf>,
+
+        /// Copies binaries without restoring their modification time.
+        #[clap(long, conflicts_with_all = ["shim", "link", "previous"])]
+        no_preserve: bool,
+
+        /// Keeps the setuid, setgid and sticky bits of copied binaries,
+        /// which are cleared by default.
+        #[clap(long, conflicts_with_all = ["shim", "link", "previous", "no_preserve"])]
+        preserve_special_bits: bool,
+
+        /// Hoists into `~/.cargo/bin`, or the `global-bin` of the user
+        /// config, as a faster `cargo install --path .`.
+        #[clap(long, conflicts_with_all = ["to", "previous"])]
+        global: bool,
+
+        /// Hoists the binary under this file name instead, e.g. to hoist
+        /// `my-tool-v2` as `my-tool`.
+        #[clap(long = "as", value_name = "NAME", conflicts_with_all = ["shim", "all", "previous"])]
+        name: Option<String>,
+
+        /// Replaces files in the destination that differ from the binaries
+        /// without asking, and hoists binaries built for another platform.
+        #[clap(long, short, conflicts_with = "previous")]
+        force: bool,
+
+        /// Keeps files in the destination that differ from the binaries,
+        /// skipping those binaries.
+        #[clap(long, conflicts_with_all = ["force", "previous"])]
+        no_clobber: bool,
+    },
+    /// Deletes the files `hoist` placed into a directory, as recorded in its
+    /// `.hoist-manifest`, leaving any other files alone.
+    Unhoist {
+        /// The hoisted files or registered binaries to delete. Deletes every
+        /// hoisted file when omitted.
+        bins: Vec<String>,
+
+        /// Deletes the files hoisted into this directory instead of the
+        /// current directory.
+        #[clap(long, value_name = "DIR")]
+        from: Option<PathBuf>,
+    },
+    /// Checks the files `hoist` placed into a directory, as recorded in its
+    /// `.hoist-manifest`, are present and executable, restoring the
+    /// executable bits of files that lost them.
+    Verify {
+        /// Checks the files hoisted into this directory instead of the
+        /// current directory.
+        #[clap(long, value_name = "DIR")]
+        from: Option<PathBuf>,
+    },
+    /// Hoists every binary required by the project's `hoist.toml` into the
+    /// project root.
+    Sync {
+        /// Overwrites differing files in the project root.
+        #[clap(long, short)]
+        force: bool,
+    },
+    /// Fails if a binary required by the project's `hoist.toml` is not
+    /// hoisted into the project root, or not of the required version.
+    Check,
+    /// Records the builds hoisted into a directory, by their versions and
+    /// hashes, into its `hoist.lock`.
+    Freeze {
+        /// Freezes the files hoisted into this directory instead of the
+        /// current directory.
+        #[clap(long, value_name = "DIR")]
+        from: Option<PathBuf>,
+    },
+    /// Places the builds recorded in a directory's `hoist.lock` back into it.
+    Thaw {
+        /// Thaws the `hoist.lock` of this directory instead of the current
+        /// directory.
+        #[clap(long, value_name = "DIR")]
+        to: Option<PathBuf>,
+
+        /// Replaces files in the way that `hoist` did not place there.
+        #[clap(long, short)]
+        force: bool,
+    },
+    /// Sets up the global registry in `~/.hoist`, or with `--local`, a
+    /// project-scoped registry that takes precedence under the current directory.
+    Init {
+        /// Create a local `.hoist/registry.toml` in the current directory,
+        /// storing paths relative to it so it can be committed.
+        #[clap(long)]
+        local: bool,
+    },
+    /// Executes a registered binary, noting when a newer build is registered.
+    Exec {
+        /// The registered binary to execute.
+        binary: String,
+
+        /// Execute the build at this location instead of the latest registered build.
+        #[clap(long)]
+        from: Option<PathBuf>,
+
+        /// Arguments passed through to the binary.
+        #[clap(last = true)]
+        args: Vec<String>,
     },
     /// List registered dependencies.
-    List,
+    List {
+        /// Prints each binary's license. Given a license id, e.g. `MIT`, only
+        /// lists binaries whose license expression names it.
+        #[clap(long, num_args = 0..=1, value_name = "ID")]
+        license: Option<Option<String>>,
+
+        /// Prints only the unique registered binary names, one per line.
+        #[clap(long, conflicts_with = "license")]
+        names: bool,
+
+        /// Only lists binaries registered or rebuilt within a duration, e.g.
+        /// `24h` or `7d`, or since a `YYYY-MM-DD` date.
+        #[clap(long, value_name = "DURATION|DATE", value_parser = crate::time::parse_since)]
+        changed_since: Option<std::time::SystemTime>,
+
+        /// Only lists binaries built with this cargo profile, e.g. `release`.
+        #[clap(long, value_name = "PROFILE")]
+        profile: Option<String>,
+
+        /// Only lists binaries with this tag.
+        #[clap(long)]
+        tag: Option<String>,
+
+        /// Prints the project each binary was built in and the git commit it
+        /// was built from.
+        #[clap(long, short, conflicts_with = "names")]
+        long: bool,
+
+        /// Sorts the listed binaries, e.g. `size` for the largest first.
+        #[clap(long, value_enum, conflicts_with = "names")]
+        sort: Option<ListSort>,
+
+        /// Also lists binaries that expired by their ttl.
+        #[clap(long)]
+        all: bool,
+    },
     /// Search for a binary in the hoist toml registry.
     #[clap(alias = "find")]
     Search {
         /// The binary to search for in the hoist toml registry.
         binary: String,
+
+        /// Match the query against every registered field, such as paths,
+        /// projects and licenses, ranking name matches first.
+        #[clap(long)]
+        all_fields: bool,
+    },
+    /// Explains where a registered binary came from, down to the git commit it
+    /// was built from, and which build `hoist` picks.
+    #[clap(alias = "info")]
+    Why {
+        /// The registered binary to explain.
+        binary: String,
+    },
+    /// Opens the directory containing a registered binary in the file manager.
+    Open {
+        /// The registered binary to reveal.
+        binary: String,
+
+        /// Print the directory instead of opening it.
+        #[clap(long)]
+        print: bool,
+    },
+    /// Opens the source repository or homepage of a registered binary in the browser.
+    Repo {
+        /// The registered binary.
+        binary: String,
+
+        /// Print the url instead of opening it.
+        #[clap(long)]
+        print: bool,
+    },
+    /// Prints `hash  name  path` checksums for registered binaries.
+    Checksum {
+        /// The binaries to checksum. Defaults to every registered binary.
+        bins: Vec<String>,
+
+        /// The digest algorithm.
+        #[clap(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+        algo: HashAlgorithm,
+
+        /// Verify the checksum lines in the given file instead of printing checksums.
+        #[clap(long)]
+        check: Option<PathBuf>,
+    },
+    /// Aliases a registered binary, so that `hoist <alias>` resolves to it.
+    Alias {
+        /// The alias.
+        alias: String,
+
+        /// The registered binary the alias resolves to.
+        #[clap(required_unless_present = "remove")]
+        binary: Option<String>,
+
+        /// Removes the alias instead.
+        #[clap(long, conflicts_with = "binary")]
+        remove: bool,
+    },
+    /// Renames a registered binary, e.g. to tell apart two projects' `cli`.
+    Rename {
+        /// The registered binary, or an alias of it.
+        old: String,
+
+        /// The new name.
+        new: String,
+
+        /// Only renames the builds registered from projects under this path.
+        #[clap(long, value_name = "PATH")]
+        project: Option<PathBuf>,
+
+        /// Also renames the stored copies in the content store, so the
+        /// binaries run as the new name.
+        #[clap(long)]
+        store: bool,
+    },
+    /// Tags every registered build of a binary, e.g. to group binaries by purpose.
+    Tag {
+        /// The registered binary.
+        binary: String,
+
+        /// The tags to add.
+        #[clap(required = true)]
+        tags: Vec<String>,
+    },
+    /// Removes tags from every registered build of a binary.
+    Untag {
+        /// The registered binary.
+        binary: String,
+
+        /// The tags to remove.
+        #[clap(required = true)]
+        tags: Vec<String>,
+    },
+    /// Pins every registered build of a binary, so `prune`, `gc` and `nuke` keep them.
+    Pin {
+        /// The registered binary to pin.
+        binary: String,
+    },
+    /// Unpins every registered build of a binary.
+    Unpin {
+        /// The registered binary to unpin.
+        binary: String,
+    },
+    /// Removes registered binaries whose files were deleted, e.g. by `cargo clean`,
+    /// or are no longer executable.
+    Prune {
+        /// Prompts for which stale binaries to remove.
+        #[clap(long, short)]
+        interactive: bool,
+    },
+    /// Manages the content store of registered binary copies.
+    Store {
+        /// The store command.
+        #[clap(subcommand)]
+        command: StoreCommand,
+    },
+    /// Manages named sets of binaries, hoisted or put on PATH together.
+    Set {
+        /// The set command.
+        #[clap(subcommand)]
+        command: SetCommand,
+    },
+    /// Diagnoses the hoist setup, printing a fix for every problem found.
+    Doctor,
+    /// Reverts the latest registry change recorded in `~/.hoist/history.log`.
+    /// Repeating it steps further back.
+    Undo,
+    /// Shows the latest registry changes recorded in `~/.hoist/history.log`.
+    Log {
+        /// The number of changes shown.
+        #[clap(long, short = 'n', default_value_t = 10)]
+        limit: usize,
+    },
+    /// Shows the most and least hoisted registered binaries.
+    Stats {
+        /// The number of binaries shown in each list.
+        #[clap(long, short = 'n', default_value_t = 5)]
+        limit: usize,
+
+        /// Shows the disk space referenced by the registry and the content
+        /// store, and the largest binaries, instead.
+        #[clap(long)]
+        disk: bool,
+    },
+    /// Removes registered binaries that have not been used in a while, and
+    /// those that expired by their ttl.
+    ///
+    /// Without a policy, applies the `keep` retention of the user config.
+    Gc {
+        /// Removes binaries not hoisted, executed or registered within a
+        /// duration, e.g. `30d`.
+        #[clap(long, value_name = "DURATION", value_parser = crate::time::parse_duration)]
+        older_than: Option<std::time::Duration>,
+
+        /// Keeps only the N most recently used binaries.
+        #[clap(long, value_name = "N")]
+        keep_last: Option<usize>,
+    },
+    /// Collapses registered binaries of the same name with byte-identical
+    /// contents, keeping the paths they were built at as sources.
+    Dedupe,
+    /// Unregisters binaries by name, alias or glob pattern, or everything
+    /// registered from a project.
+    #[clap(alias = "rm")]
+    Remove {
+        /// The names, aliases or glob patterns, e.g. `my-tool-*`, of the
+        /// binaries to remove. A `-` reads newline-separated patterns from stdin.
+        #[clap(required_unless_present = "project")]
+        patterns: Vec<String>,
+
+        /// Only removes binaries registered from projects under this path,
+        /// or with no patterns, every binary registered from them.
+        #[clap(long, value_name = "PATH")]
+        project: Option<PathBuf>,
+
+        /// Also removes pinned binaries.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Nuke wipes the hoist toml registry, backing it up first. Pinned
+    /// binaries are kept.
+    Nuke {
+        /// Only remove binaries whose name matches this glob pattern, e.g. `cargo-*`.
+        pattern: Option<String>,
+
+        /// Only remove binaries registered from projects under this path.
+        #[clap(long, value_name = "PATH")]
+        project: Option<PathBuf>,
+
+        /// Also remove pinned binaries.
+        #[clap(long)]
+        force: bool,
+
+        /// Skip the confirmation prompt.
+        #[clap(long, short)]
+        yes: bool,
+    },
+    /// Writes a timestamped snapshot of the registry to `~/.hoist/backups/`.
+    Backup {
+        /// Lists the snapshots instead, oldest first.
+        #[clap(long)]
+        list: bool,
+
+        /// The number of snapshots to keep, removing older ones.
+        #[clap(long, default_value_t = crate::backup::KEEP)]
+        keep: usize,
+    },
+    /// Exports a copy of the registry.
+    Export {
+        /// The file to write. Prints the export when omitted.
+        path: Option<PathBuf>,
+
+        /// The export format.
+        #[clap(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// Writes paths under the home directory as `~/...`, so the export
+        /// works under another user's home directory.
+        #[clap(long)]
+        portable: bool,
+    },
+    /// Registers existing executables not built by hoist, where they are
+    /// installed: scripts, downloaded tools, or binaries from other languages.
+    Adopt {
+        /// The executables to register, paths or names looked up on `PATH`.
+        #[clap(required_unless_present_any = ["cargo_install", "scan_path"])]
+        targets: Vec<String>,
+
+        /// Registers the binaries `cargo install` installed into
+        /// `~/.cargo/bin`, with their versions.
+        #[clap(long)]
+        cargo_install: bool,
+
+        /// Registers every executable on `PATH` under the home directory.
+        #[clap(long)]
+        scan_path: bool,
+    },
+    /// Imports an exported registry, replacing the current one. `~/` paths
+    /// are expanded against the home directory.
+    Import {
+        /// The exported toml or json file.
+        path: PathBuf,
+
+        /// Adds the imported binaries to the registry instead of replacing it.
+        #[clap(long)]
+        merge: bool,
+
+        /// Also moves paths under another user's home directory, e.g.
+        /// `/home/alice/...`, into the home directory.
+        #[clap(long)]
+        portable: bool,
+    },
+    /// Packs registered binaries and their registry entries into an archive,
+    /// e.g. `tools.tar.zst`, for `unbundle` on another machine.
+    Bundle {
+        /// The binaries, `name@version` specs or glob patterns to bundle.
+        #[clap(required = true)]
+        bins: Vec<String>,
+
+        /// The archive to write. Its extension picks the compression.
+        #[clap(long, short)]
+        output: PathBuf,
+    },
+    /// Registers the binaries of an archive written by `bundle`, copying them
+    /// into the content store.
+    Unbundle {
+        /// The archive.
+        path: PathBuf,
+    },
+    /// Merges another registry file into the registry.
+    ///
+    /// Conflicting entries, registered in both with different metadata, keep
+    /// the registry's own entry unless a preference is given.
+    Merge {
+        /// The other registry, a registry.toml or an exported toml or json file.
+        path: PathBuf,
+
+        /// Takes the other registry's entries and aliases on conflicts.
+        #[clap(long, conflicts_with_all = ["prefer_newer", "interactive"])]
+        prefer_theirs: bool,
+
+        /// Takes the most recently registered entry on conflicts.
+        #[clap(long, conflicts_with = "interactive")]
+        prefer_newer: bool,
+
+        /// Prompts for the conflicting entries to take from the other registry.
+        #[clap(long, short)]
+        interactive: bool,
+    },
+    /// Shows the binaries and aliases added, removed or changed in the
+    /// registry since another registry file.
+    Diff {
+        /// The registry to compare against, a registry.toml, backup or exported
+        /// toml or json file. Defaults to the newest backup.
+        path: Option<PathBuf>,
+
+        /// The output format.
+        #[clap(long, value_enum, default_value_t)]
+        format: DiffFormat,
+    },
+    /// Restores the registry from a snapshot.
+    Restore {
+        /// The snapshot to restore. Defaults to the newest backup.
+        #[clap(long, value_name = "FILE")]
+        from: Option<PathBuf>,
     },
-    /// Nuke wipes the hoist toml registry.
-    Nuke,
     /// Registers a binary in the global hoist toml registry
     #[clap(alias = "install")]
     Register {
-        /// An optional list of binaries to install in the hoist toml registry
+        /// An optional list of binaries to install in the hoist toml registry.
+        /// A `-` reads newline-separated entries from stdin, as `--stdin` does.
         bins: Option<Vec<String>>,
 
         /// Binary list flag. Merged ad de-duplicated with any binaries provided in the inline
         /// argument.
         #[clap(short, long)]
         binaries: Option<Vec<String>>,
+
+        /// Reads newline-separated entries from stdin. Entries containing a `/`
+        /// are registered as paths, like `--path`, and others as binary names.
+        #[clap(long)]
+        stdin: bool,
+
+        /// Also register compiled integration test executables.
+        #[clap(long)]
+        tests: bool,
+
+        /// Also register compiled bench executables.
+        #[clap(long)]
+        benches: bool,
+
+        /// Registers an executable file directly instead of scanning the
+        /// project's target directory. May be repeated.
+        #[clap(
+            long = "path",
+            value_name = "FILE",
+            conflicts_with_all = ["bins", "binaries", "tests", "benches"]
+        )]
+        paths: Vec<PathBuf>,
+
+        /// Registers the `--path` binary under this name instead of its file name.
+        #[clap(long = "as", value_name = "NAME", requires = "paths")]
+        name: Option<String>,
+
+        /// Registers binaries where they were built instead of copying them
+        /// into the `~/.hoist/store` content store, so they break when the
+        /// build is cleaned.
+        #[clap(long, visible_alias = "link-only")]
+        no_store: bool,
+
+        /// Expires the binaries once unused for a duration, e.g. `7d`,
+        /// instead of after the `ttl` of the user config.
+        #[clap(long, value_name = "DURATION", value_parser = crate::time::parse_duration)]
+        ttl: Option<std::time::Duration>,
+
+        /// Marks the registration as triggered by the pre-cargo shell hook.
+        #[clap(long, hide = true)]
+        hook: bool,
+    },
+    /// Checks registered binaries' Cargo.lock snapshots against the RustSec advisory database.
+    Audit {
+        /// The registered binaries to audit. Audits every binary when omitted.
+        bins: Vec<String>,
+
+        /// The advisory database checkout. Defaults to `$CARGO_HOME/advisory-db`.
+        #[clap(long)]
+        db: Option<PathBuf>,
+
+        /// Clones or updates the advisory database before auditing.
+        #[clap(long)]
+        fetch: bool,
+    },
+    /// Generates a software bill of materials for a registered binary from its
+    /// recorded Cargo.lock snapshot.
+    Sbom {
+        /// The registered binary
+        binary: String,
+
+        /// The document format.
+        #[clap(long, value_enum, default_value_t)]
+        format: SbomFormat,
+
+        /// Writes the document to a file instead of stdout.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Prints a completion spec for the Fig or carapace completion frameworks.
+    CompletionSpec {
+        /// The spec format.
+        #[clap(value_enum)]
+        format: SpecFormat,
+    },
+    /// Prints the JSON Schema for a file hoist reads or writes.
+    Schema {
+        /// The file format to describe.
+        #[clap(value_enum, default_value_t)]
+        target: SchemaTarget,
+    },
+    /// Rebuilds and re-registers binaries whose source project changed since they were built.
+    Upgrade {
+        /// The registered binaries to upgrade
+        bins: Vec<String>,
+
+        /// Upgrade every stale registered binary.
+        #[clap(long)]
+        all: bool,
+    },
+    /// Rolls a hoisted binary in the current directory back to a previously registered build.
+    Rollback {
+        /// The registered binary to roll back.
+        binary: String,
+
+        /// The sha256 digest prefix of the build to restore, as printed by
+        /// `cargo hoist checksum`. Defaults to the build preceding the latest.
+        #[clap(long, value_name = "HASH")]
+        to: Option<String>,
+    },
+    /// Prints the shell hook that scopes project manifest binaries to the session PATH.
+    ///
+    /// Add `eval "$(cargo hoist hook)"` to your shell config to enable it.
+    Hook {
+        /// The shell to print the hook for. Detected from `$SHELL` when omitted.
+        shell: Option<ShellType>,
+    },
+    /// Prints the line adding the `~/.hoist/bin` shim directory to `PATH`.
+    ///
+    /// Add `eval "$(cargo hoist path)"` to your shell config to enable it.
+    Path,
+    /// Writes shims into `~/.hoist/bin` that run the latest registered build
+    /// of each binary, resolved through the registry on every run.
+    Shim {
+        /// The registered binaries to shim.
+        #[clap(required_unless_present = "all")]
+        bins: Vec<String>,
+
+        /// Shims every registered binary.
+        #[clap(long, conflicts_with = "bins")]
+        all: bool,
+    },
+    /// Removes shims from `~/.hoist/bin`.
+    Unshim {
+        /// The binaries to remove the shims of.
+        #[clap(required_unless_present = "all")]
+        bins: Vec<String>,
+
+        /// Removes every shim.
+        #[clap(long, conflicts_with = "bins")]
+        all: bool,
+    },
+    /// Starts a subshell with registered binaries on its PATH, placed into a
+    /// temporary directory that is removed once the subshell exits.
+    Shell {
+        /// The binaries, `name@version` specs or glob patterns to put on PATH.
+        #[clap(required = true)]
+        bins: Vec<String>,
+
+        /// Prints the line prepending the directory to PATH, for
+        /// `eval "$(cargo hoist shell <bins> --print)"`, instead of starting a subshell.
+        #[clap(long)]
+        print: bool,
+    },
+    /// Prints the session PATH for the project manifest of the current directory.
+    Env {
+        /// The shell session identifier, typically the shell's pid.
+        #[clap(long)]
+        session: String,
+    },
+    /// Serves the registry and vault over HTTP for a team to share.
+    #[cfg(feature = "server")]
+    Server {
+        /// The address to listen on.
+        #[clap(long, default_value = crate::server::DEFAULT_ADDR)]
+        addr: String,
+
+        /// The bearer token clients must send. Defaults to `$HOIST_SERVER_TOKEN`.
+        /// Without a token, the server is read-only.
+        #[clap(long)]
+        token: Option<String>,
+    },
+    /// Uploads the latest registered builds of binaries to a `cargo hoist server`.
+    #[cfg(feature = "server")]
+    Push {
+        /// The binaries to upload.
+        bins: Vec<String>,
+
+        /// The server url, e.g. `http://build-box:7878`. Defaults to `$HOIST_SERVER`.
+        #[clap(long)]
+        server: Option<String>,
+
+        /// The server's bearer token. Defaults to `$HOIST_SERVER_TOKEN`.
+        #[clap(long)]
+        token: Option<String>,
+    },
+    /// Downloads binaries from a `cargo hoist server` and registers them.
+    #[cfg(feature = "server")]
+    Pull {
+        /// The binaries to download.
+        bins: Vec<String>,
+
+        /// The server url, e.g. `http://build-box:7878`. Defaults to `$HOIST_SERVER`.
+        #[clap(long)]
+        server: Option<String>,
+
+        /// The server's bearer token. Defaults to `$HOIST_SERVER_TOKEN`.
+        #[clap(long)]
+        token: Option<String>,
+    },
+    /// Serves a local web dashboard to browse, hoist and remove registered binaries.
+    #[cfg(feature = "web")]
+    Web {
+        /// The address to listen on.
+        #[clap(long, default_value = crate::web::DEFAULT_ADDR)]
+        addr: String,
+    },
+}
+
+/// Content store subcommands
+#[derive(Subcommand, Debug)]
+pub enum StoreCommand {
+    /// Removes stored binary copies no registered binary points at.
+    Gc,
+}
+
+/// Binary set subcommands
+#[derive(Subcommand, Debug)]
+pub enum SetCommand {
+    /// Creates a named set of binaries.
+    Create {
+        /// The set name.
+        name: String,
+
+        /// The binaries, `name@version` specs or glob patterns in the set.
+        #[clap(required = true)]
+        bins: Vec<String>,
+
+        /// Replaces an existing set of the same name.
+        #[clap(long, short)]
+        force: bool,
+    },
+    /// Adds binaries to a set.
+    Add {
+        /// The set name.
+        name: String,
+
+        /// The binaries to add.
+        #[clap(required = true)]
+        bins: Vec<String>,
+    },
+    /// Removes binaries from a set.
+    Remove {
+        /// The set name.
+        name: String,
+
+        /// The binaries to remove.
+        #[clap(required = true)]
+        bins: Vec<String>,
+    },
+    /// Deletes a set.
+    Delete {
+        /// The set name.
+        name: String,
+    },
+    /// Lists the sets and their binaries.
+    List,
+    /// Hoists the latest build of every binary in a set.
+    Use {
+        /// The set name.
+        name: String,
+
+        /// Starts a subshell with the set on its PATH instead, as `shell` does.
+        #[clap(long, conflicts_with_all = ["to", "force"])]
+        shell: bool,
+
+        /// Prints the line prepending the set's directory to PATH instead of
+        /// starting a subshell.
+        #[clap(long, requires = "shell")]
+        print: bool,
+
+        /// The directory to hoist into, created if missing, instead of the
+        /// working directory.
+        #[clap(long)]
+        to: Option<PathBuf>,
+
+        /// Overwrites differing files in the destination.
+        #[clap(long, short)]
+        force: bool,
     },
 }
 
@@ -77,24 +898,462 @@ pub fn run() -> Result<()> {
     let Cargo::Hoist(arg) = Cargo::parse();
 
     crate::telemetry::init_tracing_subscriber(arg.globals.verbosity)?;
+    let mut ctx = HoistContext::from_env()?;
+    if let Some(dir) = &arg.globals.hoist_dir {
+        ctx = ctx.with_hoist_dir(ctx.resolve(dir));
+    }
+    let ctx = ctx.with_strict(arg.globals.strict);
+    let config = crate::config::HoistConfig::load(&ctx)?;
+    // A project's local registry takes precedence over the global one.
+    let ctx = &match ctx.find_local_root() {
+        Some(root) => ctx.with_local_registry(root),
+        None => config.apply_store(ctx)?,
+    };
+    if let Some(jobs) = arg.globals.jobs.or(config.jobs) {
+        crate::parallel::set_jobs(jobs);
+    }
+    crate::progress::set_enabled(!arg.globals.quiet);
 
-    HoistRegistry::create_pre_hook(true, false)?;
+    if !arg.globals.dry_run {
+        HoistRegistry::create_pre_hook_with(ctx, true, false)?;
+    }
 
     let res = match arg.command {
-        None => HoistRegistry::install(None, Vec::new(), arg.globals.quiet),
+        None => match config.default_action {
+            DefaultAction::Install => HoistRegistry::install_with(
+                ctx,
+                None,
+                Vec::new(),
+                InstallOptions {
+                    dry_run: arg.globals.dry_run,
+                    ..Default::default()
+                },
+                arg.globals.quiet,
+            ),
+            DefaultAction::List => {
+                HoistRegistry::list_with(ctx, ListOptions::default(), arg.globals.quiet)
+            }
+            DefaultAction::Help => print_help(),
+        },
         Some(c) => match c {
-            Command::Hoist { binaries, bins } => HoistRegistry::hoist(
-                crate::utils::merge_and_dedup_vecs(binaries, bins),
+            Command::Hoist {
+                binaries,
+                bins,
+                stdin,
+                shim,
+                target,
+                project,
+                tag,
+                previous,
+                version,
+                latest,
+                all,
+                include_expired,
+                link,
+                strategy,
+                no_preserve,
+                preserve_special_bits,
+                to,
+                global,
+                name,
+                force,
+                no_clobber,
+            } => {
+                let (mut names, lines) = crate::utils::split_stdin(
+                    crate::utils::merge_and_dedup_vecs(binaries, bins),
+                    stdin,
+                    std::io::stdin().lock(),
+                )?;
+                match lines {
+                    Some(lines) if lines.is_empty() && names.is_empty() => Ok(()),
+                    lines if previous => {
+                        names.extend(lines.into_iter().flatten());
+                        if names.is_empty() {
+                            anyhow::bail!("Name the binaries to restore the previous builds of");
+                        }
+                        let ctx = &match &to {
+                            Some(dir) => {
+                                let dir = ctx.resolve(dir);
+                                std::fs::create_dir_all(&dir)?;
+                                ctx.with_cwd(dir)
+                            }
+                            None => ctx.clone(),
+                        };
+                        names.into_iter().try_for_each(|name| {
+                            HoistRegistry::rollback(ctx, name, None, arg.globals.quiet)
+                        })
+                    }
+                    lines => {
+                        names.extend(lines.into_iter().flatten());
+                        HoistRegistry::hoist_with(
+                            ctx,
+                            names,
+                            HoistOptions {
+                                shim,
+                                target,
+                                location: None,
+                                project,
+                                tag,
+                                all,
+                                expired: include_expired,
+                                strategy: match link {
+                                    true => Some(CopyStrategy::Symlink),
+                                    false => strategy,
+                                },
+                                no_preserve,
+                                preserve_special_bits,
+                                to,
+                                global,
+                                rename: name,
+                                version,
+                                latest,
+                                overwrite: match (force, no_clobber) {
+                                    (true, _) => OverwritePolicy::Force,
+                                    (_, true) => OverwritePolicy::NoClobber,
+                                    _ => OverwritePolicy::Prompt,
+                                },
+                                dry_run: arg.globals.dry_run,
+                            },
+                            arg.globals.quiet,
+                        )
+                    }
+                }
+            }
+            Command::Unhoist { bins, from } => {
+                let dir = from.map_or_else(|| ctx.cwd.clone(), |d| ctx.resolve(&d));
+                crate::hoisted::unhoist(&dir, bins, arg.globals.quiet)
+            }
+            Command::Verify { from } => {
+                let dir = from.map_or_else(|| ctx.cwd.clone(), |d| ctx.resolve(&d));
+                crate::hoisted::verify(&dir, arg.globals.quiet)
+            }
+            Command::Sync { force } => {
+                crate::manifest::sync(ctx, force, arg.globals.dry_run, arg.globals.quiet)
+            }
+            Command::Check => crate::manifest::check(ctx, arg.globals.quiet),
+            Command::Freeze { from } => {
+                let dir = from.map_or_else(|| ctx.cwd.clone(), |d| ctx.resolve(&d));
+                crate::freeze::freeze(ctx, &dir, arg.globals.quiet)
+            }
+            Command::Thaw { to, force } => {
+                let dir = to.map_or_else(|| ctx.cwd.clone(), |d| ctx.resolve(&d));
+                crate::freeze::thaw(ctx, &dir, force, arg.globals.quiet)
+            }
+            Command::Init { local } => match local {
+                true => HoistRegistry::init_local(ctx, arg.globals.quiet),
+                false => HoistRegistry::setup_with(ctx, arg.globals.quiet),
+            },
+            Command::Exec { binary, from, args } => HoistRegistry::exec(ctx, binary, from, args),
+            Command::Search { binary, all_fields } => match all_fields {
+                true => HoistRegistry::search_all(ctx, binary),
+                false => HoistRegistry::find_with(ctx, binary),
+            },
+            Command::List {
+                license,
+                names,
+                changed_since,
+                profile,
+                tag,
+                long,
+                sort,
+                all,
+            } => HoistRegistry::list_with(
+                ctx,
+                ListOptions {
+                    license: license.is_some(),
+                    license_filter: license.flatten(),
+                    names,
+                    changed_since,
+                    profile,
+                    tag,
+                    long,
+                    sort,
+                    all,
+                },
+                false,
+            ),
+            Command::Register {
+                binaries,
+                bins,
+                stdin,
+                tests,
+                benches,
+                mut paths,
+                name,
+                no_store,
+                ttl,
+                hook,
+            } => {
+                let (mut names, lines) = crate::utils::split_stdin(
+                    crate::utils::merge_and_dedup_vecs(binaries, bins),
+                    stdin,
+                    std::io::stdin().lock(),
+                )?;
+                let read_stdin = lines.is_some();
+                for line in lines.into_iter().flatten() {
+                    match line.contains('/') {
+                        true => paths.push(PathBuf::from(line)),
+                        false => names.push(line),
+                    }
+                }
+                let options = InstallOptions {
+                    tests,
+                    benches,
+                    source: match hook {
+                        true => RegistrationSource::Hook,
+                        false => RegistrationSource::Manual,
+                    },
+                    no_store,
+                    ttl,
+                    dry_run: arg.globals.dry_run,
+                };
+                let registered_paths = !paths.is_empty();
+                if registered_paths {
+                    HoistRegistry::register_paths(ctx, paths, name, options, arg.globals.quiet)?;
+                }
+                match names.is_empty() && (read_stdin || registered_paths) {
+                    true => Ok(()),
+                    false => {
+                        HoistRegistry::install_with(ctx, None, names, options, arg.globals.quiet)
+                    }
+                }
+            }
+            Command::Why { binary } => HoistRegistry::why(ctx, binary),
+            Command::Open { binary, print } => HoistRegistry::open(ctx, binary, print),
+            Command::Repo { binary, print } => HoistRegistry::repo(ctx, binary, print),
+            Command::Checksum { bins, algo, check } => {
+                HoistRegistry::checksum(ctx, bins, algo, check)
+            }
+            Command::Alias {
+                alias,
+                binary,
+                remove,
+            } => match binary {
+                Some(binary) if !remove => {
+                    HoistRegistry::alias(ctx, &alias, &binary, arg.globals.quiet)
+                }
+                _ => HoistRegistry::unalias(ctx, &alias, arg.globals.quiet),
+            },
+            Command::Rename {
+                old,
+                new,
+                project,
+                store,
+            } => HoistRegistry::rename_binary(
+                ctx,
+                &old,
+                &new,
+                project.as_deref(),
+                store,
                 arg.globals.quiet,
             ),
-            Command::Search { binary } => HoistRegistry::find(binary),
-            Command::List => HoistRegistry::list(false),
-            Command::Register { binaries, bins } => HoistRegistry::install(
-                None,
-                crate::utils::merge_and_dedup_vecs(binaries, bins),
+            Command::Tag { binary, tags } => {
+                HoistRegistry::tag(ctx, &binary, tags, arg.globals.quiet)
+            }
+            Command::Untag { binary, tags } => {
+                HoistRegistry::untag(ctx, &binary, tags, arg.globals.quiet)
+            }
+            Command::Pin { binary } => HoistRegistry::pin(ctx, &binary, arg.globals.quiet),
+            Command::Unpin { binary } => HoistRegistry::unpin(ctx, &binary, arg.globals.quiet),
+            Command::Prune { interactive } => {
+                HoistRegistry::prune(ctx, arg.globals.dry_run, interactive, arg.globals.quiet)
+            }
+            Command::Store { command } => match command {
+                StoreCommand::Gc => crate::content::gc(ctx, arg.globals.dry_run, arg.globals.quiet),
+            },
+            Command::Set { command } => match command {
+                SetCommand::Create { name, bins, force } => {
+                    crate::sets::create(ctx, &name, bins, force, arg.globals.quiet)
+                }
+                SetCommand::Add { name, bins } => {
+                    crate::sets::add(ctx, &name, bins, arg.globals.quiet)
+                }
+                SetCommand::Remove { name, bins } => {
+                    crate::sets::remove(ctx, &name, bins, arg.globals.quiet)
+                }
+                SetCommand::Delete { name } => crate::sets::delete(ctx, &name, arg.globals.quiet),
+                SetCommand::List => crate::sets::list(ctx),
+                SetCommand::Use {
+                    name,
+                    shell,
+                    print,
+                    to,
+                    force,
+                } => match shell {
+                    true => crate::sets::shell(ctx, &name, print, arg.globals.quiet),
+                    false => crate::sets::hoist(
+                        ctx,
+                        &name,
+                        HoistOptions {
+                            to,
+                            overwrite: match force {
+                                true => OverwritePolicy::Force,
+                                false => OverwritePolicy::Prompt,
+                            },
+                            dry_run: arg.globals.dry_run,
+                            ..Default::default()
+                        },
+                        arg.globals.quiet,
+                    ),
+                },
+            },
+            Command::Doctor => crate::doctor::doctor(ctx, arg.globals.quiet),
+            Command::Undo => reject_dry_run(&arg.globals, "undo")
+                .and_then(|_| crate::journal::undo(ctx, arg.globals.quiet)),
+            Command::Log { limit } => crate::journal::log(ctx, limit),
+            Command::Stats { limit, disk } => match disk {
+                true => HoistRegistry::disk_usage(ctx, limit),
+                false => HoistRegistry::stats(ctx, limit),
+            },
+            Command::Gc {
+                older_than,
+                keep_last,
+            } => HoistRegistry::gc(
+                ctx,
+                GcOptions {
+                    older_than,
+                    keep_last,
+                    dry_run: arg.globals.dry_run,
+                    ttl: None,
+                },
                 arg.globals.quiet,
             ),
-            Command::Nuke => HoistRegistry::nuke(false),
+            Command::Dedupe => {
+                HoistRegistry::dedupe_registry(ctx, arg.globals.dry_run, arg.globals.quiet)
+            }
+            Command::Remove {
+                patterns,
+                project,
+                force,
+            } => {
+                let (mut patterns, lines) =
+                    crate::utils::split_stdin(patterns, false, std::io::stdin().lock())?;
+                patterns.extend(lines.into_iter().flatten());
+                HoistRegistry::remove(
+                    ctx,
+                    RemoveOptions {
+                        patterns,
+                        project,
+                        force,
+                        dry_run: arg.globals.dry_run,
+                    },
+                    arg.globals.quiet,
+                )
+            }
+            Command::Nuke {
+                pattern,
+                project,
+                force,
+                yes,
+            } => HoistRegistry::nuke_with(
+                ctx,
+                NukeOptions {
+                    pattern,
+                    project,
+                    force,
+                    yes,
+                    dry_run: arg.globals.dry_run,
+                },
+                arg.globals.quiet,
+            ),
+            Command::Backup { list, keep } => {
+                crate::backup::run(ctx, list, keep, arg.globals.quiet)
+            }
+            Command::Export {
+                path,
+                format,
+                portable,
+            } => crate::export::export(ctx, format, path, portable, arg.globals.quiet),
+            Command::Bundle { bins, output } => {
+                crate::bundle::bundle(ctx, bins, &output, arg.globals.quiet)
+            }
+            Command::Unbundle { path } => crate::bundle::unbundle(ctx, &path, arg.globals.quiet),
+            Command::Adopt {
+                targets,
+                cargo_install,
+                scan_path,
+            } => {
+                if cargo_install {
+                    crate::adopt::adopt_cargo_install(ctx, arg.globals.quiet)?;
+                }
+                match targets.is_empty() && !scan_path {
+                    true => Ok(()),
+                    false => {
+                        crate::adopt::adopt_executables(ctx, targets, scan_path, arg.globals.quiet)
+                    }
+                }
+            }
+            Command::Import {
+                path,
+                merge,
+                portable,
+            } => reject_dry_run(&arg.globals, "import").and_then(|_| {
+                crate::export::import(ctx, &path, merge, portable, arg.globals.quiet)
+            }),
+            Command::Merge {
+                path,
+                prefer_theirs,
+                prefer_newer,
+                interactive,
+            } => {
+                let strategy = match (prefer_theirs, prefer_newer, interactive) {
+                    (true, _, _) => MergeStrategy::Theirs,
+                    (_, true, _) => MergeStrategy::Newer,
+                    (_, _, true) => MergeStrategy::Interactive,
+                    _ => MergeStrategy::Ours,
+                };
+                reject_dry_run(&arg.globals, "merge")
+                    .and_then(|_| crate::merge::merge(ctx, &path, strategy, arg.globals.quiet))
+            }
+            Command::Diff { path, format } => crate::diff::run(ctx, path.as_deref(), format),
+            Command::Restore { from } => reject_dry_run(&arg.globals, "restore")
+                .and_then(|_| crate::backup::restore(ctx, from.as_deref(), arg.globals.quiet)),
+            Command::Audit { bins, db, fetch } => {
+                crate::audit::audit(ctx, bins, db, fetch, arg.globals.quiet)
+            }
+            Command::Sbom {
+                binary,
+                format,
+                output,
+            } => crate::sbom::sbom(ctx, binary, format, output),
+            Command::CompletionSpec { format } => {
+                crate::completions::print_spec(&hoist_command(), format)
+            }
+            Command::Schema { target } => crate::schema::print_schema(target),
+            Command::Upgrade { bins, all } => {
+                crate::upgrade::upgrade(ctx, bins, all, arg.globals.quiet)
+            }
+            Command::Rollback { binary, to } => {
+                HoistRegistry::rollback(ctx, binary, to, arg.globals.quiet)
+            }
+            Command::Hook { shell } => crate::shell::print_session_hook(shell),
+            Command::Path => crate::shims::print_path(ctx),
+            Command::Shim { bins, all } => crate::shims::shim(ctx, bins, all, arg.globals.quiet),
+            Command::Unshim { bins, all } => {
+                crate::shims::unshim(ctx, bins, all, arg.globals.quiet)
+            }
+            Command::Shell { bins, print } => {
+                crate::session::shell(ctx, bins, print, arg.globals.quiet)
+            }
+            Command::Env { session } => HoistRegistry::env(ctx, session),
+            #[cfg(feature = "server")]
+            Command::Server { addr, token } => {
+                crate::server::serve(ctx, addr, token, arg.globals.quiet)
+            }
+            #[cfg(feature = "server")]
+            Command::Push {
+                bins,
+                server,
+                token,
+            } => crate::server::push(ctx, server, token, bins, arg.globals.quiet),
+            #[cfg(feature = "server")]
+            Command::Pull {
+                bins,
+                server,
+                token,
+            } => crate::server::pull(ctx, server, token, bins, arg.globals.quiet),
+            #[cfg(feature = "web")]
+            Command::Web { addr } => crate::web::serve(ctx, addr, arg.globals.quiet),
         },
     };
     if let Err(e) = res {
@@ -106,6 +1365,28 @@ pub fn run() -> Result<()> {
     Ok(())
 }
 
+/// Fails for a command that can't preview its changes under `--dry-run`,
+/// rather than making them.
+fn reject_dry_run(globals: &GlobalOpts, command: &str) -> Result<()> {
+    if globals.dry_run {
+        anyhow::bail!("{} does not support --dry-run", command);
+    }
+    Ok(())
+}
+
+/// Prints the help for the `hoist` subcommand.
+fn print_help() -> Result<()> {
+    hoist_command().print_help()?;
+    Ok(())
+}
+
+/// Returns the built clap command for `cargo hoist`.
+fn hoist_command() -> clap::Command {
+    let mut cmd = Cargo::command();
+    cmd.build();
+    cmd.find_subcommand("hoist").cloned().unwrap_or(cmd)
+}
+
 #[cfg(test)]
 mod tests {
     use assert_cmd::Command;
@@ -126,6 +1407,25 @@ mod tests {
         assert.success().stdout("");
     }
 
+    #[test]
+    #[serial]
+    fn test_cli_default_action_help() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test_dir(&tempdir);
+        std::fs::File::create(test_tempdir.join(".bashrc")).unwrap();
+        std::fs::create_dir_all(test_tempdir.join(".hoist")).unwrap();
+        std::fs::write(test_tempdir.join(".hoist/hook"), "hook").unwrap();
+        std::fs::write(
+            test_tempdir.join(".hoist/config.toml"),
+            "default-action = \"help\"\n",
+        )
+        .unwrap();
+        let mut cmd = Command::cargo_bin(HOIST_BIN).unwrap();
+        let assert = cmd.env("HOME", &test_tempdir).arg("hoist").assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+        assert!(stdout.contains("Usage: cargo hoist"));
+    }
+
     #[test]
     #[serial]
     fn test_cli_nuke() {
@@ -135,6 +1435,25 @@ mod tests {
         cmd.arg("hoist").arg("nuke").assert().success().stdout("");
     }
 
+    #[test]
+    #[serial]
+    fn test_cli_dry_run() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test_dir(&tempdir);
+        let hoist = |args: &[&str]| {
+            let mut cmd = Command::cargo_bin(HOIST_BIN).unwrap();
+            cmd.env("HOME", &test_tempdir)
+                .arg("hoist")
+                .args(args)
+                .assert()
+        };
+        hoist(&["gc", "--keep-last", "1", "--dry-run"]).success();
+        let undo = hoist(&["undo", "--dry-run"]).failure();
+        let stderr = String::from_utf8(undo.get_output().stderr.clone()).unwrap();
+        assert!(stderr.contains("undo does not support --dry-run"));
+        assert!(!test_tempdir.join(".hoist/registry.toml").exists());
+    }
+
     #[test]
     #[serial]
     fn test_cli_install() {
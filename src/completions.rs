@@ -0,0 +1,360 @@
+//! Completions
+//!
+//! Generates completion specs for modern completion frameworks from the clap
+//! command tree, completing registered binary names through `cargo hoist list --names`.
+
+use anyhow::Result;
+use clap::{Arg, ArgAction, Command};
+use toml::{Table, Value};
+
+/// The command that prints registered binary names for dynamic completions.
+pub const NAMES_COMMAND: [&str; 4] = ["cargo", "hoist", "list", "--names"];
+
+/// The argument ids that take registered binary names.
+const BINARY_ARGS: [&str; 3] = ["binary", "bins", "binaries"];
+
+/// A completion spec format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SpecFormat {
+    /// A [Fig](https://fig.io/docs/reference/spec) completion spec, also read by Amazon Q
+    Fig,
+    /// A [carapace](https://carapace-sh.github.io/carapace-spec/) yaml spec
+    Carapace,
+}
+
+/// Returns if the argument completes registered binary names.
+fn completes_binaries(arg: &Arg) -> bool {
+    BINARY_ARGS.contains(&arg.get_id().as_str())
+}
+
+/// Returns if the argument accepts more than one value.
+fn is_variadic(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Append)
+        || arg.get_num_args().is_some_and(|n| n.max_values() > 1)
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help().map(|h| h.to_string()).unwrap_or_default()
+}
+
+fn about(cmd: &Command) -> String {
+    cmd.get_about().map(|h| h.to_string()).unwrap_or_default()
+}
+
+/// The visible named flags and options of a command.
+fn flags(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments()
+        .filter(|a| !a.is_positional() && !a.is_hide_set())
+}
+
+/// The visible subcommands of a command, skipping the generated `help` command.
+fn subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands()
+        .filter(|c| !c.is_hide_set() && c.get_name() != "help")
+}
+
+fn string(s: impl Into<String>) -> Value {
+    Value::String(s.into())
+}
+
+fn table(entries: Vec<(&str, Value)>) -> Value {
+    Value::Table(
+        entries
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+/// Builds the Fig spec for a command.
+fn fig_command(cmd: &Command) -> Value {
+    let mut spec = Table::new();
+    let mut names = vec![string(cmd.get_name())];
+    names.extend(cmd.get_visible_aliases().map(string));
+    spec.insert(
+        "name".to_string(),
+        match names.len() {
+            1 => names.remove(0),
+            _ => Value::Array(names),
+        },
+    );
+    spec.insert("description".to_string(), string(about(cmd)));
+
+    let options: Vec<_> = flags(cmd)
+        .map(|a| {
+            let mut names = vec![];
+            names.extend(a.get_long().map(|l| string(format!("--{}", l))));
+            names.extend(a.get_short().map(|s| string(format!("-{}", s))));
+            let mut option = Table::new();
+            option.insert("name".to_string(), Value::Array(names));
+            option.insert("description".to_string(), string(help(a)));
+            if matches!(a.get_action(), ArgAction::Count) {
+                option.insert("isRepeatable".to_string(), Value::Boolean(true));
+            }
+            if a.get_action().takes_values() {
+                let name = a
+                    .get_value_names()
+                    .and_then(|v| v.first())
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| a.get_id().to_string());
+                option.insert("args".to_string(), fig_arg(a, name));
+            }
+            Value::Table(option)
+        })
+        .collect();
+    if !options.is_empty() {
+        spec.insert("options".to_string(), Value::Array(options));
+    }
+
+    let args: Vec<_> = cmd
+        .get_positionals()
+        .filter(|a| !a.is_hide_set())
+        .map(|a| fig_arg(a, a.get_id().to_string()))
+        .collect();
+    if !args.is_empty() {
+        spec.insert("args".to_string(), Value::Array(args));
+    }
+
+    let subcommands: Vec<_> = subcommands(cmd).map(fig_command).collect();
+    if !subcommands.is_empty() {
+        spec.insert("subcommands".to_string(), Value::Array(subcommands));
+    }
+    Value::Table(spec)
+}
+
+/// Builds the Fig spec for an argument's values.
+fn fig_arg(arg: &Arg, name: String) -> Value {
+    let mut spec = Table::new();
+    spec.insert("name".to_string(), string(name));
+    if !arg.is_required_set() {
+        spec.insert("isOptional".to_string(), Value::Boolean(true));
+    }
+    if is_variadic(arg) {
+        spec.insert("isVariadic".to_string(), Value::Boolean(true));
+    }
+    let suggestions: Vec<_> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| string(v.get_name()))
+        .collect();
+    if !suggestions.is_empty() {
+        spec.insert("suggestions".to_string(), Value::Array(suggestions));
+    }
+    if completes_binaries(arg) {
+        spec.insert(
+            "generators".to_string(),
+            table(vec![
+                (
+                    "script",
+                    Value::Array(NAMES_COMMAND.iter().map(|s| string(*s)).collect()),
+                ),
+                ("splitOn", string("\n")),
+            ]),
+        );
+    }
+    Value::Table(spec)
+}
+
+/// Renders a Fig completion spec module for the `cargo hoist` command.
+pub fn fig(cmd: &Command) -> Result<String> {
+    let mut spec = fig_command(cmd);
+    if let Value::Table(t) = &mut spec {
+        t.insert("name".to_string(), string("cargo-hoist"));
+    }
+    Ok(format!(
+        "const completionSpec = {};\n\nexport default completionSpec;\n",
+        crate::json::to_string_pretty(&spec)?
+    ))
+}
+
+/// Quotes a yaml scalar.
+fn quote(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes the carapace spec body for a command at the given indent.
+fn carapace_command(out: &mut String, cmd: &Command, indent: usize) {
+    let pad = " ".repeat(indent);
+    out.push_str(&format!("{}description: {}\n", pad, quote(&about(cmd))));
+    let aliases: Vec<_> = cmd.get_visible_aliases().map(quote).collect();
+    if !aliases.is_empty() {
+        out.push_str(&format!("{}aliases: [{}]\n", pad, aliases.join(", ")));
+    }
+
+    let flags: Vec<_> = flags(cmd).collect();
+    if !flags.is_empty() {
+        out.push_str(&format!("{}flags:\n", pad));
+        for a in &flags {
+            let mut names = vec![];
+            names.extend(a.get_short().map(|s| format!("-{}", s)));
+            names.extend(a.get_long().map(|l| format!("--{}", l)));
+            let mut key = names.join(", ");
+            match a.get_action() {
+                ArgAction::Count => key.push('*'),
+                action if action.takes_values() => match a.get_num_args() {
+                    Some(n) if n.min_values() == 0 => key.push('?'),
+                    _ => key.push('='),
+                },
+                _ => {}
+            }
+            out.push_str(&format!("{}  {}: {}\n", pad, quote(&key), quote(&help(a))));
+        }
+    }
+
+    let mut flag_values = vec![];
+    for a in flags.iter().filter(|a| a.get_action().takes_values()) {
+        let values = carapace_values(a);
+        if !values.is_empty() {
+            flag_values.push((a.get_long().unwrap_or(a.get_id().as_str()), values));
+        }
+    }
+    let mut positional = vec![];
+    let mut positional_any = None;
+    for a in cmd.get_positionals().filter(|a| !a.is_hide_set()) {
+        let values = carapace_values(a);
+        if is_variadic(a) {
+            positional_any = Some(values).filter(|v| !v.is_empty());
+            break;
+        }
+        positional.push(values);
+    }
+    let has_positional = positional.iter().any(|v| !v.is_empty());
+    if !flag_values.is_empty() || has_positional || positional_any.is_some() {
+        out.push_str(&format!("{}completion:\n", pad));
+        if !flag_values.is_empty() {
+            out.push_str(&format!("{}  flag:\n", pad));
+            for (name, values) in flag_values {
+                out.push_str(&format!("{}    {}: [{}]\n", pad, name, values.join(", ")));
+            }
+        }
+        if has_positional {
+            out.push_str(&format!("{}  positional:\n", pad));
+            for values in positional {
+                out.push_str(&format!("{}    - [{}]\n", pad, values.join(", ")));
+            }
+        }
+        if let Some(values) = positional_any {
+            out.push_str(&format!(
+                "{}  positionalany: [{}]\n",
+                pad,
+                values.join(", ")
+            ));
+        }
+    }
+
+    let subcommands: Vec<_> = subcommands(cmd).collect();
+    if !subcommands.is_empty() {
+        out.push_str(&format!("{}commands:\n", pad));
+        for sub in subcommands {
+            out.push_str(&format!("{}  - name: {}\n", pad, quote(sub.get_name())));
+            carapace_command(out, sub, indent + 4);
+        }
+    }
+}
+
+/// The quoted carapace completion values for an argument.
+fn carapace_values(arg: &Arg) -> Vec<String> {
+    if completes_binaries(arg) {
+        return vec![quote(&format!("$({})", NAMES_COMMAND.join(" ")))];
+    }
+    arg.get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| quote(v.get_name()))
+        .collect()
+}
+
+/// Renders a carapace yaml spec for the `cargo hoist` command.
+pub fn carapace(cmd: &Command) -> String {
+    let mut out =
+        String::from("# yaml-language-server: $schema=https://carapace.sh/schemas/command.json\n");
+    out.push_str("name: \"cargo-hoist\"\n");
+    carapace_command(&mut out, cmd, 0);
+    out
+}
+
+/// Prints the completion spec in the given format.
+pub fn print_spec(cmd: &Command, format: SpecFormat) -> Result<()> {
+    match format {
+        SpecFormat::Fig => print!("{}", fig(cmd)?),
+        SpecFormat::Carapace => print!("{}", carapace(cmd)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("hoist")
+            .about("Hoist binaries")
+            .arg(
+                Arg::new("verbosity")
+                    .short('v')
+                    .long("verbosity")
+                    .action(ArgAction::Count)
+                    .help("Verbosity level"),
+            )
+            .subcommand(
+                Command::new("hoist")
+                    .about("Hoist dependencies")
+                    .arg(Arg::new("bins").num_args(1..).help("Binaries"))
+                    .arg(
+                        Arg::new("shim")
+                            .long("shim")
+                            .action(ArgAction::SetTrue)
+                            .help("Write shims"),
+                    ),
+            )
+            .subcommand(
+                Command::new("sbom").about("Render an sbom").arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["cyclonedx", "spdx"])
+                        .help("The format"),
+                ),
+            )
+    }
+
+    #[test]
+    fn test_fig() {
+        let spec = fig(&command()).unwrap();
+        let json = spec
+            .strip_prefix("const completionSpec = ")
+            .and_then(|s| s.strip_suffix(";\n\nexport default completionSpec;\n"))
+            .unwrap();
+        let value = crate::json::parse(json).unwrap();
+        assert_eq!(value["name"].as_str(), Some("cargo-hoist"));
+        assert_eq!(value["options"][0]["isRepeatable"].as_bool(), Some(true));
+        let hoist = &value["subcommands"][0];
+        assert_eq!(hoist["args"][0]["isVariadic"].as_bool(), Some(true));
+        assert_eq!(
+            hoist["args"][0]["generators"]["script"][2].as_str(),
+            Some("list")
+        );
+        let format = &value["subcommands"][1]["options"][0]["args"];
+        assert_eq!(format["suggestions"][1].as_str(), Some("spdx"));
+    }
+
+    #[test]
+    fn test_carapace() {
+        let spec = carapace(&command());
+        assert!(spec.contains("  \"-v, --verbosity*\": \"Verbosity level\"\n"));
+        assert!(spec.contains("  - name: \"hoist\"\n"));
+        assert!(spec.contains("      positionalany: [\"$(cargo hoist list --names)\"]\n"));
+        assert!(spec.contains("      \"--format=\": \"The format\"\n"));
+        assert!(spec.contains("        format: [\"cyclonedx\", \"spdx\"]\n"));
+    }
+}
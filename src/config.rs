@@ -0,0 +1,241 @@
+//! Hoist Config
+//!
+//! User configuration stored in `~/.hoist/config.toml`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::instrument;
+
+use crate::binaries::CopyStrategy;
+use crate::context::HoistContext;
+use crate::store::TomlStore;
+
+/// The action run by a bare `cargo hoist` invocation with no subcommand.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultAction {
+    /// Register the current project's binaries
+    #[default]
+    Install,
+    /// List the registered binaries
+    List,
+    /// Print the command line help
+    Help,
+}
+
+impl DefaultAction {
+    /// Returns if the action is [DefaultAction::Install].
+    pub fn is_install(&self) -> bool {
+        *self == DefaultAction::Install
+    }
+}
+
+/// Where the registry is stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// The `~/.hoist/registry.toml` file
+    #[default]
+    Toml,
+    /// The `~/.hoist/registry.db` SQLite database, which requires the
+    /// `sqlite` feature
+    Sqlite,
+    /// The `~/.hoist/registry.d/` directory, with a file per binary, for
+    /// syncing the registry between machines
+    Dir,
+}
+
+impl StoreKind {
+    /// Returns if the store is [StoreKind::Toml].
+    pub fn is_toml(&self) -> bool {
+        *self == StoreKind::Toml
+    }
+}
+
+/// Hoist Config
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HoistConfig {
+    /// The maximum number of worker threads used for scanning, hashing and copying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+    /// The action run when no subcommand is given.
+    #[serde(default, skip_serializing_if = "DefaultAction::is_install")]
+    pub default_action: DefaultAction,
+    /// The number of registered builds kept per binary name. Older builds are
+    /// dropped from the registry on registration. Keeps every build if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+    /// The number of previous builds kept per binary when it is rebuilt and
+    /// registered again from the same path. Defaults to [DEFAULT_HISTORY].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<usize>,
+    /// Where the registry is stored. The TOML file is imported into a new
+    /// SQLite database the first time it is opened.
+    #[serde(default, skip_serializing_if = "StoreKind::is_toml")]
+    pub store: StoreKind,
+    /// Whether the toml and dir stores write paths under the home directory
+    /// as `~/...`, expanded again on load, so a synced registry works under
+    /// a different home directory.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub portable: bool,
+    /// How long binaries are kept after they were last used, e.g. `30d`.
+    /// Expired binaries are hidden from `list` and `hoist`, and removed by
+    /// `gc`. Binaries never expire if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    /// How `hoist` places binaries into the working directory, e.g.
+    /// `reflink` to share the disk space of the registered binaries on
+    /// copy-on-write filesystems, or `symlink` to link to them.
+    #[serde(default, skip_serializing_if = "CopyStrategy::is_copy")]
+    pub strategy: CopyStrategy,
+    /// The directory `hoist --global` places binaries into, e.g.
+    /// `~/.local/bin`. Defaults to the `bin` directory of the cargo home.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub global_bin: Option<PathBuf>,
+    /// Whether binaries copied into the content store are compressed with
+    /// the `zstd` command, and decompressed again when hoisted.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compress: bool,
+}
+
+/// The number of previous builds kept per binary when `history` is unset.
+pub const DEFAULT_HISTORY: usize = 3;
+
+impl HoistConfig {
+    /// The number of previous builds kept per binary.
+    pub fn history(&self) -> usize {
+        self.history.unwrap_or(DEFAULT_HISTORY)
+    }
+
+    /// The parsed `ttl`, if set.
+    pub fn ttl(&self) -> Result<Option<std::time::Duration>> {
+        self.ttl
+            .as_deref()
+            .map(|ttl| {
+                crate::time::parse_duration(ttl).map_err(|e| anyhow::anyhow!("Invalid ttl: {}", e))
+            })
+            .transpose()
+    }
+
+    /// The directory `hoist --global` places binaries into, with a leading
+    /// `~` expanded to the home directory.
+    pub fn global_bin(&self, ctx: &HoistContext) -> PathBuf {
+        match &self.global_bin {
+            Some(dir) => match dir.strip_prefix("~") {
+                Ok(rest) => ctx.home.join(rest),
+                Err(_) => ctx.resolve(dir),
+            },
+            None => ctx.cargo_home().join("bin"),
+        }
+    }
+
+    /// Returns the context with the registry store selected by the config.
+    pub fn apply_store(&self, ctx: HoistContext) -> Result<HoistContext> {
+        match self.store {
+            StoreKind::Toml if self.portable => {
+                let store = TomlStore::new(ctx.registry_path()).with_home(&ctx.home);
+                Ok(ctx.with_store(std::sync::Arc::new(store)))
+            }
+            StoreKind::Toml => Ok(ctx),
+            StoreKind::Dir => {
+                let mut store = crate::store::DirStore::open(&ctx)?;
+                if self.portable {
+                    store = store.with_home(&ctx.home);
+                }
+                Ok(ctx.with_store(std::sync::Arc::new(store)))
+            }
+            #[cfg(feature = "sqlite")]
+            StoreKind::Sqlite => {
+                let store = crate::sqlite::open(&ctx)?;
+                Ok(ctx.with_store(std::sync::Arc::new(store)))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            StoreKind::Sqlite => {
+                anyhow::bail!(
+                    "store = \"sqlite\" requires cargo-hoist built with the sqlite feature"
+                )
+            }
+        }
+    }
+}
+
+impl HoistConfig {
+    /// Loads the [HoistConfig] from the context's home, falling back to the
+    /// default config if the config file does not exist.
+    #[instrument]
+    pub fn load(ctx: &HoistContext) -> Result<Self> {
+        let path = ctx.config_path();
+        if !path.exists() {
+            return Ok(HoistConfig::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        let config: HoistConfig = toml::from_str(&contents)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+
+        assert_eq!(HoistConfig::load(&ctx).unwrap(), HoistConfig::default());
+
+        std::fs::create_dir_all(ctx.hoist_dir()).unwrap();
+        std::fs::write(ctx.config_path(), "jobs = 2\n").unwrap();
+        assert_eq!(HoistConfig::load(&ctx).unwrap().jobs, Some(2));
+        assert_eq!(
+            HoistConfig::load(&ctx).unwrap().default_action,
+            DefaultAction::Install
+        );
+
+        std::fs::write(ctx.config_path(), "default-action = \"list\"\n").unwrap();
+        assert_eq!(
+            HoistConfig::load(&ctx).unwrap().default_action,
+            DefaultAction::List
+        );
+
+        std::fs::write(ctx.config_path(), "keep = 3\n").unwrap();
+        assert_eq!(HoistConfig::load(&ctx).unwrap().keep, Some(3));
+        assert_eq!(HoistConfig::load(&ctx).unwrap().history(), DEFAULT_HISTORY);
+
+        std::fs::write(ctx.config_path(), "history = 0\n").unwrap();
+        assert_eq!(HoistConfig::load(&ctx).unwrap().history(), 0);
+
+        std::fs::write(ctx.config_path(), "strategy = \"symlink\"\n").unwrap();
+        assert_eq!(
+            HoistConfig::load(&ctx).unwrap().strategy,
+            CopyStrategy::Symlink
+        );
+
+        let config = HoistConfig::load(&ctx).unwrap();
+        assert_eq!(config.global_bin(&ctx), ctx.cargo_home().join("bin"));
+        std::fs::write(ctx.config_path(), "global-bin = \"~/.local/bin\"\n").unwrap();
+        assert_eq!(
+            HoistConfig::load(&ctx).unwrap().global_bin(&ctx),
+            tempdir.path().join(".local/bin")
+        );
+
+        std::fs::write(ctx.config_path(), "ttl = \"7d\"\n").unwrap();
+        assert_eq!(
+            HoistConfig::load(&ctx).unwrap().ttl().unwrap(),
+            Some(std::time::Duration::from_secs(7 * 24 * 60 * 60))
+        );
+        std::fs::write(ctx.config_path(), "ttl = \"soon\"\n").unwrap();
+        assert!(HoistConfig::load(&ctx).unwrap().ttl().is_err());
+
+        std::fs::write(ctx.config_path(), "store = \"sqlite\"\n").unwrap();
+        let config = HoistConfig::load(&ctx).unwrap();
+        assert_eq!(config.store, StoreKind::Sqlite);
+        assert_eq!(
+            config.apply_store(ctx.clone()).is_ok(),
+            cfg!(feature = "sqlite")
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//! Content Store
+//!
+//! Content-addressed copies of registered binaries, stored in
+//! `~/.hoist/store/<sha256>/<name>`, so that a `cargo clean` or deleting the
+//! project doesn't break hoisting the binaries registered from it.
+//!
+//! Each copy keeps the file name of the build it was taken from, inside a
+//! directory named by its sha256 digest, so it runs with the same `argv[0]`.
+//!
+//! With `compress = true` in the user config, copies are compressed with the
+//! `zstd` command into `<name>.zst`, and decompressed when they are hoisted.
+//! Shims run compressed copies from a size-bounded cache of decompressed
+//! copies in `~/.hoist/cache`, outside the store.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::registry::HoistRegistry;
+
+/// The content store directory, `~/.hoist/store`.
+pub fn dir(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("store")
+}
+
+/// The cache of decompressed copies, `~/.hoist/cache`.
+pub fn cache_dir(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("cache")
+}
+
+/// The most disk space the cache of decompressed copies may take, in bytes.
+const CACHE_LIMIT: u64 = 512 * 1024 * 1024;
+
+/// The path of the stored copy of the file `name` with the given sha256 digest.
+pub fn path(ctx: &HoistContext, digest: &str, name: &str) -> PathBuf {
+    dir(ctx).join(digest).join(name)
+}
+
+/// The extension of compressed copies.
+const COMPRESSED_EXT: &str = "zst";
+
+/// Returns if `path` is a compressed copy in a content store: a `.zst` file
+/// in a directory named by a sha256 digest.
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == COMPRESSED_EXT)
+        && path
+            .parent()
+            .and_then(|d| d.file_name())
+            .and_then(|d| d.to_str())
+            .is_some_and(|d| d.len() == 64 && d.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Returns the sha256 digest of the contents of the copy at `path`, read
+/// from the name of its directory.
+pub fn digest(path: &Path) -> Option<&str> {
+    path.parent()?.file_name()?.to_str()
+}
+
+/// Returns the hex digest of the file at `path`, memory-mapping it if it is
+/// a copy in the content store, which is never modified in place. Any other
+/// file may be rewritten while it is hashed, so it is read through a buffer.
+pub fn hash_file(ctx: &HoistContext, path: &Path, algo: HashAlgorithm) -> Result<String> {
+    match path.starts_with(dir(ctx)) {
+        true => crate::hash::hash_file_mmap(path, algo),
+        false => crate::hash::hash_file(path, algo),
+    }
+}
+
+/// Runs `zstd` with `args`, writing its output to `stdout` if given.
+fn zstd(args: &[&std::ffi::OsStr], stdout: Option<&std::fs::File>) -> Result<()> {
+    let mut command = Command::new("zstd");
+    command.arg("-q").args(args);
+    if let Some(file) = stdout {
+        command.stdout(Stdio::from(file.try_clone()?));
+    }
+    let status = command
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to run zstd: {}", e))?;
+    if !status.success() {
+        anyhow::bail!("zstd exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Writes the decompressed contents of the compressed copy at `path` to `file`.
+pub fn decompress_to(path: &Path, file: &std::fs::File) -> Result<()> {
+    zstd(&["-dc".as_ref(), path.as_os_str()], Some(file))
+}
+
+/// Returns the path of the decompressed contents of the binary at `path`,
+/// or `path` itself if it is not compressed.
+///
+/// Compressed copies are decompressed into the cache on first use. The
+/// least recently used copies are evicted once the cache outgrows its limit.
+pub fn materialize(ctx: &HoistContext, path: &Path) -> Result<PathBuf> {
+    if !is_compressed(path) {
+        return Ok(path.to_path_buf());
+    }
+    let (Some(digest), Some(name)) = (digest(path), path.file_stem()) else {
+        anyhow::bail!("Invalid compressed copy {}", path.display());
+    };
+    let plain = cache_dir(ctx).join(digest).join(name);
+    if plain.exists() {
+        // Mark the copy as used, so it is evicted last.
+        std::fs::File::open(&plain)?.set_modified(std::time::SystemTime::now())?;
+        return Ok(plain);
+    }
+    std::fs::create_dir_all(cache_dir(ctx).join(digest))?;
+    let tmp = temp_path(&plain);
+    let file = std::fs::File::create(&tmp)?;
+    decompress_to(path, &file)?;
+    file.set_permissions(std::fs::metadata(path)?.permissions())?;
+    std::fs::rename(&tmp, &plain)?;
+    evict(ctx, CACHE_LIMIT)?;
+    Ok(plain)
+}
+
+/// Removes the least recently used digest directories of the cache until
+/// it takes at most `limit` bytes, keeping the most recently used one.
+fn evict(ctx: &HoistContext, limit: u64) -> Result<()> {
+    let mut cached = vec![];
+    for entry in std::fs::read_dir(cache_dir(ctx))? {
+        let dir = entry?.path();
+        let used = std::fs::read_dir(&dir)?
+            .flatten()
+            .filter_map(|e| e.metadata().and_then(|m| m.modified()).ok())
+            .max();
+        cached.push((used, dir_size(&dir), dir));
+    }
+    cached.sort();
+    let mut total: u64 = cached.iter().map(|(_, size, _)| size).sum();
+    // The most recently used copy is about to run, so it is always kept.
+    cached.pop();
+    for (_, size, dir) in &cached {
+        if total <= limit {
+            break;
+        }
+        std::fs::remove_dir_all(dir)?;
+        total -= size;
+    }
+    Ok(())
+}
+
+/// A unique temporary path next to `path`, so a partial copy is never
+/// picked up in its place.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    static COPIES: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        name,
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Copies the binary into the content store, pointing its location at the
+/// copy and recording the build it was copied from as its origin.
+///
+/// Builds with the same contents share a single copy, which is reused as it
+/// is. Binaries already in the store are left as they are, as are binaries
+/// registered in a local registry, which points into its project instead.
+#[instrument(skip(ctx))]
+pub fn store(ctx: &HoistContext, binary: &mut HoistedBinary) -> Result<()> {
+    let store = dir(ctx);
+    if binary.location.starts_with(&store) || ctx.local_root().is_some() {
+        return Ok(());
+    }
+    let name = binary
+        .location
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or(anyhow::anyhow!(
+            "Failed to read the file name of {}",
+            binary.location.display()
+        ))?
+        .to_string();
+    let digest = crate::hash::hash_file(&binary.location, HashAlgorithm::Sha256)?;
+    let plain = path(ctx, &digest, &name);
+    let compressed = plain.with_file_name(format!("{}.{}", name, COMPRESSED_EXT));
+    let existing = [&plain, &compressed].into_iter().find(|p| p.exists());
+    let stored = match existing.cloned() {
+        Some(stored) => stored,
+        None => {
+            std::fs::create_dir_all(store.join(&digest))?;
+            let (stored, tmp) = match crate::config::HoistConfig::load(ctx)?.compress {
+                true => {
+                    let tmp = temp_path(&compressed);
+                    let args = ["-o".as_ref(), tmp.as_os_str(), binary.location.as_os_str()];
+                    zstd(&args, None)?;
+                    let mode = std::fs::metadata(&binary.location)?.permissions();
+                    std::fs::set_permissions(&tmp, mode)?;
+                    (compressed, tmp)
+                }
+                false => {
+                    let tmp = temp_path(&plain);
+                    std::fs::copy(&binary.location, &tmp)?;
+                    (plain, tmp)
+                }
+            };
+            std::fs::rename(&tmp, &stored)?;
+            stored
+        }
+    };
+    binary.origin = Some(std::mem::replace(&mut binary.location, stored));
+    Ok(())
+}
+
+/// Points a stored binary at a copy of its contents named `name`, so it
+/// runs with that `argv[0]`. The copy is hard linked next to the original,
+/// which is kept for other builds sharing it, and is compressed if the
+/// original is. Binaries outside the content store are left as they are.
+#[instrument(skip(ctx))]
+pub fn rename(ctx: &HoistContext, binary: &mut HoistedBinary, name: &str) -> Result<()> {
+    let store = dir(ctx);
+    let Some(blob_dir) = binary
+        .location
+        .parent()
+        .filter(|d| d.parent() == Some(store.as_path()))
+    else {
+        return Ok(());
+    };
+    let renamed = match is_compressed(&binary.location) {
+        true => blob_dir.join(format!("{}.{}", name, COMPRESSED_EXT)),
+        false => blob_dir.join(name),
+    };
+    if !renamed.exists() && std::fs::hard_link(&binary.location, &renamed).is_err() {
+        std::fs::copy(&binary.location, &renamed)?;
+    }
+    binary.location = renamed;
+    Ok(())
+}
+
+/// Lists the digest directories of the content store that no registered
+/// binary points into.
+pub fn unreferenced(ctx: &HoistContext, registry: &HoistRegistry) -> Result<Vec<PathBuf>> {
+    let store = dir(ctx);
+    if !store.exists() {
+        return Ok(vec![]);
+    }
+    let mut unreferenced = vec![];
+    for entry in std::fs::read_dir(&store)? {
+        let blob_dir = entry?.path();
+        if !registry
+            .binaries
+            .iter()
+            .any(|b| b.location.parent() == Some(blob_dir.as_path()))
+        {
+            unreferenced.push(blob_dir);
+        }
+    }
+    unreferenced.sort();
+    Ok(unreferenced)
+}
+
+/// Returns the total size in bytes of the files in `dir`.
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// The disk space used by the content store.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StoreUsage {
+    /// The number of stored copies
+    pub blobs: usize,
+    /// The total size of the stored copies in bytes
+    pub bytes: u64,
+    /// The size in bytes of the copies no registered binary points at
+    pub unreferenced: u64,
+}
+
+/// Measures the disk space used by the content store.
+pub fn usage(ctx: &HoistContext, registry: &HoistRegistry) -> Result<StoreUsage> {
+    let store = dir(ctx);
+    if !store.exists() {
+        return Ok(StoreUsage::default());
+    }
+    let mut usage = StoreUsage::default();
+    for entry in std::fs::read_dir(&store)? {
+        usage.blobs += 1;
+        usage.bytes += dir_size(&entry?.path());
+    }
+    usage.unreferenced = unreferenced(ctx, registry)?
+        .iter()
+        .map(|d| dir_size(d))
+        .sum();
+    Ok(usage)
+}
+
+/// Removes the stored copies no registered binary points at.
+#[instrument(skip(ctx))]
+pub fn gc(ctx: &HoistContext, dry_run: bool, quiet: bool) -> Result<()> {
+    let _lock = match dry_run {
+        true => None,
+        false => {
+            HoistRegistry::create_registry_with(ctx, quiet)?;
+            Some(HoistRegistry::lock(ctx)?)
+        }
+    };
+    let registry = HoistRegistry::load_dry_run(ctx)?;
+    let unreferenced = unreferenced(ctx, &registry)?;
+    let mut freed = 0;
+    for blob_dir in &unreferenced {
+        freed += dir_size(blob_dir);
+        if !dry_run {
+            std::fs::remove_dir_all(blob_dir)?;
+        }
+    }
+    if !quiet {
+        let verb = if dry_run { "Would remove" } else { "Removed" };
+        HoistRegistry::print_color(
+            &format!(
+                "{} {} unreferenced binaries, freeing {}",
+                verb,
+                unreferenced.len(),
+                crate::utils::format_bytes(freed)
+            ),
+            Color::Green,
+            true,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_store() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let build = tempdir.path().join("target/release/tool");
+        std::fs::create_dir_all(build.parent().unwrap()).unwrap();
+        std::fs::write(&build, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&build, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut binary = HoistedBinary::new("tool", build.clone());
+        store(&ctx, &mut binary).unwrap();
+        let digest = crate::hash::hash_file(&build, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(binary.location, path(&ctx, &digest, "tool"));
+        assert_eq!(binary.origin, Some(build.clone()));
+        assert!(!binary.is_stale());
+
+        std::fs::remove_file(&build).unwrap();
+        assert!(!binary.is_stale());
+        let stored = binary.clone();
+        store(&ctx, &mut binary).unwrap();
+        assert_eq!(binary.location, stored.location);
+
+        let mut registry = HoistRegistry::default();
+        assert_eq!(
+            unreferenced(&ctx, &registry).unwrap(),
+            vec![dir(&ctx).join(&digest)]
+        );
+        let stored_size = "#!/bin/sh\n".len() as u64;
+        assert_eq!(
+            usage(&ctx, &registry).unwrap(),
+            StoreUsage {
+                blobs: 1,
+                bytes: stored_size,
+                unreferenced: stored_size,
+            }
+        );
+        registry.insert(binary.clone());
+        assert!(unreferenced(&ctx, &registry).unwrap().is_empty());
+
+        let mut renamed = binary.clone();
+        rename(&ctx, &mut renamed, "other").unwrap();
+        assert_eq!(renamed.location, path(&ctx, &digest, "other"));
+        assert!(binary.location.exists() && renamed.location.exists());
+        assert_eq!(usage(&ctx, &registry).unwrap().unreferenced, 0);
+    }
+
+    #[test]
+    fn test_compressed_store() {
+        if Command::new("zstd").arg("--version").output().is_err() {
+            return;
+        }
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        std::fs::create_dir_all(ctx.hoist_dir()).unwrap();
+        std::fs::write(ctx.hoist_dir().join("config.toml"), "compress = true\n").unwrap();
+        let build = tempdir.path().join("target/release/tool");
+        std::fs::create_dir_all(build.parent().unwrap()).unwrap();
+        let contents = "#!/bin/sh\necho tool\n".repeat(1000);
+        std::fs::write(&build, &contents).unwrap();
+        std::fs::set_permissions(&build, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut binary = HoistedBinary::new("tool", build.clone());
+        store(&ctx, &mut binary).unwrap();
+        let digest = crate::hash::hash_file(&build, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(binary.location, path(&ctx, &digest, "tool.zst"));
+        assert!(is_compressed(&binary.location));
+        assert!(std::fs::metadata(&binary.location).unwrap().len() < contents.len() as u64);
+        assert!(!binary.is_stale());
+
+        let dest = tempdir.path().join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let strategy = crate::binaries::CopyStrategy::Symlink;
+        assert!(!binary.is_up_to_date(&dest, strategy).unwrap());
+        assert_eq!(
+            binary
+                .hoist_to_dir(&dest, strategy, crate::binaries::Preserve::Times)
+                .unwrap(),
+            crate::binaries::CopyStrategy::Copy
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("tool")).unwrap(),
+            contents
+        );
+        assert!(binary.is_up_to_date(&dest, strategy).unwrap());
+
+        let plain = materialize(&ctx, &binary.location).unwrap();
+        assert_eq!(plain, cache_dir(&ctx).join(&digest).join("tool"));
+        assert_eq!(std::fs::read_to_string(plain).unwrap(), contents);
+        assert!(!path(&ctx, &digest, "tool").exists());
+    }
+
+    #[test]
+    fn test_evict() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let now = std::time::SystemTime::now();
+        for (i, digest) in ["a", "b", "c"].iter().enumerate() {
+            let copy = cache_dir(&ctx).join(digest).join("tool");
+            std::fs::create_dir_all(copy.parent().unwrap()).unwrap();
+            std::fs::write(&copy, "0123456789").unwrap();
+            let used = now - std::time::Duration::from_secs(60 * (3 - i as u64));
+            std::fs::File::open(&copy)
+                .unwrap()
+                .set_modified(used)
+                .unwrap();
+        }
+
+        evict(&ctx, 20).unwrap();
+        assert!(!cache_dir(&ctx).join("a").exists());
+        assert!(cache_dir(&ctx).join("b").exists());
+        evict(&ctx, 0).unwrap();
+        assert!(!cache_dir(&ctx).join("b").exists());
+        assert!(cache_dir(&ctx).join("c").exists());
+    }
+}
@@ -0,0 +1,337 @@
+//! Context
+//!
+//! The directories hoist operations resolve their paths against.
+//!
+//! Library functions take a [HoistContext] instead of reading `HOME` or the
+//! process working directory, so they can run concurrently against
+//! different homes and projects in the same process.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::registry::HoistRegistry;
+use crate::store::{RegistryStore, TomlStore};
+
+/// The home and working directories a hoist operation runs in.
+#[derive(Debug, Clone)]
+pub struct HoistContext {
+    /// The user's home directory, containing the `.hoist/` directory
+    pub home: PathBuf,
+    /// The working directory binaries are hoisted into and projects are
+    /// discovered from
+    pub cwd: PathBuf,
+    /// The registry store, if not the registry file in the home directory
+    store: Option<Arc<dyn RegistryStore>>,
+    /// The project root of the local registry in use, if any
+    local: Option<PathBuf>,
+    /// The hoist directory, if not `.hoist/` in the home directory
+    hoist_dir: Option<PathBuf>,
+    /// Whether a corrupt registry is an error rather than salvaged
+    strict: bool,
+    /// The `CARGO_TARGET_DIR` in effect, resolved against the working
+    /// directory
+    target_dir: Option<PathBuf>,
+    /// The `CARGO_HOME` in effect
+    cargo_home: Option<PathBuf>,
+    /// The registry as last loaded from or saved to the store
+    snapshot: Arc<Snapshot>,
+}
+
+/// The registry as last loaded from or saved to a store, that saves journal
+/// their changes against instead of loading the store again.
+#[derive(Default)]
+struct Snapshot(Mutex<Option<HoistRegistry>>);
+
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Snapshot")
+    }
+}
+
+impl PartialEq for HoistContext {
+    fn eq(&self, other: &Self) -> bool {
+        let same_store = match (&self.store, &other.store) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        self.home == other.home
+            && self.cwd == other.cwd
+            && self.local == other.local
+            && self.hoist_dir == other.hoist_dir
+            && self.strict == other.strict
+            && self.target_dir == other.target_dir
+            && self.cargo_home == other.cargo_home
+            && same_store
+    }
+}
+
+impl Eq for HoistContext {}
+
+impl HoistContext {
+    /// Creates a context for the given home and working directories.
+    pub fn new(home: impl Into<PathBuf>, cwd: impl Into<PathBuf>) -> Self {
+        Self {
+            home: home.into(),
+            cwd: cwd.into(),
+            store: None,
+            local: None,
+            hoist_dir: None,
+            strict: false,
+            target_dir: None,
+            cargo_home: None,
+            snapshot: Default::default(),
+        }
+    }
+
+    /// Returns a copy of the context keeping its state, the registry,
+    /// config and content store, in `dir` instead of `~/.hoist`.
+    pub fn with_hoist_dir(&self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            hoist_dir: Some(dir.into()),
+            snapshot: Default::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the context honoring the `HOIST_HOME` environment
+    /// variable, if set, as its hoist directory.
+    pub fn with_hoist_home_env(self) -> Self {
+        match std::env::var_os("HOIST_HOME").filter(|d| !d.is_empty()) {
+            Some(dir) => self.with_hoist_dir(dir),
+            None => self,
+        }
+    }
+
+    /// Returns a copy of the context honoring the `CARGO_TARGET_DIR` and
+    /// `CARGO_HOME` environment variables, if set, resolving them against
+    /// the working directory as cargo does.
+    pub fn with_cargo_env(self) -> Self {
+        let var = |name| std::env::var_os(name).filter(|d| !d.is_empty());
+        let ctx = match var("CARGO_HOME") {
+            Some(dir) => self.with_cargo_home(self.resolve(Path::new(&dir))),
+            None => self,
+        };
+        match var("CARGO_TARGET_DIR") {
+            Some(dir) => ctx.with_target_dir(ctx.resolve(Path::new(&dir))),
+            None => ctx,
+        }
+    }
+
+    /// Returns a copy of the context building projects into `dir`, as
+    /// `CARGO_TARGET_DIR` does, instead of their own target directories.
+    pub fn with_target_dir(&self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            target_dir: Some(dir.into()),
+            ..self.clone()
+        }
+    }
+
+    /// The target directory every project builds into, if overridden.
+    pub fn target_dir(&self) -> Option<&Path> {
+        self.target_dir.as_deref()
+    }
+
+    /// Returns a copy of the context using `dir` as the cargo home.
+    pub fn with_cargo_home(&self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cargo_home: Some(dir.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the context failing on a corrupt registry instead
+    /// of salvaging its valid entries.
+    pub fn with_strict(&self, strict: bool) -> Self {
+        Self {
+            strict,
+            ..self.clone()
+        }
+    }
+
+    /// Whether a corrupt registry is an error rather than salvaged.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns a copy of the context persisting the registry in `store`
+    /// instead of the registry file.
+    pub fn with_store(&self, store: Arc<dyn RegistryStore>) -> Self {
+        Self {
+            store: Some(store),
+            snapshot: Default::default(),
+            ..self.clone()
+        }
+    }
+
+    /// The store the registry is loaded from and saved to.
+    pub fn store(&self) -> Arc<dyn RegistryStore> {
+        match &self.store {
+            Some(store) => Arc::clone(store),
+            None => Arc::new(TomlStore::new(self.registry_path())),
+        }
+    }
+
+    /// Takes the registry last loaded from or saved to the store through
+    /// this context, or a copy of it, if any.
+    pub(crate) fn take_snapshot(&self) -> Option<HoistRegistry> {
+        self.snapshot
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Remembers `registry` as the registry in the store.
+    pub(crate) fn set_snapshot(&self, registry: &HoistRegistry) {
+        *self.snapshot.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(registry.clone());
+    }
+
+    /// Returns a copy of the context using the project-scoped registry of
+    /// the project at `root`, `<root>/.hoist/registry.toml`, whose paths are
+    /// stored relative to `root`.
+    pub fn with_local_registry(&self, root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let root = root.canonicalize().unwrap_or(root);
+        let store = TomlStore::new(local_registry_path(&root)).relative_to(&root);
+        Self {
+            store: Some(Arc::new(store)),
+            local: Some(root),
+            snapshot: Default::default(),
+            ..self.clone()
+        }
+    }
+
+    /// The project root of the local registry in use, if any.
+    pub fn local_root(&self) -> Option<&Path> {
+        self.local.as_deref()
+    }
+
+    /// Finds the nearest directory from the working directory up with a
+    /// local registry, stopping before the home directory, whose
+    /// `.hoist/` holds the global registry, and skipping the hoist directory.
+    pub fn find_local_root(&self) -> Option<PathBuf> {
+        let hoist_dir = self.hoist_dir();
+        self.cwd
+            .ancestors()
+            .take_while(|dir| *dir != self.home)
+            .filter(|dir| dir.join(".hoist") != hoist_dir)
+            .find(|dir| local_registry_path(dir).is_file())
+            .map(Path::to_path_buf)
+    }
+
+    /// Creates a context from the `HOME`, `HOIST_HOME`, `CARGO_HOME` and
+    /// `CARGO_TARGET_DIR` environment variables and the process working
+    /// directory.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self::new(std::env::var("HOME")?, std::env::current_dir()?)
+            .with_hoist_home_env()
+            .with_cargo_env())
+    }
+
+    /// Returns a copy of the context with a different working directory.
+    pub fn with_cwd(&self, cwd: impl Into<PathBuf>) -> Self {
+        Self {
+            cwd: cwd.into(),
+            ..self.clone()
+        }
+    }
+
+    /// The path to the hoist directory, `~/.hoist` unless overridden.
+    pub fn hoist_dir(&self) -> PathBuf {
+        match &self.hoist_dir {
+            Some(dir) => dir.clone(),
+            None => self.home.join(".hoist"),
+        }
+    }
+
+    /// The path to the hoist registry file.
+    pub fn registry_path(&self) -> PathBuf {
+        self.hoist_dir().join("registry.toml")
+    }
+
+    /// The directory registry snapshots are written to.
+    pub fn backups_dir(&self) -> PathBuf {
+        self.hoist_dir().join("backups")
+    }
+
+    /// The path to the hook identifier file, which marks the pre-cargo hook as installed.
+    pub fn hook_identifier(&self) -> PathBuf {
+        self.hoist_dir().join("hook")
+    }
+
+    /// The path to the hoist config file.
+    pub fn config_path(&self) -> PathBuf {
+        self.hoist_dir().join("config.toml")
+    }
+
+    /// The cargo home directory, `CARGO_HOME` if set or `~/.cargo` otherwise.
+    pub fn cargo_home(&self) -> PathBuf {
+        self.cargo_home
+            .clone()
+            .unwrap_or_else(|| self.home.join(".cargo"))
+    }
+
+    /// Resolves `path` against the working directory.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.cwd.join(path)
+    }
+}
+
+/// The path of the local registry of the project at `root`.
+pub fn local_registry_path(root: &Path) -> PathBuf {
+    root.join(".hoist").join("registry.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        let ctx = HoistContext::new("/home/u", "/work");
+        assert_eq!(
+            ctx.registry_path(),
+            PathBuf::from("/home/u/.hoist/registry.toml")
+        );
+        assert_eq!(
+            ctx.config_path(),
+            PathBuf::from("/home/u/.hoist/config.toml")
+        );
+        assert_eq!(ctx.resolve(Path::new("bin")), PathBuf::from("/work/bin"));
+        assert_eq!(ctx.resolve(Path::new("/abs")), PathBuf::from("/abs"));
+        assert_eq!(ctx.with_cwd("/other").home, PathBuf::from("/home/u"));
+
+        let ctx = ctx.with_hoist_dir("/ci/state");
+        assert_eq!(
+            ctx.registry_path(),
+            PathBuf::from("/ci/state/registry.toml")
+        );
+        assert_eq!(
+            ctx.with_cwd("/other").hoist_dir(),
+            PathBuf::from("/ci/state")
+        );
+    }
+
+    #[test]
+    fn test_find_local_root() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let home = tempdir.path().join("home");
+        let project = home.join("project");
+        std::fs::create_dir_all(project.join("crates/a")).unwrap();
+        std::fs::create_dir_all(home.join(".hoist")).unwrap();
+        std::fs::write(home.join(".hoist/registry.toml"), "").unwrap();
+        let ctx = HoistContext::new(&home, project.join("crates/a"));
+        assert_eq!(ctx.find_local_root(), None);
+
+        std::fs::create_dir_all(project.join(".hoist")).unwrap();
+        std::fs::write(local_registry_path(&project), "").unwrap();
+        assert_eq!(ctx.find_local_root(), Some(project.clone()));
+        let local = ctx.with_local_registry(&project);
+        assert_eq!(
+            local.local_root(),
+            Some(project.canonicalize().unwrap().as_path())
+        );
+        assert_ne!(local, ctx);
+    }
+}
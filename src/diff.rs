@@ -0,0 +1,237 @@
+//! Diff
+//!
+//! Compares the registry against another registry file, such as a backup or
+//! a registry from another machine, listing the binaries and aliases that
+//! were added, removed or changed since.
+//!
+//! As in the [journal](crate::journal), usage statistics are not compared:
+//! they change on every hoist.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// The output format of a registry diff.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// A json document, for tooling
+    Json,
+}
+
+/// A binary registered in both registries with different metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    /// The binary name
+    pub name: String,
+    /// The binary location
+    pub location: PathBuf,
+    /// The names of the changed fields
+    pub fields: Vec<String>,
+    /// The entry in the old registry
+    pub from: HoistedBinary,
+    /// The entry in the new registry
+    pub to: HoistedBinary,
+}
+
+/// The differences between two registries.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct RegistryDiff {
+    /// Binaries only in the new registry
+    pub added: Vec<HoistedBinary>,
+    /// Binaries only in the old registry
+    pub removed: Vec<HoistedBinary>,
+    /// Binaries in both registries with different metadata
+    pub changed: Vec<Change>,
+    /// Aliases added or retargeted, by their new target
+    pub aliases_added: BTreeMap<String, String>,
+    /// Aliases removed or retargeted, by their old target
+    pub aliases_removed: BTreeMap<String, String>,
+}
+
+impl RegistryDiff {
+    /// Returns if the registries are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.aliases_added.is_empty()
+            && self.aliases_removed.is_empty()
+    }
+}
+
+/// The serialized fields of an entry, without its usage statistics.
+fn fields(b: &HoistedBinary) -> toml::Table {
+    let mut table = match toml::Value::try_from(b) {
+        Ok(toml::Value::Table(t)) => t,
+        _ => toml::Table::new(),
+    };
+    table.remove("last_hoisted_at");
+    table.remove("hoist_count");
+    table
+}
+
+/// Compares the `old` registry to the `new` one.
+pub fn diff(old: &HoistRegistry, new: &HoistRegistry) -> RegistryDiff {
+    let by_identity =
+        |a: &HoistedBinary, b: &HoistedBinary| (&a.name, &a.location).cmp(&(&b.name, &b.location));
+    let mut diff = RegistryDiff {
+        added: new.binaries.difference(&old.binaries).cloned().collect(),
+        removed: old.binaries.difference(&new.binaries).cloned().collect(),
+        ..Default::default()
+    };
+    for to in &new.binaries {
+        let Some(from) = old.binaries.get(to) else {
+            continue;
+        };
+        let (before, after) = (fields(from), fields(to));
+        let mut changed: Vec<_> = before
+            .keys()
+            .chain(after.keys().filter(|k| !before.contains_key(*k)))
+            .filter(|k| before.get(*k) != after.get(*k))
+            .cloned()
+            .collect();
+        if !changed.is_empty() {
+            changed.sort();
+            diff.changed.push(Change {
+                name: to.name.clone(),
+                location: to.location.clone(),
+                fields: changed,
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+    }
+    for (alias, target) in &old.aliases {
+        if new.aliases.get(alias) != Some(target) {
+            diff.aliases_removed.insert(alias.clone(), target.clone());
+        }
+    }
+    for (alias, target) in &new.aliases {
+        if old.aliases.get(alias) != Some(target) {
+            diff.aliases_added.insert(alias.clone(), target.clone());
+        }
+    }
+    diff.added.sort_by(by_identity);
+    diff.removed.sort_by(by_identity);
+    diff.changed
+        .sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+    diff
+}
+
+/// Prints the changes from the registry file at `other`, or the newest
+/// backup, to the registry.
+#[instrument(skip(ctx))]
+pub fn run(ctx: &HoistContext, other: Option<&Path>, format: DiffFormat) -> Result<()> {
+    let path = match other {
+        Some(other) => ctx.resolve(other),
+        None => match crate::backup::list(ctx)?.pop() {
+            Some(latest) => latest,
+            None => anyhow::bail!(
+                "No backups found in {}, name a registry file to compare against",
+                ctx.backups_dir().display()
+            ),
+        },
+    };
+    let old = crate::export::read(ctx, &path)?;
+    let new = HoistRegistry::load(ctx)?;
+    let diff = diff(&old, &new);
+    match format {
+        DiffFormat::Json => println!("{}", crate::json::to_string_pretty(&diff)?),
+        DiffFormat::Text => print(&diff, &path)?,
+    }
+    Ok(())
+}
+
+/// Prints the diff as `+`, `-` and `~` lines.
+fn print(diff: &RegistryDiff, old: &Path) -> Result<()> {
+    if diff.is_empty() {
+        let same = format!("No changes since {}", old.display());
+        return HoistRegistry::print_color(&same, Color::Green, true);
+    }
+    let line = |sign: &str, color: Color, b: &HoistedBinary, rest: &str| -> Result<()> {
+        HoistRegistry::print_color(&format!("{} ", sign), color, false)?;
+        HoistRegistry::print_color(&b.display_name(), Color::Magenta, false)?;
+        HoistRegistry::print_color(
+            &format!(" ({}){}", b.location.display(), rest),
+            Color::Cyan,
+            true,
+        )
+    };
+    for b in &diff.added {
+        line("+", Color::Green, b, "")?;
+    }
+    for b in &diff.removed {
+        line("-", Color::Red, b, "")?;
+    }
+    for c in &diff.changed {
+        line(
+            "~",
+            Color::Yellow,
+            &c.to,
+            &format!(": {}", c.fields.join(", ")),
+        )?;
+    }
+    for (alias, target) in &diff.aliases_removed {
+        HoistRegistry::print_color("- ", Color::Red, false)?;
+        HoistRegistry::print_color(&format!("alias {} -> {}", alias, target), Color::Cyan, true)?;
+    }
+    for (alias, target) in &diff.aliases_added {
+        HoistRegistry::print_color("+ ", Color::Green, false)?;
+        HoistRegistry::print_color(&format!("alias {} -> {}", alias, target), Color::Cyan, true)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let mut old = HoistRegistry::default();
+        old.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        old.insert(HoistedBinary::new("b", PathBuf::from("/p/b")));
+        old.aliases.insert("x".to_string(), "a".to_string());
+        old.aliases.insert("y".to_string(), "a".to_string());
+
+        let mut new = old.clone();
+        new.binaries
+            .remove(&HoistedBinary::new("b", PathBuf::from("/p/b")));
+        new.insert(HoistedBinary::new("c", PathBuf::from("/p/c")));
+        new.binaries.replace(HoistedBinary {
+            tags: vec!["tooling".to_string()],
+            hoist_count: 5,
+            ..HoistedBinary::new("a", PathBuf::from("/p/a"))
+        });
+        new.aliases.insert("y".to_string(), "c".to_string());
+
+        let diff = diff(&old, &new);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "c");
+        assert_eq!(diff.removed[0].name, "b");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].fields, vec!["tags"]);
+        assert_eq!(
+            diff.aliases_added,
+            BTreeMap::from([("y".to_string(), "c".to_string())])
+        );
+        assert_eq!(
+            diff.aliases_removed,
+            BTreeMap::from([("y".to_string(), "a".to_string())])
+        );
+        assert!(super::diff(&new, &new).is_empty());
+
+        let json = crate::json::parse(&crate::json::to_string(&diff).unwrap()).unwrap();
+        assert_eq!(json["changed"][0]["fields"][0].as_str(), Some("tags"));
+    }
+}
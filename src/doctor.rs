@@ -0,0 +1,458 @@
+//! Doctor
+//!
+//! Diagnoses the hoist environment: the home directory, the hoist directory
+//! and registry, the shell hook, and the registered binaries and shims, with
+//! a suggested fix for every problem found.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// The outcome of a [Check].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    /// Nothing to fix
+    Ok,
+    /// Hoist works, but something is likely to trip it up
+    Warning,
+    /// Hoist is broken until this is fixed
+    Error,
+}
+
+/// A single diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// What was checked
+    pub name: &'static str,
+    /// The outcome
+    pub status: Status,
+    /// What was found
+    pub detail: String,
+    /// How to fix it, for warnings and errors
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Checks that `HOME` resolves to the context home, and notes XDG
+/// variables hoist does not follow.
+fn check_home(ctx: &HoistContext) -> Check {
+    let name = "home";
+    match std::env::var_os("HOME").map(PathBuf::from) {
+        None => Check::problem(
+            name,
+            Status::Error,
+            "HOME is not set",
+            "export HOME to your home directory",
+        ),
+        Some(home) if !home.is_absolute() || !home.is_dir() => Check::problem(
+            name,
+            Status::Error,
+            format!("HOME is {}, which is not a directory", home.display()),
+            "export HOME as the absolute path of your home directory",
+        ),
+        Some(_) => {
+            let xdg: Vec<_> = ["XDG_CONFIG_HOME", "XDG_DATA_HOME"]
+                .into_iter()
+                .filter(|v| std::env::var_os(v).is_some())
+                .collect();
+            let mut detail = format!("state is kept in {}", ctx.hoist_dir().display());
+            if std::env::var_os("HOIST_HOME").is_some_and(|d| ctx.hoist_dir() == Path::new(&d)) {
+                detail.push_str(", set by HOIST_HOME");
+            }
+            if !xdg.is_empty() {
+                detail.push_str(&format!(", {} is ignored", xdg.join(" and ")));
+            }
+            Check::ok(name, detail)
+        }
+    }
+}
+
+/// Checks that the hoist directory exists and is writable.
+fn check_hoist_dir(ctx: &HoistContext) -> Check {
+    let name = "hoist dir";
+    let dir = ctx.hoist_dir();
+    if !dir.is_dir() {
+        return Check::problem(
+            name,
+            Status::Warning,
+            format!("{} does not exist", dir.display()),
+            "run `cargo hoist init`",
+        );
+    }
+    let probe = dir.join(format!(".doctor.{}", std::process::id()));
+    match std::fs::write(&probe, "") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::ok(name, format!("{} is writable", dir.display()))
+        }
+        Err(e) => Check::problem(
+            name,
+            Status::Error,
+            format!("{} is not writable: {}", dir.display(), e),
+            format!("run `chmod u+rwx {}`", dir.display()),
+        ),
+    }
+}
+
+/// Checks that the registry loads, returning it for the checks of its entries.
+fn check_registry(ctx: &HoistContext) -> (Check, Option<HoistRegistry>) {
+    let name = "registry";
+    let path = match ctx.local_root() {
+        Some(root) => crate::context::local_registry_path(root),
+        None => ctx.registry_path(),
+    };
+    match ctx.store().load() {
+        Ok(registry) => {
+            let mut detail = format!(
+                "{} entries and {} aliases",
+                registry.binaries.len(),
+                registry.aliases.len()
+            );
+            if let Some(root) = ctx.local_root() {
+                detail.push_str(&format!(", using the local registry of {}", root.display()));
+            }
+            (Check::ok(name, detail), Some(registry))
+        }
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            let check = Check::problem(
+                name,
+                Status::Warning,
+                format!("{} does not exist", path.display()),
+                "run `cargo hoist init`",
+            );
+            (check, None)
+        }
+        Err(e) => {
+            let backups = crate::backup::list(ctx).map(|b| b.len()).unwrap_or(0);
+            let fix = match backups {
+                0 => "run any hoist command to salvage its valid entries, \
+                    or `cargo hoist init` after fixing it"
+                    .to_string(),
+                n => format!(
+                    "run `cargo hoist restore` to recover the latest of {} backups",
+                    n
+                ),
+            };
+            let check = Check::problem(
+                name,
+                Status::Error,
+                format!("{} failed to load: {:#}", path.display(), e),
+                fix,
+            );
+            (check, None)
+        }
+    }
+}
+
+/// Checks that the pre-cargo hook of the current shell is installed and
+/// matches the hook this version of hoist installs.
+fn check_shell_hook(ctx: &HoistContext) -> Check {
+    let name = "shell hook";
+    let Ok(shell) = crate::shell::detect_shell() else {
+        return Check::problem(
+            name,
+            Status::Warning,
+            "SHELL is not set",
+            "export SHELL to your shell binary",
+        );
+    };
+    let hook = shell.hook();
+    let config = hook.config_file(&ctx.home);
+    let reinstall = match ctx.hook_identifier().exists() {
+        true => format!(
+            "delete {}, then run `cargo hoist` to reinstall it",
+            ctx.hook_identifier().display()
+        ),
+        false => "run `cargo hoist` in a terminal to install it".to_string(),
+    };
+    match hook.is_installed(&ctx.home) {
+        Ok(true) => Check::ok(name, format!("installed in {}", config.display())),
+        Ok(false) => {
+            let outdated = std::fs::read_to_string(&config)
+                .is_ok_and(|c| c.contains("cargo hoist") || c.contains("cargo-hoist"));
+            match outdated {
+                true => Check::problem(
+                    name,
+                    Status::Warning,
+                    format!("{} has an outdated hoist hook", config.display()),
+                    format!(
+                        "remove the old hook from {}, and {}",
+                        config.display(),
+                        reinstall
+                    ),
+                ),
+                false => Check::problem(
+                    name,
+                    Status::Warning,
+                    format!("not installed in {}", config.display()),
+                    reinstall,
+                ),
+            }
+        }
+        Err(e) => Check::problem(
+            name,
+            Status::Warning,
+            format!("failed to read {}: {}", config.display(), e),
+            format!("check the permissions of {}", config.display()),
+        ),
+    }
+}
+
+/// Checks for registered binaries whose files are missing or not executable.
+fn check_stale(registry: &HoistRegistry) -> Check {
+    let name = "stale entries";
+    let mut stale: Vec<_> = registry
+        .binaries
+        .iter()
+        .filter(|b| b.is_stale())
+        .map(|b| b.name.as_str())
+        .collect();
+    stale.sort();
+    match stale.len() {
+        0 => Check::ok(name, "every registered binary is on disk"),
+        n => Check::problem(
+            name,
+            Status::Warning,
+            format!(
+                "{} entries are missing or not executable: {}",
+                n,
+                stale.join(", ")
+            ),
+            "run `cargo hoist prune`",
+        ),
+    }
+}
+
+/// Checks for binary names with several current builds, which `hoist`
+/// prompts to choose between.
+fn check_duplicates(registry: &HoistRegistry) -> Check {
+    let name = "duplicate names";
+    let mut current = registry.clone();
+    current.trim_history(0);
+    let mut builds: BTreeMap<&str, usize> = BTreeMap::new();
+    for b in &current.binaries {
+        *builds.entry(b.name.as_str()).or_default() += 1;
+    }
+    let duplicates: Vec<_> = builds
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .map(|(name, n)| format!("{} ({} builds)", name, n))
+        .collect();
+    match duplicates.is_empty() {
+        true => Check::ok(name, "every binary name has a single build"),
+        false => Check::problem(
+            name,
+            Status::Warning,
+            duplicates.join(", "),
+            "set `keep = 1` in ~/.hoist/config.toml to keep only the latest build of each",
+        ),
+    }
+}
+
+/// Reads the location a hoist sh shim at `path` runs, if it is one.
+fn shim_target(path: &Path) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > 4096 {
+        return None;
+    }
+    let contents = std::fs::read_to_string(path).ok()?;
+    if !contents.contains("# Generated by cargo-hoist.") {
+        return None;
+    }
+    // The location is single-quoted, with quotes escaped as `'\''`.
+    let quoted = contents.split_once("--from '")?.1;
+    let mut target = String::new();
+    let mut rest = quoted;
+    loop {
+        let (part, after) = rest.split_once('\'')?;
+        target.push_str(part);
+        match after.strip_prefix("\\''") {
+            Some(after) => {
+                target.push('\'');
+                rest = after;
+            }
+            None => return Some(PathBuf::from(target)),
+        }
+    }
+}
+
+/// Checks the hoist shims and session links on `path`.
+fn check_shims(path: &str) -> Check {
+    let name = "shims";
+    let mut shims = 0;
+    let mut broken = vec![];
+    for dir in std::env::split_paths(path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file = entry.path();
+            let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+            let session_link = is_link && dir.components().any(|c| c.as_os_str() == "sessions");
+            if session_link {
+                shims += 1;
+                if !file.exists() {
+                    broken.push(file);
+                }
+            } else if let Some(target) = shim_target(&file) {
+                shims += 1;
+                if !target.exists() {
+                    broken.push(file);
+                }
+            }
+        }
+    }
+    let hoist_on_path = std::env::var_os("CARGO_HOIST").is_some()
+        || std::env::split_paths(path).any(|d| d.join("cargo-hoist").is_file());
+    if !broken.is_empty() {
+        let broken: Vec<_> = broken.iter().map(|p| p.display().to_string()).collect();
+        return Check::problem(
+            name,
+            Status::Warning,
+            format!(
+                "{} shims point at missing binaries: {}",
+                broken.len(),
+                broken.join(", ")
+            ),
+            "delete them, or rewrite them with `cargo hoist hoist --shim <bin>`",
+        );
+    }
+    if shims > 0 && !hoist_on_path {
+        return Check::problem(
+            name,
+            Status::Error,
+            format!("{} shims on PATH, but cargo-hoist is not", shims),
+            "add ~/.cargo/bin to PATH, or export CARGO_HOIST as the path to cargo-hoist",
+        );
+    }
+    Check::ok(name, format!("{} shims on PATH", shims))
+}
+
+/// Runs every diagnostic against the context, checking the shims on the
+/// `PATH`-style list `path`.
+pub fn checks(ctx: &HoistContext, path: &str) -> Vec<Check> {
+    let mut checks = vec![check_home(ctx), check_hoist_dir(ctx)];
+    let (registry_check, registry) = check_registry(ctx);
+    checks.push(registry_check);
+    checks.push(check_shell_hook(ctx));
+    if let Some(registry) = registry {
+        checks.push(check_stale(&registry));
+        checks.push(check_duplicates(&registry));
+    }
+    checks.push(check_shims(path));
+    checks
+}
+
+/// Prints the diagnostics with their fixes, failing if any found an error.
+#[instrument(skip(ctx))]
+pub fn doctor(ctx: &HoistContext, quiet: bool) -> Result<()> {
+    let checks = checks(ctx, &std::env::var("PATH").unwrap_or_default());
+    if !quiet {
+        for check in &checks {
+            let (label, color) = match check.status {
+                Status::Ok => ("[ok]    ", Color::Green),
+                Status::Warning => ("[warn]  ", Color::Yellow),
+                Status::Error => ("[error] ", Color::Red),
+            };
+            HoistRegistry::print_color(label, color, false)?;
+            HoistRegistry::print_color(&format!("{}: ", check.name), Color::Blue, false)?;
+            HoistRegistry::print_color(&check.detail, Color::White, true)?;
+            if let Some(fix) = &check.fix {
+                HoistRegistry::print_color(&format!("        fix: {}", fix), Color::Cyan, true)?;
+            }
+        }
+    }
+    let errors = checks.iter().filter(|c| c.status == Status::Error).count();
+    match errors {
+        0 => Ok(()),
+        n => anyhow::bail!("cargo hoist doctor found {} errors", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::HoistedBinary;
+
+    fn find<'a>(checks: &'a [Check], name: &str) -> &'a Check {
+        checks.iter().find(|c| c.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_checks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let found = checks(&ctx, "");
+        assert_eq!(find(&found, "hoist dir").status, Status::Warning);
+        assert_eq!(find(&found, "registry").status, Status::Warning);
+
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary::new("a", tempdir.path().join("a1")));
+        registry.insert(HoistedBinary::new("a", tempdir.path().join("a2")));
+        registry.save(&ctx).unwrap();
+        let found = checks(&ctx, "");
+        assert_eq!(find(&found, "hoist dir").status, Status::Ok);
+        assert_eq!(find(&found, "registry").status, Status::Ok);
+        assert!(find(&found, "stale entries")
+            .detail
+            .starts_with("2 entries"));
+        assert_eq!(find(&found, "duplicate names").detail, "a (2 builds)");
+
+        std::fs::write(ctx.registry_path(), "binaries = 3").unwrap();
+        let found = checks(&ctx, "");
+        assert_eq!(find(&found, "registry").status, Status::Error);
+        assert!(found.iter().all(|c| c.name != "stale entries"));
+    }
+
+    #[test]
+    fn test_check_shims() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let bin = tempdir.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("tool's"), "").unwrap();
+        HoistedBinary::new("shim", bin.join("tool's"))
+            .shim_to_dir(&bin)
+            .unwrap();
+        assert_eq!(shim_target(&bin.join("shim")), Some(bin.join("tool's")));
+        assert_eq!(shim_target(&bin.join("tool's")), None);
+
+        let path = bin.display().to_string();
+        std::fs::write(bin.join("cargo-hoist"), "").unwrap();
+        assert_eq!(check_shims(&path).status, Status::Ok);
+        std::fs::remove_file(bin.join("tool's")).unwrap();
+        assert_eq!(check_shims(&path).status, Status::Warning);
+    }
+}
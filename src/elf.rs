@@ -0,0 +1,286 @@
+//! Elf
+//!
+//! Reads the libc linkage of Linux ELF binaries: whether they are dynamically
+//! linked against glibc or musl, or statically linked, and the newest
+//! `GLIBC_*` symbol version they require.
+//!
+//! Only the program and section headers are read, so inspecting large
+//! binaries stays cheap enough to run on every registration.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+const PT_INTERP: u32 = 3;
+const SHT_GNU_VERNEED: u32 = 0x6fff_fffe;
+
+/// The libc a binary links against.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LibcFlavor {
+    /// Dynamically linked against glibc
+    Glibc,
+    /// Dynamically linked against musl
+    Musl,
+    /// Statically linked, with no dynamic loader
+    Static,
+}
+
+impl std::fmt::Display for LibcFlavor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibcFlavor::Glibc => write!(f, "glibc"),
+            LibcFlavor::Musl => write!(f, "musl"),
+            LibcFlavor::Static => write!(f, "static"),
+        }
+    }
+}
+
+/// The libc linkage of an ELF binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linkage {
+    /// The dynamic loader requested by the binary's `PT_INTERP` header
+    pub interpreter: Option<PathBuf>,
+    /// The libc flavor
+    pub libc: LibcFlavor,
+    /// The newest `GLIBC_*` symbol version the binary requires, e.g. `2.34`
+    pub min_glibc: Option<String>,
+}
+
+/// Reads fields of an ELF file in its declared class and byte order.
+struct Reader {
+    file: File,
+    is_64: bool,
+    is_le: bool,
+}
+
+impl Reader {
+    fn bytes(&self, offset: u64, len: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0; len];
+        self.file.read_exact_at(&mut buf, offset).ok()?;
+        Some(buf)
+    }
+
+    fn uint(&self, buf: &[u8], at: usize, size: usize) -> Option<u64> {
+        let bytes = buf.get(at..at + size)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = match self.is_le {
+                true => bytes[size - 1 - i],
+                false => bytes[i],
+            };
+            value = (value << 8) | b as u64;
+        }
+        Some(value)
+    }
+
+    /// Reads an address-sized field, 8 bytes for ELF64 and 4 for ELF32.
+    fn word(&self, buf: &[u8], at64: usize, at32: usize) -> Option<u64> {
+        match self.is_64 {
+            true => self.uint(buf, at64, 8),
+            false => self.uint(buf, at32, 4),
+        }
+    }
+
+    /// Reads a nul-terminated string at `offset`.
+    fn string(&self, offset: u64) -> Option<String> {
+        let mut out = vec![];
+        let mut chunk = [0; 64];
+        loop {
+            let n = self
+                .file
+                .read_at(&mut chunk, offset + out.len() as u64)
+                .ok()?;
+            if n == 0 {
+                return None;
+            }
+            match chunk[..n].iter().position(|b| *b == 0) {
+                Some(end) => {
+                    out.extend_from_slice(&chunk[..end]);
+                    return String::from_utf8(out).ok();
+                }
+                None => out.extend_from_slice(&chunk[..n]),
+            }
+            if out.len() > 4096 {
+                return None;
+            }
+        }
+    }
+}
+
+/// Reads the libc linkage of the binary at `path`. Returns [None] if it is
+/// not an ELF file.
+pub fn inspect(path: &Path) -> Option<Linkage> {
+    let file = File::open(path).ok()?;
+    let mut ident = [0; 16];
+    file.read_exact_at(&mut ident, 0).ok()?;
+    if &ident[..4] != b"\x7fELF" {
+        return None;
+    }
+    let reader = Reader {
+        file,
+        is_64: ident[4] == 2,
+        is_le: ident[5] == 1,
+    };
+    let header = reader.bytes(0, if reader.is_64 { 64 } else { 52 })?;
+    let phoff = reader.word(&header, 0x20, 0x1c)?;
+    let shoff = reader.word(&header, 0x28, 0x20)?;
+    let (phentsize, phnum, shentsize, shnum) = match reader.is_64 {
+        true => (0x36, 0x38, 0x3a, 0x3c),
+        false => (0x2a, 0x2c, 0x2e, 0x30),
+    };
+    let phentsize = reader.uint(&header, phentsize, 2)?;
+    let phnum = reader.uint(&header, phnum, 2)?;
+    let shentsize = reader.uint(&header, shentsize, 2)?;
+    let shnum = reader.uint(&header, shnum, 2)?;
+
+    let mut interpreter = None;
+    for i in 0..phnum {
+        let ph = reader.bytes(phoff + i * phentsize, phentsize as usize)?;
+        if reader.uint(&ph, 0, 4)? == PT_INTERP as u64 {
+            let offset = reader.word(&ph, 0x08, 0x04)?;
+            interpreter = reader.string(offset).map(PathBuf::from);
+            break;
+        }
+    }
+
+    let section = |i: u64| reader.bytes(shoff + i * shentsize, shentsize as usize);
+    let mut glibc: Option<String> = None;
+    for i in 0..shnum {
+        let Some(sh) = section(i) else {
+            break;
+        };
+        if reader.uint(&sh, 4, 4) != Some(SHT_GNU_VERNEED as u64) {
+            continue;
+        }
+        let offset = reader.word(&sh, 0x18, 0x10)?;
+        let link = reader.uint(&sh, if reader.is_64 { 0x28 } else { 0x18 }, 4)?;
+        let strtab = reader.word(&section(link)?, 0x18, 0x10)?;
+        // Walk the Elf_Verneed entries and their Elf_Vernaux version names.
+        let mut need = offset;
+        loop {
+            let entry = reader.bytes(need, 16)?;
+            let mut aux = need + reader.uint(&entry, 8, 4)?;
+            for _ in 0..reader.uint(&entry, 2, 2)? {
+                let vernaux = reader.bytes(aux, 16)?;
+                let name = reader.string(strtab + reader.uint(&vernaux, 8, 4)?);
+                if let Some(v) = name.as_deref().and_then(|n| n.strip_prefix("GLIBC_")) {
+                    if glibc
+                        .as_deref()
+                        .is_none_or(|g| version_key(v) > version_key(g))
+                    {
+                        glibc = Some(v.to_string());
+                    }
+                }
+                match reader.uint(&vernaux, 12, 4)? {
+                    0 => break,
+                    next => aux += next,
+                }
+            }
+            match reader.uint(&entry, 12, 4)? {
+                0 => break,
+                next => need += next,
+            }
+        }
+    }
+
+    let libc = match &interpreter {
+        None => LibcFlavor::Static,
+        Some(i) if i.to_string_lossy().contains("ld-musl") => LibcFlavor::Musl,
+        Some(_) => LibcFlavor::Glibc,
+    };
+    Some(Linkage {
+        interpreter,
+        libc,
+        min_glibc: glibc,
+    })
+}
+
+/// Splits a dotted version into its numeric parts for comparison.
+fn version_key(v: &str) -> Vec<u64> {
+    v.split('.').map(|p| p.parse().unwrap_or(0)).collect()
+}
+
+/// The glibc version of the running system, if hoist itself links against glibc.
+pub fn host_glibc() -> Option<String> {
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    {
+        // SAFETY: gnu_get_libc_version returns a pointer to a static string.
+        let version = unsafe { std::ffi::CStr::from_ptr(libc::gnu_get_libc_version()) };
+        version.to_str().ok().map(str::to_string)
+    }
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    {
+        None
+    }
+}
+
+/// Explains why the binary at `path` may fail to run on this system: its
+/// dynamic loader is missing, or it needs a newer glibc than the system's.
+pub fn compatibility_warning(path: &Path) -> Option<String> {
+    let linkage = inspect(path)?;
+    check(&linkage, host_glibc().as_deref(), |p| p.exists())
+}
+
+fn check(linkage: &Linkage, host: Option<&str>, exists: impl Fn(&Path) -> bool) -> Option<String> {
+    if let Some(interpreter) = linkage.interpreter.as_ref().filter(|i| !exists(i)) {
+        return Some(format!(
+            "is linked against {}, but its loader {} is missing on this system",
+            linkage.libc,
+            interpreter.display()
+        ));
+    }
+    match (&linkage.min_glibc, host) {
+        (Some(needed), Some(host)) if version_key(needed) > version_key(host) => Some(format!(
+            "requires glibc {}, but this system has glibc {}",
+            needed, host
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect() {
+        let exe = std::env::current_exe().unwrap();
+        let linkage = inspect(&exe).unwrap();
+        if cfg!(all(target_os = "linux", target_env = "gnu")) {
+            assert_eq!(linkage.libc, LibcFlavor::Glibc);
+            let needed = linkage.min_glibc.unwrap();
+            assert!(version_key(&needed) <= version_key(&host_glibc().unwrap()));
+            assert!(compatibility_warning(&exe).is_none());
+        }
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let script = tempdir.path().join("script");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        assert_eq!(inspect(&script), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let linkage = Linkage {
+            interpreter: Some(PathBuf::from("/lib/ld-musl-x86_64.so.1")),
+            libc: LibcFlavor::Musl,
+            min_glibc: None,
+        };
+        assert!(check(&linkage, Some("2.35"), |_| false)
+            .unwrap()
+            .contains("linked against musl"));
+        assert_eq!(check(&linkage, Some("2.35"), |_| true), None);
+        let linkage = Linkage {
+            interpreter: None,
+            libc: LibcFlavor::Glibc,
+            min_glibc: Some("2.34".to_string()),
+        };
+        assert!(check(&linkage, Some("2.31"), |_| true)
+            .unwrap()
+            .contains("requires glibc 2.34"));
+        assert_eq!(check(&linkage, Some("2.38"), |_| true), None);
+        assert_eq!(check(&linkage, None, |_| true), None);
+    }
+}
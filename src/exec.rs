@@ -0,0 +1,70 @@
+//! Exec
+//!
+//! Running a registered binary with `cargo hoist exec`, which the shims
+//! written by `cargo hoist --shim` run through.
+
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+impl HoistRegistry {
+    /// Executes a registered binary, replacing the current process.
+    ///
+    /// Runs the binary at `from` if provided, as hoist shims do, otherwise the
+    /// latest registered build. Prints a one-line notice to stderr if a newer
+    /// build of the same binary is registered.
+    #[instrument(skip(binary, args))]
+    pub fn exec(
+        ctx: &HoistContext,
+        binary: String,
+        from: Option<PathBuf>,
+        args: Vec<String>,
+    ) -> Result<()> {
+        let select = |name: &str| {
+            HoistRegistry::select(ctx, &crate::store::Filter::name(name)).unwrap_or_default()
+        };
+        let mut registry = select(&binary);
+        let name = registry.resolve_alias(&binary);
+        if name != binary {
+            registry = select(&name);
+        }
+        let target = match from {
+            Some(location) => HoistedBinary::new(binary, location),
+            None => registry
+                .latest(&name)
+                .cloned()
+                .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?,
+        };
+        if let Some(newer) = registry.newer_build(&target) {
+            eprintln!(
+                "cargo-hoist: a newer build of {} is registered at {}, run `cargo hoist hoist {}` to refresh",
+                target.name,
+                newer.location.display(),
+                target.name
+            );
+        }
+        if let Some(warning) = crate::elf::compatibility_warning(&target.location) {
+            eprintln!("cargo-hoist: warning: {} {}", target.name, warning);
+        }
+        // Shims exec on every invocation, so usage is only recorded hourly.
+        if target
+            .last_hoisted_at
+            .is_none_or(|at| crate::time::unix_now().saturating_sub(at) >= 3600)
+        {
+            HoistRegistry::record_hoisted(ctx, std::slice::from_ref(&target), false);
+        }
+
+        use std::os::unix::process::CommandExt;
+        let path = crate::content::materialize(ctx, &target.location)?;
+        let err = std::process::Command::new(&path).args(args).exec();
+        Err(anyhow::anyhow!(
+            "Failed to execute {}: {}",
+            path.display(),
+            err
+        ))
+    }
+}
@@ -29,6 +29,30 @@ pub fn exec_path(exec: &Path) -> Result<String> {
     Ok(binary_name)
 }
 
+/// Sets the permission mode of the file at `path` to `0o755`, keeping any
+/// setuid bits, if it isn't executable, e.g. after a copy onto a filesystem
+/// or with a umask that strips the executable bits. Returns if it was fixed.
+#[instrument]
+pub fn ensure_executable(path: &Path) -> Result<bool> {
+    let mode = std::fs::metadata(path)?.permissions().mode();
+    if mode & 0o111 != 0 {
+        return Ok(false);
+    }
+    let mode = (mode & !0o777) | 0o755;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(true)
+}
+
+/// Strips a cargo metadata hash suffix (`-` followed by 16 hex characters)
+/// from an artifact file name, returning the target stem.
+///
+/// Returns [None] if the file name does not carry a hash suffix.
+pub fn strip_hash_suffix(name: &str) -> Option<&str> {
+    let (stem, hash) = name.rsplit_once('-')?;
+    let is_hash = hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit());
+    (is_hash && !stem.is_empty()).then_some(stem)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,6 +61,16 @@ mod tests {
     use tempfile::TempDir;
 
     #[test]
+    fn test_strip_hash_suffix() {
+        assert_eq!(strip_hash_suffix("my_bin-3fa8c91d0e2b64a7"), Some("my_bin"));
+        assert_eq!(strip_hash_suffix("my-bin-3fa8c91d0e2b64a7"), Some("my-bin"));
+        assert_eq!(strip_hash_suffix("my-bin"), None);
+        assert_eq!(strip_hash_suffix("my-bin-3fa8c91d"), None);
+        assert_eq!(strip_hash_suffix("-3fa8c91d0e2b64a7"), None);
+    }
+
+    #[test]
+    #[allow(clippy::get_first)]
     fn test_exec_path() {
         let tempdir = tempfile::tempdir().unwrap();
         let test_dir = setup_test(&tempdir, "test_exec_path");
@@ -47,6 +81,21 @@ mod tests {
         assert_eq!(bin1, &bin1_exec_path);
     }
 
+    #[test]
+    fn test_ensure_executable() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("binary");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(exec_path(&path).is_err());
+        assert!(ensure_executable(&path).unwrap());
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert!(!ensure_executable(&path).unwrap());
+    }
+
     fn setup_test(tempdir: &TempDir, t: &str) -> PathBuf {
         let test_dir = tempdir.path().join(t);
         std::fs::create_dir(&test_dir).unwrap();
@@ -54,6 +103,7 @@ mod tests {
         test_dir
     }
 
+    #[allow(clippy::suspicious_open_options, clippy::vec_init_then_push)]
     fn create_binaries(p: &Path) -> Vec<String> {
         let target_dir = p.join("target/release/");
         std::fs::create_dir_all(&target_dir).unwrap();
@@ -0,0 +1,292 @@
+//! Export
+//!
+//! Portable copies of the registry, for moving it between machines or
+//! committing it to a dotfiles repository.
+//!
+//! With `--portable`, exported paths under the home directory are written
+//! relative to it, as `~/...`. Imports expand them against the importing
+//! user's home again, and with `--portable` also move paths under another
+//! user's home, e.g. `/home/alice/...`, into the importing user's home.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use toml::Value;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// The file format of an exported registry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// The registry TOML format
+    #[default]
+    Toml,
+    /// Json, with the same layout as the TOML format
+    Json,
+}
+
+impl ExportFormat {
+    /// Guesses the format of an exported file from its extension, falling
+    /// back to its contents.
+    fn detect(path: &Path, contents: &str) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => ExportFormat::Json,
+            Some("toml") => ExportFormat::Toml,
+            _ if contents.trim_start().starts_with('{') => ExportFormat::Json,
+            _ => ExportFormat::Toml,
+        }
+    }
+}
+
+/// Rewrites `path` relative to `home` as `~/...`, if it is under it.
+fn contract(path: &Path, home: &Path) -> PathBuf {
+    match path.strip_prefix(home) {
+        Ok(rest) if home.is_absolute() => Path::new("~").join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Expands a leading `~` or `$HOME` component of `path` to `home`.
+fn expand(path: &Path, home: &Path) -> PathBuf {
+    ["~", "$HOME"]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix).ok())
+        .map(|rest| home.join(rest))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Moves `path` from under another user's home directory, `/home/<user>` or
+/// `/Users/<user>`, to `home`.
+fn rehome(path: &Path, home: &Path) -> PathBuf {
+    if path.starts_with(home) {
+        return path.to_path_buf();
+    }
+    ["/home", "/Users"]
+        .iter()
+        .find_map(|homes| {
+            let mut rest = path.strip_prefix(homes).ok()?.components();
+            rest.next()?;
+            Some(home.join(rest.as_path()))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Maps the location, origin, sources and project path of every binary in the
+/// registry.
+pub(crate) fn map_paths(registry: &HoistRegistry, f: impl Fn(&Path) -> PathBuf) -> HoistRegistry {
+    HoistRegistry {
+        binaries: registry
+            .binaries
+            .iter()
+            .map(|b| {
+                let mut b = b.clone();
+                b.location = f(&b.location);
+                b.origin = b.origin.as_deref().map(&f);
+                b.sources = b.sources.iter().map(|s| f(s)).collect();
+                if let Some(r) = b.registration.as_mut() {
+                    r.project = f(&r.project);
+                }
+                b
+            })
+            .collect(),
+        ..registry.clone()
+    }
+}
+
+/// Returns a copy of the registry with paths under `home` made `~/`-relative.
+pub fn portable(registry: &HoistRegistry, home: &Path) -> HoistRegistry {
+    map_paths(registry, |p| contract(p, home))
+}
+
+/// Returns a copy of a [portable] registry with its paths expanded against `home`.
+pub fn localize(registry: &HoistRegistry, home: &Path) -> HoistRegistry {
+    map_paths(registry, |p| expand(p, home))
+}
+
+/// Serializes the registry in the given format.
+pub fn to_string(registry: &HoistRegistry, format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Toml => Ok(toml::to_string(registry)?),
+        ExportFormat::Json => Ok(crate::json::to_string_pretty(registry)? + "\n"),
+    }
+}
+
+/// Parses an exported registry, migrating it to the current format.
+pub fn from_str(contents: &str, format: ExportFormat) -> Result<HoistRegistry> {
+    match format {
+        ExportFormat::Toml => crate::migrate::parse(contents),
+        ExportFormat::Json => match crate::json::parse(contents)? {
+            Value::Table(table) => crate::migrate::from_table(table),
+            _ => anyhow::bail!("An exported registry must be a json object"),
+        },
+    }
+}
+
+/// Reads an exported toml or json registry, expanding its paths against the
+/// context's home.
+pub fn read(ctx: &HoistContext, path: &Path) -> Result<HoistRegistry> {
+    let contents = std::fs::read_to_string(path)?;
+    let registry = from_str(&contents, ExportFormat::detect(path, &contents))?;
+    Ok(localize(&registry, &ctx.home))
+}
+
+/// Writes a copy of the registry to `path`, or stdout, with home-relative
+/// paths if `portable` is set.
+#[instrument(skip(ctx))]
+pub fn export(
+    ctx: &HoistContext,
+    format: ExportFormat,
+    path: Option<PathBuf>,
+    portable: bool,
+    quiet: bool,
+) -> Result<()> {
+    HoistRegistry::create_registry_with(ctx, true)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    if portable {
+        registry = self::portable(&registry, &ctx.home);
+    }
+    let contents = to_string(&registry, format)?;
+    match path {
+        Some(path) => {
+            let path = ctx.resolve(&path);
+            std::fs::write(&path, contents)?;
+            if !quiet {
+                HoistRegistry::print_color(
+                    &format!("Exported {} binaries to ", registry.binaries.len()),
+                    Color::Green,
+                    false,
+                )?;
+                HoistRegistry::print_color(&path.display().to_string(), Color::Cyan, true)?;
+            }
+        }
+        None => print!("{}", contents),
+    }
+    Ok(())
+}
+
+/// Imports an exported registry, replacing the registry or, with `merge`,
+/// adding its entries to it. With `portable`, paths under another user's
+/// home directory are moved into the context's home.
+///
+/// Entries with the name and location of a registered binary replace it
+/// rather than being duplicated. A replaced registry is backed up first.
+#[instrument(skip(ctx))]
+pub fn import(
+    ctx: &HoistContext,
+    path: &Path,
+    merge: bool,
+    portable: bool,
+    quiet: bool,
+) -> Result<()> {
+    let path = ctx.resolve(path);
+    let mut imported = read(ctx, &path)?;
+    if portable {
+        imported = map_paths(&imported, |p| rehome(p, &ctx.home));
+    }
+    HoistRegistry::create_registry_with(ctx, quiet)?;
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    if !merge {
+        if registry != HoistRegistry::default() {
+            crate::backup::backup(ctx, crate::backup::KEEP)?;
+        }
+        registry = HoistRegistry::default();
+    }
+    let added = imported
+        .binaries
+        .iter()
+        .filter(|b| !registry.binaries.contains(*b))
+        .count();
+    for b in imported.binaries {
+        registry.insert(b);
+    }
+    registry.aliases.extend(imported.aliases);
+    registry.save(ctx)?;
+    if !quiet {
+        HoistRegistry::print_color(
+            &format!("Imported {} new binaries from ", added),
+            Color::Green,
+            false,
+        )?;
+        HoistRegistry::print_color(&path.display().to_string(), Color::Cyan, true)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::HoistedBinary;
+
+    #[test]
+    fn test_portable_paths() {
+        let home = Path::new("/home/u");
+        assert_eq!(
+            contract(Path::new("/home/u/p/target/release/a"), home),
+            PathBuf::from("~/p/target/release/a")
+        );
+        assert_eq!(contract(Path::new("/opt/a"), home), PathBuf::from("/opt/a"));
+        assert_eq!(
+            expand(Path::new("~/p/a"), Path::new("/Users/v")),
+            PathBuf::from("/Users/v/p/a")
+        );
+        assert_eq!(
+            expand(Path::new("$HOME/p/a"), Path::new("/Users/v")),
+            PathBuf::from("/Users/v/p/a")
+        );
+        assert_eq!(expand(Path::new("/opt/a"), home), PathBuf::from("/opt/a"));
+        assert_eq!(
+            rehome(Path::new("/home/alice/p/a"), home),
+            PathBuf::from("/home/u/p/a")
+        );
+        assert_eq!(
+            rehome(Path::new("/Users/alice/p/a"), home),
+            PathBuf::from("/home/u/p/a")
+        );
+        assert_eq!(
+            rehome(Path::new("/home/u/p/a"), home),
+            PathBuf::from("/home/u/p/a")
+        );
+        assert_eq!(rehome(Path::new("/opt/a"), home), PathBuf::from("/opt/a"));
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary::new("a", tempdir.path().join("p/a")));
+        registry.aliases.insert("b".to_string(), "a".to_string());
+        registry.save(&ctx).unwrap();
+
+        for format in [ExportFormat::Toml, ExportFormat::Json] {
+            let contents = to_string(&portable(&registry, &ctx.home), format).unwrap();
+            assert!(contents.contains("~/p/a"));
+            let parsed = from_str(&contents, format).unwrap();
+            assert_eq!(localize(&parsed, &ctx.home), registry);
+        }
+
+        let exported = tempdir.path().join("registry.json");
+        export(&ctx, ExportFormat::Json, Some(exported.clone()), true, true).unwrap();
+        assert!(std::fs::read_to_string(&exported)
+            .unwrap()
+            .contains("~/p/a"));
+        let mut other = HoistRegistry::default();
+        other.insert(HoistedBinary::new("c", PathBuf::from("/opt/c")));
+        other.save(&ctx).unwrap();
+
+        import(&ctx, &exported, true, false, true).unwrap();
+        let merged = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(merged.binaries.len(), 2);
+        assert_eq!(merged.resolve_alias("b"), "a");
+        import(&ctx, &exported, true, false, true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), merged);
+
+        import(&ctx, &exported, false, false, true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), registry);
+        assert_eq!(crate::backup::list(&ctx).unwrap().len(), 1);
+    }
+}
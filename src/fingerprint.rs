@@ -0,0 +1,108 @@
+//! Fingerprint
+//!
+//! Reads how cargo compiled an artifact: the features it was built with,
+//! from the fingerprints cargo keeps in `target/<profile>/.fingerprint/`,
+//! and the version of the rustc toolchain building the project.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::binaries::ArtifactKind;
+
+/// The `.fingerprint` directory next to an artifact built at `location`,
+/// e.g. `target/release/.fingerprint` for `target/release/examples/demo`.
+fn fingerprint_dir(location: &Path) -> Option<PathBuf> {
+    let mut dir = location.parent()?;
+    if matches!(
+        dir.file_name().and_then(|n| n.to_str()),
+        Some("deps" | "examples")
+    ) {
+        dir = dir.parent()?;
+    }
+    Some(dir.join(".fingerprint"))
+}
+
+/// Returns the features the artifact `name` of `kind` built at `location`
+/// was last compiled with, or [None] if cargo left no fingerprint of it.
+///
+/// A binary built with several feature sets has a fingerprint per set, so
+/// the most recently written one is used.
+pub fn features(location: &Path, name: &str, kind: ArtifactKind) -> Option<Vec<String>> {
+    let prefix = match kind {
+        ArtifactKind::Bin => "bin",
+        ArtifactKind::Example => "example",
+        ArtifactKind::Test | ArtifactKind::Bench => return None,
+    };
+    let file_name = format!("{}-{}.json", prefix, name);
+    let newest = std::fs::read_dir(fingerprint_dir(location)?)
+        .ok()?
+        .flatten()
+        .map(|e| e.path().join(&file_name))
+        .filter_map(|p| Some((std::fs::metadata(&p).ok()?.modified().ok()?, p)))
+        .max()?
+        .1;
+    let fingerprint = crate::json::parse(&std::fs::read_to_string(newest).ok()?).ok()?;
+    // Cargo writes the feature list as a string holding a json array.
+    let features = crate::json::parse(fingerprint.get("features")?.as_str()?).ok()?;
+    let mut features: Vec<_> = features
+        .as_array()?
+        .iter()
+        .filter_map(|f| f.as_str().map(str::to_string))
+        .collect();
+    features.sort();
+    Some(features)
+}
+
+/// Returns the version of the rustc cargo uses in `dir`, honoring `RUSTC`
+/// and rustup toolchain overrides, e.g. `1.80.0 (051478957 2024-07-21)`.
+pub fn rustc_version(dir: &Path) -> Option<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc)
+        .arg("-vV")
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.lines().next()?.strip_prefix("rustc ")?;
+    Some(version.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let release = tempdir.path().join("target/release");
+        let write = |hash: &str, file: &str, features: &str| {
+            let dir = release.join(".fingerprint").join(format!("app-{}", hash));
+            std::fs::create_dir_all(&dir).unwrap();
+            let fingerprint = format!(r#"{{"rustc":1,"features":"{}","target":2}}"#, features);
+            std::fs::write(dir.join(file), fingerprint).unwrap();
+        };
+        let app = release.join("app");
+        assert_eq!(features(&app, "app", ArtifactKind::Bin), None);
+
+        write("00", "bin-app.json", r#"[\"default\"]"#);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write("01", "bin-app.json", r#"[\"tls\", \"default\"]"#);
+        write("02", "example-demo.json", "[]");
+        assert_eq!(
+            features(&app, "app", ArtifactKind::Bin),
+            Some(vec!["default".to_string(), "tls".to_string()])
+        );
+        let demo = release.join("examples/demo");
+        assert_eq!(features(&demo, "demo", ArtifactKind::Example), Some(vec![]));
+        assert_eq!(features(&app, "app", ArtifactKind::Test), None);
+    }
+
+    #[test]
+    fn test_rustc_version() {
+        let version = rustc_version(Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert!(version.starts_with(char::is_numeric), "{}", version);
+    }
+}
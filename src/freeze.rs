@@ -0,0 +1,275 @@
+//! Freeze
+//!
+//! `cargo hoist freeze` records the exact builds hoisted into a directory,
+//! by the sha256 of each hoisted file, into a `hoist.lock`, and
+//! `cargo hoist thaw` places those builds back later, or on another machine
+//! sharing the registry or content store.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Preserve};
+use crate::config::HoistConfig;
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::hoisted::{HoistManifest, HoistedFile};
+use crate::registry::HoistRegistry;
+
+/// The freeze lock file name.
+pub const LOCK_FILE: &str = "hoist.lock";
+
+/// A hoisted file recorded by `freeze`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FrozenBinary {
+    /// The file name in the directory
+    pub name: String,
+    /// The name of the registered binary placed as the file
+    pub binary: String,
+    /// The version of the registered binary, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The sha256 digest of the file
+    pub sha256: String,
+}
+
+/// The builds hoisted into a directory, as recorded by `freeze`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HoistLock {
+    /// The frozen files, sorted by name.
+    #[serde(default, rename = "binary", skip_serializing_if = "Vec::is_empty")]
+    pub binaries: Vec<FrozenBinary>,
+}
+
+impl HoistLock {
+    /// The path of the freeze lock of `dir`.
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(LOCK_FILE)
+    }
+
+    /// Loads the freeze lock of `dir`.
+    #[instrument]
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = HoistLock::path(dir);
+        if !path.exists() {
+            anyhow::bail!(
+                "{} does not exist, create it with `cargo hoist freeze`",
+                path.display()
+            );
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the freeze lock of `dir`.
+    #[instrument(skip(self))]
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::write(HoistLock::path(dir), toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// The sha256 digest of the contents of the registered build `b`, read from
+/// the name of its content store directory where it is stored there.
+fn build_digest(ctx: &HoistContext, b: &HoistedBinary) -> Option<String> {
+    if b.location.starts_with(crate::content::dir(ctx)) {
+        if let Some(digest) = crate::content::digest(&b.location) {
+            return Some(digest.to_string());
+        }
+    }
+    crate::hash::hash_file(&b.location, HashAlgorithm::Sha256).ok()
+}
+
+/// Records the files hoisted into `dir`, as listed in its hoist manifest,
+/// with their versions and digests into the `hoist.lock` of `dir`.
+///
+/// Fails if nothing was hoisted into `dir` or a hoisted file is missing.
+/// Files that no registered build matches, e.g. because they were edited
+/// after they were hoisted, are recorded with a warning, as `thaw` can't
+/// restore them.
+#[instrument(skip(ctx))]
+pub fn freeze(ctx: &HoistContext, dir: &Path, quiet: bool) -> Result<()> {
+    let manifest = HoistManifest::load(dir)?;
+    if manifest.files.is_empty() {
+        anyhow::bail!("Nothing was hoisted into {}", dir.display());
+    }
+    let registry = HoistRegistry::load(ctx)?;
+    let hashed = crate::parallel::try_map(manifest.files, |f| {
+        let path = dir.join(&f.name);
+        if !path.exists() {
+            anyhow::bail!(
+                "{} was hoisted into {} but is missing",
+                f.name,
+                dir.display()
+            );
+        }
+        let sha256 = crate::hash::hash_file(&path, HashAlgorithm::Sha256)?;
+        Ok((f, sha256))
+    })?;
+
+    let mut lock = HoistLock::default();
+    for (f, sha256) in hashed {
+        let registered = registry
+            .binaries
+            .iter()
+            .filter(|b| b.name == f.binary)
+            .any(|b| build_digest(ctx, b).as_deref() == Some(sha256.as_str()));
+        if !registered {
+            eprintln!(
+                "cargo-hoist: warning: no registered build of {} matches {}, thaw can't restore it",
+                f.binary, f.name
+            );
+        }
+        if !quiet {
+            HoistRegistry::print_color("Froze ", Color::Green, false)?;
+            HoistRegistry::print_color(&f.name, Color::Magenta, false)?;
+            let version = f
+                .version
+                .as_deref()
+                .map_or_else(String::new, |v| format!(" {}", v));
+            HoistRegistry::print_color(
+                &format!("{} ({})", version, &sha256[..12]),
+                Color::Cyan,
+                true,
+            )?;
+        }
+        lock.binaries.push(FrozenBinary {
+            name: f.name,
+            binary: f.binary,
+            version: f.version,
+            sha256,
+        });
+    }
+    lock.save(dir)
+}
+
+/// Places the builds recorded in the `hoist.lock` of `dir` back into `dir`,
+/// finding each among the registered builds of its binary by its digest.
+///
+/// Nothing is placed if any recorded build is not registered. Files in the
+/// way that `hoist` did not place there are only replaced with `force`.
+#[instrument(skip(ctx))]
+pub fn thaw(ctx: &HoistContext, dir: &Path, force: bool, quiet: bool) -> Result<()> {
+    let lock = HoistLock::load(dir)?;
+    let registry = HoistRegistry::load(ctx)?;
+    let manifest = HoistManifest::load(dir)?;
+
+    let builds = crate::parallel::try_map(lock.binaries, |frozen| {
+        let build = registry
+            .binaries
+            .iter()
+            .filter(|b| b.name == frozen.binary && b.location.exists())
+            .find(|b| build_digest(ctx, b).as_deref() == Some(frozen.sha256.as_str()))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No registered build of {} matches the frozen {} ({})",
+                    frozen.binary,
+                    frozen.name,
+                    frozen.sha256
+                )
+            })?;
+        let path = dir.join(&frozen.name);
+        let up_to_date =
+            path.exists() && crate::hash::hash_file(&path, HashAlgorithm::Sha256)? == frozen.sha256;
+        Ok((frozen, build.clone(), up_to_date))
+    })?;
+    if !force {
+        if let Some((frozen, _, _)) = builds.iter().find(|(f, _, up_to_date)| {
+            !up_to_date
+                && dir.join(&f.name).symlink_metadata().is_ok()
+                && !manifest.files.iter().any(|m| m.name == f.name)
+        }) {
+            anyhow::bail!(
+                "{} exists in {} and was not hoisted there, pass --force to replace it",
+                frozen.name,
+                dir.display()
+            );
+        }
+    }
+
+    let strategy = HoistConfig::load(ctx)?.strategy;
+    let mut files = Vec::with_capacity(builds.len());
+    let mut hoisted = Vec::with_capacity(builds.len());
+    for (frozen, b, up_to_date) in builds {
+        if !up_to_date {
+            if let Some(mismatch) = crate::platform::mismatch(&b.location, b.triple.as_deref()) {
+                eprintln!("cargo-hoist: warning: {} {}", b.name, mismatch);
+            }
+            let placed = HoistedBinary {
+                name: frozen.name.clone(),
+                ..b.clone()
+            };
+            placed.hoist_to_dir(dir, strategy, Preserve::Times)?;
+        }
+        if !quiet {
+            HoistRegistry::print_color(&frozen.name, Color::Magenta, false)?;
+            match up_to_date {
+                true => HoistRegistry::print_color(" is up to date", Color::Green, true)?,
+                false => HoistRegistry::print_color(" thawed", Color::Green, true)?,
+            }
+        }
+        files.push(HoistedFile::new(&frozen.name, &b));
+        hoisted.push(b);
+    }
+    HoistManifest::record_in(dir, files);
+    HoistRegistry::record_hoisted(ctx, &hoisted, true);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{CopyStrategy, Preserve};
+
+    #[test]
+    fn test_freeze_and_thaw() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path().join("work"));
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let build = |dir: &str, contents: &str| {
+            let path = tempdir.path().join(dir).join("tool");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            HoistedBinary {
+                version: Some(dir.to_string()),
+                ..HoistedBinary::new("tool", path)
+            }
+        };
+        let old = build("1.0.0", "#!/bin/sh\necho old\n");
+        let new = build("2.0.0", "#!/bin/sh\necho new\n");
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.binaries.insert(old.clone());
+        registry.binaries.insert(new.clone());
+        registry.save(&ctx).unwrap();
+
+        let work = ctx.cwd.clone();
+        std::fs::create_dir_all(&work).unwrap();
+        assert!(freeze(&ctx, &work, true).is_err());
+        old.hoist_to_dir(&work, CopyStrategy::Copy, Preserve::Times)
+            .unwrap();
+        HoistManifest::record_in(&work, vec![HoistedFile::new("tool", &old)]);
+        freeze(&ctx, &work, true).unwrap();
+        let lock = HoistLock::load(&work).unwrap();
+        assert_eq!(lock.binaries.len(), 1);
+        assert_eq!(lock.binaries[0].version.as_deref(), Some("1.0.0"));
+
+        new.hoist_to_dir(&work, CopyStrategy::Copy, Preserve::Times)
+            .unwrap();
+        thaw(&ctx, &work, false, true).unwrap();
+        let contents = std::fs::read_to_string(work.join("tool")).unwrap();
+        assert_eq!(contents, "#!/bin/sh\necho old\n");
+
+        std::fs::remove_file(HoistManifest::path(&work)).unwrap();
+        std::fs::write(work.join("tool"), "mine").unwrap();
+        assert!(thaw(&ctx, &work, false, true).is_err());
+        thaw(&ctx, &work, true, true).unwrap();
+        assert!(HoistManifest::load(&work).unwrap().files[0].matches("tool"));
+
+        let mut lock = HoistLock::load(&work).unwrap();
+        lock.binaries[0].sha256 = "0".repeat(64);
+        lock.save(&work).unwrap();
+        assert!(thaw(&ctx, &work, true, true).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+//! Garbage Collection
+//!
+//! Dropping registered binaries that expired, went unused, are missing from
+//! disk or are duplicated, with `cargo hoist gc`, `prune` and `dedupe`.
+
+use anyhow::Result;
+use inquire::MultiSelect;
+use std::collections::{HashMap, HashSet};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::registry::{recency, HoistRegistry};
+
+/// The policies [HoistRegistry::gc] removes registered binaries by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcOptions {
+    /// Remove binaries not hoisted, executed or registered within this duration.
+    pub older_than: Option<std::time::Duration>,
+    /// Keep only this many of the most recently used binaries.
+    pub keep_last: Option<usize>,
+    /// Print what would be removed without changing the registry.
+    pub dry_run: bool,
+    /// Remove binaries expired by this ttl, or their own.
+    pub ttl: Option<std::time::Duration>,
+}
+
+impl HoistRegistry {
+    /// Removes the binaries selected by the [GcOptions] policies, and the
+    /// expired binaries, returning them. Binaries are aged by when they were
+    /// last used, see [HoistedBinary::last_used_at].
+    ///
+    /// `older_than` spares binaries of unknown age, registered before
+    /// provenance tracking, while `keep_last` counts them as least recently used.
+    /// Pinned binaries are never removed, but count towards `keep_last`.
+    pub fn collect_garbage(&mut self, options: &GcOptions, now: u64) -> Vec<HoistedBinary> {
+        let mut removed: Vec<_> = self
+            .binaries
+            .iter()
+            .filter(|b| b.is_expired(options.ttl, now))
+            .cloned()
+            .collect();
+        if let Some(older_than) = options.older_than {
+            let cutoff = now.saturating_sub(older_than.as_secs());
+            removed.extend(
+                self.binaries
+                    .iter()
+                    .filter(|b| b.last_used_at().is_some_and(|at| at < cutoff))
+                    .cloned(),
+            );
+        }
+        if let Some(keep) = options.keep_last {
+            let mut by_use: Vec<_> = self.binaries.iter().collect();
+            by_use.sort_by(|a, b| {
+                b.last_used_at()
+                    .cmp(&a.last_used_at())
+                    .then_with(|| (&a.name, &a.location).cmp(&(&b.name, &b.location)))
+            });
+            removed.extend(by_use.into_iter().skip(keep).cloned());
+        }
+        removed.retain(|b| !b.pinned);
+        removed.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        removed.dedup();
+        for b in &removed {
+            self.binaries.remove(b);
+        }
+        removed
+    }
+
+    /// Removes expired registered binaries, and those selected by the
+    /// [GcOptions] age and LRU policies, falling back to the `keep` retention
+    /// of the user config when no policy is given.
+    #[instrument(skip(ctx))]
+    pub fn gc(ctx: &HoistContext, options: GcOptions, quiet: bool) -> Result<()> {
+        let (_lock, mut registry) = HoistRegistry::load_for_update(ctx, options.dry_run, quiet)?;
+        let config = crate::config::HoistConfig::load(ctx)?;
+        let options = GcOptions {
+            ttl: options.ttl.or(config.ttl()?),
+            ..options
+        };
+        let now = crate::time::unix_now();
+        let expires = options.ttl.is_some() || registry.binaries.iter().any(|b| b.ttl.is_some());
+        let mut removed = match (options.older_than, options.keep_last) {
+            (None, None) => match config.keep {
+                Some(keep) => {
+                    let mut removed = registry.collect_garbage(&options, now);
+                    removed.extend(registry.retain_latest(keep));
+                    removed
+                }
+                None if expires => registry.collect_garbage(&options, now),
+                None => anyhow::bail!(
+                    "Pass --older-than or --keep-last, or set `keep` or `ttl` in {}",
+                    ctx.config_path().display()
+                ),
+            },
+            _ => registry.collect_garbage(&options, now),
+        };
+        removed.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        if !quiet {
+            let verb = if options.dry_run {
+                "Would remove "
+            } else {
+                "Removed "
+            };
+            for b in &removed {
+                let last_used = b
+                    .last_used_at()
+                    .map(|at| crate::time::to_rfc3339(crate::time::from_unix(at)))
+                    .unwrap_or_else(|| "unknown".to_string());
+                HoistRegistry::print_color(verb, Color::Yellow, false)?;
+                HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(
+                    &format!(" ({}), last used {}", b.location.display(), last_used),
+                    Color::Cyan,
+                    true,
+                )?;
+            }
+            if removed.is_empty() {
+                HoistRegistry::print_color("Nothing to collect", Color::Green, true)?;
+            }
+        }
+        if !removed.is_empty() && !options.dry_run {
+            registry.save(ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the binaries expired by their own ttl or the `ttl` of the user
+    /// config, see [HoistedBinary::expires_at].
+    pub(crate) fn drop_expired(&mut self, ctx: &HoistContext) -> Result<()> {
+        let ttl = crate::config::HoistConfig::load(ctx)?.ttl()?;
+        let now = crate::time::unix_now();
+        self.binaries.retain(|b| !b.is_expired(ttl, now));
+        Ok(())
+    }
+
+    /// Drops builds past the `keep` limit of the user config, if set, and
+    /// previous builds past its `history` limit, returning the dropped builds.
+    pub(crate) fn apply_retention(&mut self, ctx: &HoistContext) -> Result<Vec<HoistedBinary>> {
+        let config = crate::config::HoistConfig::load(ctx)?;
+        let mut dropped = self.trim_history(config.history());
+        if let Some(keep) = config.keep {
+            dropped.extend(self.retain_latest(keep));
+        }
+        for b in &dropped {
+            tracing::info!(
+                "Dropped {} ({}) past retention",
+                b.name,
+                b.location.display()
+            );
+        }
+        Ok(dropped)
+    }
+
+    /// Removes registered binaries whose location no longer holds an
+    /// executable, along with aliases left pointing at no binary. Pinned
+    /// binaries are kept.
+    ///
+    /// With `interactive`, prompts for which of the stale entries to remove.
+    /// With `dry_run`, only prints what would be removed.
+    #[instrument(skip(ctx))]
+    pub fn prune(ctx: &HoistContext, dry_run: bool, interactive: bool, quiet: bool) -> Result<()> {
+        let (_lock, mut registry) = HoistRegistry::load_for_update(ctx, dry_run, quiet)?;
+        let mut stale: Vec<_> = registry
+            .binaries
+            .iter()
+            .filter(|b| b.is_stale() && !b.pinned)
+            .cloned()
+            .collect();
+        stale.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        if interactive && !stale.is_empty() {
+            let options: Vec<_> = stale
+                .iter()
+                .map(|b| format!("{} ({})", b.name, b.location.display()))
+                .collect();
+            let all: Vec<_> = (0..options.len()).collect();
+            let chosen = MultiSelect::new("Which stale binaries would you like to prune?", options)
+                .with_default(&all)
+                .raw_prompt()?;
+            let chosen: HashSet<_> = chosen.into_iter().map(|c| c.index).collect();
+            stale = stale
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| chosen.contains(i))
+                .map(|(_, b)| b)
+                .collect();
+        }
+        for b in &stale {
+            registry.binaries.remove(b);
+        }
+        let dangling: Vec<_> = registry
+            .aliases
+            .iter()
+            .filter(|(_, name)| registry.latest(name).is_none())
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        for alias in &dangling {
+            registry.aliases.remove(alias);
+        }
+
+        let changed = !stale.is_empty() || !dangling.is_empty();
+        if !quiet {
+            let verb = if dry_run { "Would prune " } else { "Pruned " };
+            for b in &stale {
+                HoistRegistry::print_color(verb, Color::Yellow, false)?;
+                HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(
+                    &format!(" ({})", b.location.display()),
+                    Color::Cyan,
+                    true,
+                )?;
+            }
+            for alias in &dangling {
+                HoistRegistry::print_color(verb, Color::Yellow, false)?;
+                HoistRegistry::print_color(&format!("alias {}", alias), Color::Blue, true)?;
+            }
+            if !changed {
+                HoistRegistry::print_color("No stale binaries found", Color::Green, true)?;
+            }
+        }
+        if changed && !dry_run {
+            registry.save(ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Collapses registered binaries of the same name with byte-identical
+    /// contents into the most recently registered one, which keeps their
+    /// build paths as sources, their tags, and their usage. Returns the
+    /// removed entries, paired with the entry each was collapsed into.
+    ///
+    /// Binaries missing from disk are left as they are.
+    pub fn dedupe(&mut self) -> Vec<(HoistedBinary, HoistedBinary)> {
+        let mut groups: HashMap<(String, String), Vec<_>> = HashMap::new();
+        for b in &self.binaries {
+            if let Ok(digest) = crate::hash::hash_file_mmap(&b.location, HashAlgorithm::Sha256) {
+                groups
+                    .entry((b.name.clone(), digest))
+                    .or_default()
+                    .push((recency(b), b.clone()));
+            }
+        }
+        let mut collapsed = vec![];
+        for (_, mut builds) in groups {
+            if builds.len() < 2 {
+                continue;
+            }
+            builds.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.location.cmp(&b.1.location)));
+            let mut builds = builds.into_iter().map(|(_, b)| b);
+            let mut keeper = builds.next().expect("groups have at least two builds");
+            let duplicates: Vec<_> = builds.collect();
+            for d in &duplicates {
+                keeper.add_sources(d);
+                keeper.pinned |= d.pinned;
+                for tag in &d.tags {
+                    if !keeper.has_tag(tag) {
+                        keeper.tags.push(tag.clone());
+                    }
+                }
+                keeper.last_hoisted_at = keeper.last_hoisted_at.max(d.last_hoisted_at);
+                keeper.hoist_count = keeper.hoist_count.max(d.hoist_count);
+                self.binaries.remove(d);
+            }
+            self.binaries.replace(keeper.clone());
+            collapsed.extend(duplicates.into_iter().map(|d| (d, keeper.clone())));
+        }
+        collapsed.sort_by(|(a, _), (b, _)| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        collapsed
+    }
+
+    /// Collapses registered binaries with byte-identical contents, see
+    /// [HoistRegistry::dedupe].
+    #[instrument(skip(ctx))]
+    pub fn dedupe_registry(ctx: &HoistContext, dry_run: bool, quiet: bool) -> Result<()> {
+        let (_lock, mut registry) = HoistRegistry::load_for_update(ctx, dry_run, quiet)?;
+        let collapsed = registry.dedupe();
+        if !quiet {
+            let verb = match dry_run {
+                true => "Would collapse ",
+                false => "Collapsed ",
+            };
+            for (removed, kept) in &collapsed {
+                HoistRegistry::print_color(verb, Color::Yellow, false)?;
+                HoistRegistry::print_color(&removed.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(
+                    &format!(
+                        " ({}) into {}",
+                        removed.built_path().display(),
+                        kept.location.display()
+                    ),
+                    Color::Cyan,
+                    true,
+                )?;
+            }
+            if collapsed.is_empty() {
+                HoistRegistry::print_color("No duplicate binaries", Color::Green, true)?;
+            }
+        }
+        if !collapsed.is_empty() && !dry_run {
+            registry.save(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{Registration, RegistrationSource};
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_collect_garbage() {
+        let used = |name: &str, registered: Option<u64>, hoisted: Option<u64>| HoistedBinary {
+            registration: registered.map(|at| Registration {
+                at,
+                ..Registration::now(RegistrationSource::Manual, PathBuf::from("/p"))
+            }),
+            last_hoisted_at: hoisted,
+            ..HoistedBinary::new(name, PathBuf::from(format!("/p/{}", name)))
+        };
+        let registry = HoistRegistry {
+            binaries: HashSet::from([
+                used("legacy", None, None),
+                used("stale", Some(100), None),
+                used("hoisted", Some(100), Some(900)),
+                used("fresh", Some(1000), None),
+            ]),
+            ..Default::default()
+        };
+        let names = |removed: Vec<HoistedBinary>| -> Vec<String> {
+            removed.into_iter().map(|b| b.name).collect()
+        };
+
+        let older_than = GcOptions {
+            older_than: Some(std::time::Duration::from_secs(500)),
+            ..Default::default()
+        };
+        let mut r = registry.clone();
+        assert_eq!(names(r.collect_garbage(&older_than, 1000)), vec!["stale"]);
+        assert_eq!(r.binaries.len(), 3);
+
+        let keep_last = GcOptions {
+            keep_last: Some(2),
+            ..Default::default()
+        };
+        let mut r = registry.clone();
+        assert_eq!(
+            names(r.collect_garbage(&keep_last, 1000)),
+            vec!["legacy", "stale"]
+        );
+        let mut r = registry.clone();
+        r.insert(HoistedBinary {
+            pinned: true,
+            ..used("stale", Some(100), None)
+        });
+        assert!(r.collect_garbage(&older_than, 1000).is_empty());
+
+        let mut r = registry.clone();
+        r.insert(used("hoisted", Some(1000), None));
+        assert_eq!(r.latest("hoisted").unwrap().last_hoisted_at, Some(900));
+    }
+
+    #[test]
+    fn test_ttl() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        std::fs::write(tempdir.path().join(".bashrc"), "").unwrap();
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let now = crate::time::unix_now();
+        let used = |name: &str, ago: u64, ttl: Option<u64>| HoistedBinary {
+            last_hoisted_at: Some(now - ago),
+            ttl,
+            ..HoistedBinary::new(name, PathBuf::from(format!("/p/{}", name)))
+        };
+        let mut registry = HoistRegistry::default();
+        registry.insert(used("fresh", 60, None));
+        registry.insert(used("stale", 3600, None));
+        registry.insert(used("short", 60, Some(30)));
+        registry.insert(HoistedBinary {
+            pinned: true,
+            ..used("pinned", 3600, Some(30))
+        });
+        registry.insert(HoistedBinary::new("unknown", PathBuf::from("/p/unknown")));
+
+        let mut rebuilt = registry.clone();
+        rebuilt.insert(HoistedBinary::new("short", PathBuf::from("/p/short")));
+        assert_eq!(rebuilt.latest("short").unwrap().ttl, Some(30));
+
+        let names = |registry: &HoistRegistry| {
+            let mut names: Vec<_> = registry.binaries.iter().map(|b| b.name.clone()).collect();
+            names.sort();
+            names
+        };
+        let mut visible = registry.clone();
+        visible.drop_expired(&ctx).unwrap();
+        assert_eq!(names(&visible), ["fresh", "pinned", "stale", "unknown"]);
+
+        std::fs::write(ctx.config_path(), "ttl = \"10m\"\n").unwrap();
+        let mut visible = registry.clone();
+        visible.drop_expired(&ctx).unwrap();
+        assert_eq!(names(&visible), ["fresh", "pinned", "unknown"]);
+
+        registry.save(&ctx).unwrap();
+        HoistRegistry::gc(&ctx, GcOptions::default(), true).unwrap();
+        let collected = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(names(&collected), ["fresh", "pinned", "unknown"]);
+    }
+
+    #[test]
+    fn test_prune() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_prune");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        let release = test_tempdir.join("target/release");
+        HoistRegistry::register_paths(
+            &ctx,
+            vec![release.join("binary1"), release.join("binary2")],
+            None,
+            InstallOptions {
+                no_store: true,
+                ..Default::default()
+            },
+            true,
+        )
+        .unwrap();
+        HoistRegistry::alias(&ctx, "one", "binary1", true).unwrap();
+        std::fs::remove_file(release.join("binary1")).unwrap();
+
+        HoistRegistry::prune(&ctx, true, false, true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap().binaries.len(), 2);
+
+        HoistRegistry::prune(&ctx, false, false, true).unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert!(registry.latest("binary1").is_none());
+        assert!(registry.latest("binary2").is_some());
+        assert!(registry.aliases.is_empty());
+
+        std::fs::set_permissions(
+            release.join("binary2"),
+            std::os::unix::fs::PermissionsExt::from_mode(0o644),
+        )
+        .unwrap();
+        HoistRegistry::pin(&ctx, "binary2", true).unwrap();
+        HoistRegistry::prune(&ctx, false, false, true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap().binaries.len(), 1);
+        HoistRegistry::unpin(&ctx, "binary2", true).unwrap();
+        HoistRegistry::prune(&ctx, false, false, true).unwrap();
+        assert!(HoistRegistry::load(&ctx).unwrap().binaries.is_empty());
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let build = |project: &str, contents: &str, at: u64| {
+            let location = tempdir.path().join(project).join("tool");
+            std::fs::create_dir_all(location.parent().unwrap()).unwrap();
+            std::fs::write(&location, contents).unwrap();
+            HoistedBinary {
+                registration: Some(Registration {
+                    at,
+                    ..Registration::now(RegistrationSource::Manual, PathBuf::from(project))
+                }),
+                tags: vec![project.to_string()],
+                hoist_count: at,
+                ..HoistedBinary::new("tool", location)
+            }
+        };
+        let mut registry = HoistRegistry {
+            binaries: HashSet::from([
+                build("a", "same", 1),
+                build("b", "same", 3),
+                build("c", "same", 2),
+                build("d", "different", 4),
+                HoistedBinary::new("tool", tempdir.path().join("missing")),
+            ]),
+            ..Default::default()
+        };
+        let collapsed = registry.dedupe();
+        assert_eq!(collapsed.len(), 2);
+        assert!(collapsed
+            .iter()
+            .all(|(_, kept)| kept.location == tempdir.path().join("b/tool")));
+        assert_eq!(registry.binaries.len(), 3);
+        let kept = registry
+            .binaries
+            .iter()
+            .find(|b| b.location == tempdir.path().join("b/tool"))
+            .unwrap();
+        assert_eq!(
+            kept.sources,
+            vec![tempdir.path().join("c/tool"), tempdir.path().join("a/tool")]
+        );
+        assert_eq!(kept.tags, vec!["b", "c", "a"]);
+        assert_eq!(kept.hoist_count, 3);
+        assert!(registry.dedupe().is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+//! Git
+//!
+//! Reads the git revision a project's working tree is at, so registrations
+//! record exactly what code a binary was built from.
+
+use std::path::Path;
+use std::process::Command;
+
+/// The state of a project's git working tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    /// The full hash of the checked out commit
+    pub commit: String,
+    /// Whether the working tree had uncommitted changes
+    pub dirty: bool,
+}
+
+/// Runs `git` in `dir`, returning its trimmed stdout on success.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the revision of the git repository containing `project`, or
+/// [None] if it is not in a repository, has no commits, or git is not
+/// installed.
+pub fn revision(project: &Path) -> Option<Revision> {
+    let commit = git(project, &["rev-parse", "--verify", "--quiet", "HEAD"])?;
+    let dirty = git(project, &["status", "--porcelain"]).is_some_and(|s| !s.is_empty());
+    Some(Revision { commit, dirty })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revision() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        assert!(revision(dir).is_none());
+
+        let run = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args([
+                    "-c",
+                    "user.name=hoist",
+                    "-c",
+                    "user.email=hoist@example.com",
+                ])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        run(&["init", "--quiet"]);
+        assert!(revision(dir).is_none());
+        std::fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        run(&["add", "main.rs"]);
+        run(&["commit", "--quiet", "-m", "init"]);
+
+        let clean = revision(dir).unwrap();
+        assert_eq!(clean.commit.len(), 40);
+        assert!(!clean.dirty);
+
+        std::fs::write(dir.join("main.rs"), "fn main() { todo!() }").unwrap();
+        let dirty = revision(dir).unwrap();
+        assert_eq!(dirty.commit, clean.commit);
+        assert!(dirty.dirty);
+    }
+}
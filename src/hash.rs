@@ -0,0 +1,279 @@
+//! Hashing
+//!
+//! Content digests for hoisted binaries.
+//!
+//! [Hasher] streams input into the `sha2` or `blake3` hasher so artifacts can
+//! be hashed without reading them into memory at once.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::io::Read;
+use std::path::Path;
+use tracing::instrument;
+
+/// The size of the buffer used when hashing files.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A supported digest algorithm.
+#[derive(
+    Debug, Default, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-256
+    #[default]
+    Sha256,
+    /// BLAKE3
+    Blake3,
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Blake3 => write!(f, "blake3"),
+        }
+    }
+}
+
+/// A streaming hasher for any [HashAlgorithm].
+#[derive(Debug, Clone)]
+pub enum Hasher {
+    /// A SHA-256 hasher
+    Sha256(sha2::Sha256),
+    /// A BLAKE3 hasher
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// Creates a new hasher for the given algorithm.
+    pub fn new(algo: HashAlgorithm) -> Self {
+        match algo {
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// Feeds `input` into the hasher.
+    pub fn update(&mut self, input: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(input),
+            Hasher::Blake3(h) => {
+                h.update(input);
+            }
+        }
+    }
+
+    /// Returns the 32 byte digest of everything fed into the hasher.
+    pub fn finalize(&self) -> [u8; 32] {
+        match self {
+            Hasher::Sha256(h) => h.clone().finalize().into(),
+            Hasher::Blake3(h) => h.finalize().into(),
+        }
+    }
+}
+
+/// Hex encodes a digest.
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the hex digest of `bytes`.
+pub fn hash_bytes(bytes: &[u8], algo: HashAlgorithm) -> String {
+    let mut hasher = Hasher::new(algo);
+    hasher.update(bytes);
+    to_hex(&hasher.finalize())
+}
+
+/// Returns the hex digest of the file at `path`, read through a buffer.
+#[instrument]
+pub fn hash_file(path: &Path, algo: HashAlgorithm) -> Result<String> {
+    hash_buffered(std::fs::File::open(path)?, algo)
+}
+
+/// Returns the hex digest of the file at `path`, hashing it through a memory
+/// map regardless of its size.
+///
+/// Only use this for files that are never modified in place, like copies in
+/// the content store: truncating a mapped file raises `SIGBUS`. Falls back
+/// to buffered reads if the file can't be mapped, e.g. when it is empty or
+/// lives on a filesystem that doesn't support `mmap`.
+#[instrument]
+pub fn hash_file_mmap(path: &Path, algo: HashAlgorithm) -> Result<String> {
+    hash_mapped(std::fs::File::open(path)?, algo)
+}
+
+fn hash_mapped(file: std::fs::File, algo: HashAlgorithm) -> Result<String> {
+    let Some(map) = Mmap::map(&file)? else {
+        tracing::debug!("failed to mmap file, falling back to buffered reads");
+        return hash_buffered(file, algo);
+    };
+    let mut hasher = Hasher::new(algo);
+    hasher.update(map.as_slice());
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn hash_buffered(mut file: std::fs::File, algo: HashAlgorithm) -> Result<String> {
+    let mut hasher = Hasher::new(algo);
+    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// A read-only, private memory map of an entire file, unmapped on drop.
+///
+/// The file must not be truncated while mapped, so only immutable files,
+/// like copies in the content store, may be mapped.
+#[derive(Debug)]
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Maps the file, returning [None] if it can't be mapped.
+    fn map(file: &std::fs::File) -> Result<Option<Self>> {
+        use std::os::unix::io::AsRawFd;
+        let Ok(len) = usize::try_from(file.metadata()?.len()) else {
+            return Ok(None);
+        };
+        if len == 0 {
+            return Ok(None);
+        }
+        // SAFETY: the descriptor is valid for the duration of the call and the
+        // result is checked against MAP_FAILED before use.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Ok(None);
+        }
+        // SAFETY: ptr and len describe the mapping that was just created.
+        unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+        Ok(Some(Self { ptr, len }))
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the mapping is readable for len bytes until dropped.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: ptr and len describe a live mapping owned by self.
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            hash_bytes(b"", HashAlgorithm::Sha256),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hash_bytes(b"abc", HashAlgorithm::Sha256),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash_bytes(&pattern(100_000), HashAlgorithm::Sha256),
+            "cd2df694e424bc7968cc37f47751019e5ca0cd1bdf2e479ea537c3a1c32ee1aa"
+        );
+    }
+
+    #[test]
+    fn test_blake3() {
+        // Vectors from the official BLAKE3 test_vectors.json.
+        let vectors = [
+            (
+                0,
+                "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            ),
+            (
+                1,
+                "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213",
+            ),
+            (
+                1024,
+                "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7",
+            ),
+            (
+                1025,
+                "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444",
+            ),
+            (
+                2048,
+                "e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a",
+            ),
+        ];
+        for (len, expected) in vectors {
+            assert_eq!(hash_bytes(&pattern(len), HashAlgorithm::Blake3), expected);
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_oneshot() {
+        let data = pattern(10_000);
+        for algo in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            let mut hasher = Hasher::new(algo);
+            data.chunks(333).for_each(|c| hasher.update(c));
+            assert_eq!(to_hex(&hasher.finalize()), hash_bytes(&data, algo));
+        }
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("binary1");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            hash_file(&path, HashAlgorithm::Sha256).unwrap(),
+            hash_bytes(b"abc", HashAlgorithm::Sha256)
+        );
+    }
+
+    #[test]
+    fn test_hash_file_mmap() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("binary1");
+        let data = pattern(300_000);
+        std::fs::write(&path, &data).unwrap();
+        for algo in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            assert_eq!(
+                hash_file_mmap(&path, algo).unwrap(),
+                hash_bytes(&data, algo)
+            );
+        }
+
+        // Empty files can't be mapped and fall back to buffered reads.
+        let empty = tempdir.path().join("empty");
+        std::fs::write(&empty, b"").unwrap();
+        assert_eq!(
+            hash_file_mmap(&empty, HashAlgorithm::Sha256).unwrap(),
+            hash_bytes(b"", HashAlgorithm::Sha256)
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Build History
+//!
+//! The earlier builds kept for each binary name, which
+//! `cargo hoist rollback` hoists again.
+
+use anyhow::Result;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::hoisted::{HoistManifest, HoistedFile};
+use crate::registry::{recency, HoistRegistry};
+
+impl HoistRegistry {
+    /// Groups the builds of each binary name by the path they were built at,
+    /// newest first, so each group is a build and its history.
+    fn histories(&self) -> Vec<Vec<&HoistedBinary>> {
+        let mut groups: std::collections::HashMap<_, Vec<_>> = Default::default();
+        for b in &self.binaries {
+            groups
+                .entry((b.name.as_str(), b.built_path()))
+                .or_default()
+                .push(b);
+        }
+        groups
+            .into_values()
+            .map(|mut builds| {
+                builds.sort_by(|a, b| {
+                    recency(b)
+                        .cmp(&recency(a))
+                        .then_with(|| a.location.cmp(&b.location))
+                });
+                builds
+            })
+            .collect()
+    }
+
+    /// Returns the stored copies of earlier builds that a newer build from
+    /// the same path has superseded. They are skipped when hoisting, but can
+    /// be restored with [HoistRegistry::rollback].
+    pub fn previous_builds(&self) -> Vec<&HoistedBinary> {
+        self.histories()
+            .into_iter()
+            .flat_map(|builds| builds.into_iter().skip(1))
+            .collect()
+    }
+
+    /// Drops all but the `keep` most recent previous builds of each binary,
+    /// returning the removed entries. Their stored copies are left for
+    /// `cargo hoist store gc`.
+    pub fn trim_history(&mut self, keep: usize) -> Vec<HoistedBinary> {
+        let mut removed: Vec<_> = self
+            .histories()
+            .into_iter()
+            .flat_map(|builds| builds.into_iter().skip(keep + 1).cloned())
+            .collect();
+        for b in &removed {
+            self.binaries.remove(b);
+        }
+        removed.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        removed
+    }
+
+    /// Drops all but the `keep` most recently registered builds of each
+    /// binary name, returning the removed entries.
+    ///
+    /// Builds are ordered by registration time, then by build time, so
+    /// entries recorded before provenance tracking are trimmed first.
+    /// Builds of pinned binaries are kept.
+    pub fn retain_latest(&mut self, keep: usize) -> Vec<HoistedBinary> {
+        let keep = keep.max(1);
+        let mut by_name: std::collections::HashMap<&str, Vec<_>> = Default::default();
+        for b in &self.binaries {
+            by_name.entry(&b.name).or_default().push((recency(b), b));
+        }
+        let mut removed = vec![];
+        for (_, mut builds) in by_name {
+            builds.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.location.cmp(&b.1.location)));
+            removed.extend(
+                builds
+                    .into_iter()
+                    .skip(keep)
+                    .filter(|(_, b)| !b.pinned)
+                    .map(|(_, b)| b.clone()),
+            );
+        }
+        for b in &removed {
+            self.binaries.remove(b);
+        }
+        removed.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        removed
+    }
+
+    /// Rolls a binary in the current directory back to a previously registered build.
+    ///
+    /// Copies the build preceding the latest one, or, given `to`, the build
+    /// whose sha256 digest starts with `to`, as printed by `cargo hoist checksum`.
+    #[instrument(skip(binary))]
+    pub fn rollback(
+        ctx: &HoistContext,
+        binary: impl AsRef<str>,
+        to: Option<String>,
+        quiet: bool,
+    ) -> Result<()> {
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let binary = binary.as_ref();
+        let builds = registry.builds(binary);
+        let target = match to {
+            Some(prefix) => {
+                let prefix = prefix.to_lowercase();
+                let mut matches = vec![];
+                for b in &builds {
+                    if crate::hash::hash_file(&b.location, HashAlgorithm::Sha256)?
+                        .starts_with(&prefix)
+                    {
+                        matches.push(*b);
+                    }
+                }
+                match matches.as_slice() {
+                    [b] => *b,
+                    [] => anyhow::bail!("No registered build of {} has digest {}", binary, prefix),
+                    _ => anyhow::bail!(
+                        "{} builds of {} match digest {}, use a longer prefix",
+                        matches.len(),
+                        binary,
+                        prefix
+                    ),
+                }
+            }
+            None => match builds.as_slice() {
+                [] => anyhow::bail!("No registered build of {} exists on disk", binary),
+                [_] => anyhow::bail!("{} has no earlier registered build to roll back to", binary),
+                [_, previous, ..] => *previous,
+            },
+        };
+        target.copy_to_dir(&ctx.cwd)?;
+        HoistManifest::record_in(&ctx.cwd, vec![HoistedFile::new(&target.name, target)]);
+        if !quiet {
+            HoistRegistry::print_color("Rolled back ", Color::Green, false)?;
+            HoistRegistry::print_color(binary, Color::Magenta, false)?;
+            HoistRegistry::print_color(
+                &format!(" to {}", target.location.display()),
+                Color::Cyan,
+                true,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{Registration, RegistrationSource};
+    use crate::hoist::HoistOptions;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_build_history() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_build_history");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        std::fs::create_dir_all(ctx.hoist_dir()).unwrap();
+        std::fs::write(ctx.config_path(), "history = 1\n").unwrap();
+        let build = test_tempdir.join("target/release/binary1");
+        let register = |contents: &str| {
+            std::fs::write(&build, contents).unwrap();
+            HoistRegistry::register_paths(
+                &ctx,
+                vec![build.clone()],
+                None,
+                InstallOptions::default(),
+                true,
+            )
+            .unwrap();
+        };
+        register("first build");
+        register("second build");
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(registry.builds("binary1").len(), 2);
+        let previous = registry.previous_builds();
+        assert_eq!(previous.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&previous[0].location).unwrap(),
+            "first build"
+        );
+
+        register("third build");
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(registry.builds("binary1").len(), 2);
+        std::fs::remove_file(&build).unwrap();
+        HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "third build"
+        );
+        HoistRegistry::rollback(&ctx, "binary1", None, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "second build"
+        );
+    }
+
+    #[test]
+    fn test_retain_latest() {
+        let registered = |name: &str, location: &str, at: u64| HoistedBinary {
+            registration: Some(Registration {
+                at,
+                ..Registration::now(RegistrationSource::Manual, PathBuf::from("/p"))
+            }),
+            ..HoistedBinary::new(name, PathBuf::from(location))
+        };
+        let mut registry = HoistRegistry {
+            binaries: HashSet::from([
+                HoistedBinary::new("a", PathBuf::from("/untracked/a")),
+                registered("a", "/old/a", 1),
+                registered("a", "/new/a", 3),
+                registered("a", "/mid/a", 2),
+                registered("b", "/b", 1),
+            ]),
+            ..Default::default()
+        };
+        let removed: Vec<_> = registry
+            .retain_latest(2)
+            .into_iter()
+            .map(|b| b.location)
+            .collect();
+        assert_eq!(
+            removed,
+            vec![PathBuf::from("/old/a"), PathBuf::from("/untracked/a")]
+        );
+        assert_eq!(registry.binaries.len(), 3);
+        assert_eq!(registry.retain_latest(0).len(), 1);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_rollback");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        let old = test_tempdir.join("old/target/release/binary1");
+        std::fs::create_dir_all(old.parent().unwrap()).unwrap();
+        std::fs::write(&old, "old build").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(60))
+            .unwrap();
+        std::fs::write(test_tempdir.join("target/release/binary1"), "new build").unwrap();
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary::new("binary1", old.clone()));
+        registry.save(&ctx).unwrap();
+
+        assert!(HoistRegistry::rollback(&ctx, "binary2", None, true).is_err());
+        HoistRegistry::rollback(&ctx, "binary1", None, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "old build"
+        );
+
+        let digest = crate::hash::hash_bytes(b"new build", HashAlgorithm::Sha256);
+        HoistRegistry::rollback(&ctx, "binary1", Some(digest[..8].to_string()), true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "new build"
+        );
+        assert!(HoistRegistry::rollback(&ctx, "binary1", Some("zz".to_string()), true).is_err());
+    }
+}
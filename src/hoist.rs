@@ -0,0 +1,1023 @@
+//! Hoist
+//!
+//! Placing registered binaries into a directory with `cargo hoist`, by the
+//! configured [CopyStrategy] or as shims.
+
+use anyhow::Result;
+use indicatif::ProgressBar;
+use inquire::Confirm;
+use inquire::{
+    formatter::MultiOptionFormatter, list_option::ListOption, validator::Validation, MultiSelect,
+};
+use is_terminal::IsTerminal;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{CopyStrategy, HoistedBinary, Preserve};
+use crate::context::HoistContext;
+use crate::hoisted::{HoistManifest, HoistedFile};
+use crate::progress::Unit;
+use crate::registry::{is_valid_name, recency, HoistRegistry};
+
+/// What [HoistRegistry::hoist_with] does when a file in the destination
+/// differs from the binary about to replace it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Show what changes and ask before replacing the file, or replace it
+    /// with a warning when not run in a terminal
+    #[default]
+    Prompt,
+    /// Replace the file
+    Force,
+    /// Keep the file, skipping the binary
+    NoClobber,
+}
+
+/// Options controlling how [HoistRegistry::hoist_with] brings binaries into scope.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HoistOptions {
+    /// Write a shim that execs the registered binary instead of copying it.
+    pub shim: bool,
+    /// Only hoist builds cross-compiled for this target triple.
+    pub target: Option<String>,
+    /// Only hoist the registered build at this location.
+    pub location: Option<PathBuf>,
+    /// The project whose target directory unregistered binaries are hoisted
+    /// from, instead of the working directory.
+    pub project: Option<PathBuf>,
+    /// Only hoist binaries with this tag, hoisting every tagged binary when
+    /// no binaries are named.
+    pub tag: Option<String>,
+    /// Hoist every registered binary, as if each was named.
+    pub all: bool,
+    /// Also hoist expired binaries.
+    pub expired: bool,
+    /// How to place the binaries, instead of the `strategy` of the user config.
+    pub strategy: Option<CopyStrategy>,
+    /// The directory to hoist into, created if missing, instead of the
+    /// working directory.
+    pub to: Option<PathBuf>,
+    /// Hoist into the global bin directory of the user config, by default
+    /// `~/.cargo/bin`, instead of the working directory.
+    pub global: bool,
+    /// Copy binaries without their modification time.
+    pub no_preserve: bool,
+    /// Keep the setuid, setgid and sticky bits of copied binaries, which are
+    /// cleared by default.
+    pub preserve_special_bits: bool,
+    /// The file name to hoist a single binary as, instead of its name.
+    pub rename: Option<String>,
+    /// Hoist the highest registered version of each named binary satisfying
+    /// this semver requirement, e.g. `^1.2`, as a `name@^1.2` spec does.
+    pub version: Option<String>,
+    /// Hoist only the most recently registered or built of the builds
+    /// sharing a name, instead of prompting for which to hoist.
+    pub latest: bool,
+    /// What to do when a file in the destination differs from the binary.
+    /// [OverwritePolicy::Force] also hoists binaries built for another
+    /// platform, which are refused otherwise.
+    pub overwrite: OverwritePolicy,
+    /// Print what would be placed into the destination without touching it
+    /// or the registry.
+    pub dry_run: bool,
+}
+
+impl HoistRegistry {
+    /// Hoists binaries from the hoist toml registry into scope.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::hoist_with`"
+    )]
+    #[instrument(skip(binaries))]
+    pub fn hoist(binaries: Vec<String>, quiet: bool) -> Result<()> {
+        let ctx = HoistContext::from_env()?;
+        HoistRegistry::hoist_with(&ctx, binaries, HoistOptions::default(), quiet)
+    }
+
+    /// Hoists binaries from the hoist toml registry into scope with the given [HoistOptions].
+    #[instrument(skip(binaries))]
+    pub fn hoist_with(
+        ctx: &HoistContext,
+        binaries: Vec<String>,
+        options: HoistOptions,
+        quiet: bool,
+    ) -> Result<()> {
+        let mut registry = match options.dry_run {
+            true => HoistRegistry::load_dry_run(ctx)?,
+            false => {
+                HoistRegistry::setup_with(ctx, quiet)?;
+                HoistRegistry::load(ctx)?
+            }
+        };
+        if !options.expired {
+            registry.drop_expired(ctx)?;
+        }
+        let config = crate::config::HoistConfig::load(ctx)?;
+        let strategy = options.strategy.unwrap_or(config.strategy);
+        let mut binaries = match options.all {
+            true => registry.names(),
+            false => registry.expand_globs(&binaries)?,
+        };
+        if options.all && binaries.is_empty() {
+            anyhow::bail!("No binaries are registered");
+        }
+        // Previous builds are only restored by a rollback.
+        registry.trim_history(0);
+
+        // If binaries not contained in the global registry,
+        // check the local build path to see if we want to hoist a local
+        // bin.
+        let mut registered = registry.binaries;
+        if !registered
+            .iter()
+            .any(|b| binaries.iter().any(|s| b.matches(s)))
+        {
+            // todo(refcell): fuzzy match binaries in case of mispellings
+            //                if found, prompt the user with an inquire confirm
+            let project = options
+                .project
+                .as_ref()
+                .map_or(ctx.cwd.clone(), |p| ctx.resolve(p));
+            if !project.is_dir() {
+                anyhow::bail!("Project {} does not exist", project.display());
+            }
+            let mut p = crate::project::Project::new(ctx, &project);
+            p.load()?;
+            p.hoisted_binaries()?.into_iter().for_each(|hb| {
+                let _ = registered.insert(hb);
+            });
+        }
+        if let Some(target) = &options.target {
+            registered.retain(|b| b.triple.as_ref() == Some(target));
+        }
+        if let Some(location) = &options.location {
+            registered.retain(|b| &b.location == location);
+        }
+        if let Some(tag) = &options.tag {
+            registered.retain(|b| b.has_tag(tag));
+            if binaries.is_empty() {
+                binaries = registered.iter().map(|b| b.name.clone()).collect();
+                binaries.sort();
+                binaries.dedup();
+                if binaries.is_empty() {
+                    anyhow::bail!("No registered binaries are tagged {}", tag);
+                }
+            }
+        }
+
+        if options.version.is_some() && binaries.is_empty() {
+            anyhow::bail!("Name the binaries to hoist by version requirement");
+        }
+        let binaries =
+            HoistRegistry::resolve_versions(&registered, binaries, options.version.as_deref())?;
+
+        // If the user provided no binaries, use an inquire select to prompt
+        // the user to select which binaries to hoist.
+        let mut selected;
+        if binaries.is_empty() {
+            selected = HoistRegistry::multiselect_registered(&registered, quiet)?;
+        }
+        // With `latest`, hoist the most recent of the builds sharing a name.
+        else if options.latest {
+            let mut latest: HashMap<String, HoistedBinary> = HashMap::new();
+            for b in registered
+                .into_iter()
+                .filter(|b| binaries.iter().any(|s| b.matches(s)))
+            {
+                match latest.get(&b.name) {
+                    Some(l) if (recency(l), &l.location) >= (recency(&b), &b.location) => {}
+                    _ => {
+                        latest.insert(b.name.clone(), b);
+                    }
+                }
+            }
+            selected = latest.into_values().collect();
+        }
+        // If no tty, hoist all binaries, including redundant ones.
+        else if !std::io::stdout().is_terminal() {
+            selected = registered
+                .into_iter()
+                .filter(|b| binaries.iter().any(|s| b.matches(s)))
+                .collect();
+        }
+        // Otherwise, we want to convert the binaries to a set of de-duplicated hoisted binaries.
+        else {
+            let found: Vec<_> = registered
+                .into_iter()
+                .filter(|b| binaries.iter().any(|s| b.matches(s)))
+                .collect();
+            // Get the non-duplicate binaries from the found binaries.
+            let non_duplicate = found
+                .iter()
+                .filter(|b| {
+                    found
+                        .iter()
+                        .filter(|b2| b2.name == b.name)
+                        .collect::<Vec<_>>()
+                        .len()
+                        == 1
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            HoistRegistry::print_color(
+                &format!(
+                    "Found {} conflicting registered binaries, opening a multiselect prompt to select which binaries to hoist.",
+                    found.len()
+                ),
+                Color::Yellow,
+                true,
+            )?;
+            selected = HoistRegistry::multiselect_registered(
+                &HashSet::from_iter(found.into_iter()),
+                quiet,
+            )?;
+            // Extend the selected binaries with the non-duplicate binaries.
+            selected.extend(non_duplicate);
+        }
+
+        let dest = match (&options.to, options.global) {
+            (Some(dir), _) => ctx.resolve(dir),
+            (None, true) => config.global_bin(ctx),
+            (None, false) => ctx.cwd.clone(),
+        };
+        if dest != ctx.cwd && !options.dry_run {
+            std::fs::create_dir_all(&dest)?;
+        }
+        // Like `cargo install --root`, the tracking files of the binaries
+        // cargo installed into a bin directory are kept in its parent.
+        let installed = match (options.global, dest.parent()) {
+            (true, Some(root)) => crate::adopt::installed_packages(root).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        if let Some(name) = &options.rename {
+            if !is_valid_name(name) {
+                anyhow::bail!("Invalid binary name: {:?}", name);
+            }
+            if selected.iter().any(|b| b.name != selected[0].name) {
+                anyhow::bail!("--as can only rename a single hoisted binary");
+            }
+        }
+        let into = options.to.is_some() || options.global;
+        let placements = selected
+            .into_iter()
+            .map(|b| {
+                // The binary placed into the destination, under its new name.
+                let placed = HoistedBinary {
+                    name: options.rename.clone().unwrap_or_else(|| b.name.clone()),
+                    ..b.clone()
+                };
+                (b, placed)
+            })
+            .collect();
+        // Hashing the files about to be replaced takes a while for large
+        // binaries, so the destinations are compared on the worker pool.
+        let placements = crate::parallel::try_map(placements, |(b, placed)| {
+            let up_to_date = !options.shim && placed.is_up_to_date(&dest, strategy)?;
+            let summary = match options.shim || up_to_date {
+                true => None,
+                false => placed.change_summary(&dest)?,
+            };
+            Ok((b, placed, up_to_date, summary))
+        })?;
+
+        // Warnings and prompts come first, so that the binaries can then be
+        // placed in parallel.
+        let mut pending = Vec::with_capacity(placements.len());
+        let mut hoisted = Vec::with_capacity(placements.len());
+        let mut files = Vec::with_capacity(placements.len());
+        for (b, placed, up_to_date, mut summary) in placements {
+            if up_to_date {
+                if !quiet {
+                    HoistRegistry::print_color(&placed.name, Color::Magenta, false)?;
+                    HoistRegistry::print_color(" is up to date", Color::Green, true)?;
+                }
+                if !options.dry_run {
+                    #[cfg(feature = "server")]
+                    crate::metrics::METRICS.record_hoists(1);
+                    files.push(HoistedFile::new(&placed.name, &b));
+                    hoisted.push(b);
+                }
+                continue;
+            }
+            if let Some(mismatch) = crate::platform::mismatch(&b.location, b.triple.as_deref()) {
+                if options.overwrite != OverwritePolicy::Force && !options.dry_run {
+                    anyhow::bail!("{} {}, pass --force to hoist it anyway", b.name, mismatch);
+                }
+                eprintln!("cargo-hoist: warning: {} {}", b.name, mismatch);
+            }
+            if let Some(warning) = crate::elf::compatibility_warning(&b.location) {
+                eprintln!("cargo-hoist: warning: {} {}", b.name, warning);
+            }
+            if let Some(p) = installed.iter().find(|p| p.bins.contains(&placed.name)) {
+                if dest.join(&placed.name).exists() {
+                    eprintln!(
+                        "cargo-hoist: warning: replacing {} installed by `cargo install` of {} {}",
+                        placed.name, p.name, p.version
+                    );
+                }
+            }
+            if options.dry_run {
+                if !quiet {
+                    let verb = match options.shim {
+                        true => "shim".to_string(),
+                        false => strategy.to_string(),
+                    };
+                    HoistRegistry::print_dry_run_hoist(
+                        &placed,
+                        &dest,
+                        &verb,
+                        summary,
+                        options.overwrite,
+                    )?;
+                }
+                continue;
+            }
+            if let Some(s) = summary.as_ref().filter(|s| !s.is_unchanged()) {
+                if !HoistRegistry::confirm_overwrite(&placed, &dest, s, options.overwrite)? {
+                    if !quiet {
+                        HoistRegistry::print_color("Skipped ", Color::Yellow, false)?;
+                        HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+                        HoistRegistry::print_color(
+                            ", a different file exists",
+                            Color::Yellow,
+                            true,
+                        )?;
+                    }
+                    continue;
+                }
+                // The prompt already showed what changes.
+                if options.overwrite == OverwritePolicy::Prompt && std::io::stdin().is_terminal() {
+                    summary = None;
+                }
+            }
+            pending.push((b, placed, summary));
+        }
+
+        // Builds hoisted under the same name are placed one after another,
+        // so the last one wins, and different names in parallel.
+        let mut groups: Vec<Vec<(HoistedBinary, HoistedBinary, _)>> = Vec::new();
+        for p in pending {
+            match groups.iter_mut().find(|g| g[0].1.name == p.1.name) {
+                Some(group) => group.push(p),
+                None => groups.push(vec![p]),
+            }
+        }
+        let count = groups.iter().map(Vec::len).sum::<usize>();
+        let preserve = match (options.no_preserve, options.preserve_special_bits) {
+            (true, _) => Preserve::None,
+            (false, true) => Preserve::All,
+            (false, false) => Preserve::Times,
+        };
+        let progress = match !quiet && count > 1 {
+            true => crate::progress::bar("Hoisting", count as u64, Unit::Items),
+            false => ProgressBar::hidden(),
+        };
+        let placed = crate::parallel::try_map(groups, |group| {
+            group
+                .into_iter()
+                .map(|(b, placed, summary)| {
+                    let used = match options.shim {
+                        true => {
+                            b.shim_to_dir(&dest)?;
+                            None
+                        }
+                        false => Some(placed.hoist_to_dir(&dest, strategy, preserve)?),
+                    };
+                    progress.inc(1);
+                    Ok((b, placed, summary, used))
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        progress.finish_and_clear();
+
+        for (b, placed, summary, used) in placed.into_iter().flatten() {
+            let compressed = crate::content::is_compressed(&b.location);
+            if used.is_some_and(|used| used != strategy) && !compressed {
+                eprintln!(
+                    "cargo-hoist: warning: could not {} {}, copied it instead",
+                    strategy, b.name
+                );
+            }
+            #[cfg(feature = "server")]
+            crate::metrics::METRICS.record_hoists(1);
+            if !quiet {
+                HoistRegistry::print_color("Successfully hoisted ", Color::Green, false)?;
+                let renamed = placed.name != b.name;
+                HoistRegistry::print_color(&b.name, Color::Magenta, !into && !renamed)?;
+                if renamed {
+                    let name = format!(" as {}", placed.name);
+                    HoistRegistry::print_color(&name, Color::Magenta, !into)?;
+                }
+                if into {
+                    let into = format!(" into {}", dest.display());
+                    HoistRegistry::print_color(&into, Color::Cyan, true)?;
+                }
+                match summary {
+                    Some(s) if s.is_unchanged() => {
+                        HoistRegistry::print_color("  unchanged", Color::White, true)?
+                    }
+                    Some(s) => HoistRegistry::print_color(&s.to_string(), Color::White, true)?,
+                    None => {}
+                }
+            }
+            files.push(HoistedFile::new(&placed.name, &b));
+            hoisted.push(b);
+        }
+        if !options.dry_run {
+            HoistManifest::record_in(&dest, files);
+            HoistRegistry::record_hoisted(ctx, &hoisted, true);
+        }
+        Ok(())
+    }
+
+    /// Replaces each `name@requirement` spec, e.g. `my-tool@^1.2`, or with a
+    /// `version` requirement each spec naming no version, with the
+    /// `name@version` of the highest registered version satisfying it.
+    /// Specs naming an exact version, e.g. `my-tool@1.2.3`, are kept as is.
+    ///
+    /// Fails, listing the registered versions, if no build satisfies it.
+    fn resolve_versions(
+        registered: &HashSet<HoistedBinary>,
+        specs: Vec<String>,
+        version: Option<&str>,
+    ) -> Result<Vec<String>> {
+        specs
+            .into_iter()
+            .map(|spec| {
+                let (name, req) = match (spec.split_once('@'), version) {
+                    (Some((name, req)), _) => (name, req),
+                    (None, Some(req)) => (spec.as_str(), req),
+                    (None, None) => return Ok(spec),
+                };
+                let req = req.trim().trim_start_matches('v');
+                if crate::semver::Version::parse(req).is_ok() {
+                    return Ok(format!("{}@{}", name, req));
+                }
+                let parsed = crate::semver::VersionReq::parse(req)?;
+                let mut versions: Vec<_> = registered
+                    .iter()
+                    .filter(|b| b.name == name)
+                    .filter_map(|b| crate::semver::Version::parse(b.version.as_deref()?).ok())
+                    .collect();
+                versions.sort();
+                versions.dedup();
+                match versions.iter().rev().find(|v| parsed.matches(v)) {
+                    Some(best) => Ok(format!("{}@{}", name, best)),
+                    None if versions.is_empty() => {
+                        anyhow::bail!("No registered build of {} has a known version", name)
+                    }
+                    None => anyhow::bail!(
+                        "No registered build of {} matches {}, available versions: {}",
+                        name,
+                        req,
+                        versions
+                            .iter()
+                            .rev()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Prints what hoisting `binary` into `dest` by `verb`, e.g. `copy`,
+    /// would do to the existing file summarized by `summary`, if any.
+    fn print_dry_run_hoist(
+        binary: &HoistedBinary,
+        dest: &Path,
+        verb: &str,
+        summary: Option<crate::binaries::ChangeSummary>,
+        policy: OverwritePolicy,
+    ) -> Result<()> {
+        let target = dest.join(&binary.name).display().to_string();
+        match summary {
+            Some(s) if !s.is_unchanged() && policy == OverwritePolicy::NoClobber => {
+                HoistRegistry::print_color("Would skip ", Color::Yellow, false)?;
+                HoistRegistry::print_color(&binary.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(", a different file exists", Color::Yellow, true)
+            }
+            summary => {
+                HoistRegistry::print_color(&format!("Would {} ", verb), Color::Green, false)?;
+                HoistRegistry::print_color(&binary.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(&format!(" to {}", target), Color::Cyan, true)?;
+                match summary {
+                    Some(s) if s.is_unchanged() => {
+                        HoistRegistry::print_color("  unchanged", Color::White, true)
+                    }
+                    Some(s) => {
+                        HoistRegistry::print_color("  replacing:", Color::Yellow, true)?;
+                        HoistRegistry::print_color(&s.to_string(), Color::White, true)
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Returns if `binary` may replace the different file of the same name in
+    /// `dest`, summarized by `summary`, under the [OverwritePolicy].
+    fn confirm_overwrite(
+        binary: &HoistedBinary,
+        dest: &Path,
+        summary: &crate::binaries::ChangeSummary,
+        policy: OverwritePolicy,
+    ) -> Result<bool> {
+        let existing = dest.join(&binary.name);
+        match policy {
+            OverwritePolicy::Force => Ok(true),
+            OverwritePolicy::NoClobber => Ok(false),
+            OverwritePolicy::Prompt if std::io::stdin().is_terminal() => {
+                HoistRegistry::print_color(
+                    &format!(
+                        "{} differs from the registered {}:",
+                        existing.display(),
+                        binary.name
+                    ),
+                    Color::Yellow,
+                    true,
+                )?;
+                HoistRegistry::print_color(&summary.to_string(), Color::White, true)?;
+                Ok(Confirm::new(&format!("Replace {}?", existing.display()))
+                    .with_default(false)
+                    .prompt()?)
+            }
+            OverwritePolicy::Prompt => {
+                eprintln!(
+                    "cargo-hoist: warning: replacing {}, which differs from the registered {}",
+                    existing.display(),
+                    binary.name
+                );
+                Ok(true)
+            }
+        }
+    }
+
+    /// Stamps the registered binaries among `binaries` as hoisted now, and
+    /// with `count`, bumps their hoist counters.
+    ///
+    /// Failing to record usage only logs a warning, so that a read-only
+    /// registry never blocks hoisting or executing a binary.
+    pub(crate) fn record_hoisted(ctx: &HoistContext, binaries: &[HoistedBinary], count: bool) {
+        let res = (|| -> Result<()> {
+            let _lock = HoistRegistry::lock(ctx)?;
+            let mut registry = HoistRegistry::load(ctx)?;
+            let now = crate::time::unix_now();
+            let hoisted: Vec<_> = binaries
+                .iter()
+                .filter_map(|b| registry.binaries.get(b).cloned())
+                .collect();
+            if hoisted.is_empty() {
+                return Ok(());
+            }
+            for mut b in hoisted {
+                b.last_hoisted_at = Some(now);
+                b.hoist_count += count as u64;
+                registry.binaries.replace(b);
+            }
+            registry.save(ctx)
+        })();
+        if let Err(e) = res {
+            tracing::warn!("Failed to record hoisted binaries: {}", e);
+        }
+    }
+
+    /// Prompts the user for a list of hoisted binaries with a [MultiSelect].
+    #[instrument(skip(registered, quiet))]
+    pub fn multiselect_registered(
+        registered: &HashSet<HoistedBinary>,
+        quiet: bool,
+    ) -> Result<Vec<HoistedBinary>> {
+        // Offer the most hoisted binaries first.
+        let mut registered: Vec<_> = registered.iter().collect();
+        registered.sort_by(|a, b| {
+            b.hoist_count
+                .cmp(&a.hoist_count)
+                .then_with(|| (&a.name, &a.location).cmp(&(&b.name, &b.location)))
+        });
+        let options = registered
+            .iter()
+            .map(|b| match &b.version {
+                Some(v) => format!("{} ({}) v{}", b.name, b.location.display(), v),
+                None => format!("{} ({})", b.name, b.location.display()),
+            })
+            .collect();
+        let validator = move |a: &[ListOption<&String>]| {
+            if !quiet {
+                tracing::debug!("Received binary input selection: {:?}", a);
+            }
+            Ok(Validation::Valid)
+        };
+        let formatter: MultiOptionFormatter<'_, String> =
+            &|a| format!("{} different bins", a.len());
+        match MultiSelect::new("Which binaries would you like to hoist?", options)
+            .with_validator(validator)
+            .with_formatter(formatter)
+            .prompt()
+        {
+            Ok(choices) => {
+                // The maximum hoisted binary size is how many binaries are registered.
+                let mut res = Vec::with_capacity(choices.len());
+                for c in choices {
+                    let mut split = c.split_whitespace();
+                    let name = split.next().ok_or(anyhow::anyhow!(
+                        "Failed to parse selected binary name: {}",
+                        c
+                    ))?;
+                    let location = split.next().ok_or(anyhow::anyhow!(
+                        "Failed to parse selected binary location: {}",
+                        c
+                    ))?;
+                    let location = location
+                        .trim_start_matches('(')
+                        .trim_end_matches(')')
+                        .to_string();
+                    res.push(HoistedBinary::new(
+                        name.to_string(),
+                        PathBuf::from(location),
+                    ));
+                }
+                Ok(res)
+            }
+            Err(e) => {
+                if !quiet {
+                    HoistRegistry::print_color(
+                        "Failed to hoist selected binary",
+                        Color::Red,
+                        true,
+                    )?;
+                }
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::{install_linked, setup_test};
+    use std::io::Write;
+    use std::os::unix::prelude::OpenOptionsExt;
+
+    #[test]
+    fn test_hoist() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_hoist");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let build = registry.latest("binary1").unwrap();
+        assert_eq!(build.profile.as_deref(), Some("release"));
+        assert_eq!(build.triple, None);
+
+        let cross = HoistOptions {
+            target: Some("x86_64-unknown-linux-musl".to_string()),
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary1".to_string()], cross, false).unwrap();
+        let binary1 = test_tempdir.join("binary1");
+        assert!(!binary1.exists());
+
+        HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1".to_string()],
+            HoistOptions::default(),
+            false,
+        )
+        .unwrap();
+        HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1".to_string()],
+            HoistOptions::default(),
+            false,
+        )
+        .unwrap();
+
+        assert!(std::path::Path::new(&binary1).exists());
+        let binary2 = test_tempdir.join("binary2");
+        assert!(!std::path::Path::new(&binary2).exists());
+
+        let to = HoistOptions {
+            to: Some(PathBuf::from("bin/tools")),
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary2".to_string()], to, true).unwrap();
+        assert!(test_tempdir.join("bin/tools/binary2").exists());
+        assert!(!binary2.exists());
+
+        std::fs::write(&binary2, "mine").unwrap();
+        let no_clobber = HoistOptions {
+            overwrite: OverwritePolicy::NoClobber,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary2".to_string()], no_clobber, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&binary2).unwrap(), "mine");
+        let force = HoistOptions {
+            overwrite: OverwritePolicy::Force,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary2".to_string()], force, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&binary2).unwrap(), "");
+        std::fs::remove_file(&binary2).unwrap();
+
+        std::fs::write(ctx.config_path(), "global-bin = \"~/.local/bin\"\n").unwrap();
+        let global = HoistOptions {
+            global: true,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary2".to_string()], global, true).unwrap();
+        assert!(test_tempdir.join(".local/bin/binary2").exists());
+
+        let globbed = HoistOptions {
+            to: Some(PathBuf::from("globbed")),
+            ..Default::default()
+        };
+        let patterns = vec!["binary?".to_string()];
+        HoistRegistry::hoist_with(&ctx, patterns, globbed.clone(), true).unwrap();
+        assert!(test_tempdir.join("globbed/binary1").exists());
+        assert!(test_tempdir.join("globbed/binary2").exists());
+        assert!(HoistRegistry::hoist_with(&ctx, vec!["op-*".to_string()], globbed, true).is_err());
+
+        let all = HoistOptions {
+            to: Some(PathBuf::from("all")),
+            all: true,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec![], all, true).unwrap();
+        assert!(test_tempdir.join("all/binary1").exists());
+        assert!(test_tempdir.join("all/binary2").exists());
+
+        let renamed = HoistOptions {
+            to: Some(PathBuf::from("renamed")),
+            rename: Some("tool".to_string()),
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary1".to_string()], renamed.clone(), true)
+            .unwrap();
+        assert!(test_tempdir.join("renamed/tool").exists());
+        assert!(!test_tempdir.join("renamed/binary1").exists());
+        let manifest = HoistManifest::load(&test_tempdir.join("renamed")).unwrap();
+        assert_eq!(manifest.files[0].name, "tool");
+        assert_eq!(manifest.files[0].binary, "binary1");
+        let both = vec!["binary1".to_string(), "binary2".to_string()];
+        assert!(HoistRegistry::hoist_with(&ctx, both, renamed, true).is_err());
+
+        let sibling = test_tempdir.join("sibling");
+        std::fs::create_dir_all(sibling.join("target/release")).unwrap();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(sibling.join("target/release/sibling-tool"))
+            .unwrap()
+            .write_all(b"sibling")
+            .unwrap();
+        let elsewhere = HoistOptions {
+            project: Some(PathBuf::from("sibling")),
+            ..Default::default()
+        };
+        let names = vec!["sibling-tool".to_string()];
+        HoistRegistry::hoist_with(&ctx, names.clone(), elsewhere, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("sibling-tool")).unwrap(),
+            "sibling"
+        );
+        let missing = HoistOptions {
+            project: Some(PathBuf::from("missing")),
+            ..Default::default()
+        };
+        assert!(HoistRegistry::hoist_with(&ctx, names, missing, true).is_err());
+
+        let foreign = test_tempdir.join("target/aarch64-apple-darwin/release/mac-tool");
+        std::fs::create_dir_all(foreign.parent().unwrap()).unwrap();
+        std::fs::write(&foreign, "mac").unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary {
+            triple: Some("aarch64-apple-darwin".to_string()),
+            ..HoistedBinary::new("mac-tool", foreign)
+        });
+        registry.save(&ctx).unwrap();
+        let names = vec!["mac-tool".to_string()];
+        if !cfg!(target_os = "macos") {
+            let err = HoistRegistry::hoist_with(&ctx, names.clone(), HoistOptions::default(), true)
+                .unwrap_err();
+            assert!(err.to_string().contains("is built for aarch64 Mach-O"));
+            assert!(!test_tempdir.join("mac-tool").exists());
+        }
+        let force = HoistOptions {
+            overwrite: OverwritePolicy::Force,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, names, force, true).unwrap();
+        assert!(test_tempdir.join("mac-tool").exists());
+    }
+
+    #[test]
+    fn test_hoist_version() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_hoist_version");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        std::fs::write(
+            test_tempdir.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.2.0\"\n",
+        )
+        .unwrap();
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        let current = registry.latest("binary1").unwrap().clone();
+        assert_eq!(current.version.as_deref(), Some("0.2.0"));
+        assert_eq!(current.display_name(), "binary1@0.2.0");
+
+        let old = test_tempdir.join("old/binary1");
+        std::fs::create_dir_all(old.parent().unwrap()).unwrap();
+        std::fs::write(&old, "old").unwrap();
+        registry.insert(HoistedBinary {
+            version: Some("0.1.0".to_string()),
+            ..HoistedBinary::new("binary1", old)
+        });
+        registry.save(&ctx).unwrap();
+        assert!(registry
+            .binaries
+            .iter()
+            .all(|b| !b.matches("binary1@0.3.0")));
+
+        HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1@v0.1.0".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "old"
+        );
+
+        HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1@>=0.1, <1".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        assert_ne!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "old"
+        );
+        let options = HoistOptions {
+            version: Some("~0.1".to_string()),
+            overwrite: OverwritePolicy::Force,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary1".to_string()], options, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "old"
+        );
+        let latest = HoistOptions {
+            latest: true,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary1".to_string()], latest, true).unwrap();
+        assert_ne!(
+            std::fs::read_to_string(test_tempdir.join("binary1")).unwrap(),
+            "old"
+        );
+        let err = HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1@^1.2".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No registered build of binary1 matches ^1.2, available versions: 0.2.0, 0.1.0"
+        );
+    }
+
+    #[test]
+    fn test_hoist_shim_and_newer_build() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_hoist_shim_and_newer_build");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        install_linked(&ctx, &test_tempdir);
+        HoistRegistry::hoist_with(
+            &ctx,
+            vec!["binary1".to_string()],
+            HoistOptions {
+                shim: true,
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+        let shim = std::fs::read_to_string(test_tempdir.join("binary1")).unwrap();
+        let release = test_tempdir.join("target/release/binary1");
+        assert!(shim.contains(&release.canonicalize().unwrap().display().to_string()));
+
+        // A newer debug build of the same binary makes the release build stale.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let debug = test_tempdir.join("target/debug");
+        std::fs::create_dir_all(&debug).unwrap();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(debug.join("binary1"))
+            .unwrap();
+        install_linked(&ctx, &test_tempdir);
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let stale = HoistedBinary::new("binary1", release.canonicalize().unwrap());
+        let newer = registry.newer_build(&stale).unwrap();
+        assert_eq!(
+            newer.location,
+            debug.join("binary1").canonicalize().unwrap()
+        );
+        assert!(registry.newer_build(newer).is_none());
+    }
+
+    #[test]
+    fn test_hoist_counts() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_hoist_counts");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let dest_ctx = ctx.with_cwd(dest.path());
+
+        HoistRegistry::hoist_with(
+            &dest_ctx,
+            vec!["binary1".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        HoistRegistry::hoist_with(
+            &dest_ctx,
+            vec!["binary1".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        HoistRegistry::hoist_with(
+            &dest_ctx,
+            vec!["binary2".to_string()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let ranked: Vec<_> = registry
+            .by_usage()
+            .into_iter()
+            .map(|b| (b.name.as_str(), b.hoist_count))
+            .collect();
+        assert_eq!(ranked, vec![("binary1", 2), ("binary2", 1)]);
+        assert!(registry
+            .latest("binary1")
+            .unwrap()
+            .last_hoisted_at
+            .is_some());
+    }
+}
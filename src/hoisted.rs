@@ -0,0 +1,237 @@
+//! Hoisted Files
+//!
+//! Every directory hoist places binaries into keeps a `.hoist-manifest`
+//! listing the files it placed there, so that `cargo hoist unhoist` deletes
+//! exactly those files and nothing the user put there.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::registry::HoistRegistry;
+
+/// The hoist manifest file name.
+pub const MANIFEST_FILE: &str = ".hoist-manifest";
+
+/// A file hoist placed into a directory.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HoistedFile {
+    /// The file name in the directory
+    pub name: String,
+    /// The name of the registered binary placed as the file
+    pub binary: String,
+    /// The location of the registered binary
+    pub location: PathBuf,
+    /// The version of the registered binary, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl HoistedFile {
+    /// Records `binary` placed into a directory as the file `name`.
+    pub fn new(name: impl Into<String>, binary: &HoistedBinary) -> Self {
+        Self {
+            name: name.into(),
+            binary: binary.name.clone(),
+            location: binary.location.clone(),
+            version: binary.version.clone(),
+        }
+    }
+
+    /// Returns if the file is selected by `name`, its file name or the name
+    /// of the registered binary placed as it.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.binary == name
+    }
+}
+
+/// The files hoist placed into a directory.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HoistManifest {
+    /// The placed files, sorted by name.
+    #[serde(default, rename = "file", skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<HoistedFile>,
+}
+
+impl HoistManifest {
+    /// The path of the hoist manifest of `dir`.
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(MANIFEST_FILE)
+    }
+
+    /// Loads the hoist manifest of `dir`, which is empty if `dir` has none.
+    #[instrument]
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = HoistManifest::path(dir);
+        if !path.exists() {
+            return Ok(HoistManifest::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the hoist manifest of `dir`, removing it once no files are left.
+    #[instrument(skip(self))]
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = HoistManifest::path(dir);
+        if self.files.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Records `file`, replacing the record of the file of the same name.
+    pub fn record(&mut self, file: HoistedFile) {
+        self.files.retain(|f| f.name != file.name);
+        self.files.push(file);
+        self.files.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// Records the files placed into `dir` in its hoist manifest.
+    ///
+    /// Failing to record them only logs a warning, as the files were hoisted.
+    pub fn record_in(dir: &Path, files: Vec<HoistedFile>) {
+        if files.is_empty() {
+            return;
+        }
+        let res = HoistManifest::load(dir).and_then(|mut manifest| {
+            files.into_iter().for_each(|f| manifest.record(f));
+            manifest.save(dir)
+        });
+        if let Err(e) = res {
+            tracing::warn!("Failed to record the hoisted files: {}", e);
+        }
+    }
+}
+
+/// Deletes the files hoist placed into `dir` selected by `names`, or every
+/// file it placed there when no names are given, as recorded in the hoist
+/// manifest of `dir`.
+///
+/// Fails if a name selects no recorded file.
+#[instrument(skip(names))]
+pub fn unhoist(dir: &Path, names: Vec<String>, quiet: bool) -> Result<()> {
+    let mut manifest = HoistManifest::load(dir)?;
+    if let Some(missing) = names
+        .iter()
+        .find(|n| !manifest.files.iter().any(|f| f.matches(n)))
+    {
+        anyhow::bail!("{} was not hoisted into {}", missing, dir.display());
+    }
+    let (removed, kept): (Vec<_>, Vec<_>) = manifest
+        .files
+        .into_iter()
+        .partition(|f| names.is_empty() || names.iter().any(|n| f.matches(n)));
+    manifest.files = kept;
+    for f in &removed {
+        let path = dir.join(&f.name);
+        if path.symlink_metadata().is_ok() {
+            std::fs::remove_file(&path)?;
+        }
+        if !quiet {
+            HoistRegistry::print_color("Unhoisted ", Color::Green, false)?;
+            HoistRegistry::print_color(&f.name, Color::Magenta, true)?;
+        }
+    }
+    if !quiet && removed.is_empty() {
+        HoistRegistry::print_color(
+            &format!("Nothing was hoisted into {}", dir.display()),
+            Color::Yellow,
+            true,
+        )?;
+    }
+    manifest.save(dir)
+}
+
+/// Checks that the files hoist placed into `dir`, as recorded in its hoist
+/// manifest, are present and executable, restoring the executable bits of
+/// files that lost them.
+///
+/// Fails if any recorded file is missing.
+#[instrument]
+pub fn verify(dir: &Path, quiet: bool) -> Result<()> {
+    let manifest = HoistManifest::load(dir)?;
+    let mut missing = 0;
+    for f in &manifest.files {
+        let path = dir.join(&f.name);
+        let (status, color) = match path.metadata() {
+            Err(_) => {
+                missing += 1;
+                ("missing", Color::Red)
+            }
+            // Symlinks run with the mode of the registered binary.
+            Ok(_) if path.is_symlink() => ("ok", Color::Green),
+            Ok(_) if crate::executables::ensure_executable(&path)? => {
+                ("restored the executable bits", Color::Yellow)
+            }
+            Ok(_) => ("ok", Color::Green),
+        };
+        if !quiet || color == Color::Red {
+            HoistRegistry::print_color(&format!("{}: ", f.name), Color::Blue, false)?;
+            HoistRegistry::print_color(status, color, true)?;
+        }
+    }
+    if !quiet && manifest.files.is_empty() {
+        HoistRegistry::print_color(
+            &format!("Nothing was hoisted into {}", dir.display()),
+            Color::Yellow,
+            true,
+        )?;
+    }
+    if missing > 0 {
+        anyhow::bail!("{} hoisted files are missing", missing);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_unhoist() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        let tool = HoistedBinary::new("tool", dir.join("store/tool"));
+        let other = HoistedBinary::new("other", dir.join("store/other"));
+        for name in ["tool", "renamed", "mine"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        HoistManifest::record_in(
+            dir,
+            vec![
+                HoistedFile::new("tool", &tool),
+                HoistedFile::new("renamed", &other),
+            ],
+        );
+        HoistManifest::record_in(dir, vec![HoistedFile::new("tool", &tool)]);
+        let manifest = HoistManifest::load(dir).unwrap();
+        let names: Vec<_> = manifest.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["renamed", "tool"]);
+
+        assert!(unhoist(dir, vec!["mine".to_string()], true).is_err());
+        unhoist(dir, vec!["other".to_string()], true).unwrap();
+        assert!(!dir.join("renamed").exists());
+        assert!(dir.join("tool").exists());
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        std::fs::set_permissions(dir.join("tool"), std::fs::Permissions::from_mode(0o644)).unwrap();
+        verify(dir, true).unwrap();
+        assert_eq!(mode(&dir.join("tool")), 0o755);
+        HoistManifest::record_in(dir, vec![HoistedFile::new("gone", &tool)]);
+        assert!(verify(dir, true).is_err());
+
+        unhoist(dir, vec![], true).unwrap();
+        assert!(!dir.join("tool").exists());
+        assert!(dir.join("mine").exists());
+        assert!(!HoistManifest::path(dir).exists());
+    }
+}
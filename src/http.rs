@@ -0,0 +1,593 @@
+//! Http
+//!
+//! A minimal HTTP/1.1 server and client over [std::net], enough to serve the
+//! registry to other hoist clients and the local dashboard without pulling in
+//! an async runtime.
+//!
+//! Connections are handled by a fixed pool of [WORKERS] threads and closed
+//! after a single request and response. The request line and headers are
+//! bounded by [MAX_LINE] and [MAX_HEADERS], and a connection that stalls for
+//! longer than [TIMEOUT] is dropped.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// The largest request body accepted by default, in bytes.
+pub const MAX_BODY: usize = 4 << 20;
+
+/// The longest request or header line accepted, in bytes.
+pub const MAX_LINE: usize = 8 << 10;
+
+/// The most headers accepted on a request.
+pub const MAX_HEADERS: usize = 100;
+
+/// How long a connection may stall on a read or write.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The number of connections handled at once.
+pub const WORKERS: usize = 16;
+
+/// How large a request body may be and where it is read to, decided from
+/// the request line and headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyLimit {
+    /// Buffer a body of at most this many bytes into [Request::body]
+    Buffered(usize),
+    /// Stream a body of at most this many bytes into a file at the path,
+    /// described by [Request::spooled]
+    Spooled(PathBuf, usize),
+}
+
+/// A request body streamed into a file.
+///
+/// The file is removed once the request is handled, unless the handler
+/// moves it elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spooled {
+    /// The file the body was written to
+    pub path: PathBuf,
+    /// The length of the body, in bytes
+    pub len: u64,
+    /// The hex encoded sha256 digest of the body
+    pub sha256: String,
+}
+
+/// A parsed HTTP request.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// The request method, e.g. `GET`
+    pub method: String,
+    /// The request path, without the query string
+    pub path: String,
+    /// The decoded query string parameters
+    pub query: HashMap<String, String>,
+    /// The request headers, keyed by lowercased name
+    pub headers: HashMap<String, String>,
+    /// The request body, when buffered
+    pub body: Vec<u8>,
+    /// The request body, when streamed into a file
+    pub spooled: Option<Spooled>,
+}
+
+impl Request {
+    /// Creates a request with the given method and target, e.g. `/search?q=a`.
+    pub fn new(method: &str, target: &str) -> Self {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Self {
+            method: method.to_string(),
+            path: decode(path),
+            query: parse_form(query),
+            ..Default::default()
+        }
+    }
+
+    /// Parses an `application/x-www-form-urlencoded` body.
+    pub fn form(&self) -> HashMap<String, String> {
+        parse_form(&String::from_utf8_lossy(&self.body))
+    }
+
+    /// Returns the value of the header with the given name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Returns the token of a `Authorization: Bearer <token>` header.
+    pub fn bearer(&self) -> Option<&str> {
+        self.header("authorization")?.strip_prefix("Bearer ")
+    }
+
+    /// Reads a request from the stream, with a body of at most [MAX_BODY] bytes.
+    pub fn read(reader: &mut impl BufRead) -> Result<Self> {
+        Request::read_with_limit(reader, |_| BodyLimit::Buffered(MAX_BODY))
+    }
+
+    /// Reads a request from the stream, with a body read as `limit` decides
+    /// for the request line and headers.
+    ///
+    /// The body is buffered or written as it arrives, so a client only makes
+    /// the server allocate as much as it actually sends.
+    pub fn read_with_limit(
+        reader: &mut impl BufRead,
+        limit: impl Fn(&Request) -> BodyLimit,
+    ) -> Result<Self> {
+        let mut line = String::new();
+        read_line(reader, &mut line)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            anyhow::bail!("malformed request line: {:?}", line.trim());
+        };
+        let mut request = Request::new(method, target);
+        for count in 0.. {
+            let mut line = String::new();
+            if read_line(reader, &mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if count == MAX_HEADERS {
+                anyhow::bail!("more than {} request headers", MAX_HEADERS);
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                request
+                    .headers
+                    .insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        let length = match request.header("content-length") {
+            Some(l) => l.parse::<usize>()?,
+            None => 0,
+        };
+        let read = match limit(&request) {
+            BodyLimit::Buffered(max) if length <= max => {
+                reader.take(length as u64).read_to_end(&mut request.body)?
+            }
+            BodyLimit::Spooled(path, max) if length <= max => {
+                let spooled = spool(&mut reader.take(length as u64), path)?;
+                let read = spooled.len as usize;
+                request.spooled = Some(spooled);
+                read
+            }
+            _ => anyhow::bail!("request body of {} bytes is too large", length),
+        };
+        if read < length {
+            if let Some(spooled) = &request.spooled {
+                std::fs::remove_file(&spooled.path)?;
+            }
+            anyhow::bail!("request body ended after {} of {} bytes", read, length);
+        }
+        Ok(request)
+    }
+}
+
+/// Writes everything the reader returns into a new file at `path`, hashing
+/// it on the way.
+fn spool(reader: &mut impl Read, path: PathBuf) -> Result<Spooled> {
+    let mut file = std::fs::File::create(&path)?;
+    let mut hasher = crate::hash::Hasher::new(crate::hash::HashAlgorithm::Sha256);
+    let mut buf = vec![0; 64 << 10];
+    let mut len = 0;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) => {
+                drop(file);
+                std::fs::remove_file(&path)?;
+                return Err(e.into());
+            }
+        };
+        hasher.update(&buf[..read]);
+        file.write_all(&buf[..read])?;
+        len += read as u64;
+    }
+    file.flush()?;
+    Ok(Spooled {
+        path,
+        len,
+        sha256: crate::hash::to_hex(&hasher.finalize()),
+    })
+}
+
+/// Reads a line of at most [MAX_LINE] bytes into `line`, returning the
+/// number of bytes read.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<usize> {
+    let read = reader.take(MAX_LINE as u64 + 1).read_line(line)?;
+    if read > MAX_LINE {
+        anyhow::bail!("request line longer than {} bytes", MAX_LINE);
+    }
+    Ok(read)
+}
+
+/// An HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// The status code
+    pub status: u16,
+    /// The `Content-Type` header
+    pub content_type: &'static str,
+    /// Additional response headers
+    pub headers: Vec<(&'static str, String)>,
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates a response with the given status, content type and body.
+    pub fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            content_type,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// A `text/html` response.
+    pub fn html(body: impl Into<String>) -> Self {
+        Self::new(200, "text/html; charset=utf-8", body.into())
+    }
+
+    /// A `303 See Other` redirect to `location`.
+    pub fn redirect(location: impl Into<String>) -> Self {
+        Self::error(303).with_header("Location", location)
+    }
+
+    /// A `text/plain` response.
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", body.into())
+    }
+
+    /// An `application/json` response.
+    pub fn json(body: impl Into<String>) -> Self {
+        Self::new(200, "application/json", body.into())
+    }
+
+    /// A plain text error response, with the status reason as the body.
+    pub fn error(status: u16) -> Self {
+        let response = Self::text(status, format!("{}\n", reason(status)));
+        match status {
+            401 => response.with_header("WWW-Authenticate", "Bearer"),
+            _ => response,
+        }
+    }
+
+    /// Writes the response to the stream.
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        for (name, value) in &self.headers {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+        write!(writer, "\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The reason phrase of a status code.
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        303 => "See Other",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Parses `key=value` pairs separated by `&`, percent-decoding both.
+pub fn parse_form(s: &str) -> HashMap<String, String> {
+    s.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            (decode(k), decode(v))
+        })
+        .collect()
+}
+
+/// Percent-encodes a url component.
+pub fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Percent-decodes a url component, treating `+` as a space.
+pub fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Compares two secrets in time independent of where they first differ.
+pub fn secrets_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Sends a request to `url`, an `http://host[:port][/path]` url, with the
+/// token as an `Authorization: Bearer <token>` header if given, returning the
+/// response status and body.
+pub fn fetch(method: &str, url: &str, token: Option<&str>, body: &[u8]) -> Result<(u16, Vec<u8>)> {
+    let rest = url.strip_prefix("http://").ok_or(anyhow::anyhow!(
+        "Only http:// urls are supported, got {}",
+        url
+    ))?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let mut stream = match host.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => TcpStream::connect(host)?,
+        _ => TcpStream::connect((host, 80))?,
+    };
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        method,
+        path,
+        host,
+        body.len()
+    )?;
+    if let Some(token) = token {
+        write!(stream, "Authorization: Bearer {}\r\n", token)?;
+    }
+    write!(stream, "\r\n")?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or(anyhow::anyhow!("malformed status line: {:?}", line.trim()))?;
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<u64>()?);
+            }
+        }
+    }
+    let mut body = vec![];
+    match length {
+        Some(length) => reader.take(length).read_to_end(&mut body)?,
+        None => reader.read_to_end(&mut body)?,
+    };
+    Ok((status, body))
+}
+
+/// Serves requests on `addr` with the handler until the process exits,
+/// reading request bodies as `limit` decides.
+pub fn serve<F, L>(addr: impl ToSocketAddrs, limit: L, handler: F) -> Result<()>
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+    L: Fn(&Request) -> BodyLimit + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    tracing::info!("Listening on http://{}", listener.local_addr()?);
+    serve_listener(listener, limit, handler)
+}
+
+/// Serves requests accepted by the listener with the handler, on a pool of
+/// [WORKERS] threads.
+///
+/// Accepted connections queue for a free worker, and the listener stops
+/// accepting while the queue is full.
+pub fn serve_listener<F, L>(listener: TcpListener, limit: L, handler: F) -> Result<()>
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+    L: Fn(&Request) -> BodyLimit + Send + Sync + 'static,
+{
+    let limit = Arc::new(limit);
+    let handler = Arc::new(handler);
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(WORKERS);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let limit = Arc::clone(&limit);
+        let handler = Arc::clone(&handler);
+        let receiver = Arc::clone(&receiver);
+        std::thread::spawn(move || loop {
+            let stream = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+            let Ok(stream) = stream else { return };
+            if let Err(e) = handle(stream, limit.as_ref(), handler.as_ref()) {
+                tracing::warn!("Failed to handle request: {:?}", e);
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!("Failed to accept connection: {:?}", e);
+                continue;
+            }
+        };
+        sender.send(stream)?;
+    }
+    Ok(())
+}
+
+fn handle(
+    stream: TcpStream,
+    limit: &dyn Fn(&Request) -> BodyLimit,
+    handler: &dyn Fn(&Request) -> Response,
+) -> Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match Request::read_with_limit(&mut reader, limit) {
+        Ok(request) => {
+            let response = handler(&request);
+            tracing::debug!("{} {} {}", request.method, request.path, response.status);
+            if let Some(spooled) = &request.spooled {
+                // The handler moved the file if it kept the body.
+                let _ = std::fs::remove_file(&spooled.path);
+            }
+            response
+        }
+        Err(e) => Response::text(400, format!("{}\n", e)),
+    };
+    let mut stream = stream;
+    response.write_to(&mut stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_roundtrip() {
+        let raw = "PUT /binaries/my%20bin?force=1&q=a+b HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer s3cret\r\nContent-Length: 4\r\n\r\nbodyextra";
+        let request = Request::read(&mut raw.as_bytes()).unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/binaries/my bin");
+        assert_eq!(request.query["q"], "a b");
+        assert_eq!(request.bearer(), Some("s3cret"));
+        assert_eq!(request.body, b"body");
+        assert!(Request::read(&mut "\r\n".as_bytes()).is_err());
+        assert!(Request::read_with_limit(&mut raw.as_bytes(), |_| BodyLimit::Buffered(3)).is_err());
+        let truncated = "PUT / HTTP/1.1\r\nContent-Length: 1048576\r\n\r\nbody";
+        assert!(Request::read(&mut truncated.as_bytes()).is_err());
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(Request::read(&mut long.as_bytes()).is_err());
+        let headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X: y\r\n".repeat(MAX_HEADERS));
+        assert!(Request::read(&mut headers.as_bytes()).is_ok());
+        let headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: y\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert!(Request::read(&mut headers.as_bytes()).is_err());
+
+        let mut out = vec![];
+        Response::error(401).write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(out.contains("WWW-Authenticate: Bearer\r\n"));
+        assert!(out.ends_with("\r\n\r\nUnauthorized\n"));
+
+        assert!(secrets_match("abc", "abc"));
+        assert!(!secrets_match("abc", "abd"));
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode(&encode("a b/ü")), "a b/ü");
+    }
+
+    #[test]
+    fn test_spooled_body() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("body");
+        let spool = |max| BodyLimit::Spooled(path.clone(), max);
+        let raw = "PUT /a HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody";
+        let request = Request::read_with_limit(&mut raw.as_bytes(), |_| spool(4)).unwrap();
+        assert!(request.body.is_empty());
+        let spooled = request.spooled.unwrap();
+        assert_eq!(spooled.len, 4);
+        assert_eq!(
+            spooled.sha256,
+            crate::hash::hash_bytes(b"body", crate::hash::HashAlgorithm::Sha256)
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"body");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(Request::read_with_limit(&mut raw.as_bytes(), |_| spool(3)).is_err());
+        let truncated = "PUT /a HTTP/1.1\r\nContent-Length: 8\r\n\r\nbody";
+        assert!(Request::read_with_limit(&mut truncated.as_bytes(), |_| spool(8)).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_fetch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/echo", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            serve_listener(
+                listener,
+                |_| BodyLimit::Buffered(8),
+                |r| match r.bearer() {
+                    Some(token) => Response::text(200, format!("{} {}", token, r.body.len())),
+                    None => Response::error(401),
+                },
+            )
+        });
+
+        assert_eq!(
+            fetch("PUT", &url, Some("t"), b"body").unwrap(),
+            (200, b"t 4".to_vec())
+        );
+        assert_eq!(fetch("GET", &url, None, b"").unwrap().0, 401);
+        assert_eq!(fetch("PUT", &url, Some("t"), &[0; 9]).unwrap().0, 400);
+        assert!(fetch("GET", "https://example.com", None, b"").is_err());
+    }
+}
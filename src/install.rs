@@ -0,0 +1,529 @@
+//! Install
+//!
+//! Registering the binaries cargo built, by `cargo hoist install`, the pre-cargo
+//! shell hook and `cargo hoist register`.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Registration, RegistrationSource};
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// Options controlling which artifacts [HoistRegistry::install_with] registers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InstallOptions {
+    /// Also register integration test harnesses.
+    pub tests: bool,
+    /// Also register bench harnesses.
+    pub benches: bool,
+    /// What triggered the registration.
+    pub source: RegistrationSource,
+    /// Register binaries at their build paths instead of copying them into
+    /// the content store.
+    pub no_store: bool,
+    /// How long the binaries are kept after they were last used, overriding
+    /// the `ttl` of the user config.
+    pub ttl: Option<std::time::Duration>,
+    /// Print what would be registered without changing the registry or
+    /// the content store.
+    pub dry_run: bool,
+}
+
+impl HoistRegistry {
+    /// Installs binaries in the hoist toml registry.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::install_with`"
+    )]
+    #[instrument(skip(pdir, binaries, quiet))]
+    pub fn install(pdir: Option<&Path>, binaries: Vec<String>, quiet: bool) -> Result<()> {
+        let ctx = HoistContext::from_env()?;
+        HoistRegistry::install_with(&ctx, pdir, binaries, InstallOptions::default(), quiet)
+    }
+
+    /// Installs binaries in the hoist toml registry with the given [InstallOptions].
+    #[instrument(skip(pdir, binaries, quiet))]
+    pub fn install_with(
+        ctx: &HoistContext,
+        pdir: Option<&Path>,
+        binaries: Vec<String>,
+        options: InstallOptions,
+        quiet: bool,
+    ) -> Result<()> {
+        // Build the hoist registry.
+        let (_lock, mut registry) = HoistRegistry::load_for_update(ctx, options.dry_run, quiet)?;
+
+        // Load binaries from the project
+        let mut p = crate::project::Project::new(ctx, &ctx.resolve(pdir.unwrap_or(&ctx.cwd)));
+        let harnesses = options.tests || options.benches;
+        #[cfg(feature = "server")]
+        let scan = std::time::Instant::now();
+        let mut hoisted = if binaries.is_empty() {
+            p.load()?;
+            p.hoisted_binaries()?
+        } else if !harnesses {
+            p.set_binaries(binaries.clone())?;
+            p.hoisted_binaries()?
+        } else {
+            p.load()?;
+            p.hoisted_binaries()?
+                .into_iter()
+                .filter(|b| binaries.contains(&b.name))
+                .collect()
+        };
+        if harnesses {
+            let artifacts = p.test_artifacts(options.tests, options.benches)?;
+            hoisted.extend(
+                artifacts
+                    .into_iter()
+                    .filter(|b| binaries.is_empty() || binaries.contains(&b.name)),
+            );
+        }
+
+        #[cfg(feature = "server")]
+        crate::metrics::METRICS.record_scan(scan.elapsed());
+
+        // Insert hoisted binaries
+        let registered = hoisted.len();
+        let project = p.root.canonicalize().unwrap_or(p.root.clone());
+        let packages = crate::metadata::ProjectPackages::load(&p.workspace_root());
+        let rustc = crate::fingerprint::rustc_version(&project);
+        let registration = match options.dry_run {
+            true => Registration::now(options.source, project),
+            false => Registration::snapshot(ctx, options.source, project),
+        };
+        for mut hb in hoisted {
+            hb.inspect_linkage();
+            hb.record_size();
+            hb.record_compilation(rustc.as_deref());
+            if let Some((profile, triple)) = p.build_of(&hb.location) {
+                hb.profile = Some(profile);
+                hb.triple = triple;
+            }
+            if options.dry_run {
+                HoistRegistry::print_registration(&hb, &registry, quiet)?;
+            } else if !options.no_store {
+                crate::content::store(ctx, &mut hb)?;
+            }
+            let package = packages.get(&hb.name).cloned().unwrap_or_default();
+            hb.ttl = options.ttl.map(|ttl| ttl.as_secs());
+            registry.insert(HoistedBinary {
+                version: package.version,
+                license: package.license,
+                description: package.description,
+                repository: package.repository,
+                homepage: package.homepage,
+                registration: Some(registration.clone()),
+                ..hb
+            });
+        }
+
+        let dropped = registry.apply_retention(ctx)?;
+
+        // Only perform a writeback if there are binaries to hoist.
+        match registered {
+            0 => tracing::warn!("No binaries found in the target directory"),
+            _ if options.dry_run => return HoistRegistry::print_dropped(&dropped, quiet),
+            _ => registry.save(ctx)?,
+        }
+        #[cfg(feature = "server")]
+        crate::metrics::METRICS.record_registrations(registered);
+
+        Ok(())
+    }
+
+    /// Registers explicit executable files, bypassing project scanning.
+    ///
+    /// Each binary is named after its file name unless `name` overrides it,
+    /// which is only allowed when registering a single path. Binaries are
+    /// copied into the content store unless the [InstallOptions] set
+    /// `no_store`. The `tests` and `benches` options don't apply.
+    #[instrument(skip(ctx, paths))]
+    pub fn register_paths(
+        ctx: &HoistContext,
+        paths: Vec<PathBuf>,
+        name: Option<String>,
+        options: InstallOptions,
+        quiet: bool,
+    ) -> Result<()> {
+        if name.is_some() && paths.len() != 1 {
+            anyhow::bail!("--as can only name a single registered path");
+        }
+        let (_lock, mut registry) = HoistRegistry::load_for_update(ctx, options.dry_run, quiet)?;
+        for path in &paths {
+            let path = ctx.resolve(path);
+            let file_name = crate::executables::exec_path(&path)?;
+            let location = path.canonicalize()?;
+            let mut binary = HoistedBinary::new(name.clone().unwrap_or(file_name), location);
+            binary.inspect_linkage();
+            binary.record_size();
+            let project = binary
+                .project()
+                .or_else(|| binary.location.parent().map(Path::to_path_buf))
+                .unwrap_or_default();
+            if let Some((profile, triple)) =
+                crate::project::Project::new(ctx, &project).build_of(&binary.location)
+            {
+                binary.profile = Some(profile);
+                binary.triple = triple;
+                binary.record_compilation(crate::fingerprint::rustc_version(&project).as_deref());
+            }
+            if options.dry_run {
+                HoistRegistry::print_registration(&binary, &registry, quiet)?;
+            } else if !options.no_store {
+                crate::content::store(ctx, &mut binary)?;
+            }
+            binary.ttl = options.ttl.map(|ttl| ttl.as_secs());
+            let package = crate::metadata::ProjectPackages::load(&project)
+                .get(&binary.name)
+                .cloned()
+                .unwrap_or_default();
+            let registration = match options.dry_run {
+                true => Registration::now(options.source, project),
+                false => Registration::snapshot(ctx, options.source, project),
+            };
+            if !quiet && !options.dry_run {
+                HoistRegistry::print_color("Registered ", Color::Green, false)?;
+                HoistRegistry::print_color(&binary.name, Color::Magenta, true)?;
+            }
+            registry.insert(HoistedBinary {
+                version: package.version,
+                license: package.license,
+                description: package.description,
+                repository: package.repository,
+                homepage: package.homepage,
+                registration: Some(registration),
+                ..binary
+            });
+        }
+        let dropped = registry.apply_retention(ctx)?;
+        if options.dry_run {
+            return HoistRegistry::print_dropped(&dropped, quiet);
+        }
+        registry.save(ctx)?;
+        #[cfg(feature = "server")]
+        crate::metrics::METRICS.record_registrations(paths.len());
+        Ok(())
+    }
+
+    /// Sets up the registry, locks and loads it to be updated and saved, or
+    /// for a dry run, only loads it, see [HoistRegistry::load_dry_run].
+    pub(crate) fn load_for_update(
+        ctx: &HoistContext,
+        dry_run: bool,
+        quiet: bool,
+    ) -> Result<(Option<crate::store::RegistryLock>, HoistRegistry)> {
+        if dry_run {
+            return Ok((None, HoistRegistry::load_dry_run(ctx)?));
+        }
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let lock = HoistRegistry::lock(ctx)?;
+        Ok((Some(lock), HoistRegistry::load(ctx)?))
+    }
+
+    /// Prints the registry change a dry run registering `binary` would make.
+    fn print_registration(
+        binary: &HoistedBinary,
+        registry: &HoistRegistry,
+        quiet: bool,
+    ) -> Result<()> {
+        if quiet {
+            return Ok(());
+        }
+        let verb = match registry
+            .binaries
+            .iter()
+            .any(|b| b.name == binary.name && b.built_path() == binary.built_path())
+        {
+            true => "Would update ",
+            false => "Would register ",
+        };
+        HoistRegistry::print_color(verb, Color::Green, false)?;
+        HoistRegistry::print_color(&binary.name, Color::Magenta, false)?;
+        HoistRegistry::print_color(
+            &format!(" ({})", binary.location.display()),
+            Color::Cyan,
+            true,
+        )
+    }
+
+    /// Prints the builds a dry run would drop past retention.
+    fn print_dropped(dropped: &[HoistedBinary], quiet: bool) -> Result<()> {
+        if quiet {
+            return Ok(());
+        }
+        for b in dropped {
+            HoistRegistry::print_color("Would drop ", Color::Yellow, false)?;
+            HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+            HoistRegistry::print_color(
+                &format!(" ({}) past retention", b.location.display()),
+                Color::Cyan,
+                true,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hoist::HoistOptions;
+    use crate::registry::tests::{install_linked, setup_test};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_install() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_install");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        install_linked(&ctx, &test_tempdir);
+
+        assert_eq!(
+            HoistRegistry::load(&ctx).unwrap(),
+            HoistRegistry {
+                binaries: HashSet::from([
+                    HoistedBinary::new(
+                        "binary1".to_string(),
+                        test_tempdir
+                            .join("target/release/binary1")
+                            .canonicalize()
+                            .unwrap()
+                    ),
+                    HoistedBinary::new(
+                        "binary2".to_string(),
+                        test_tempdir
+                            .join("target/release/binary2")
+                            .canonicalize()
+                            .unwrap()
+                    ),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_install_stores_copies() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_install_stores_copies");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        let binary1 = test_tempdir.join("target/release/binary1");
+        std::fs::write(&binary1, "v1").unwrap();
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        let stored = HoistRegistry::load(&ctx)
+            .unwrap()
+            .latest("binary1")
+            .cloned()
+            .unwrap();
+        assert!(stored.location.starts_with(crate::content::dir(&ctx)));
+        assert_eq!(stored.origin, Some(binary1.canonicalize().unwrap()));
+
+        // A rebuild keeps the old copy as history until it is trimmed,
+        // leaving the old copy unreferenced.
+        std::fs::write(&binary1, "v2").unwrap();
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(registry.builds("binary1").len(), 2);
+        let rebuilt = registry.latest("binary1").unwrap();
+        assert_ne!(rebuilt.location, stored.location);
+        std::fs::write(ctx.config_path(), "history = 0\n").unwrap();
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            HoistRegistry::load(&ctx).unwrap().builds("binary1").len(),
+            1
+        );
+        crate::content::gc(&ctx, false, true).unwrap();
+        assert!(!stored.location.exists());
+
+        std::fs::remove_dir_all(test_tempdir.join("target")).unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        HoistRegistry::hoist_with(
+            &ctx.with_cwd(dest.path()),
+            vec!["binary1".into()],
+            HoistOptions::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(dest.path().join("binary1")).unwrap(), b"v2");
+    }
+
+    #[test]
+    fn test_multiple_installs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_multiple_installs");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        install_linked(&ctx, &test_tempdir);
+        install_linked(&ctx, &test_tempdir);
+        install_linked(&ctx, &test_tempdir);
+        install_linked(&ctx, &test_tempdir);
+
+        assert_eq!(
+            HoistRegistry::load(&ctx).unwrap(),
+            HoistRegistry {
+                binaries: HashSet::from([
+                    HoistedBinary::new(
+                        "binary1".to_string(),
+                        test_tempdir
+                            .join("target/release/binary1")
+                            .canonicalize()
+                            .unwrap()
+                    ),
+                    HoistedBinary::new(
+                        "binary2".to_string(),
+                        test_tempdir
+                            .join("target/release/binary2")
+                            .canonicalize()
+                            .unwrap()
+                    ),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_install_records_package_metadata() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_install_records_package_metadata");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        std::fs::write(
+            test_tempdir.join("Cargo.toml"),
+            "[package]\nname = \"binary1\"\nlicense = \"MIT OR Apache-2.0\"\ndescription = \"A binary\"\nrepository = \"https://example.com/binary1\"\n",
+        )
+        .unwrap();
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let binary = registry.latest("binary1").unwrap();
+        assert_eq!(binary.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(binary.description.as_deref(), Some("A binary"));
+        assert!(HoistRegistry::repo(&ctx, "binary1", true).is_ok());
+        assert!(HoistRegistry::repo(&ctx, "binary2", true).is_ok());
+        assert!(registry
+            .explain("binary1")
+            .unwrap()
+            .contains("    repository: https://example.com/binary1\n"));
+        assert!(binary.has_license("apache-2.0"));
+        assert!(!binary.has_license("GPL-3.0"));
+    }
+
+    #[test]
+    fn test_register_paths() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_register_paths");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        std::fs::write(test_tempdir.join("notes.txt"), "").unwrap();
+
+        let release = PathBuf::from("target/release");
+        let linked = InstallOptions {
+            no_store: true,
+            ..Default::default()
+        };
+        HoistRegistry::register_paths(
+            &ctx,
+            vec![release.join("binary1")],
+            Some("tool".into()),
+            linked,
+            true,
+        )
+        .unwrap();
+        HoistRegistry::register_paths(&ctx, vec![release.join("binary2")], None, linked, true)
+            .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let tool = registry.latest("tool").unwrap();
+        assert_eq!(
+            tool.location,
+            test_tempdir
+                .join("target/release/binary1")
+                .canonicalize()
+                .unwrap()
+        );
+        assert_eq!(
+            tool.registration.as_ref().map(|r| r.project.clone()),
+            Some(test_tempdir.canonicalize().unwrap())
+        );
+        assert!(registry.latest("binary2").is_some());
+        assert!(registry.latest("binary1").is_none());
+
+        assert!(HoistRegistry::register_paths(
+            &ctx,
+            vec![release.join("binary1"), release.join("binary2")],
+            Some("tool".into()),
+            linked,
+            true
+        )
+        .is_err());
+        assert!(
+            HoistRegistry::register_paths(&ctx, vec!["notes.txt".into()], None, linked, true)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_concurrent_registration() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_concurrent_registration");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        HoistRegistry::setup_with(&ctx, true).unwrap();
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let ctx = ctx.clone();
+                let path = test_tempdir.join(format!("target/release/tool{}", i));
+                std::fs::write(&path, "").unwrap();
+                std::fs::set_permissions(
+                    &path,
+                    std::os::unix::fs::PermissionsExt::from_mode(0o755),
+                )
+                .unwrap();
+                std::thread::spawn(move || {
+                    HoistRegistry::register_paths(
+                        &ctx,
+                        vec![path],
+                        None,
+                        InstallOptions::default(),
+                        true,
+                    )
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap().unwrap();
+        }
+        assert_eq!(HoistRegistry::load(&ctx).unwrap().binaries.len(), 8);
+    }
+}
@@ -0,0 +1,329 @@
+//! Journal
+//!
+//! An append-only log of registry mutations in `~/.hoist/history.log`, one
+//! json [Entry] per line, recording the entries and aliases each save
+//! changed so `cargo hoist undo` can revert them.
+//!
+//! Usage statistics, the hoist counts and times, are not journaled: they
+//! change on every hoist and are carried along with the entries instead.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// The journal file, `~/.hoist/history.log`.
+pub fn path(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("history.log")
+}
+
+/// A journaled registry mutation.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When the mutation was saved, in seconds since the unix epoch
+    pub at: u64,
+    /// The hoist command line that made the mutation
+    pub command: String,
+    /// For an undo, the 1-based line of the entry it reverted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<usize>,
+    /// The removed entries, and the previous metadata of changed entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<HoistedBinary>,
+    /// The added entries, and the new metadata of changed entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<HoistedBinary>,
+    /// The previous targets of the changed aliases
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases_before: BTreeMap<String, String>,
+    /// The new targets of the changed aliases
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases_after: BTreeMap<String, String>,
+}
+
+impl Entry {
+    /// Returns the changes from `old` to `new`, or [None] if only usage
+    /// statistics changed.
+    pub fn diff(old: &HoistRegistry, new: &HoistRegistry) -> Option<Entry> {
+        let unchanged = |a: &HoistedBinary, b: &HoistedBinary| {
+            let without_usage = |b: &HoistedBinary| {
+                toml::Value::try_from(HoistedBinary {
+                    last_hoisted_at: None,
+                    hoist_count: 0,
+                    ..b.clone()
+                })
+                .ok()
+            };
+            without_usage(a) == without_usage(b)
+        };
+        let mut entry = Entry::default();
+        for b in &old.binaries {
+            if !new.binaries.get(b).is_some_and(|n| unchanged(b, n)) {
+                entry.before.push(b.clone());
+            }
+        }
+        for b in &new.binaries {
+            if !old.binaries.get(b).is_some_and(|o| unchanged(o, b)) {
+                entry.after.push(b.clone());
+            }
+        }
+        let names: HashSet<_> = old.aliases.keys().chain(new.aliases.keys()).collect();
+        for name in names {
+            if old.aliases.get(name) != new.aliases.get(name) {
+                if let Some(target) = old.aliases.get(name) {
+                    entry.aliases_before.insert(name.clone(), target.clone());
+                }
+                if let Some(target) = new.aliases.get(name) {
+                    entry.aliases_after.insert(name.clone(), target.clone());
+                }
+            }
+        }
+        if entry.before.is_empty()
+            && entry.after.is_empty()
+            && entry.aliases_before.is_empty()
+            && entry.aliases_after.is_empty()
+        {
+            return None;
+        }
+        let by_identity = |a: &HoistedBinary, b: &HoistedBinary| {
+            (&a.name, &a.location).cmp(&(&b.name, &b.location))
+        };
+        entry.before.sort_by(by_identity);
+        entry.after.sort_by(by_identity);
+        entry.at = crate::time::unix_now();
+        entry.command = command();
+        Some(entry)
+    }
+
+    /// Reverts the mutation on `registry`.
+    pub fn revert(&self, registry: &mut HoistRegistry) {
+        for b in &self.after {
+            registry.binaries.remove(b);
+        }
+        for b in &self.before {
+            registry.binaries.replace(b.clone());
+        }
+        for name in self.aliases_after.keys() {
+            registry.aliases.remove(name);
+        }
+        registry.aliases.extend(self.aliases_before.clone());
+    }
+}
+
+/// The hoist arguments of the running process, e.g. `register --hook`.
+fn command() -> String {
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("hoist") => args[1..].join(" "),
+        _ => args.join(" "),
+    }
+}
+
+/// Appends the entry to the journal.
+pub fn append(ctx: &HoistContext, entry: &Entry) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path(ctx))?;
+    writeln!(file, "{}", crate::json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Journals the changes from `old` to `new`, if any, warning instead of
+/// failing so a registry save never fails on its journal.
+pub fn record(ctx: &HoistContext, old: &HoistRegistry, new: &HoistRegistry) {
+    if !ctx.hoist_dir().exists() {
+        return;
+    }
+    if let Some(entry) = Entry::diff(old, new) {
+        if let Err(e) = append(ctx, &entry) {
+            tracing::warn!("Failed to journal the registry change: {}", e);
+        }
+    }
+}
+
+/// Reads the journal, oldest entry first.
+pub fn read(ctx: &HoistContext) -> Result<Vec<Entry>> {
+    let path = path(ctx);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(crate::json::from_str)
+        .collect()
+}
+
+/// Returns the 1-based line of the latest entry that is not an undo, nor
+/// already undone.
+fn undoable(entries: &[Entry]) -> Option<usize> {
+    let undone: HashSet<_> = entries.iter().filter_map(|e| e.undoes).collect();
+    entries
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, e)| (i + 1, e))
+        .find(|(line, e)| e.undoes.is_none() && !undone.contains(line))
+        .map(|(line, _)| line)
+}
+
+/// Reverts the latest registry mutation not yet undone, journaling the undo
+/// itself. Repeated undos step further back through the journal.
+#[instrument(skip(ctx))]
+pub fn undo(ctx: &HoistContext, quiet: bool) -> Result<()> {
+    HoistRegistry::create_registry_with(ctx, quiet)?;
+    let _lock = HoistRegistry::lock(ctx)?;
+    let entries = read(ctx)?;
+    let Some(line) = undoable(&entries) else {
+        anyhow::bail!("No registry changes to undo in {}", path(ctx).display());
+    };
+    let entry = &entries[line - 1];
+    let old = HoistRegistry::load(ctx)?;
+    let mut registry = old.clone();
+    entry.revert(&mut registry);
+    ctx.store().save(&registry)?;
+    ctx.set_snapshot(&registry);
+    // Journal the undo even if the registry already matched, so the next
+    // undo steps past the entry.
+    let undo = Entry::diff(&old, &registry).unwrap_or_else(|| Entry {
+        at: crate::time::unix_now(),
+        command: command(),
+        ..Default::default()
+    });
+    append(
+        ctx,
+        &Entry {
+            undoes: Some(line),
+            ..undo
+        },
+    )?;
+    if !quiet {
+        HoistRegistry::print_color("Undid ", Color::Green, false)?;
+        HoistRegistry::print_color(&format!("#{} ", line), Color::Blue, false)?;
+        HoistRegistry::print_color(&format!("`{}`", entry.command), Color::Cyan, true)?;
+    }
+    Ok(())
+}
+
+/// Prints the latest `limit` journal entries, newest first.
+#[instrument(skip(ctx))]
+pub fn log(ctx: &HoistContext, limit: usize) -> Result<()> {
+    let entries = read(ctx)?;
+    let undone: HashSet<_> = entries.iter().filter_map(|e| e.undoes).collect();
+    for (i, entry) in entries.iter().enumerate().rev().take(limit) {
+        let line = i + 1;
+        HoistRegistry::print_color(&format!("#{} ", line), Color::Blue, false)?;
+        HoistRegistry::print_color(
+            &crate::time::to_rfc3339(crate::time::from_unix(entry.at)),
+            Color::White,
+            false,
+        )?;
+        HoistRegistry::print_color(&format!(" `{}`", entry.command), Color::Cyan, false)?;
+        match (entry.undoes, undone.contains(&line)) {
+            (Some(of), _) => {
+                HoistRegistry::print_color(&format!(" undo of #{}", of), Color::Yellow, true)?
+            }
+            (None, true) => HoistRegistry::print_color(" (undone)", Color::Yellow, true)?,
+            (None, false) => println!(),
+        }
+        for b in &entry.before {
+            let sign = match entry.after.contains(b) {
+                true => "~",
+                false => "-",
+            };
+            println!("  {} {} ({})", sign, b.name, b.location.display());
+        }
+        for b in entry.after.iter().filter(|b| !entry.before.contains(b)) {
+            println!("  + {} ({})", b.name, b.location.display());
+        }
+        for (alias, target) in &entry.aliases_before {
+            match entry.aliases_after.get(alias) {
+                Some(new) => println!("  ~ alias {} -> {} (was {})", alias, new, target),
+                None => println!("  - alias {} -> {}", alias, target),
+            }
+        }
+        for (alias, target) in &entry.aliases_after {
+            if !entry.aliases_before.contains_key(alias) {
+                println!("  + alias {} -> {}", alias, target);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        registry.aliases.insert("x".to_string(), "a".to_string());
+        registry.save(&ctx).unwrap();
+        let mut tagged = registry.clone();
+        tagged.insert(HoistedBinary {
+            tags: vec!["tooling".to_string()],
+            ..HoistedBinary::new("a", PathBuf::from("/p/a"))
+        });
+        tagged.insert(HoistedBinary::new("b", PathBuf::from("/p/b")));
+        tagged.save(&ctx).unwrap();
+        let mut used = tagged.clone();
+        used.insert(HoistedBinary {
+            hoist_count: 3,
+            ..tagged.latest("b").cloned().unwrap()
+        });
+        used.save(&ctx).unwrap();
+
+        let entries = read(&ctx).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].before.len(), 1);
+        assert_eq!(entries[1].after.len(), 2);
+
+        undo(&ctx, true).unwrap();
+        let reverted = HoistRegistry::load(&ctx).unwrap();
+        assert!(reverted.latest("b").is_none());
+        assert!(reverted.latest("a").unwrap().tags.is_empty());
+
+        undo(&ctx, true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), HoistRegistry::default());
+        assert!(undo(&ctx, true).is_err());
+        let entries = read(&ctx).unwrap();
+        assert_eq!(entries[3].undoes, Some(1));
+        log(&ctx, 10).unwrap();
+    }
+
+    #[test]
+    fn test_save_diffs_against_loaded() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        let mut other = registry.clone();
+        other.insert(HoistedBinary::new("b", PathBuf::from("/p/b")));
+        ctx.store().save(&other).unwrap();
+        registry.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        registry.save(&ctx).unwrap();
+
+        // The entry holds the changes since the load, not since the store
+        // was last written.
+        let entries = read(&ctx).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].before.is_empty());
+        assert_eq!(entries[0].after.len(), 1);
+        assert_eq!(entries[0].after[0].name, "a");
+    }
+}
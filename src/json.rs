@@ -0,0 +1,398 @@
+//! Json
+//!
+//! A minimal json reader and writer.
+//!
+//! Values are bridged through [toml::Value] so any type implementing serde's
+//! [Serialize] or [Deserialize] can be read from or written as json without
+//! pulling in a dedicated json dependency. Since toml has no notion of null,
+//! an object member that is `null` parses as absent, which deserializes as
+//! [None], while a `null` anywhere else is an error.
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use toml::Value;
+
+/// The deepest nesting of arrays and objects the parser accepts.
+const MAX_DEPTH: usize = 128;
+
+/// Deserializes a json string into `T`.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    let value = parse(s)?;
+    Ok(value.try_into()?)
+}
+
+/// Serializes `T` into a compact json string.
+pub fn to_string<T: Serialize>(t: &T) -> Result<String> {
+    let mut out = String::new();
+    write_value(&mut out, &Value::try_from(t)?, None);
+    Ok(out)
+}
+
+/// Serializes `T` into an indented json string.
+pub fn to_string_pretty<T: Serialize>(t: &T) -> Result<String> {
+    let mut out = String::new();
+    write_value(&mut out, &Value::try_from(t)?, Some(0));
+    Ok(out)
+}
+
+/// Parses a json string into a [toml::Value].
+pub fn parse(s: &str) -> Result<Value> {
+    let mut parser = Parser {
+        bytes: s.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser
+        .value()?
+        .ok_or(anyhow::anyhow!("json document must not be null"))?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        anyhow::bail!("trailing characters at json offset {}", parser.pos);
+    }
+    Ok(value)
+}
+
+/// Writes a [toml::Value] as json, indenting by `indent` levels when set.
+fn write_value(out: &mut String, value: &Value, indent: Option<usize>) {
+    let newline = |out: &mut String, level: usize| {
+        if indent.is_some() {
+            out.push('\n');
+            out.push_str(&"  ".repeat(level));
+        }
+    };
+    let level = indent.unwrap_or(0);
+    match value {
+        Value::String(s) => write_string(out, s),
+        Value::Integer(i) => out.push_str(&i.to_string()),
+        Value::Float(f) if f.is_finite() => out.push_str(&f.to_string()),
+        Value::Float(_) => out.push_str("null"),
+        Value::Boolean(b) => out.push_str(&b.to_string()),
+        Value::Datetime(d) => write_string(out, &d.to_string()),
+        Value::Array(a) if a.is_empty() => out.push_str("[]"),
+        Value::Array(a) => {
+            out.push('[');
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_value(out, v, indent.map(|l| l + 1));
+            }
+            newline(out, level);
+            out.push(']');
+        }
+        Value::Table(t) if t.is_empty() => out.push_str("{}"),
+        Value::Table(t) => {
+            out.push('{');
+            for (i, (k, v)) in t.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_string(out, k);
+                out.push(':');
+                if indent.is_some() {
+                    out.push(' ');
+                }
+                write_value(out, v, indent.map(|l| l + 1));
+            }
+            newline(out, level);
+            out.push('}');
+        }
+    }
+}
+
+/// Writes an escaped json string.
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A recursive descent json parser.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\n' | b'\r' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> Result<()> {
+        if self.peek() != Some(b) {
+            anyhow::bail!("expected '{}' at json offset {}", b as char, self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, lit: &str) -> Result<()> {
+        if !self.bytes[self.pos..].starts_with(lit.as_bytes()) {
+            anyhow::bail!("expected '{}' at json offset {}", lit, self.pos);
+        }
+        self.pos += lit.len();
+        Ok(())
+    }
+
+    /// Parses a single value, returning [None] for `null`.
+    fn value(&mut self) -> Result<Option<Value>> {
+        let value = match self.peek() {
+            Some(b'{' | b'[') => self.nested()?,
+            Some(b'"') => Value::String(self.string()?),
+            Some(b't') => {
+                self.literal("true")?;
+                Value::Boolean(true)
+            }
+            Some(b'f') => {
+                self.literal("false")?;
+                Value::Boolean(false)
+            }
+            Some(b'n') => {
+                self.literal("null")?;
+                return Ok(None);
+            }
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            _ => anyhow::bail!("unexpected character at json offset {}", self.pos),
+        };
+        Ok(Some(value))
+    }
+
+    /// Parses an object or array, at most [MAX_DEPTH] levels deep.
+    fn nested(&mut self) -> Result<Value> {
+        if self.depth == MAX_DEPTH {
+            anyhow::bail!(
+                "json nested deeper than {} levels at offset {}",
+                MAX_DEPTH,
+                self.pos
+            );
+        }
+        self.depth += 1;
+        let value = match self.peek() {
+            Some(b'{') => self.object(),
+            _ => self.array(),
+        };
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect(b'{')?;
+        let mut table = toml::map::Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            if let Some(v) = self.value()? {
+                table.insert(key, v);
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Table(table));
+                }
+                _ => anyhow::bail!("expected ',' or '}}' at json offset {}", self.pos),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect(b'[')?;
+        let mut array = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(array));
+        }
+        loop {
+            self.skip_whitespace();
+            let at = self.pos;
+            match self.value()? {
+                Some(v) => array.push(v),
+                None => anyhow::bail!("unsupported null in json array at offset {}", at),
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(array));
+                }
+                _ => anyhow::bail!("expected ',' or ']' at json offset {}", self.pos),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while !matches!(self.peek(), Some(b'"' | b'\\') | None) {
+                self.pos += 1;
+            }
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.pos])?);
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or(anyhow::anyhow!("unterminated json string"))?;
+                    self.pos += 1;
+                    match escaped {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => out.push(self.unicode_escape()?),
+                        _ => anyhow::bail!("invalid json escape at offset {}", self.pos),
+                    }
+                }
+                _ => anyhow::bail!("unterminated json string"),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let hex = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .ok_or(anyhow::anyhow!("truncated json unicode escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(std::str::from_utf8(hex)?, 16)?)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            self.literal("\\u")?;
+            let low = self.hex4()?;
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or(anyhow::anyhow!("invalid json unicode escape"))
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        let mut float = false;
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' | b'-' | b'+' => {}
+                b'.' | b'e' | b'E' => float = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let raw = std::str::from_utf8(&self.bytes[start..self.pos])?;
+        match float {
+            false => Ok(raw
+                .parse::<i64>()
+                .map(Value::Integer)
+                .or_else(|_| raw.parse::<f64>().map(Value::Float))?),
+            true => Ok(Value::Float(raw.parse()?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Target {
+        name: String,
+        kind: Vec<String>,
+        edition: Option<String>,
+    }
+
+    #[test]
+    fn test_from_str() {
+        let target: Target =
+            from_str(r#"{"name": "cargo-hoist", "kind": ["bin"], "edition": null, "doc": true}"#)
+                .unwrap();
+        assert_eq!(
+            target,
+            Target {
+                name: "cargo-hoist".to_string(),
+                kind: vec!["bin".to_string()],
+                edition: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let target = Target {
+            name: "a \"quoted\"\nname \u{e9}".to_string(),
+            kind: vec!["test".to_string(), "bench".to_string()],
+            edition: Some("2021".to_string()),
+        };
+        let compact = to_string(&target).unwrap();
+        assert_eq!(from_str::<Target>(&compact).unwrap(), target);
+        let pretty = to_string_pretty(&target).unwrap();
+        assert!(pretty.contains("\n  \"kind\": [\n    \"test\","));
+        assert_eq!(from_str::<Target>(&pretty).unwrap(), target);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("{\"a\": }").is_err());
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("null").is_err());
+        assert!(parse("{} {}").is_err());
+        assert!(parse("[1, null, 2]").is_err());
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(parse(&"[".repeat(1_000_000)).is_err());
+        assert!(parse(&format!(
+            "{}1{}",
+            "{\"a\":".repeat(MAX_DEPTH + 1),
+            "}".repeat(MAX_DEPTH + 1)
+        ))
+        .is_err());
+    }
+}
@@ -13,14 +13,72 @@
 #![deny(unused_must_use, rust_2018_idioms)]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+pub mod adopt;
+pub mod aliases;
+pub mod audit;
+pub mod backup;
 pub mod binaries;
+pub mod bundle;
+pub mod checksum;
 pub mod cli;
+pub mod completions;
+pub mod config;
+pub mod content;
+pub mod context;
+pub mod diff;
+pub mod doctor;
+pub mod elf;
+pub mod exec;
 pub mod executables;
+pub mod export;
+pub mod fingerprint;
+pub mod freeze;
+pub mod gc;
+pub mod git;
+pub mod hash;
+pub mod history;
+pub mod hoist;
+pub mod hoisted;
+#[cfg(any(feature = "server", feature = "web"))]
+pub mod http;
+pub mod install;
+pub mod journal;
+pub mod json;
+pub mod list;
+pub mod lockfile;
+pub mod manifest;
+pub mod merge;
+pub mod metadata;
+#[cfg(feature = "server")]
+pub mod metrics;
+pub mod migrate;
+pub mod opener;
+pub mod parallel;
+pub mod platform;
+pub mod progress;
 pub mod project;
 pub mod registry;
+pub mod remove;
+pub mod sbom;
+pub mod schema;
+pub mod search;
+pub mod semver;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod sets;
 pub mod shell;
+pub mod shims;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod tags;
 pub mod telemetry;
+pub mod time;
+pub mod upgrade;
 pub mod utils;
+#[cfg(feature = "web")]
+pub mod web;
 
 #[doc(inline)]
 pub use cli::run;
@@ -0,0 +1,276 @@
+//! List
+//!
+//! Printing the registered binaries, along with their disk usage and how
+//! often they are used.
+
+use anyhow::Result;
+use std::collections::{BTreeSet, HashSet};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Registration};
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// Options controlling what [HoistRegistry::list_with] prints.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ListOptions {
+    /// Print each binary's license.
+    pub license: bool,
+    /// Only list binaries whose license expression names this license.
+    pub license_filter: Option<String>,
+    /// Print only the unique binary names, for shell completions.
+    pub names: bool,
+    /// Only list binaries registered or rebuilt at or after this time.
+    pub changed_since: Option<std::time::SystemTime>,
+    /// Only list binaries built with this cargo profile, e.g. `release`.
+    pub profile: Option<String>,
+    /// Only list binaries with this tag.
+    pub tag: Option<String>,
+    /// Print each binary's project and the git commit it was built from.
+    pub long: bool,
+    /// The order to list binaries in.
+    pub sort: Option<ListSort>,
+    /// Also list expired binaries.
+    pub all: bool,
+}
+
+/// The orders [HoistRegistry::list_with] can list binaries in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListSort {
+    /// By name, then location
+    Name,
+    /// Largest first, binaries of unknown size last
+    Size,
+}
+
+impl HoistRegistry {
+    /// Lists the binaries in the hoist toml registry.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::list_with`"
+    )]
+    #[instrument]
+    pub fn list(quiet: bool) -> Result<()> {
+        HoistRegistry::list_with(&HoistContext::from_env()?, ListOptions::default(), quiet)
+    }
+
+    /// Returns the registered binaries, most hoisted first, breaking ties by
+    /// the most recent use.
+    pub fn by_usage(&self) -> Vec<&HoistedBinary> {
+        let mut binaries: Vec<_> = self.binaries.iter().collect();
+        binaries.sort_by(|a, b| {
+            (b.hoist_count, b.last_used_at())
+                .cmp(&(a.hoist_count, a.last_used_at()))
+                .then_with(|| (&a.name, &a.location).cmp(&(&b.name, &b.location)))
+        });
+        binaries
+    }
+
+    /// Prints the disk space referenced by the registry and used by the
+    /// content store, followed by the `limit` largest binaries.
+    ///
+    /// Sizes are read from disk, so binaries rebuilt since they were
+    /// registered count at their current size.
+    #[instrument(skip(ctx))]
+    pub fn disk_usage(ctx: &HoistContext, limit: usize) -> Result<()> {
+        HoistRegistry::create_registry_with(ctx, true)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let mut sizes: Vec<_> = registry
+            .binaries
+            .iter()
+            .filter_map(|b| std::fs::metadata(&b.location).ok().map(|m| (m.len(), b)))
+            .collect();
+        sizes.sort_by(|(sa, a), (sb, b)| {
+            sb.cmp(sa)
+                .then_with(|| (&a.name, &a.location).cmp(&(&b.name, &b.location)))
+        });
+        let mut locations = HashSet::new();
+        let referenced: u64 = sizes
+            .iter()
+            .filter(|(_, b)| locations.insert(&b.location))
+            .map(|(size, _)| size)
+            .sum();
+        let missing = registry.binaries.len() - sizes.len();
+        let mut summary = format!(
+            "{} referenced by {} binaries",
+            crate::utils::format_bytes(referenced),
+            locations.len()
+        );
+        if missing > 0 {
+            summary.push_str(&format!(", {} missing from disk", missing));
+        }
+        HoistRegistry::print_color(&summary, Color::Blue, true)?;
+        let store = crate::content::usage(ctx, &registry)?;
+        let mut summary = format!(
+            "{} in {} stored copies",
+            crate::utils::format_bytes(store.bytes),
+            store.blobs
+        );
+        if store.unreferenced > 0 {
+            summary.push_str(&format!(
+                ", {} unreferenced, see `cargo hoist store gc`",
+                crate::utils::format_bytes(store.unreferenced)
+            ));
+        }
+        HoistRegistry::print_color(&summary, Color::Blue, true)?;
+        if !sizes.is_empty() {
+            HoistRegistry::print_color("Largest", Color::Blue, true)?;
+        }
+        for (size, b) in sizes.iter().take(limit) {
+            let size = format!("{:>10}  ", crate::utils::format_bytes(*size));
+            HoistRegistry::print_color(&size, Color::Yellow, false)?;
+            HoistRegistry::print_color(&b.display_name(), Color::Magenta, false)?;
+            HoistRegistry::print_color(&format!(" ({})", b.location.display()), Color::Cyan, true)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the `limit` most and least hoisted binaries.
+    #[instrument(skip(ctx))]
+    pub fn stats(ctx: &HoistContext, limit: usize) -> Result<()> {
+        HoistRegistry::create_registry_with(ctx, true)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let ranked = registry.by_usage();
+        let most = &ranked[..limit.min(ranked.len())];
+        let least: Vec<_> = ranked[most.len()..]
+            .iter()
+            .rev()
+            .take(limit)
+            .copied()
+            .collect();
+        let print = |b: &HoistedBinary| -> Result<()> {
+            let last = b
+                .last_hoisted_at
+                .map(|at| {
+                    format!(
+                        "last hoisted {}",
+                        crate::time::to_rfc3339(crate::time::from_unix(at))
+                    )
+                })
+                .unwrap_or_else(|| "never hoisted".to_string());
+            HoistRegistry::print_color(&format!("{:>6}  ", b.hoist_count), Color::Yellow, false)?;
+            HoistRegistry::print_color(&b.display_name(), Color::Magenta, false)?;
+            HoistRegistry::print_color(
+                &format!(" ({}), {}", b.location.display(), last),
+                Color::Cyan,
+                true,
+            )
+        };
+        if ranked.is_empty() {
+            return HoistRegistry::print_color("No binaries registered", Color::Yellow, true);
+        }
+        HoistRegistry::print_color("Most used", Color::Blue, true)?;
+        most.iter().try_for_each(|b| print(b))?;
+        if !least.is_empty() {
+            HoistRegistry::print_color("Least used", Color::Blue, true)?;
+            least.iter().try_for_each(|b| print(b))?;
+        }
+        Ok(())
+    }
+
+    /// Lists the binaries in the hoist toml registry with the given [ListOptions].
+    #[instrument]
+    pub fn list_with(ctx: &HoistContext, options: ListOptions, quiet: bool) -> Result<()> {
+        if options.names {
+            let mut registry = HoistRegistry::load(ctx).unwrap_or_default();
+            if !options.all {
+                registry.drop_expired(ctx)?;
+            }
+            let names: BTreeSet<_> = registry
+                .binaries
+                .iter()
+                .map(|b| &b.name)
+                .chain(registry.aliases.keys())
+                .collect();
+            names.iter().for_each(|n| println!("{}", n));
+            return Ok(());
+        }
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let filter = crate::store::Filter {
+            name: None,
+            profile: options.profile.clone(),
+            tag: options.tag.clone(),
+        };
+        let mut registry = HoistRegistry::select(ctx, &filter)?;
+        if !options.all {
+            registry.drop_expired(ctx)?;
+        }
+        let mut binaries: Vec<_> = registry
+            .binaries
+            .iter()
+            .filter(|b| {
+                options
+                    .license_filter
+                    .as_ref()
+                    .is_none_or(|l| b.has_license(l))
+                    && options.changed_since.is_none_or(|t| b.changed_since(t))
+            })
+            .collect();
+        let by_identity = |a: &&HoistedBinary, b: &&HoistedBinary| {
+            (&a.name, &a.location).cmp(&(&b.name, &b.location))
+        };
+        match options.sort {
+            Some(ListSort::Name) => binaries.sort_by(by_identity),
+            Some(ListSort::Size) => {
+                binaries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| by_identity(a, b)))
+            }
+            None => {}
+        }
+        for binary in binaries {
+            let aliases = registry.aliases_of(&binary.name);
+            HoistRegistry::print_entry(binary, &aliases, options.license)?;
+            if options.long {
+                HoistRegistry::print_provenance(binary)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints a `name (aliases): location` registry entry line, followed by the
+    /// binary's size if known, its license when `license` is set, and its
+    /// description.
+    pub(crate) fn print_entry(
+        binary: &HoistedBinary,
+        aliases: &[&str],
+        license: bool,
+    ) -> Result<()> {
+        let name = match aliases {
+            [] => binary.display_name(),
+            aliases => format!("{} ({})", binary.display_name(), aliases.join(", ")),
+        };
+        HoistRegistry::print_color(&format!("{}: ", name), Color::Blue, false)?;
+        HoistRegistry::print_color(&binary.location.display().to_string(), Color::Cyan, false)?;
+        if let Some(size) = binary.size {
+            let size = format!(" ({})", crate::utils::format_bytes(size));
+            HoistRegistry::print_color(&size, Color::White, false)?;
+        }
+        if license {
+            let license = binary.license.as_deref().unwrap_or("unknown");
+            HoistRegistry::print_color(&format!(" [{}]", license), Color::Yellow, false)?;
+        }
+        match &binary.description {
+            Some(d) => HoistRegistry::print_color(&format!("  {}", d), Color::White, true),
+            None => HoistRegistry::print_color("", Color::White, true),
+        }
+    }
+
+    /// Prints an indented line with the project a binary was built in and,
+    /// if recorded, the git commit it was built from.
+    fn print_provenance(binary: &HoistedBinary) -> Result<()> {
+        let project = binary
+            .project()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "unknown project".to_string());
+        HoistRegistry::print_color(&format!("    {}", project), Color::White, false)?;
+        match binary
+            .registration
+            .as_ref()
+            .and_then(Registration::revision)
+        {
+            Some(revision) => {
+                HoistRegistry::print_color(&format!(" @ {}", revision), Color::Yellow, true)
+            }
+            None => HoistRegistry::print_color("", Color::White, true),
+        }
+    }
+}
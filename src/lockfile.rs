@@ -0,0 +1,212 @@
+//! Lockfile Snapshots
+//!
+//! Content-addressed copies of the `Cargo.lock` a binary was built against,
+//! stored in `~/.hoist/locks/<sha256>.lock`.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+
+/// A parsed `Cargo.lock`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct Lockfile {
+    /// The locked packages.
+    #[serde(default)]
+    pub package: Vec<LockedPackage>,
+}
+
+/// A package pinned in a `Cargo.lock`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct LockedPackage {
+    /// The package name.
+    pub name: String,
+    /// The package version.
+    pub version: String,
+    /// The package source, e.g. `registry+https://github.com/rust-lang/crates.io-index`.
+    /// Workspace and path packages have no source.
+    pub source: Option<String>,
+    /// The sha256 checksum of the registry package.
+    pub checksum: Option<String>,
+    /// The package's dependencies as `name`, `name version` or `name version (source)`.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+impl LockedPackage {
+    /// The package url, e.g. `pkg:cargo/serde@1.0.0`.
+    pub fn purl(&self) -> String {
+        format!("pkg:cargo/{}@{}", self.name, self.version)
+    }
+
+    /// Returns if the package comes from the crates.io registry.
+    pub fn is_crates_io(&self) -> bool {
+        self.source.as_deref().is_some_and(|s| {
+            s.contains("github.com/rust-lang/crates.io-index") || s.contains("index.crates.io")
+        })
+    }
+}
+
+impl Lockfile {
+    /// Parses the contents of a `Cargo.lock`.
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Resolves a dependency entry to the package it refers to.
+    pub fn resolve(&self, dependency: &str) -> Option<&LockedPackage> {
+        let mut parts = dependency.split_whitespace();
+        let name = parts.next()?;
+        let version = parts.next();
+        self.package
+            .iter()
+            .find(|p| p.name == name && version.is_none_or(|v| p.version == v))
+    }
+
+    /// Returns the packages reachable from the package named `root`, which
+    /// comes first, followed by its transitive dependencies in name order.
+    /// Returns every package if `root` is not locked.
+    pub fn reachable(&self, root: &str) -> Vec<&LockedPackage> {
+        let Some(start) = self
+            .package
+            .iter()
+            .find(|p| p.name == root && p.source.is_none())
+            .or_else(|| self.package.iter().find(|p| p.name == root))
+        else {
+            return self.package.iter().collect();
+        };
+        let mut seen = vec![start];
+        let mut pending = vec![start];
+        while let Some(p) = pending.pop() {
+            for dep in p.dependencies.iter().filter_map(|d| self.resolve(d)) {
+                if !seen.iter().any(|s| std::ptr::eq(*s, dep)) {
+                    seen.push(dep);
+                    pending.push(dep);
+                }
+            }
+        }
+        seen[1..].sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        seen
+    }
+}
+
+/// The directory containing lockfile snapshots.
+pub fn dir(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("locks")
+}
+
+/// The path to the lockfile snapshot with the given sha256 digest.
+pub fn path(ctx: &HoistContext, digest: &str) -> PathBuf {
+    dir(ctx).join(format!("{}.lock", digest))
+}
+
+/// Finds the `Cargo.lock` governing the project at `project`, searching up
+/// through its ancestors to cover workspace members.
+pub fn find(project: &Path) -> Option<PathBuf> {
+    project
+        .ancestors()
+        .map(|a| a.join("Cargo.lock"))
+        .find(|p| p.is_file())
+}
+
+/// Snapshots the project's `Cargo.lock` into the lockfile store, returning its
+/// sha256 digest. Returns [None] if the project has no lockfile.
+#[instrument]
+pub fn snapshot(ctx: &HoistContext, project: &Path) -> Result<Option<String>> {
+    let Some(lockfile) = find(project) else {
+        return Ok(None);
+    };
+    let contents = std::fs::read(&lockfile)?;
+    let digest = crate::hash::hash_bytes(&contents, HashAlgorithm::Sha256);
+    let snapshot = path(ctx, &digest);
+    if !snapshot.exists() {
+        std::fs::create_dir_all(dir(ctx))?;
+        std::fs::write(snapshot, contents)?;
+    }
+    Ok(Some(digest))
+}
+
+/// Reads the lockfile snapshot with the given sha256 digest.
+pub fn load(ctx: &HoistContext, digest: &str) -> Result<String> {
+    let snapshot = path(ctx, digest);
+    std::fs::read_to_string(&snapshot).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read lockfile snapshot {}: {}",
+            snapshot.display(),
+            e
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde",
+ "syn 2.0.0",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "abc"
+
+[[package]]
+name = "syn"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "2.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "serde",
+]
+"#;
+
+    #[test]
+    fn test_reachable() {
+        let lock = Lockfile::parse(LOCK).unwrap();
+        let reachable: Vec<_> = lock.reachable("app").iter().map(|p| p.purl()).collect();
+        assert_eq!(
+            reachable,
+            vec![
+                "pkg:cargo/app@0.1.0",
+                "pkg:cargo/serde@1.0.0",
+                "pkg:cargo/syn@2.0.0"
+            ]
+        );
+        assert!(lock.resolve("serde").unwrap().is_crates_io());
+        assert_eq!(lock.reachable("missing").len(), 4);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let member = tempdir.path().join("workspace/crates/member");
+        std::fs::create_dir_all(&member).unwrap();
+        assert_eq!(snapshot(&ctx, &member).unwrap(), None);
+
+        std::fs::write(tempdir.path().join("workspace/Cargo.lock"), "version = 3\n").unwrap();
+        let digest = snapshot(&ctx, &member).unwrap().unwrap();
+        assert_eq!(
+            digest,
+            crate::hash::hash_bytes(b"version = 3\n", HashAlgorithm::Sha256)
+        );
+        assert_eq!(load(&ctx, &digest).unwrap(), "version = 3\n");
+        assert_eq!(snapshot(&ctx, &member).unwrap(), Some(digest));
+    }
+}
@@ -0,0 +1,251 @@
+//! Project Manifest
+//!
+//! A project manifest is a `hoist.toml`, or `.hoist.toml`, file at the root
+//! of a project that declares which registered binaries the project expects
+//! in scope, optionally with a version requirement, e.g. `op-node@^1.2`.
+//!
+//! Committed to the project, it lets `cargo hoist sync` hoist the tools a
+//! team needs into the project root, and `cargo hoist check` fail when any of
+//! them is missing or of the wrong version.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::hoist::{HoistOptions, OverwritePolicy};
+use crate::hoisted::HoistManifest;
+use crate::registry::HoistRegistry;
+use crate::semver::{Version, VersionReq};
+
+/// The hidden project manifest file name.
+pub const MANIFEST_FILE: &str = ".hoist.toml";
+
+/// The project manifest file names, in the order they are looked for.
+pub const MANIFEST_FILES: [&str; 2] = ["hoist.toml", MANIFEST_FILE];
+
+/// Project Manifest
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    /// The binaries the project expects to be hoisted into scope, as names
+    /// or `name@requirement` specs.
+    #[serde(default)]
+    pub binaries: Vec<String>,
+}
+
+impl ProjectManifest {
+    /// Walks up from the `start` directory looking for a project manifest,
+    /// returning the path to the first manifest found.
+    #[instrument]
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .flat_map(|d| MANIFEST_FILES.iter().map(move |f| d.join(f)))
+            .find(|m| m.is_file())
+    }
+
+    /// Loads a [ProjectManifest] from the given manifest file.
+    #[instrument]
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let manifest: ProjectManifest = toml::from_str(&contents)?;
+        Ok(manifest)
+    }
+
+    /// The required binaries, as their names and version requirements.
+    pub fn requirements(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.binaries.iter().map(|spec| match spec.split_once('@') {
+            Some((name, req)) => (name, Some(req)),
+            None => (spec.as_str(), None),
+        })
+    }
+}
+
+/// Finds the project manifest of the working directory, returning it along
+/// with the project root it is in.
+fn find_project(ctx: &HoistContext) -> Result<(ProjectManifest, PathBuf)> {
+    let path = ProjectManifest::find(&ctx.cwd).ok_or(anyhow::anyhow!(
+        "No hoist.toml found in {} or its parents",
+        ctx.cwd.display()
+    ))?;
+    let manifest = ProjectManifest::load(&path)?;
+    let root = path
+        .parent()
+        .map_or_else(|| ctx.cwd.clone(), Path::to_path_buf);
+    Ok((manifest, root))
+}
+
+/// Returns if `version` satisfies `req`. A plain version, e.g. `1.2.3`,
+/// requires exactly that version, as it does when hoisting.
+fn satisfies(version: &str, req: &str) -> Result<bool> {
+    let req = req.trim().trim_start_matches('v');
+    let Ok(version) = Version::parse(version.trim_start_matches('v')) else {
+        return Ok(false);
+    };
+    Ok(match Version::parse(req) {
+        Ok(exact) => version == exact,
+        Err(_) => VersionReq::parse(req)?.matches(&version),
+    })
+}
+
+/// Hoists the latest registered build satisfying each requirement of the
+/// project manifest of the working directory into the project root.
+#[instrument(skip(ctx))]
+pub fn sync(ctx: &HoistContext, force: bool, dry_run: bool, quiet: bool) -> Result<()> {
+    let (manifest, root) = find_project(ctx)?;
+    if manifest.binaries.is_empty() {
+        if !quiet {
+            HoistRegistry::print_color("No binaries are required", Color::Yellow, true)?;
+        }
+        return Ok(());
+    }
+    HoistRegistry::hoist_with(
+        &ctx.with_cwd(root),
+        manifest.binaries,
+        HoistOptions {
+            latest: true,
+            overwrite: match force {
+                true => OverwritePolicy::Force,
+                false => OverwritePolicy::Prompt,
+            },
+            dry_run,
+            ..Default::default()
+        },
+        quiet,
+    )
+}
+
+/// Checks that every binary required by the project manifest of the working
+/// directory is hoisted into the project root, with a version satisfying
+/// its requirement, as recorded in the root's hoist manifest.
+///
+/// Fails if any is missing or of the wrong version.
+#[instrument(skip(ctx))]
+pub fn check(ctx: &HoistContext, quiet: bool) -> Result<()> {
+    let (manifest, root) = find_project(ctx)?;
+    let hoisted = HoistManifest::load(&root)?;
+    let mut failed = 0;
+    for (name, req) in manifest.requirements() {
+        let version = hoisted
+            .files
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.version.as_deref());
+        let status = match (root.join(name).exists(), req, version) {
+            (false, _, _) => Err("missing".to_string()),
+            (true, None, _) => Ok("ok".to_string()),
+            (true, Some(req), None) => Err(format!("unknown version, requires {}", req)),
+            (true, Some(req), Some(v)) if satisfies(v, req)? => Ok(v.to_string()),
+            (true, Some(req), Some(v)) => Err(format!("{}, requires {}", v, req)),
+        };
+        if status.is_err() {
+            failed += 1;
+        }
+        if !quiet || status.is_err() {
+            HoistRegistry::print_color(&format!("{}: ", name), Color::Blue, false)?;
+            match status {
+                Ok(s) => HoistRegistry::print_color(&s, Color::Green, true)?,
+                Err(s) => HoistRegistry::print_color(&s, Color::Red, true)?,
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "{} required binaries are missing or mismatched, run `cargo hoist sync`",
+            failed
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_walks_up() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let nested = tempdir.path().join("crates/foo/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(
+            tempdir.path().join(MANIFEST_FILE),
+            "binaries = [\"binary1\"]\n",
+        )
+        .unwrap();
+
+        let found = ProjectManifest::find(&nested).unwrap();
+        assert_eq!(found, tempdir.path().join(MANIFEST_FILE));
+        let manifest = ProjectManifest::load(&found).unwrap();
+        assert_eq!(manifest.binaries, vec!["binary1".to_string()]);
+    }
+
+    #[test]
+    fn test_find_prefers_hoist_toml() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join(MANIFEST_FILE), "").unwrap();
+        std::fs::write(
+            tempdir.path().join("hoist.toml"),
+            "binaries = [\"op-node@^1.2\", \"op-batcher\"]\n",
+        )
+        .unwrap();
+        let found = ProjectManifest::find(tempdir.path()).unwrap();
+        assert_eq!(found, tempdir.path().join("hoist.toml"));
+        let manifest = ProjectManifest::load(&found).unwrap();
+        let requirements: Vec<_> = manifest.requirements().collect();
+        assert_eq!(
+            requirements,
+            vec![("op-node", Some("^1.2")), ("op-batcher", None)]
+        );
+    }
+
+    #[test]
+    fn test_satisfies() {
+        assert!(satisfies("1.2.3", "^1.2").unwrap());
+        assert!(satisfies("1.2.3", "v1.2.3").unwrap());
+        assert!(!satisfies("1.2.4", "1.2.3").unwrap());
+        assert!(!satisfies("2.0.0", ">=1, <2").unwrap());
+        assert!(!satisfies("unknown", "^1").unwrap());
+        assert!(satisfies("1.0.0", "not a requirement").is_err());
+    }
+
+    #[test]
+    fn test_sync_and_check() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().join("project");
+        let ctx = HoistContext::new(tempdir.path(), root.join("src"));
+        std::fs::create_dir_all(&ctx.cwd).unwrap();
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        std::fs::write(ctx.hook_identifier(), "").unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        for version in ["1.2.0", "2.0.0"] {
+            let path = tempdir.path().join(version).join("tool");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, version).unwrap();
+            registry.binaries.insert(crate::binaries::HoistedBinary {
+                version: Some(version.to_string()),
+                ..crate::binaries::HoistedBinary::new("tool", path)
+            });
+        }
+        registry.save(&ctx).unwrap();
+
+        assert!(check(&ctx, true).is_err());
+        std::fs::write(root.join("hoist.toml"), "binaries = [\"tool@^1\"]\n").unwrap();
+        assert!(check(&ctx, true).is_err());
+        sync(&ctx, false, false, true).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("tool")).unwrap(), "1.2.0");
+        check(&ctx, true).unwrap();
+
+        std::fs::write(root.join("hoist.toml"), "binaries = [\"tool@^2\"]\n").unwrap();
+        assert!(check(&ctx, true).is_err());
+        sync(&ctx, true, false, true).unwrap();
+        check(&ctx, true).unwrap();
+    }
+
+    #[test]
+    fn test_find_missing() {
+        let tempdir = tempfile::tempdir().unwrap();
+        assert!(ProjectManifest::find(tempdir.path()).is_none());
+    }
+}
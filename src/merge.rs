@@ -0,0 +1,247 @@
+//! Merge
+//!
+//! Combines another registry file into the registry, e.g. when consolidating
+//! the registries of several user accounts or machines.
+//!
+//! A conflict is a binary registered in both registries, with the same name and
+//! location, whose metadata differs. Binaries only in the other registry are
+//! always added.
+
+use anyhow::Result;
+use inquire::MultiSelect;
+use is_terminal::IsTerminal;
+use std::path::Path;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// How conflicting entries are resolved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the registry's own entries
+    #[default]
+    Ours,
+    /// Take the other registry's entries
+    Theirs,
+    /// Take whichever entry was registered most recently
+    Newer,
+    /// Prompt for the entries to take from the other registry
+    Interactive,
+}
+
+/// What a merge changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// Binaries added from the other registry
+    pub added: usize,
+    /// Conflicting entries replaced by the other registry's
+    pub replaced: usize,
+    /// Conflicting entries kept
+    pub kept: usize,
+}
+
+/// The registration time of a binary, `0` if it has none.
+fn registered_at(b: &HoistedBinary) -> u64 {
+    b.registration.as_ref().map(|r| r.at).unwrap_or_default()
+}
+
+/// Compares every field of two entries, unlike their name and location
+/// identity.
+fn same_metadata(a: &HoistedBinary, b: &HoistedBinary) -> bool {
+    toml::Value::try_from(a).ok() == toml::Value::try_from(b).ok()
+}
+
+/// Merges `theirs` into `ours`. `take` is given the conflicting `(ours,
+/// theirs)` entry pairs and returns, for each, whether to take theirs.
+///
+/// Aliases only in `theirs` are added. Conflicting aliases are only replaced
+/// when `take_aliases` is set, since aliases carry no registration time.
+pub fn merge_registries(
+    ours: &mut HoistRegistry,
+    theirs: HoistRegistry,
+    take_aliases: bool,
+    take: impl FnOnce(&[(HoistedBinary, HoistedBinary)]) -> Result<Vec<bool>>,
+) -> Result<MergeSummary> {
+    let mut summary = MergeSummary::default();
+    let mut conflicts = vec![];
+    for b in theirs.binaries {
+        match ours.binaries.get(&b) {
+            None => {
+                summary.added += 1;
+                ours.insert(b);
+            }
+            Some(existing) if same_metadata(existing, &b) => {}
+            Some(existing) => conflicts.push((existing.clone(), b)),
+        }
+    }
+    conflicts.sort_by(|a, b| (&a.0.name, &a.0.location).cmp(&(&b.0.name, &b.0.location)));
+    let choices = take(&conflicts)?;
+    for ((_, theirs), take) in conflicts.into_iter().zip(choices) {
+        match take {
+            true => {
+                summary.replaced += 1;
+                ours.insert(theirs);
+            }
+            false => summary.kept += 1,
+        }
+    }
+    for (alias, name) in theirs.aliases {
+        if take_aliases || !ours.aliases.contains_key(&alias) {
+            ours.aliases.insert(alias, name);
+        }
+    }
+    Ok(summary)
+}
+
+/// Prompts for the conflicting entries to take from the other registry.
+fn prompt(conflicts: &[(HoistedBinary, HoistedBinary)]) -> Result<Vec<bool>> {
+    if conflicts.is_empty() {
+        return Ok(vec![]);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "An interactive merge needs a terminal, pass --prefer-theirs or --prefer-newer instead"
+        );
+    }
+    let options = conflicts
+        .iter()
+        .map(|(ours, theirs)| {
+            format!(
+                "{} ({}) registered {} here, {} there",
+                ours.name,
+                ours.location.display(),
+                crate::time::to_rfc3339(crate::time::from_unix(registered_at(ours))),
+                crate::time::to_rfc3339(crate::time::from_unix(registered_at(theirs))),
+            )
+        })
+        .collect();
+    let chosen =
+        MultiSelect::new("Which conflicting entries should be replaced?", options).raw_prompt()?;
+    let mut take = vec![false; conflicts.len()];
+    for choice in chosen {
+        take[choice.index] = true;
+    }
+    Ok(take)
+}
+
+/// Merges the registry file at `path` into the registry.
+#[instrument(skip(ctx))]
+pub fn merge(ctx: &HoistContext, path: &Path, strategy: MergeStrategy, quiet: bool) -> Result<()> {
+    let path = ctx.resolve(path);
+    let theirs = crate::export::read(ctx, &path)?;
+    HoistRegistry::create_registry_with(ctx, quiet)?;
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    let summary = merge_registries(
+        &mut registry,
+        theirs,
+        strategy == MergeStrategy::Theirs,
+        |conflicts| match strategy {
+            MergeStrategy::Ours => Ok(vec![false; conflicts.len()]),
+            MergeStrategy::Theirs => Ok(vec![true; conflicts.len()]),
+            MergeStrategy::Newer => Ok(conflicts
+                .iter()
+                .map(|(ours, theirs)| registered_at(theirs) > registered_at(ours))
+                .collect()),
+            MergeStrategy::Interactive => prompt(conflicts),
+        },
+    )?;
+    registry.save(ctx)?;
+    if !quiet {
+        HoistRegistry::print_color(
+            &format!(
+                "Merged {}: {} added, {} replaced, {} kept",
+                path.display(),
+                summary.added,
+                summary.replaced,
+                summary.kept
+            ),
+            Color::Green,
+            true,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{Registration, RegistrationSource};
+    use std::path::PathBuf;
+
+    fn registered(name: &str, at: u64, version: &str) -> HoistedBinary {
+        HoistedBinary {
+            version: Some(version.to_string()),
+            registration: Some(Registration {
+                at,
+                ..Registration::now(RegistrationSource::Manual, PathBuf::from("/p"))
+            }),
+            ..HoistedBinary::new(name, PathBuf::from(format!("/p/{}", name)))
+        }
+    }
+
+    #[test]
+    fn test_merge_registries() {
+        let mut ours = HoistRegistry::default();
+        ours.insert(registered("a", 2, "0.2.0"));
+        ours.insert(registered("b", 1, "0.1.0"));
+        ours.aliases.insert("x".to_string(), "a".to_string());
+        let mut theirs = HoistRegistry::default();
+        theirs.insert(registered("a", 1, "0.1.0"));
+        theirs.insert(registered("b", 2, "0.2.0"));
+        theirs.insert(registered("c", 1, "0.1.0"));
+        theirs.aliases.insert("x".to_string(), "b".to_string());
+        theirs.aliases.insert("y".to_string(), "c".to_string());
+
+        let mut merged = ours.clone();
+        let summary = merge_registries(&mut merged, theirs.clone(), false, |conflicts| {
+            assert_eq!(conflicts.len(), 2);
+            Ok(conflicts
+                .iter()
+                .map(|(o, t)| registered_at(t) > registered_at(o))
+                .collect())
+        })
+        .unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                added: 1,
+                replaced: 1,
+                kept: 1
+            }
+        );
+        assert_eq!(
+            merged.latest("a").unwrap().version.as_deref(),
+            Some("0.2.0")
+        );
+        assert_eq!(
+            merged.latest("b").unwrap().version.as_deref(),
+            Some("0.2.0")
+        );
+        assert_eq!(merged.resolve_alias("x"), "a");
+        assert_eq!(merged.resolve_alias("y"), "c");
+
+        let mut merged = ours.clone();
+        merge_registries(&mut merged, theirs.clone(), true, |c| {
+            Ok(vec![true; c.len()])
+        })
+        .unwrap();
+        assert_eq!(
+            merged.latest("a").unwrap().version.as_deref(),
+            Some("0.1.0")
+        );
+        assert_eq!(merged.resolve_alias("x"), "b");
+
+        let mut merged = ours.clone();
+        let summary = merge_registries(&mut merged, ours.clone(), false, |c| {
+            assert!(c.is_empty());
+            Ok(vec![])
+        })
+        .unwrap();
+        assert_eq!(summary, MergeSummary::default());
+        assert_eq!(merged, ours);
+    }
+}
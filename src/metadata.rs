@@ -0,0 +1,262 @@
+//! Cargo Metadata
+//!
+//! A thin wrapper around `cargo metadata` for resolving a project's
+//! packages and build targets.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+/// The output of `cargo metadata`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize)]
+pub struct Metadata {
+    /// The workspace packages.
+    #[serde(default)]
+    pub packages: Vec<Package>,
+    /// The workspace target directory.
+    pub target_directory: PathBuf,
+    /// The workspace root directory.
+    pub workspace_root: PathBuf,
+}
+
+/// A cargo package.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize)]
+pub struct Package {
+    /// The package name.
+    pub name: String,
+    /// The package version.
+    pub version: String,
+    /// The package build targets.
+    #[serde(default)]
+    pub targets: Vec<Target>,
+    /// The path to the package manifest.
+    pub manifest_path: PathBuf,
+    /// The package's SPDX license expression.
+    pub license: Option<String>,
+    /// The package description.
+    pub description: Option<String>,
+    /// The package's source repository url.
+    pub repository: Option<String>,
+    /// The package's homepage url.
+    pub homepage: Option<String>,
+}
+
+/// A cargo build target.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize)]
+pub struct Target {
+    /// The target name.
+    pub name: String,
+    /// The target kinds, e.g. `bin`, `test` or `bench`.
+    #[serde(default)]
+    pub kind: Vec<String>,
+    /// The target's root source file.
+    pub src_path: PathBuf,
+}
+
+impl Target {
+    /// Returns if the target is of the given kind.
+    pub fn is(&self, kind: &str) -> bool {
+        self.kind.iter().any(|k| k == kind)
+    }
+
+    /// The file stem rustc uses for the target's artifacts.
+    pub fn crate_name(&self) -> String {
+        self.name.replace('-', "_")
+    }
+}
+
+impl Metadata {
+    /// Loads the metadata for the project at `root` by running `cargo metadata`.
+    #[instrument]
+    pub fn load(root: &Path) -> Result<Self> {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let output = std::process::Command::new(cargo)
+            .args([
+                "metadata",
+                "--no-deps",
+                "--format-version",
+                "1",
+                "--offline",
+            ])
+            .current_dir(root)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        crate::json::from_str(std::str::from_utf8(&output.stdout)?)
+    }
+
+    /// Returns an iterator over every target in the workspace.
+    pub fn targets(&self) -> impl Iterator<Item = &Target> {
+        self.packages.iter().flat_map(|p| p.targets.iter())
+    }
+}
+
+/// The registry metadata of a package, read from its manifest.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackageInfo {
+    /// The package version.
+    pub version: Option<String>,
+    /// The package's SPDX license expression.
+    pub license: Option<String>,
+    /// The package description.
+    pub description: Option<String>,
+    /// The package's source repository url.
+    pub repository: Option<String>,
+    /// The package's homepage url.
+    pub homepage: Option<String>,
+}
+
+impl From<&Package> for PackageInfo {
+    fn from(p: &Package) -> Self {
+        Self {
+            version: Some(p.version.clone()).filter(|v| !v.is_empty()),
+            license: p.license.clone(),
+            description: p.description.clone(),
+            repository: p.repository.clone(),
+            homepage: p.homepage.clone(),
+        }
+    }
+}
+
+/// The [PackageInfo] of the packages in a project.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProjectPackages {
+    /// The project's own package, if it is not a virtual workspace.
+    pub package: Option<PackageInfo>,
+    /// The package of each workspace member's build targets, keyed by target name.
+    pub targets: HashMap<String, PackageInfo>,
+}
+
+impl ProjectPackages {
+    /// Resolves the packages for the project at `root`.
+    ///
+    /// Reads the project's `Cargo.toml` directly when it declares a single
+    /// package, since this runs on every hooked cargo invocation, and only falls
+    /// back to `cargo metadata` for workspaces with members, including virtual
+    /// manifests with no root package.
+    #[instrument]
+    pub fn load(root: &Path) -> Self {
+        let Some(manifest) = read_manifest(&root.join("Cargo.toml")) else {
+            return Self::default();
+        };
+        let members = manifest
+            .get("workspace")
+            .and_then(|w| w.get("members"))
+            .and_then(|m| m.as_array())
+            .is_some_and(|m| !m.is_empty());
+        if let Some(package) = manifest.get("package").filter(|_| !members) {
+            let field = |key: &str| match package.get(key) {
+                Some(toml::Value::String(v)) => Some(v.clone()),
+                Some(toml::Value::Table(t)) if t.get("workspace").is_some() => root
+                    .ancestors()
+                    .filter_map(|a| read_manifest(&a.join("Cargo.toml")))
+                    .find_map(|m| {
+                        m.get("workspace")?
+                            .get("package")?
+                            .get(key)?
+                            .as_str()
+                            .map(str::to_string)
+                    }),
+                _ => None,
+            };
+            return Self {
+                package: Some(PackageInfo {
+                    version: field("version"),
+                    license: field("license"),
+                    description: field("description"),
+                    repository: field("repository"),
+                    homepage: field("homepage"),
+                }),
+                ..Default::default()
+            };
+        }
+        let targets = Metadata::load(root)
+            .map(|m| {
+                m.packages
+                    .iter()
+                    .flat_map(|p| {
+                        p.targets
+                            .iter()
+                            .map(move |t| (t.name.clone(), PackageInfo::from(p)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            package: None,
+            targets,
+        }
+    }
+
+    /// Returns the package owning the named target.
+    pub fn get(&self, target: &str) -> Option<&PackageInfo> {
+        self.targets.get(target).or(self.package.as_ref())
+    }
+}
+
+/// Reads a `Cargo.toml` as a [toml::Value].
+pub(crate) fn read_manifest(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo-app\"\nversion = \"0.2.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let metadata = Metadata::load(root).unwrap();
+        assert_eq!(metadata.packages.len(), 1);
+        assert_eq!(metadata.packages[0].version, "0.2.0");
+        let target = metadata.targets().next().unwrap();
+        assert!(target.is("bin"));
+        assert_eq!(target.crate_name(), "demo_app");
+    }
+
+    #[test]
+    fn test_project_packages() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.package]\nlicense = \"MIT\"\nrepository = \"https://example.com/app\"\n",
+        )
+        .unwrap();
+        let app = root.join("app");
+        std::fs::create_dir_all(app.join("src")).unwrap();
+        std::fs::write(
+            app.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\nlicense.workspace = true\nrepository.workspace = true\ndescription = \"An app\"\n",
+        )
+        .unwrap();
+        std::fs::write(app.join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let expected = PackageInfo {
+            version: Some("0.1.0".to_string()),
+            license: Some("MIT".to_string()),
+            description: Some("An app".to_string()),
+            repository: Some("https://example.com/app".to_string()),
+            homepage: None,
+        };
+        assert_eq!(ProjectPackages::load(&app).get("app"), Some(&expected));
+        let workspace = ProjectPackages::load(root);
+        assert_eq!(workspace.package, None);
+        assert_eq!(workspace.get("app"), Some(&expected));
+    }
+}
@@ -0,0 +1,240 @@
+//! Metrics
+//!
+//! Counters of the operations of `cargo hoist server`, rendered in the
+//! Prometheus text exposition format by its `/metrics` endpoint.
+//!
+//! The server counts in [METRICS] and adds its counts to the totals in
+//! `~/.hoist/metrics.toml` when it renders the metrics, so the totals
+//! survive a restart. Other commands never persist what they count.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+
+/// The metrics recorded by this process.
+pub static METRICS: Metrics = Metrics::new();
+
+/// Counters recorded across every operation in the process.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registrations: AtomicU64,
+    hoists: AtomicU64,
+    errors: AtomicU64,
+    scans: AtomicU64,
+    scan_micros: AtomicU64,
+}
+
+impl Metrics {
+    /// Creates a set of zeroed counters.
+    pub const fn new() -> Self {
+        Self {
+            registrations: AtomicU64::new(0),
+            hoists: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            scans: AtomicU64::new(0),
+            scan_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Records `n` binaries registered.
+    pub fn record_registrations(&self, n: usize) {
+        self.registrations.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Records `n` binaries hoisted into a directory.
+    pub fn record_hoists(&self, n: usize) {
+        self.hoists.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Records a failed operation.
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a project target directory scan that took `elapsed`.
+    pub fn record_scan(&self, elapsed: Duration) {
+        self.scans.fetch_add(1, Ordering::Relaxed);
+        self.scan_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Takes the counts recorded since they were last taken, zeroing the
+    /// counters.
+    pub fn take(&self) -> MetricTotals {
+        let take = |c: &AtomicU64| c.swap(0, Ordering::Relaxed);
+        MetricTotals {
+            registrations: take(&self.registrations),
+            hoists: take(&self.hoists),
+            errors: take(&self.errors),
+            scans: take(&self.scans),
+            scan_micros: take(&self.scan_micros),
+        }
+    }
+
+    /// Adds the counts recorded since the last flush to the persisted totals,
+    /// under the lock of the context's store, and returns the new totals.
+    #[instrument(skip(self, ctx))]
+    pub fn flush(&self, ctx: &HoistContext) -> Result<MetricTotals> {
+        let counts = self.take();
+        if counts == MetricTotals::default() {
+            return MetricTotals::load(ctx);
+        }
+        std::fs::create_dir_all(ctx.hoist_dir())?;
+        let _lock = crate::registry::HoistRegistry::lock(ctx)?;
+        let mut totals = MetricTotals::load(ctx)?;
+        totals.add(&counts);
+        totals.save(ctx)?;
+        Ok(totals)
+    }
+}
+
+/// The counts of every hoist process, persisted in the hoist directory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MetricTotals {
+    /// Binaries registered
+    pub registrations: u64,
+    /// Binaries hoisted into a directory
+    pub hoists: u64,
+    /// Failed operations
+    pub errors: u64,
+    /// Project target directory scans
+    pub scans: u64,
+    /// The total duration of the scans, in microseconds
+    pub scan_micros: u64,
+}
+
+impl MetricTotals {
+    /// The path of the persisted totals.
+    pub fn path(ctx: &HoistContext) -> PathBuf {
+        ctx.hoist_dir().join("metrics.toml")
+    }
+
+    /// Loads the persisted totals, which are zero if none were persisted.
+    pub fn load(ctx: &HoistContext) -> Result<Self> {
+        let path = MetricTotals::path(ctx);
+        if !path.exists() {
+            return Ok(MetricTotals::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the totals to a temporary file and renames it over the totals
+    /// in the hoist directory, so readers never see partially written totals.
+    pub fn save(&self, ctx: &HoistContext) -> Result<()> {
+        let path = MetricTotals::path(ctx);
+        let tmp = path.with_extension(format!("toml.{}.tmp", std::process::id()));
+        let mut f = std::fs::File::create(&tmp)?;
+        std::io::Write::write_all(&mut f, toml::to_string(self)?.as_bytes())?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Adds `other` to the totals.
+    pub fn add(&mut self, other: &MetricTotals) {
+        self.registrations += other.registrations;
+        self.hoists += other.hoists;
+        self.errors += other.errors;
+        self.scans += other.scans;
+        self.scan_micros += other.scan_micros;
+    }
+
+    /// Renders the totals, along with the current number of registered
+    /// binaries, in the Prometheus text exposition format.
+    pub fn render(&self, registry_size: usize) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        metric(
+            "hoist_registrations_total",
+            "counter",
+            "Binaries registered.",
+            self.registrations.to_string(),
+        );
+        metric(
+            "hoist_hoists_total",
+            "counter",
+            "Binaries hoisted into a directory.",
+            self.hoists.to_string(),
+        );
+        metric(
+            "hoist_errors_total",
+            "counter",
+            "Failed operations.",
+            self.errors.to_string(),
+        );
+        metric(
+            "hoist_registry_binaries",
+            "gauge",
+            "Binaries in the registry.",
+            registry_size.to_string(),
+        );
+        let _ = writeln!(
+            out,
+            "# HELP hoist_scan_duration_seconds Project target directory scans."
+        );
+        let _ = writeln!(out, "# TYPE hoist_scan_duration_seconds summary");
+        let _ = writeln!(
+            out,
+            "hoist_scan_duration_seconds_sum {}",
+            self.scan_micros as f64 / 1e6
+        );
+        let _ = writeln!(out, "hoist_scan_duration_seconds_count {}", self.scans);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.record_registrations(2);
+        metrics.record_hoists(1);
+        metrics.record_error();
+        metrics.record_scan(Duration::from_millis(1500));
+        let out = metrics.take().render(3);
+        assert!(
+            out.contains("# TYPE hoist_registrations_total counter\nhoist_registrations_total 2\n")
+        );
+        assert!(out.contains("hoist_hoists_total 1\n"));
+        assert!(out.contains("hoist_errors_total 1\n"));
+        assert!(out.contains("# TYPE hoist_registry_binaries gauge\nhoist_registry_binaries 3\n"));
+        assert!(out.contains("hoist_scan_duration_seconds_sum 1.5\n"));
+        assert!(out.contains("hoist_scan_duration_seconds_count 1\n"));
+        assert_eq!(metrics.take(), MetricTotals::default());
+    }
+
+    #[test]
+    fn test_flush() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let metrics = Metrics::new();
+        assert_eq!(metrics.flush(&ctx).unwrap(), MetricTotals::default());
+        assert!(!MetricTotals::path(&ctx).exists());
+
+        // Counts of separate processes add up.
+        metrics.record_hoists(2);
+        metrics.flush(&ctx).unwrap();
+        let other = Metrics::new();
+        other.record_hoists(1);
+        other.record_scan(Duration::from_millis(2));
+        let totals = other.flush(&ctx).unwrap();
+        assert_eq!(totals.hoists, 3);
+        assert_eq!(totals.scans, 1);
+        assert_eq!(MetricTotals::load(&ctx).unwrap(), totals);
+    }
+}
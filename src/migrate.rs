@@ -0,0 +1,252 @@
+//! Migrate
+//!
+//! Upgrades registry files written by older versions of hoist.
+//!
+//! Each registry records the [REGISTRY_VERSION] it was written with. On load,
+//! the raw TOML table is passed through every migration between that version
+//! and the current one before it is deserialized, so registries keep working
+//! as the format changes, without a `nuke` and re-registration.
+
+use anyhow::Result;
+use toml::{Table, Value};
+
+use crate::registry::HoistRegistry;
+
+/// The registry format version written by this version of hoist.
+pub const REGISTRY_VERSION: u32 = 1;
+
+/// Upgrades a registry table by one version, in place.
+type Migration = fn(&mut Table) -> Result<()>;
+
+/// The migrations, where `MIGRATIONS[n]` upgrades a version `n` registry to
+/// version `n + 1`.
+const MIGRATIONS: [Migration; REGISTRY_VERSION as usize] = [unversioned];
+
+/// Registries written before the `version` field was introduced. Every field
+/// added since is optional, so they already share the version 1 layout.
+fn unversioned(_: &mut Table) -> Result<()> {
+    Ok(())
+}
+
+/// The format version of a registry table, `0` if it has none.
+pub fn version(table: &Table) -> Result<u32> {
+    match table.get("version") {
+        None => Ok(0),
+        Some(Value::Integer(v)) => Ok(u32::try_from(*v)?),
+        Some(v) => anyhow::bail!("Invalid registry version {}", v),
+    }
+}
+
+/// Upgrades a registry table to [REGISTRY_VERSION], returning the version it
+/// was written with.
+///
+/// Fails for registries written by a newer hoist, rather than dropping the
+/// fields this version doesn't know about on the next save.
+pub fn migrate(table: &mut Table) -> Result<u32> {
+    let from = version(table)?;
+    if from > REGISTRY_VERSION {
+        anyhow::bail!(
+            "The registry has version {}, but this cargo-hoist only supports up to version {}. Upgrade cargo-hoist to use it.",
+            from,
+            REGISTRY_VERSION
+        );
+    }
+    for (v, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        migration(table)?;
+        tracing::info!("Migrated the registry from version {} to {}", v, v + 1);
+    }
+    table.insert(
+        "version".to_string(),
+        Value::Integer(REGISTRY_VERSION as i64),
+    );
+    Ok(from)
+}
+
+/// Parses a serialized registry, migrating it to the current format.
+pub fn parse(registry_toml: &str) -> Result<HoistRegistry> {
+    from_table(toml::from_str(registry_toml)?)
+}
+
+/// Deserializes a registry table, migrating it to the current format.
+pub fn from_table(mut table: Table) -> Result<HoistRegistry> {
+    migrate(&mut table)?;
+    Ok(table.try_into()?)
+}
+
+/// A part of a registry file [salvage] dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dropped {
+    /// The first dropped line, 1-based
+    pub first: usize,
+    /// The last dropped line, 1-based
+    pub last: usize,
+    /// Why the lines were dropped
+    pub reason: String,
+}
+
+impl std::fmt::Display for Dropped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.first == self.last {
+            true => write!(f, "line {}: {}", self.first, self.reason),
+            false => write!(f, "lines {}-{}: {}", self.first, self.last, self.reason),
+        }
+    }
+}
+
+/// Parses as much of a corrupt registry file as possible, returning the
+/// registry of its valid entries and aliases, and the lines dropped.
+///
+/// The file is split into the top-level keys, each `[[binaries]]` entry with
+/// its subtables, and the `[aliases]` table, which are parsed on their own.
+/// Aliases are salvaged line by line. Fails, rather than salvaging, for
+/// registries written by a newer hoist.
+pub fn salvage(registry_toml: &str) -> Result<(HoistRegistry, Vec<Dropped>)> {
+    let lines: Vec<_> = registry_toml.lines().collect();
+    // Split into sections, each starting at a top-level header.
+    let mut starts = vec![0];
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.trim_start();
+        if line.starts_with('[') && !line.starts_with("[binaries.") && i > 0 {
+            starts.push(i);
+        }
+    }
+    starts.dedup();
+    let mut dropped = vec![];
+    let mut drop = |first: usize, last: usize, reason: String| {
+        let reason = reason.lines().next().unwrap_or_default().trim().to_string();
+        dropped.push(Dropped {
+            first: first + 1,
+            last: last + 1,
+            reason,
+        });
+    };
+    let mut table = Table::new();
+    let mut binaries = vec![];
+    let mut aliases = Table::new();
+    for (n, &start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(lines.len());
+        let last = end.saturating_sub(1).max(start);
+        let section = lines[start..end].join("\n");
+        if section.trim().is_empty() {
+            continue;
+        }
+        let header = lines[start].trim();
+        if header.starts_with("[aliases]") {
+            for (i, line) in lines.iter().enumerate().take(end).skip(start + 1) {
+                if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                    continue;
+                }
+                match toml::from_str::<Table>(line) {
+                    Ok(alias) if alias.values().all(Value::is_str) => aliases.extend(alias),
+                    Ok(_) => drop(i, i, "an alias must name a binary".to_string()),
+                    Err(e) => drop(i, i, e.to_string()),
+                }
+            }
+            continue;
+        }
+        let parsed = match toml::from_str::<Table>(&section) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                drop(start, last, e.to_string());
+                continue;
+            }
+        };
+        if header.starts_with("[[binaries]]") {
+            let entry = parsed
+                .get("binaries")
+                .and_then(Value::as_array)
+                .and_then(|b| b.first())
+                .cloned()
+                .unwrap_or_else(|| Value::Table(Table::new()));
+            match entry.clone().try_into::<crate::binaries::HoistedBinary>() {
+                Ok(_) => binaries.push(entry),
+                Err(e) => drop(start, last, e.to_string()),
+            }
+        } else if n == 0 && !header.starts_with('[') {
+            for (key, value) in parsed {
+                match key.as_str() {
+                    "version" => {
+                        table.insert(key, value);
+                    }
+                    _ => drop(start, last, format!("unexpected top-level key `{}`", key)),
+                }
+            }
+        } else {
+            drop(start, last, format!("unexpected table {}", header));
+        }
+    }
+    if version(&table).is_err() {
+        table.remove("version");
+    }
+    table.insert("binaries".to_string(), Value::Array(binaries));
+    table.insert("aliases".to_string(), Value::Table(aliases));
+    Ok((from_table(table)?, dropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate() {
+        let unversioned = r#"
+[[binaries]]
+name = "a"
+location = "/p/target/release/a"
+"#;
+        let registry = parse(unversioned).unwrap();
+        assert_eq!(registry.version, REGISTRY_VERSION);
+        assert_eq!(registry.binaries.len(), 1);
+        assert!(toml::to_string(&registry)
+            .unwrap()
+            .starts_with(&format!("version = {}\n", REGISTRY_VERSION)));
+
+        let current = toml::to_string(&HoistRegistry::default()).unwrap();
+        assert_eq!(parse(&current).unwrap(), HoistRegistry::default());
+
+        let newer = format!("version = {}\n", REGISTRY_VERSION + 1);
+        assert!(parse(&newer)
+            .unwrap_err()
+            .to_string()
+            .contains("Upgrade cargo-hoist"));
+        assert!(parse("version = \"1\"").is_err());
+    }
+
+    #[test]
+    fn test_salvage() {
+        let corrupt = r#"version = 1
+
+[[binaries]]
+name = "a"
+location = "/p/a"
+
+[[binaries]]
+name = "b"
+location = 
+[binaries.registration]
+source = "manual"
+
+[[binaries]]
+name = "c"
+location = "/p/c"
+hoist_count = "many"
+
+[aliases]
+x = "a"
+y = 
+z = 3
+"#;
+        assert!(parse(corrupt).is_err());
+        let (registry, dropped) = salvage(corrupt).unwrap();
+        assert_eq!(registry.binaries.len(), 1);
+        assert!(registry.latest("a").is_some());
+        assert_eq!(registry.aliases.len(), 1);
+        assert_eq!(registry.aliases["x"], "a");
+        let lines: Vec<_> = dropped.iter().map(|d| (d.first, d.last)).collect();
+        assert_eq!(lines, vec![(7, 12), (13, 17), (20, 20), (21, 21)]);
+        assert!(dropped[0].to_string().starts_with("lines 7-12: "));
+
+        let newer = format!("version = {}\n[[binaries]]\nname = ", REGISTRY_VERSION + 1);
+        assert!(salvage(&newer).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! Opener
+//!
+//! Opens paths and urls with the platform's default handler.
+
+use anyhow::Result;
+use std::ffi::OsStr;
+use tracing::instrument;
+
+/// The platform command used to open paths and urls.
+pub fn open_command() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Opens the `target` path or url with the platform's default handler.
+#[instrument(skip(target))]
+pub fn open(target: impl AsRef<OsStr>) -> Result<()> {
+    let command = open_command();
+    let status = std::process::Command::new(command)
+        .arg(target.as_ref())
+        .status()
+        .map_err(|e| anyhow::anyhow!("failed to run {}: {}", command, e))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", command, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Parallelism
+//!
+//! A small scoped worker pool bounded by the `--jobs` flag or the `jobs` config key.
+//!
+//! The cli resolves the worker count once at startup with [set_jobs].
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The explicitly requested worker count, or 0 if unset.
+static JOBS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the worker count for the process, overriding the config.
+/// A count of 0 resets to the detected parallelism.
+pub fn set_jobs(jobs: usize) {
+    JOBS.store(jobs, Ordering::Relaxed);
+}
+
+/// Returns the maximum number of worker threads.
+///
+/// Resolves the count set with [set_jobs], falling back to the available parallelism.
+pub fn jobs() -> usize {
+    match JOBS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+        j => j,
+    }
+}
+
+/// Maps `f` over `items` using up to [jobs] worker threads, preserving the
+/// order of the results. Returns the first error in item order, if any.
+pub fn try_map<T, R, F>(items: Vec<T>, f: F) -> Result<Vec<R>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Result<R> + Sync,
+{
+    let workers = jobs().min(items.len());
+    if workers <= 1 {
+        return items.into_iter().map(f).collect();
+    }
+
+    let count = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(count));
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| loop {
+                let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                let Some((i, item)) = next else {
+                    break;
+                };
+                let res = f(item);
+                results
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push((i, res));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_try_map_preserves_order() {
+        set_jobs(4);
+        let doubled = try_map((0..100).collect(), |i: usize| Ok(i * 2)).unwrap();
+        assert_eq!(doubled, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+        set_jobs(0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_try_map_propagates_errors() {
+        set_jobs(4);
+        let res = try_map((0..10).collect(), |i: usize| match i {
+            7 => anyhow::bail!("failed on {}", i),
+            _ => Ok(i),
+        });
+        assert_eq!(res.unwrap_err().to_string(), "failed on 7");
+        set_jobs(0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_explicit_jobs() {
+        set_jobs(3);
+        assert_eq!(jobs(), 3);
+        set_jobs(0);
+        assert!(jobs() >= 1);
+    }
+}
@@ -0,0 +1,298 @@
+//! Platform
+//!
+//! Works out which platform a binary runs on, from its recorded target
+//! triple or by reading its ELF, Mach-O or PE header, so hoist can refuse to
+//! place binaries that can't run on this system.
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// The executable format of a platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    /// ELF, used by Linux and the BSDs
+    Elf,
+    /// Mach-O, used by macOS
+    MachO,
+    /// PE, used by Windows
+    Pe,
+}
+
+impl std::fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryFormat::Elf => write!(f, "ELF"),
+            BinaryFormat::MachO => write!(f, "Mach-O"),
+            BinaryFormat::Pe => write!(f, "PE"),
+        }
+    }
+}
+
+/// The executable format and architectures a binary runs on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    /// The executable format
+    pub format: BinaryFormat,
+    /// The architectures, named as [std::env::consts::ARCH] names them.
+    /// Universal Mach-O binaries have several.
+    pub archs: Vec<String>,
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.archs.join("+"), self.format)
+    }
+}
+
+impl Platform {
+    /// The platform of this system.
+    pub fn host() -> Self {
+        let format = match std::env::consts::OS {
+            "macos" | "ios" => BinaryFormat::MachO,
+            "windows" => BinaryFormat::Pe,
+            _ => BinaryFormat::Elf,
+        };
+        Self {
+            format,
+            archs: vec![std::env::consts::ARCH.to_string()],
+        }
+    }
+
+    /// The platform of a target triple, e.g. `aarch64-apple-darwin`, or
+    /// [None] for targets without a native executable format, e.g. wasm.
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        let arch = normalize_arch(triple.split('-').next()?)?;
+        let format = if triple.contains("-apple-") || triple.contains("darwin") {
+            BinaryFormat::MachO
+        } else if triple.contains("windows") {
+            BinaryFormat::Pe
+        } else if triple.contains("wasm") || triple.contains("-none") {
+            return None;
+        } else {
+            BinaryFormat::Elf
+        };
+        Some(Self {
+            format,
+            archs: vec![arch.to_string()],
+        })
+    }
+
+    /// Reads the platform of the binary at `path` from its header, or
+    /// [None] if it is not an ELF, Mach-O or PE executable of a known
+    /// architecture, e.g. a script.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let mut header = [0; 64];
+        let n = file.read_at(&mut header, 0).ok()?;
+        let header = &header[..n];
+        let u16_le =
+            |b: &[u8], at: usize| Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?));
+        let u32_le =
+            |b: &[u8], at: usize| Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?));
+        let u32_be =
+            |b: &[u8], at: usize| Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?));
+
+        if header.starts_with(b"\x7fELF") {
+            let machine = match header.get(5)? {
+                1 => u16_le(header, 0x12)?,
+                _ => u16::from_be_bytes(header.get(0x12..0x14)?.try_into().ok()?),
+            };
+            let arch = match machine {
+                0x03 => "x86",
+                0x08 => "mips",
+                0x14 => "powerpc",
+                0x15 => "powerpc64",
+                0x16 => "s390x",
+                0x28 => "arm",
+                0x3e => "x86_64",
+                0xb7 => "aarch64",
+                0xf3 => "riscv64",
+                0x102 => "loongarch64",
+                _ => return None,
+            };
+            return Some(Self {
+                format: BinaryFormat::Elf,
+                archs: vec![arch.to_string()],
+            });
+        }
+
+        let magic = u32_le(header, 0)?;
+        if magic == 0xfeed_face || magic == 0xfeed_facf {
+            let arch = macho_arch(u32_le(header, 4)?)?;
+            return Some(Self {
+                format: BinaryFormat::MachO,
+                archs: vec![arch.to_string()],
+            });
+        }
+        // Universal binaries list the architectures they contain. Java class
+        // files share the magic, but their version reads as 45 or more.
+        if u32_be(header, 0)? == 0xcafe_babe {
+            let count = u32_be(header, 4)?;
+            if count == 0 || count > 20 {
+                return None;
+            }
+            let mut archs = Vec::with_capacity(count as usize);
+            for i in 0..count as u64 {
+                let mut entry = [0; 4];
+                file.read_exact_at(&mut entry, 8 + i * 20).ok()?;
+                if let Some(arch) = macho_arch(u32::from_be_bytes(entry)) {
+                    archs.push(arch.to_string());
+                }
+            }
+            return Some(Self {
+                format: BinaryFormat::MachO,
+                archs,
+            });
+        }
+
+        if header.starts_with(b"MZ") {
+            let offset = u32_le(header, 0x3c)? as u64;
+            let mut pe = [0; 6];
+            file.read_exact_at(&mut pe, offset).ok()?;
+            if &pe[..4] != b"PE\0\0" {
+                return None;
+            }
+            let arch = match u16_le(&pe, 4)? {
+                0x014c => "x86",
+                0x01c4 => "arm",
+                0x8664 => "x86_64",
+                0xaa64 => "aarch64",
+                _ => return None,
+            };
+            return Some(Self {
+                format: BinaryFormat::Pe,
+                archs: vec![arch.to_string()],
+            });
+        }
+        None
+    }
+
+    /// Returns if binaries of this platform run on `host`.
+    pub fn runs_on(&self, host: &Platform) -> bool {
+        self.format == host.format && self.archs.iter().any(|a| host.archs.contains(a))
+    }
+}
+
+/// Names the architecture of a triple as [std::env::consts::ARCH] does.
+fn normalize_arch(arch: &str) -> Option<&'static str> {
+    Some(match arch {
+        "x86_64" | "amd64" => "x86_64",
+        "aarch64" | "arm64" | "arm64e" => "aarch64",
+        "i386" | "i586" | "i686" => "x86",
+        a if a.starts_with("arm") || a.starts_with("thumb") => "arm",
+        "riscv64gc" | "riscv64" => "riscv64",
+        "powerpc64" | "powerpc64le" => "powerpc64",
+        "powerpc" => "powerpc",
+        "s390x" => "s390x",
+        "loongarch64" => "loongarch64",
+        a if a.starts_with("mips") => "mips",
+        _ => return None,
+    })
+}
+
+/// Names the architecture of a Mach-O cpu type.
+fn macho_arch(cputype: u32) -> Option<&'static str> {
+    match cputype {
+        7 => Some("x86"),
+        12 => Some("arm"),
+        0x0100_0007 => Some("x86_64"),
+        0x0100_000c => Some("aarch64"),
+        _ => None,
+    }
+}
+
+/// Explains why a binary built for `triple`, or if it wasn't recorded, the
+/// platform read from the header of the file at `path`, can't run on this
+/// system. Returns [None] if it can, or its platform is unknown.
+pub fn mismatch(path: &Path, triple: Option<&str>) -> Option<String> {
+    let platform = match triple {
+        Some(triple) => Platform::from_triple(triple)?,
+        None => Platform::detect(path)?,
+    };
+    let host = Platform::host();
+    if platform.runs_on(&host) {
+        return None;
+    }
+    Some(match triple {
+        Some(triple) => format!(
+            "is built for {} ({}), but this system is {}",
+            platform, triple, host
+        ),
+        None => format!("is built for {}, but this system is {}", platform, host),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_triple() {
+        let mac = Platform::from_triple("aarch64-apple-darwin").unwrap();
+        assert_eq!(mac.format, BinaryFormat::MachO);
+        assert_eq!(mac.to_string(), "aarch64 Mach-O");
+        assert_eq!(
+            Platform::from_triple("i686-pc-windows-msvc").unwrap().archs,
+            vec!["x86"]
+        );
+        assert_eq!(
+            Platform::from_triple("armv7-unknown-linux-gnueabihf").unwrap(),
+            Platform {
+                format: BinaryFormat::Elf,
+                archs: vec!["arm".to_string()],
+            }
+        );
+        assert_eq!(Platform::from_triple("wasm32-unknown-unknown"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(Platform::detect(&exe), Some(Platform::host()));
+        assert_eq!(mismatch(&exe, None), None);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let script = tempdir.path().join("script");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        assert_eq!(Platform::detect(&script), None);
+        assert_eq!(mismatch(&script, None), None);
+
+        let mut macho = vec![0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01];
+        macho.resize(64, 0);
+        let path = tempdir.path().join("macho");
+        std::fs::write(&path, &macho).unwrap();
+        let platform = Platform::detect(&path).unwrap();
+        assert_eq!(platform.format, BinaryFormat::MachO);
+        assert_eq!(platform.archs, vec!["aarch64"]);
+
+        let mut pe = vec![0; 0x86];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x80;
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+        let path = tempdir.path().join("pe");
+        std::fs::write(&path, &pe).unwrap();
+        assert_eq!(Platform::detect(&path).unwrap().to_string(), "x86_64 PE");
+        if Platform::host().format != BinaryFormat::Pe {
+            assert!(mismatch(&path, None)
+                .unwrap()
+                .starts_with("is built for x86_64 PE"));
+        }
+    }
+
+    #[test]
+    fn test_mismatch_by_triple() {
+        let host = Platform::host();
+        let other = match host.format {
+            BinaryFormat::MachO => "x86_64-pc-windows-msvc",
+            _ => "aarch64-apple-darwin",
+        };
+        let message = mismatch(Path::new("/nonexistent"), Some(other)).unwrap();
+        assert!(message.contains(other));
+        let native = format!("{}-unknown-linux-gnu", std::env::consts::ARCH);
+        if host.format == BinaryFormat::Elf {
+            assert_eq!(mismatch(Path::new("/nonexistent"), Some(&native)), None);
+        }
+    }
+}
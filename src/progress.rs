@@ -0,0 +1,77 @@
+//! Progress
+//!
+//! Progress bars drawn on stderr with `indicatif`, so that copying large
+//! binaries doesn't look frozen.
+//!
+//! Bars are hidden when stderr is not a terminal, and once the cli disables
+//! them with [set_enabled] for `--quiet`. Visible bars share one
+//! [MultiProgress], so per-file bars don't fight with the bar of the hoist
+//! they are part of.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use is_terminal::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Whether progress bars may be drawn.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// The bars drawn by the process.
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Files at least this large are copied with a progress bar.
+pub const LARGE_FILE: u64 = 64 * 1024 * 1024;
+
+/// Enables or disables progress bars for the process.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// What a progress bar counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Bytes, shown with binary units
+    Bytes,
+    /// Items, e.g. hoisted binaries
+    Items,
+}
+
+impl Unit {
+    /// The style of bars counting the unit.
+    fn style(self) -> ProgressStyle {
+        let template = match self {
+            Unit::Bytes => "{msg} [{bar:30}] {binary_bytes}/{binary_total_bytes}",
+            Unit::Items => "{msg} [{bar:30}] {pos}/{len}",
+        };
+        ProgressStyle::with_template(template)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> ")
+    }
+}
+
+/// Creates a progress bar counting up to `total`, or a hidden one if
+/// progress bars are disabled or stderr is not a terminal.
+pub fn bar(label: impl Into<String>, total: u64, unit: Unit) -> ProgressBar {
+    if !ENABLED.load(Ordering::Relaxed) || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total)
+        .with_style(unit.style())
+        .with_message(label.into());
+    BARS.get_or_init(MultiProgress::new).add(bar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_hidden_when_disabled() {
+        set_enabled(false);
+        let progress = bar("Copying", 10, Unit::Bytes);
+        set_enabled(true);
+        assert!(progress.is_hidden());
+        progress.inc(10);
+        assert_eq!(progress.position(), 10);
+    }
+}
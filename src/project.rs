@@ -3,10 +3,14 @@
 //! The [Project] is a wrapper for interacting with rust projects and their output binaries.
 
 use anyhow::Result;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tracing::instrument;
 
-use crate::binaries::HoistedBinary;
+use crate::binaries::{ArtifactKind, HoistedBinary};
+use crate::context::HoistContext;
+use crate::metadata::{read_manifest, Metadata};
 
 /// Project
 #[derive(Debug, Default, Clone, Hash, Eq, PartialEq)]
@@ -15,6 +19,168 @@ pub struct Project {
     pub root: PathBuf,
     /// Binaries
     pub binaries: Vec<PathBuf>,
+    /// The `CARGO_TARGET_DIR` the project builds into, if set
+    target_dir: Option<PathBuf>,
+    /// The cargo home whose config may set the target directory
+    cargo_home: Option<PathBuf>,
+}
+
+/// A build artifact discovered in a project's target directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// The canonical artifact path
+    pub path: PathBuf,
+    /// The kind of artifact
+    pub kind: ArtifactKind,
+    /// The build profile directory, e.g. `debug` or `release`
+    pub profile: String,
+    /// The target triple, for cross-compiled `target/<triple>/<profile>` builds
+    pub triple: Option<String>,
+    /// The last modification time
+    pub modified: Option<SystemTime>,
+}
+
+impl Artifact {
+    /// The artifact file name.
+    pub fn name(&self) -> Option<&str> {
+        self.path.file_name().and_then(|n| n.to_str())
+    }
+}
+
+/// A directory of artifacts pending a scan.
+#[derive(Debug, Clone)]
+struct ArtifactDir {
+    dir: PathBuf,
+    kind: ArtifactKind,
+    profile: String,
+    triple: Option<String>,
+}
+
+/// A lazy iterator over the executable artifacts of a target directory.
+///
+/// Only the top level of the target directory is read up front. Profile
+/// directories are read entry by entry as the iterator is advanced.
+#[derive(Debug)]
+pub struct Artifacts {
+    pending: Vec<ArtifactDir>,
+    current: Option<(std::fs::ReadDir, ArtifactDir)>,
+}
+
+impl Artifacts {
+    /// Creates a new artifact iterator over the target directory.
+    pub fn new(target_dir: &Path) -> Self {
+        let mut pending = vec![];
+        for (dir, profile, triple) in Artifacts::build_dirs(target_dir) {
+            pending.extend(Artifacts::profile_dirs(&dir, profile, triple));
+        }
+        // Scan in directory order since artifacts are popped off the back.
+        pending.reverse();
+        Self {
+            pending,
+            current: None,
+        }
+    }
+
+    /// Lists the profile directories of a target directory, sorted by path,
+    /// with their profile names and, for cross-compiled
+    /// `target/<triple>/<profile>` builds, their target triples.
+    pub fn build_dirs(target_dir: &Path) -> Vec<(PathBuf, String, Option<String>)> {
+        let mut dirs = vec![];
+        for dir in Artifacts::subdirs(target_dir) {
+            let Some(name) = dir.file_name().and_then(|n| n.to_str()).map(String::from) else {
+                continue;
+            };
+            let profiles = Artifacts::subdirs(&dir)
+                .into_iter()
+                .filter(|d| Artifacts::is_profile_dir(d))
+                .collect::<Vec<_>>();
+            if Artifacts::is_profile_dir(&dir) || profiles.is_empty() {
+                dirs.push((dir, name, None));
+                continue;
+            }
+            for profile in profiles {
+                let Some(p) = profile.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                let p = p.to_string();
+                dirs.push((profile, p, Some(name.clone())));
+            }
+        }
+        dirs
+    }
+
+    /// A profile directory holds cargo's build bookkeeping.
+    fn is_profile_dir(dir: &Path) -> bool {
+        dir.join(".fingerprint").is_dir() || dir.join("deps").is_dir()
+    }
+
+    /// The artifact directories of a single profile.
+    fn profile_dirs(dir: &Path, profile: String, triple: Option<String>) -> Vec<ArtifactDir> {
+        vec![
+            ArtifactDir {
+                dir: dir.to_path_buf(),
+                kind: ArtifactKind::Bin,
+                profile: profile.clone(),
+                triple: triple.clone(),
+            },
+            ArtifactDir {
+                dir: dir.join("examples"),
+                kind: ArtifactKind::Example,
+                profile,
+                triple,
+            },
+        ]
+    }
+
+    /// Lists the sub-directories of `dir`, sorted by name.
+    fn subdirs(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+        let mut dirs = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect::<Vec<_>>();
+        dirs.sort();
+        dirs
+    }
+}
+
+impl Iterator for Artifacts {
+    type Item = Artifact;
+
+    fn next(&mut self) -> Option<Artifact> {
+        loop {
+            if let Some((entries, dir)) = &mut self.current {
+                match entries.next() {
+                    Some(Ok(e)) => {
+                        let path = e.path();
+                        if crate::executables::exec_path(&path).is_err() {
+                            continue;
+                        }
+                        let modified = e.metadata().and_then(|m| m.modified()).ok();
+                        return Some(Artifact {
+                            path: std::fs::canonicalize(&path).unwrap_or(path),
+                            kind: dir.kind,
+                            profile: dir.profile.clone(),
+                            triple: dir.triple.clone(),
+                            modified,
+                        });
+                    }
+                    Some(Err(e)) => {
+                        tracing::warn!("Failed to read entry: {:?}", e);
+                        continue;
+                    }
+                    None => self.current = None,
+                }
+            }
+            let dir = self.pending.pop()?;
+            if let Ok(entries) = std::fs::read_dir(&dir.dir) {
+                self.current = Some((entries, dir));
+            }
+        }
+    }
 }
 
 impl TryFrom<Option<&Path>> for Project {
@@ -22,13 +188,8 @@ impl TryFrom<Option<&Path>> for Project {
 
     #[instrument(skip(p))]
     fn try_from(p: Option<&Path>) -> Result<Self> {
-        let p = p
-            .map(|p| p.to_path_buf())
-            .unwrap_or(std::env::current_dir()?);
-        Ok(Self {
-            root: p.to_path_buf(),
-            binaries: vec![],
-        })
+        let p = p.map_or_else(std::env::current_dir, |p| Ok(p.to_path_buf()))?;
+        Ok(Self::from(p.as_path()))
     }
 }
 
@@ -37,13 +198,8 @@ impl TryFrom<Option<PathBuf>> for Project {
 
     #[instrument(skip(p))]
     fn try_from(p: Option<PathBuf>) -> Result<Self> {
-        let p = p
-            .map(|p| p.to_path_buf())
-            .unwrap_or(std::env::current_dir()?);
-        Ok(Self {
-            root: p,
-            binaries: vec![],
-        })
+        let p = p.map_or_else(std::env::current_dir, Ok)?;
+        Ok(Self::from(p.as_path()))
     }
 }
 
@@ -52,20 +208,29 @@ impl From<&Path> for Project {
     fn from(p: &Path) -> Self {
         Self {
             root: p.to_path_buf(),
-            binaries: vec![],
+            ..Default::default()
         }
     }
 }
 
 impl Project {
+    /// Creates a [Project] at `root` that resolves its target directory with
+    /// the `CARGO_TARGET_DIR` and cargo home of the context.
+    pub fn new(ctx: &HoistContext, root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            binaries: vec![],
+            target_dir: ctx.target_dir().map(Path::to_path_buf),
+            cargo_home: Some(ctx.cargo_home()),
+        }
+    }
+
     /// Create a new [Project] from the current dir.
+    #[deprecated(note = "depends on the process working directory, use `Project::new`")]
     #[instrument]
     pub fn from_current_dir() -> Result<Self> {
-        let root = std::env::current_dir()?;
-        Ok(Self {
-            root,
-            binaries: vec![],
-        })
+        let ctx = HoistContext::from_env()?;
+        Ok(Self::new(&ctx, &ctx.cwd))
     }
 
     /// Constructs project binaries from a list of binary string names.
@@ -78,7 +243,10 @@ impl Project {
             let binary = self
                 .binaries
                 .iter()
-                .find(|b| b.file_name().unwrap_or_default().to_string_lossy() == binary)
+                .find(|b| {
+                    let name = b.file_name().unwrap_or_default().to_string_lossy();
+                    name == binary || crate::executables::strip_hash_suffix(&name) == Some(&binary)
+                })
                 .cloned();
             bins.push(binary.ok_or(anyhow::anyhow!("[std] failed to find binary"))?);
         }
@@ -94,11 +262,11 @@ impl Project {
             let binary_name = binary
                 .file_name()
                 .ok_or(anyhow::anyhow!("[std] failed to extract binary name"))?;
-            let binary_name = binary_name
-                .to_str()
-                .ok_or(anyhow::anyhow!(
-                    "[std] failed to convert binary path name to string"
-                ))?
+            let binary_name = binary_name.to_str().ok_or(anyhow::anyhow!(
+                "[std] failed to convert binary path name to string"
+            ))?;
+            let binary_name = crate::executables::strip_hash_suffix(binary_name)
+                .unwrap_or(binary_name)
                 .to_string();
             let binary = HoistedBinary::new(binary_name, binary.clone());
             hoisted.push(binary);
@@ -106,14 +274,126 @@ impl Project {
         Ok(hoisted)
     }
 
+    /// Returns the root of the cargo workspace the project belongs to.
+    ///
+    /// This is the nearest ancestor of the project's package whose `Cargo.toml`
+    /// declares a `[workspace]` not excluding the package, which may be a
+    /// virtual manifest with no package of its own. Falls back to the package
+    /// root for standalone packages, and to the project root when no manifest
+    /// is found at all.
+    #[instrument(skip(self))]
+    pub fn workspace_root(&self) -> PathBuf {
+        let mut manifests = self
+            .root
+            .ancestors()
+            .filter_map(|a| Some((a, read_manifest(&a.join("Cargo.toml"))?)));
+        let Some((package, manifest)) = manifests.next() else {
+            return self.root.clone();
+        };
+        let excludes = |root: &Path, manifest: &toml::Value| {
+            manifest
+                .get("workspace")
+                .and_then(|w| w.get("exclude"))
+                .and_then(|e| e.as_array())
+                .is_some_and(|e| {
+                    e.iter()
+                        .filter_map(|e| e.as_str())
+                        .any(|e| package.starts_with(root.join(e)))
+                })
+        };
+        std::iter::once((package, manifest))
+            .chain(manifests)
+            .find(|(a, m)| m.get("workspace").is_some() && !excludes(a, m))
+            .map_or(package, |(a, _)| a)
+            .to_path_buf()
+    }
+
+    /// The target directory shared by the project's workspace, as cargo
+    /// resolves it: the `CARGO_TARGET_DIR` of the context the project was
+    /// created in, then the `build.target-dir` of the nearest cargo config,
+    /// then `target` in the workspace root.
+    pub fn target_dir(&self) -> PathBuf {
+        match &self.target_dir {
+            Some(dir) => dir.clone(),
+            None => self
+                .configured_target_dir()
+                .unwrap_or_else(|| self.workspace_root().join("target")),
+        }
+    }
+
+    /// The `build.target-dir` of the nearest cargo config, the
+    /// `.cargo/config.toml` or `.cargo/config` of the project root or one of
+    /// its ancestors, falling back to the config in the cargo home of the
+    /// context the project was created in.
+    ///
+    /// Relative paths are relative to the directory holding the `.cargo`
+    /// directory, as cargo reads them.
+    fn configured_target_dir(&self) -> Option<PathBuf> {
+        let cargo_home = self.cargo_home.clone();
+        let configs = self
+            .root
+            .ancestors()
+            .map(|a| (a.join(".cargo"), a.to_path_buf()))
+            .chain(cargo_home.map(|h| {
+                let base = h.parent().map_or_else(|| h.clone(), Path::to_path_buf);
+                (h, base)
+            }));
+        configs
+            .flat_map(|(dir, base)| {
+                ["config.toml", "config"]
+                    .into_iter()
+                    .map(move |name| (dir.join(name), base.clone()))
+            })
+            .find_map(|(config, base)| {
+                let config = read_manifest(&config)?;
+                let dir = config.get("build")?.get("target-dir")?.as_str()?;
+                Some(base.join(dir))
+            })
+    }
+
+    /// Returns the build profile and, for cross-compiled builds, the target
+    /// triple of an artifact in the project's target directory, e.g.
+    /// `release` and `x86_64-unknown-linux-musl` for
+    /// `target/x86_64-unknown-linux-musl/release/app`.
+    pub fn build_of(&self, path: &Path) -> Option<(String, Option<String>)> {
+        let target_dir = self.target_dir();
+        let canonical = target_dir.canonicalize().unwrap_or(target_dir.clone());
+        let rel = path
+            .strip_prefix(&target_dir)
+            .or_else(|_| path.strip_prefix(&canonical))
+            .ok()?;
+        let mut dirs = rel
+            .parent()?
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .collect::<Vec<_>>();
+        if matches!(dirs.last(), Some(&"deps" | &"examples")) {
+            dirs.pop();
+        }
+        match dirs.as_slice() {
+            [profile] => Some((profile.to_string(), None)),
+            [triple, profile] => Some((profile.to_string(), Some(triple.to_string()))),
+            _ => None,
+        }
+    }
+
+    /// Returns a lazy iterator over the executable artifacts in the project's
+    /// target directory, including `examples/` and cross-compiled
+    /// `target/<triple>/<profile>` builds.
+    #[instrument(skip(self))]
+    pub fn artifacts(&self) -> Artifacts {
+        Artifacts::new(&self.target_dir())
+    }
+
     /// Get a list of targets for the project.
     #[instrument(skip(self))]
     pub fn get_targets(&self) -> Result<Vec<String>> {
         let mut targets = vec![];
-        if !self.root.join("target").exists() {
+        let target_dir = self.target_dir();
+        if !target_dir.exists() {
             return Ok(targets);
         }
-        for entry in std::fs::read_dir(self.root.join("target"))? {
+        for entry in std::fs::read_dir(target_dir)? {
             let Ok(e) = entry else {
                 tracing::warn!("Failed to read entry: {:?}", entry);
                 continue;
@@ -127,20 +407,151 @@ impl Project {
         Ok(targets)
     }
 
-    /// Attempts to load local binaries from the target directory.
+    /// Attempts to load local binaries from the profile directories of the
+    /// target directory, including cross-compiled `target/<triple>/<profile>`
+    /// builds.
     #[instrument(skip(self))]
     pub fn load(&mut self) -> Result<()> {
-        let targets = self.get_targets()?;
-        let mut binaries = vec![];
-        for target in targets {
-            let target = self.root.join("target").join(target);
-            let bins = Project::extract_binaries(&target)?;
-            binaries.extend(bins);
-        }
-        self.binaries = binaries;
+        let dirs = Artifacts::build_dirs(&self.target_dir());
+        let binaries =
+            crate::parallel::try_map(dirs, |(dir, _, _)| Project::extract_binaries(&dir))?;
+        let binaries = Project::dedup_hashed(binaries.into_iter().flatten().collect());
+        self.binaries = match self.bin_targets() {
+            Some(targets) => binaries
+                .into_iter()
+                .filter(|b| Project::is_bin_target(b, &targets))
+                .collect(),
+            None => binaries,
+        };
         Ok(())
     }
 
+    /// Resolves the names of the `[[bin]]` targets of the project's workspace
+    /// with `cargo metadata`, so that build outputs which aren't binaries of
+    /// the project, e.g. copied-in tools or stray scripts, aren't registered.
+    ///
+    /// Returns [None] when the project has no cargo manifest or `cargo
+    /// metadata` fails, in which case every executable is registered.
+    #[instrument(skip(self))]
+    pub fn bin_targets(&self) -> Option<HashSet<String>> {
+        read_manifest(&self.workspace_root().join("Cargo.toml"))?;
+        match Metadata::load(&self.root) {
+            Ok(metadata) => Some(
+                metadata
+                    .targets()
+                    .filter(|t| t.is("bin"))
+                    .map(|t| t.name.clone())
+                    .collect(),
+            ),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to resolve the bin targets, registering every executable: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns if the artifact at `path` is the output of one of the named
+    /// bin targets, allowing for a hash suffix and an `.exe` extension.
+    fn is_bin_target(path: &Path, targets: &HashSet<String>) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        let name = name.strip_suffix(".exe").unwrap_or(name);
+        let name = crate::executables::strip_hash_suffix(name).unwrap_or(name);
+        targets.contains(name) || targets.contains(&name.replace('_', "-"))
+    }
+
+    /// De-duplicates hash-suffixed artifacts (e.g. `mybin-3fa8c91d0e2b64a7`)
+    /// against their canonical counterparts.
+    ///
+    /// Artifacts are grouped by profile directory and target name. A hash-suffixed
+    /// artifact is dropped whenever the canonical `mybin` exists in its group.
+    /// Otherwise, only the most recently modified hash-suffixed artifact is kept.
+    #[instrument(skip(binaries))]
+    pub fn dedup_hashed(binaries: Vec<PathBuf>) -> Vec<PathBuf> {
+        let key = |p: &Path| -> Option<(PathBuf, String, bool)> {
+            let name = p.file_name()?.to_str()?;
+            let (stem, hashed) = match crate::executables::strip_hash_suffix(name) {
+                Some(stem) => (stem, true),
+                None => (name, false),
+            };
+            let mut dir = p.parent()?;
+            if dir.file_name().is_some_and(|d| d == "deps") {
+                dir = dir.parent()?;
+            }
+            Some((dir.to_path_buf(), stem.replace('_', "-"), hashed))
+        };
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+
+        let mut canonical = std::collections::HashSet::new();
+        let mut freshest = std::collections::HashMap::new();
+        for b in &binaries {
+            match key(b) {
+                Some((dir, stem, false)) => {
+                    canonical.insert((dir, stem));
+                }
+                Some((dir, stem, true)) => {
+                    let entry = freshest.entry((dir, stem)).or_insert(b);
+                    if modified(b) > modified(entry) {
+                        *entry = b;
+                    }
+                }
+                None => {}
+            }
+        }
+        binaries
+            .iter()
+            .filter(|b| match key(b) {
+                Some((dir, stem, true)) => {
+                    let group = (dir, stem);
+                    !canonical.contains(&group) && freshest.get(&group) == Some(b)
+                }
+                _ => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Loads test and bench harnesses from the `deps/` directories of each
+    /// target, resolving their hash-suffixed file names to the cargo target
+    /// names reported by `cargo metadata`.
+    #[instrument(skip(self))]
+    pub fn test_artifacts(&self, tests: bool, benches: bool) -> Result<Vec<HoistedBinary>> {
+        let metadata = Metadata::load(&self.root)?;
+        let targets = metadata
+            .targets()
+            .filter_map(|t| match (t.is("test"), t.is("bench")) {
+                (true, _) if tests => Some((t, ArtifactKind::Test)),
+                (_, true) if benches => Some((t, ArtifactKind::Bench)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut artifacts = vec![];
+        for (dir, _, _) in Artifacts::build_dirs(&self.target_dir()) {
+            let deps = dir.join("deps");
+            for exec in Project::extract_binaries(&deps)? {
+                let Some(file_name) = exec.file_name().and_then(|f| f.to_str()) else {
+                    continue;
+                };
+                let Some(stem) = crate::executables::strip_hash_suffix(file_name) else {
+                    continue;
+                };
+                let Some((t, kind)) = targets.iter().find(|(t, _)| t.crate_name() == stem) else {
+                    continue;
+                };
+                artifacts.push(HoistedBinary {
+                    kind: *kind,
+                    ..HoistedBinary::new(t.name.clone(), exec)
+                });
+            }
+        }
+        Ok(artifacts)
+    }
+
     /// Extract binaries from a target directory.
     #[instrument(skip(target))]
     pub fn extract_binaries(target: &Path) -> Result<Vec<PathBuf>> {
@@ -177,6 +588,7 @@ mod tests {
         test_dir
     }
 
+    #[allow(clippy::suspicious_open_options, clippy::vec_init_then_push)]
     fn create_binaries(p: &Path) -> Vec<PathBuf> {
         let target_dir = p.join("target").join("release");
         std::fs::create_dir_all(&target_dir).unwrap();
@@ -222,6 +634,7 @@ mod tests {
 
     #[test]
     #[serial]
+    #[allow(clippy::suspicious_open_options)]
     fn test_extract_binaries_from_non_dir() {
         let tempdir = tempfile::tempdir().unwrap();
         let test_dir = setup_test(&tempdir, "test_extract_binaries_from_non_dir");
@@ -265,6 +678,311 @@ mod tests {
         assert!(Project::extract_binaries(&target).unwrap().is_empty());
     }
 
+    #[test]
+    #[serial]
+    fn test_artifacts() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_artifacts");
+        create_binaries(&test_dir);
+        let cross = test_dir.join("target/x86_64-unknown-linux-musl/release");
+        std::fs::create_dir_all(cross.join("deps")).unwrap();
+        std::fs::create_dir_all(test_dir.join("target/release/examples")).unwrap();
+        for bin in [
+            cross.join("binary3"),
+            test_dir.join("target/release/examples/ex"),
+        ] {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o755)
+                .open(bin)
+                .unwrap();
+        }
+
+        let project = Project::from(test_dir.as_path());
+        let mut artifacts = project
+            .artifacts()
+            .map(|a| (a.name().unwrap().to_string(), a.kind, a.profile, a.triple))
+            .collect::<Vec<_>>();
+        artifacts.sort();
+        assert_eq!(
+            artifacts,
+            vec![
+                (
+                    "binary1".to_string(),
+                    ArtifactKind::Bin,
+                    "release".to_string(),
+                    None
+                ),
+                (
+                    "binary2".to_string(),
+                    ArtifactKind::Bin,
+                    "release".to_string(),
+                    None
+                ),
+                (
+                    "binary3".to_string(),
+                    ArtifactKind::Bin,
+                    "release".to_string(),
+                    Some("x86_64-unknown-linux-musl".to_string())
+                ),
+                (
+                    "ex".to_string(),
+                    ArtifactKind::Example,
+                    "release".to_string(),
+                    None
+                ),
+            ]
+        );
+        assert!(project.artifacts().all(|a| a.modified.is_some()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_dedup_hashed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_dedup_hashed");
+        let release = test_dir.join("target/release");
+        std::fs::create_dir_all(release.join("deps")).unwrap();
+        let touch = |p: &Path| {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o755)
+                .open(p)
+                .unwrap();
+        };
+        touch(&release.join("my-bin"));
+        touch(&release.join("deps/my_bin-0123456789abcdef"));
+        touch(&release.join("other-0123456789abcdef"));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        touch(&release.join("other-fedcba9876543210"));
+
+        let deduped = Project::dedup_hashed(vec![
+            release.join("my-bin"),
+            release.join("deps/my_bin-0123456789abcdef"),
+            release.join("other-0123456789abcdef"),
+            release.join("other-fedcba9876543210"),
+        ]);
+        assert_eq!(
+            deduped,
+            vec![
+                release.join("my-bin"),
+                release.join("other-fedcba9876543210")
+            ]
+        );
+
+        let mut project = Project {
+            root: test_dir.clone(),
+            binaries: deduped,
+            ..Default::default()
+        };
+        let names = project
+            .hoisted_binaries()
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["my-bin", "other"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_test_artifacts() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_dir = setup_test(&tempdir, "test_test_artifacts");
+        std::fs::write(
+            test_dir.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(test_dir.join("src")).unwrap();
+        std::fs::write(test_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::create_dir_all(test_dir.join("tests")).unwrap();
+        std::fs::write(test_dir.join("tests/end-to-end.rs"), "").unwrap();
+        let deps = test_dir.join("target/debug/deps");
+        std::fs::create_dir_all(&deps).unwrap();
+        for name in ["end_to_end-0123456789abcdef", "demo-0123456789abcdef"] {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o755)
+                .open(deps.join(name))
+                .unwrap();
+        }
+
+        let project = Project::from(test_dir.as_path());
+        let artifacts = project.test_artifacts(true, false).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].name, "end-to-end");
+        assert_eq!(artifacts[0].kind, ArtifactKind::Test);
+        assert!(project.test_artifacts(false, true).unwrap().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_bin_targets() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().canonicalize().unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[[bin]]\nname = \"demo-cli\"\npath = \"src/cli.rs\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("src/cli.rs"), "fn main() {}\n").unwrap();
+        let release = root.join("target/release");
+        std::fs::create_dir_all(&release).unwrap();
+        for name in ["demo", "demo-cli", "stray-tool"] {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o755)
+                .open(release.join(name))
+                .unwrap();
+        }
+
+        let mut project = Project::from(root.as_path());
+        let targets = project.bin_targets().unwrap();
+        assert_eq!(targets.len(), 2);
+        project.load().unwrap();
+        project.binaries.sort();
+        assert_eq!(
+            project.binaries,
+            vec![release.join("demo"), release.join("demo-cli")]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_workspace_member() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().canonicalize().unwrap().join("workspace");
+        let member = root.join("crates/app");
+        let excluded = root.join("vendor/tool");
+        std::fs::create_dir_all(member.join("src/bin")).unwrap();
+        std::fs::create_dir_all(&excluded).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"vendor\"]\n",
+        )
+        .unwrap();
+        for dir in [&member, &excluded] {
+            std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"app\"\n").unwrap();
+        }
+        let binaries = create_binaries(&root);
+
+        let mut project = Project::from(member.join("src/bin").as_path());
+        assert_eq!(project.workspace_root(), root);
+        project.load().unwrap();
+        project.binaries.sort();
+        assert_eq!(project.binaries, binaries);
+        assert_eq!(
+            Project::from(root.as_path()).target_dir(),
+            root.join("target")
+        );
+        assert_eq!(Project::from(excluded.as_path()).workspace_root(), excluded);
+    }
+
+    #[test]
+    #[serial]
+    fn test_configured_target_dir() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().canonicalize().unwrap();
+        let ctx = HoistContext::new(&root, &root).with_cargo_home(root.join("cargo"));
+        let project = Project::new(&ctx, &root.join("app"));
+        std::fs::create_dir_all(&project.root).unwrap();
+        assert_eq!(project.target_dir(), project.root.join("target"));
+
+        std::fs::create_dir_all(root.join("cargo")).unwrap();
+        std::fs::write(
+            root.join("cargo/config.toml"),
+            "[build]\ntarget-dir = \"/home/target\"\n",
+        )
+        .unwrap();
+        assert_eq!(project.target_dir(), PathBuf::from("/home/target"));
+
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::write(
+            root.join(".cargo/config.toml"),
+            "[build]\ntarget-dir = \"shared/target\"\n",
+        )
+        .unwrap();
+        assert_eq!(project.target_dir(), root.join("shared/target"));
+
+        std::fs::create_dir_all(project.root.join(".cargo")).unwrap();
+        std::fs::write(
+            project.root.join(".cargo/config"),
+            "[build]\ntarget-dir = \"/opt/target\"\n",
+        )
+        .unwrap();
+        assert_eq!(project.target_dir(), PathBuf::from("/opt/target"));
+
+        let ctx = ctx.with_target_dir("/ci/target");
+        assert_eq!(
+            Project::new(&ctx, &project.root).target_dir(),
+            PathBuf::from("/ci/target")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_cross_compiled() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().canonicalize().unwrap();
+        let native = create_binaries(&root);
+        let triple = "aarch64-unknown-linux-gnu";
+        let cross = root.join("target").join(triple).join("release");
+        std::fs::create_dir_all(cross.join("deps")).unwrap();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(cross.join("cross-tool"))
+            .unwrap();
+
+        let mut project = Project::from(root.as_path());
+        project.load().unwrap();
+        project.binaries.sort();
+        let mut expected = native;
+        expected.push(cross.join("cross-tool"));
+        expected.sort();
+        assert_eq!(project.binaries, expected);
+        assert_eq!(
+            project.build_of(&cross.join("cross-tool")),
+            Some(("release".to_string(), Some(triple.to_string())))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_build_of() {
+        let project = Project::from(Path::new("/p"));
+        let build = |path: &str| project.build_of(Path::new(path));
+        assert_eq!(
+            build("/p/target/release/app"),
+            Some(("release".to_string(), None))
+        );
+        assert_eq!(
+            build("/p/target/debug/deps/app-3fa8c91d0e2b64a7"),
+            Some(("debug".to_string(), None))
+        );
+        assert_eq!(
+            build("/p/target/x86_64-unknown-linux-musl/release/examples/demo"),
+            Some((
+                "release".to_string(),
+                Some("x86_64-unknown-linux-musl".to_string())
+            ))
+        );
+        assert_eq!(build("/p/bin/app"), None);
+    }
+
     #[test]
     #[serial]
     fn test_extract_binaries() {
@@ -1,102 +1,382 @@
 //! Hoist Registry
 //!
-//! The registry module contains the core hoist registry logic.
+//! The registry module contains the core hoist registry logic: loading,
+//! saving and inserting into the registry, and the lookups the commands
+//! share. Each command adds its operations on [HoistRegistry] in its own
+//! module, e.g. [crate::hoist] and [crate::install].
 
 use anyhow::Result;
 use inquire::Confirm;
-use inquire::{
-    formatter::MultiOptionFormatter, list_option::ListOption, validator::Validation, MultiSelect,
-};
 use is_terminal::IsTerminal;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::io::{Read, Write};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tracing::instrument;
 
 use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
 use crate::shell::*;
 
+/// Orders builds by registration time, then by build time, so entries
+/// recorded before provenance tracking sort as the oldest.
+pub(crate) fn recency(b: &HoistedBinary) -> (Option<u64>, Option<std::time::SystemTime>) {
+    let registered = b.registration.as_ref().map(|r| r.at);
+    let built = std::fs::metadata(&b.location)
+        .and_then(|m| m.modified())
+        .ok();
+    (registered, built)
+}
+
+/// Returns if `name` can name a binary or an alias: it is not empty, and has
+/// no whitespace, `/` or `@`.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '@']) && !name.chars().any(char::is_whitespace)
+}
+
+/// A context for the deprecated global-state helpers, resolving only `HOME`
+/// and `HOIST_HOME`.
+fn home_context() -> Result<HoistContext> {
+    Ok(HoistContext::new(std::env::var("HOME")?, PathBuf::new()).with_hoist_home_env())
+}
+
 /// Hoist Registry
 ///
 /// The global hoist registry is stored in ~/.hoist/registry.toml
 /// and contains the memoized list of binaries that have been
 /// built with cargo and saved as [HoistedBinary] objects.
-#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct HoistRegistry {
-    /// The list of hoisted binaries.
-    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    /// The registry format version, upgraded on load by [crate::migrate].
+    /// Registries written before versioning was introduced are version `0`.
+    #[serde(default)]
+    pub version: u32,
+    /// The list of hoisted binaries, written sorted by name and location.
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
     pub binaries: HashSet<HoistedBinary>,
+    /// Alternative names for registered binaries, mapping each alias to the
+    /// binary name it resolves to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// Serializes the binaries sorted by name and location, so the registry file
+/// only changes where its entries do.
+fn serialize_sorted<S: serde::Serializer>(
+    binaries: &HashSet<HoistedBinary>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut sorted: Vec<_> = binaries.iter().collect();
+    sorted.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+    serializer.collect_seq(sorted)
+}
+
+impl Default for HoistRegistry {
+    fn default() -> Self {
+        Self {
+            version: crate::migrate::REGISTRY_VERSION,
+            binaries: HashSet::new(),
+            aliases: BTreeMap::new(),
+        }
+    }
 }
 
 impl HoistRegistry {
     /// Inserts a [HoistedBinary] into the registry.
-    /// Will not insert if the binary already exists in the registry.
+    /// Replaces the entry with the same name and location if it already
+    /// exists, refreshing its metadata. A rebuild copied into the content
+    /// store is kept alongside the copies of earlier builds from the same
+    /// path, which become its history, see [HoistRegistry::previous_builds].
+    ///
+    /// Tags and pins are assigned per binary name, so the binary inherits the
+    /// tags of every registered build with the same name, and is pinned if
+    /// any of them is.
     #[instrument(skip(self, binary))]
-    pub fn insert(&mut self, binary: HoistedBinary) {
-        if !self.binaries.contains(&binary) {
-            self.binaries.insert(binary);
+    pub fn insert(&mut self, mut binary: HoistedBinary) {
+        for b in self.binaries.iter().filter(|b| b.name == binary.name) {
+            binary.pinned |= b.pinned;
+            for tag in &b.tags {
+                if !binary.has_tag(tag) {
+                    binary.tags.push(tag.clone());
+                }
+            }
+        }
+        let previous: Vec<_> = self
+            .binaries
+            .iter()
+            .filter(|b| {
+                *b == &binary || (b.name == binary.name && b.built_path() == binary.built_path())
+            })
+            .cloned()
+            .collect();
+        for existing in previous {
+            binary.last_hoisted_at = binary.last_hoisted_at.max(existing.last_hoisted_at);
+            binary.hoist_count = binary.hoist_count.max(existing.hoist_count);
+            binary.ttl = binary.ttl.or(existing.ttl);
+            if existing == binary {
+                // A stored copy shared with a build from elsewhere keeps
+                // the other build's path as a source.
+                binary.add_sources(&existing);
+                self.binaries.remove(&existing);
+            }
+        }
+        self.binaries.replace(binary);
+    }
+
+    /// Resolves a binary name or `name@version` spec through the registry
+    /// aliases, returning it unchanged if it is not an alias.
+    pub fn resolve_alias(&self, spec: &str) -> String {
+        let (name, version) = match spec.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (spec, None),
+        };
+        match (self.aliases.get(name), version) {
+            (Some(target), Some(version)) => format!("{}@{}", target, version),
+            (Some(target), None) => target.clone(),
+            (None, _) => spec.to_string(),
         }
     }
 
-    /// The path to the hoist directory.
+    /// Returns the unique names of the registered `[[bin]]` binaries, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let names: BTreeSet<_> = self
+            .binaries
+            .iter()
+            .filter(|b| b.kind.is_bin())
+            .map(|b| b.name.clone())
+            .collect();
+        names.into_iter().collect()
+    }
+
+    /// Resolves the aliases among `specs`, and replaces each glob pattern,
+    /// e.g. `op-*`, with the registered binary names it matches.
+    ///
+    /// Fails if a pattern matches no registered binary.
+    pub fn expand_globs(&self, specs: &[String]) -> Result<Vec<String>> {
+        let mut expanded = Vec::with_capacity(specs.len());
+        for spec in specs {
+            if !crate::utils::is_glob(spec) {
+                expanded.push(self.resolve_alias(spec));
+                continue;
+            }
+            let matched: Vec<_> = self
+                .names()
+                .into_iter()
+                .filter(|n| crate::utils::glob_match(spec, n))
+                .collect();
+            if matched.is_empty() {
+                anyhow::bail!("No registered binaries match {}", spec);
+            }
+            expanded.extend(matched);
+        }
+        let mut seen = HashSet::new();
+        expanded.retain(|n| seen.insert(n.clone()));
+        Ok(expanded)
+    }
+
+    /// Returns the aliases resolving to the binary `name`.
+    pub fn aliases_of(&self, name: &str) -> Vec<&str> {
+        self.aliases
+            .iter()
+            .filter(|(_, target)| *target == name)
+            .map(|(alias, _)| alias.as_str())
+            .collect()
+    }
+
+    /// Returns the most recently built registered binary with the given name.
+    pub fn latest(&self, name: &str) -> Option<&HoistedBinary> {
+        self.binaries
+            .iter()
+            .filter(|b| b.name == name)
+            .max_by_key(|b| recency(b))
+    }
+
+    /// Returns the registered builds of `name` present on disk, newest first.
+    pub fn builds(&self, name: &str) -> Vec<&HoistedBinary> {
+        let mut builds: Vec<_> = self
+            .binaries
+            .iter()
+            .filter(|b| b.name == name && b.location.exists())
+            .map(|b| (recency(b), b))
+            .collect();
+        builds.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.location.cmp(&b.1.location)));
+        builds.into_iter().map(|(_, b)| b).collect()
+    }
+
+    /// Returns a registered binary with the same name as `binary` that was
+    /// built more recently than the file at `binary`'s location.
+    pub fn newer_build(&self, binary: &HoistedBinary) -> Option<&HoistedBinary> {
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        let current = modified(&binary.location);
+        self.latest(&binary.name)
+            .filter(|b| b.location != binary.location && modified(&b.location) > current)
+    }
+
+    /// The path to the hoist directory, `HOIST_HOME` if set or `~/.hoist`
+    /// otherwise.
+    #[deprecated(note = "reads `HOME` from the process environment, use `HoistContext::hoist_dir`")]
     pub fn dir() -> Result<PathBuf> {
-        let hoist_dir = std::env::var("HOME")? + "/.hoist/";
-        Ok(PathBuf::from(hoist_dir))
+        Ok(home_context()?.hoist_dir())
     }
 
     /// The path to the hoist registry file.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistContext::registry_path`"
+    )]
     pub fn path() -> Result<PathBuf> {
-        let hoist_dir = HoistRegistry::dir()?;
-        Ok(hoist_dir.join("registry.toml"))
+        Ok(home_context()?.registry_path())
     }
 
     /// Hook identifier file.
     /// This is used to indicate that the hoist pre-hook has been installed.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistContext::hook_identifier`"
+    )]
     pub fn hook_identifier() -> Result<PathBuf> {
-        let hoist_dir = HoistRegistry::dir()?;
-        Ok(hoist_dir.join("hook"))
+        Ok(home_context()?.hook_identifier())
     }
 
     /// Create the hoist directory if it doesn't exist.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::create_dir_with`"
+    )]
     pub fn create_dir(quiet: bool) -> Result<()> {
-        let hoist_dir = HoistRegistry::dir()?;
-        if !std::path::Path::new(&hoist_dir).exists() {
+        HoistRegistry::create_dir_with(&HoistContext::from_env()?, quiet)
+    }
+
+    /// Create the hoist directory of the context if it doesn't exist.
+    pub fn create_dir_with(ctx: &HoistContext, quiet: bool) -> Result<()> {
+        let hoist_dir = ctx.hoist_dir();
+        if !hoist_dir.exists() {
             if !quiet {
                 tracing::info!("Creating ~/.hoist/ directory");
             }
-            std::fs::create_dir(&hoist_dir)?;
+            std::fs::create_dir_all(&hoist_dir)?;
         }
         Ok(())
     }
 
     /// Create the hoist registry file.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::create_registry_with`"
+    )]
     pub fn create_registry(quiet: bool) -> Result<()> {
-        HoistRegistry::create_dir(quiet)?;
-        let registry_file = HoistRegistry::path()?;
-        if !std::path::Path::new(&registry_file).exists() {
-            HoistRegistry::default().write()?;
-        }
-        Ok(())
+        HoistRegistry::create_registry_with(&HoistContext::from_env()?, quiet)
+    }
+
+    /// Create the hoist registry file, or the empty registry of the context's store.
+    pub fn create_registry_with(ctx: &HoistContext, quiet: bool) -> Result<()> {
+        HoistRegistry::create_dir_with(ctx, quiet)?;
+        ctx.store().init()
     }
 
     /// Build a new [HoistRegistry] from the registry file.
+    #[deprecated(note = "reads `HOME` from the process environment, use `HoistRegistry::load`")]
     pub fn new() -> Result<HoistRegistry> {
-        let registry_file = HoistRegistry::path()?;
-        let mut file = std::fs::OpenOptions::new().read(true).open(registry_file)?;
-        file.sync_all()?;
-        let mut registry_toml = String::new();
-        file.read_to_string(&mut registry_toml)?;
-        let registry: HoistRegistry = toml::from_str(&registry_toml)?;
-        Ok(registry)
+        HoistRegistry::load(&home_context()?)
+    }
+
+    /// Loads the [HoistRegistry] from the context's [RegistryStore], the
+    /// registry file in the context's home by default.
+    ///
+    /// [RegistryStore]: crate::store::RegistryStore
+    ///
+    /// If the stored registry is corrupt, its valid entries are salvaged and
+    /// the broken file set aside, reporting what was dropped, unless the
+    /// context is [strict](HoistContext::strict).
+    pub fn load(ctx: &HoistContext) -> Result<HoistRegistry> {
+        match ctx.store().load() {
+            Ok(registry) => {
+                ctx.set_snapshot(&registry);
+                Ok(registry)
+            }
+            Err(e) => {
+                ctx.take_snapshot();
+                HoistRegistry::recover(ctx, e)
+            }
+        }
+    }
+
+    /// Loads the [HoistRegistry] for a dry run or `env`, which create nothing:
+    /// a registry that doesn't exist yet loads empty.
+    pub(crate) fn load_dry_run(ctx: &HoistContext) -> Result<HoistRegistry> {
+        match HoistRegistry::load(ctx) {
+            Err(e)
+                if e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                Ok(HoistRegistry::default())
+            }
+            res => res,
+        }
+    }
+
+    /// Salvages the registry after `err` failed loading it, or returns the
+    /// error if it isn't recoverable.
+    fn recover(ctx: &HoistContext, err: anyhow::Error) -> Result<HoistRegistry> {
+        let missing = err
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound);
+        if missing || ctx.strict() {
+            return Err(err);
+        }
+        let Ok(Some(recovery)) = ctx.store().recover() else {
+            return Err(err);
+        };
+        eprintln!("cargo-hoist: warning: the registry is corrupt: {:#}", err);
+        for dropped in &recovery.dropped {
+            eprintln!("cargo-hoist: warning: dropped {}", dropped);
+        }
+        eprintln!(
+            "cargo-hoist: warning: recovered {} binaries, the corrupt registry was moved to {}",
+            recovery.registry.binaries.len(),
+            recovery.quarantined.display()
+        );
+        Ok(recovery.registry)
     }
 
-    /// Create the hoist pre-hook in the user bash file.
+    /// Takes the exclusive lock of the context's store, blocking until other
+    /// hoist processes release it.
+    ///
+    /// Hold the guard across a [HoistRegistry::load] and the following
+    /// [HoistRegistry::save], so concurrent invocations can't drop each
+    /// other's entries.
+    pub fn lock(ctx: &HoistContext) -> Result<crate::store::RegistryLock> {
+        ctx.store().lock()
+    }
+
+    /// Loads the registry entries passing `filter`, along with every alias,
+    /// using the indexes of the context's store where it has them.
+    pub fn select(ctx: &HoistContext, filter: &crate::store::Filter) -> Result<HoistRegistry> {
+        match ctx.store().select(filter) {
+            Ok(registry) => Ok(registry),
+            Err(e) => {
+                let mut registry = HoistRegistry::recover(ctx, e)?;
+                registry.binaries.retain(|b| filter.matches(b));
+                Ok(registry)
+            }
+        }
+    }
+
+    /// Create the hoist pre-hook in the user's shell config file.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::create_pre_hook_with`"
+    )]
     pub fn create_pre_hook(with_confirm: bool, quiet: bool) -> Result<()> {
-        HoistRegistry::create_dir(quiet)?;
-        let hook_file = HoistRegistry::hook_identifier()?;
-        if !std::path::Path::new(&hook_file).exists() {
+        HoistRegistry::create_pre_hook_with(&HoistContext::from_env()?, with_confirm, quiet)
+    }
+
+    /// Create the hoist pre-hook in the user's shell config file, or once it
+    /// is installed, upgrade hooks installed by earlier versions of hoist.
+    pub fn create_pre_hook_with(ctx: &HoistContext, with_confirm: bool, quiet: bool) -> Result<()> {
+        HoistRegistry::create_dir_with(ctx, quiet)?;
+        let hook_file = ctx.hook_identifier();
+        if !hook_file.exists() {
             let should_prompt = std::io::stdout().is_terminal() && with_confirm;
             if should_prompt {
                 tracing::debug!("detected tty, prompting user for install");
@@ -104,127 +384,107 @@ impl HoistRegistry {
             if should_prompt && !Confirm::new("Cargo hoist pre-cargo hook not installed. Do you want to install? ([y]/n) Once installed, this prompt will not bother you again :)").prompt()? {
                 anyhow::bail!("cargo hoist installation rejected");
             }
-            // Write the bash function to the user's bash file.
-            let shell_config = get_shell_config_file(detect_shell()?)?;
-            if !shell_config.as_path().exists() {
-                anyhow::bail!("~/.bashrc file does not exist");
-            }
-            let mut file = std::fs::OpenOptions::new()
-                .append(true)
-                .open(shell_config)?;
-            file.write_all(INSTALL_BASH_FUNCTION.as_bytes())?;
+            // Write the hook to the user's shell config file.
+            let shell = detect_shell()?;
+            shell.hook().install(&ctx.home)?;
 
             let mut file = std::fs::OpenOptions::new()
                 .write(true)
                 .create(true)
+                .truncate(true)
                 .open(hook_file)?;
             file.write_all("hook".as_bytes())?;
+            return Ok(());
+        }
+        // Hooks installed by earlier versions register builds as if
+        // `cargo hoist install` was run by hand.
+        for shell in SHELLS {
+            match shell.upgrade(&ctx.home) {
+                Ok(true) if !quiet => {
+                    let config = shell.config_file(&ctx.home);
+                    HoistRegistry::print_color(
+                        "Upgraded the pre-cargo hook in ",
+                        Color::Green,
+                        false,
+                    )?;
+                    HoistRegistry::print_color(&config.display().to_string(), Color::Cyan, false)?;
+                    HoistRegistry::print_color(
+                        ", restart your shell to use it",
+                        Color::Green,
+                        true,
+                    )?;
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to upgrade the {} hook: {:?}", shell.name(), e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates an empty local registry for the project in the working
+    /// directory, at `.hoist/registry.toml`, which hoist then uses instead
+    /// of the global registry anywhere under the project.
+    #[instrument(skip(ctx))]
+    pub fn init_local(ctx: &HoistContext, quiet: bool) -> Result<()> {
+        let path = crate::context::local_registry_path(&ctx.cwd);
+        if path.exists() {
+            anyhow::bail!("A local registry already exists at {}", path.display());
+        }
+        let dir = ctx.cwd.join(".hoist");
+        std::fs::create_dir_all(&dir)?;
+        // Only the registry itself is meant to be committed.
+        std::fs::write(dir.join(".gitignore"), "registry.lock\n*.tmp\n")?;
+        ctx.with_local_registry(&ctx.cwd).store().init()?;
+        if !quiet {
+            HoistRegistry::print_color("Created a local registry at ", Color::Green, false)?;
+            HoistRegistry::print_color(&path.display().to_string(), Color::Cyan, true)?;
         }
         Ok(())
     }
 
     /// Installs the hoist registry to a `.hoist/` subdir in the
     /// user's home directory.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::setup_with`"
+    )]
     #[instrument]
     pub fn setup(quiet: bool) -> Result<()> {
-        HoistRegistry::create_dir(quiet)?;
-        HoistRegistry::create_registry(quiet)?;
-        HoistRegistry::create_pre_hook(false, quiet)?;
-        Ok(())
+        HoistRegistry::setup_with(&HoistContext::from_env()?, quiet)
     }
 
-    /// Nukes the hoist toml registry.
-    /// This writes an empty registry to the registry file.
+    /// Installs the hoist registry to the hoist directory of the context.
     #[instrument]
-    pub fn nuke(quiet: bool) -> Result<()> {
-        HoistRegistry::setup(quiet)?;
-        HoistRegistry::default().write()?;
-        Ok(())
-    }
-
-    /// Installs binaries in the hoist toml registry.
-    #[instrument(skip(pdir, binaries, quiet))]
-    pub fn install(pdir: Option<&Path>, binaries: Vec<String>, quiet: bool) -> Result<()> {
-        HoistRegistry::setup(quiet)?;
-
-        // Build the hoist registry.
-        let mut registry = HoistRegistry::new()?;
-
-        // Load binaries from the project
-        let mut p = match crate::project::Project::try_from(pdir) {
-            Ok(p) => p,
-            Err(e) => {
-                if !quiet {
-                    tracing::warn!("Failed to load project: {}", e);
-                }
-                return Ok(());
-            }
-        };
-        let hoisted = if binaries.is_empty() {
-            p.load()?;
-            p.hoisted_binaries()?
-        } else {
-            p.set_binaries(binaries)?;
-            p.hoisted_binaries()?
-        };
-
-        // Insert hoisted binaries
-        let registered = hoisted.len();
-        hoisted.into_iter().for_each(|hb| {
-            registry.insert(hb);
-        });
-
-        // Only perform a writeback if there are binaries to hoist.
-        match registered {
-            0 => tracing::warn!("No binaries found in the target directory"),
-            _ => registry.write()?,
-        }
-
+    pub fn setup_with(ctx: &HoistContext, quiet: bool) -> Result<()> {
+        HoistRegistry::create_dir_with(ctx, quiet)?;
+        HoistRegistry::create_registry_with(ctx, quiet)?;
+        HoistRegistry::create_pre_hook_with(ctx, false, quiet)?;
         Ok(())
     }
 
     /// Writes the [HoistRegistry] to the registry file.
-    #[instrument(skip(self))]
+    #[deprecated(note = "reads `HOME` from the process environment, use `HoistRegistry::save`")]
     pub fn write(&self) -> Result<()> {
-        let registry_file = HoistRegistry::path()?;
-        let mut f = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(registry_file)?;
-        let toml = toml::to_string(&self)?;
-        f.write_all(toml.as_bytes())?;
-        f.sync_all()?;
-        Ok(())
+        self.save(&home_context()?)
     }
 
-    /// Finds a given binary in the hoist registry toml.
-    #[instrument(skip(binary))]
-    pub fn find(binary: impl AsRef<str>) -> Result<()> {
-        HoistRegistry::setup(false)?;
-        let registry = HoistRegistry::new()?;
-
-        // Find the binary in the registry.
-        let binary = binary.as_ref();
-        let binary = registry
-            .binaries
-            .iter()
-            .find(|b| b.name == binary)
-            .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?;
-        HoistRegistry::print_color(&format!("{}: ", binary.name), Color::Blue, false)?;
-        HoistRegistry::print_color(&binary.location.display().to_string(), Color::Cyan, true)?;
-        Ok(())
-    }
-
-    /// Lists the binaries in the hoist toml registry.
-    #[instrument]
-    pub fn list(quiet: bool) -> Result<()> {
-        HoistRegistry::setup(quiet)?;
-        let registry = HoistRegistry::new()?;
-        for binary in registry.binaries {
-            HoistRegistry::print_color(&format!("{}: ", binary.name), Color::Blue, false)?;
-            HoistRegistry::print_color(&binary.location.display().to_string(), Color::Cyan, true)?;
-        }
+    /// Writes the [HoistRegistry] to the context's [RegistryStore], the
+    /// registry file in the context's home by default, journaling the
+    /// changes to [crate::journal].
+    ///
+    /// The changes are those since the registry was last loaded or saved
+    /// through the context. The store is only loaded again to find them if
+    /// it wasn't.
+    ///
+    /// [RegistryStore]: crate::store::RegistryStore
+    #[instrument(skip(self))]
+    pub fn save(&self, ctx: &HoistContext) -> Result<()> {
+        let previous = match ctx.take_snapshot() {
+            Some(previous) => previous,
+            None => ctx.store().load().unwrap_or_default(),
+        };
+        ctx.store().save(self)?;
+        crate::journal::record(ctx, &previous, self);
+        ctx.set_snapshot(self);
         Ok(())
     }
 
@@ -237,161 +497,33 @@ impl HoistRegistry {
         write!(&mut stdout, "{}{}", text, newline)?;
         Ok(())
     }
-
-    /// Hoists binaries from the hoist toml registry into scope.
-    #[instrument(skip(binaries))]
-    pub fn hoist(binaries: Vec<String>, quiet: bool) -> Result<()> {
-        HoistRegistry::setup(quiet)?;
-        let registry = HoistRegistry::new()?;
-
-        // If binaries not contained in the global registry,
-        // check the local build path to see if we want to hoist a local
-        // bin.
-        let mut registered = registry.binaries;
-        if !registered.iter().any(|b| binaries.contains(&b.name)) {
-            // todo(refcell): fuzzy match binaries in case of mispellings
-            //                if found, prompt the user with an inquire confirm
-            let hoisted = crate::project::Project::from_current_dir()?.hoisted_binaries()?;
-            hoisted.into_iter().for_each(|hb| {
-                let _ = registered.insert(hb);
-            });
-        }
-
-        // If the user provided no binaries, use an inquire select to prompt
-        // the user to select which binaries to hoist.
-        let mut selected;
-        if binaries.is_empty() {
-            selected = HoistRegistry::multiselect_registered(&registered, quiet)?;
-        }
-        // If no tty, hoist all binaries, including redundant ones.
-        else if !std::io::stdout().is_terminal() {
-            selected = registered
-                .into_iter()
-                .filter(|b| binaries.contains(&b.name))
-                .collect();
-        }
-        // Otherwise, we want to convert the binaries to a set of de-duplicated hoisted binaries.
-        else {
-            let found: Vec<_> = registered
-                .into_iter()
-                .filter(|b| binaries.contains(&b.name))
-                .collect();
-            // Get the non-duplicate binaries from the found binaries.
-            let non_duplicate = found
-                .iter()
-                .filter(|b| {
-                    found
-                        .iter()
-                        .filter(|b2| b2.name == b.name)
-                        .collect::<Vec<_>>()
-                        .len()
-                        == 1
-                })
-                .cloned()
-                .collect::<Vec<_>>();
-
-            HoistRegistry::print_color(
-                &format!(
-                    "Found {} conflicting registered binaries, opening a multiselect prompt to select which binaries to hoist.",
-                    found.len()
-                ),
-                Color::Yellow,
-                true,
-            )?;
-            selected = HoistRegistry::multiselect_registered(
-                &HashSet::from_iter(found.into_iter()),
-                quiet,
-            )?;
-            // Extend the selected binaries with the non-duplicate binaries.
-            selected.extend(non_duplicate);
-        }
-
-        selected
-            .iter()
-            .try_for_each(|b| match b.copy_to_current_dir() {
-                Ok(_) => {
-                    if !quiet {
-                        HoistRegistry::print_color("Successfully hoisted ", Color::Green, false)?;
-                        HoistRegistry::print_color(&b.name, Color::Magenta, true)?;
-                    }
-                    Ok(())
-                }
-                Err(e) => Err(e),
-            })
-    }
-
-    /// Prompts the user for a list of hoisted binaries with a [MultiSelect].
-    #[instrument(skip(registered, quiet))]
-    pub fn multiselect_registered(
-        registered: &HashSet<HoistedBinary>,
-        quiet: bool,
-    ) -> Result<Vec<HoistedBinary>> {
-        let options = registered
-            .iter()
-            .map(|b| format!("{} ({})", b.name, b.location.display()))
-            .collect();
-        let validator = move |a: &[ListOption<&String>]| {
-            if !quiet {
-                tracing::debug!("Received binary input selection: {:?}", a);
-            }
-            Ok(Validation::Valid)
-        };
-        let formatter: MultiOptionFormatter<'_, String> =
-            &|a| format!("{} different bins", a.len());
-        match MultiSelect::new("Which binaries would you like to hoist?", options)
-            .with_validator(validator)
-            .with_formatter(formatter)
-            .prompt()
-        {
-            Ok(choices) => {
-                // The maximum hoisted binary size is how many binaries are registered.
-                let mut res = Vec::with_capacity(choices.len());
-                for c in choices {
-                    let mut split = c.split_whitespace();
-                    let name = split.next().ok_or(anyhow::anyhow!(
-                        "Failed to parse selected binary name: {}",
-                        c
-                    ))?;
-                    let location = split.next().ok_or(anyhow::anyhow!(
-                        "Failed to parse selected binary location: {}",
-                        c
-                    ))?;
-                    let location = location
-                        .trim_start_matches('(')
-                        .trim_end_matches(')')
-                        .to_string();
-                    res.push(HoistedBinary::new(
-                        name.to_string(),
-                        PathBuf::from(location),
-                    ));
-                }
-                Ok(res)
-            }
-            Err(e) => {
-                if !quiet {
-                    HoistRegistry::print_color(
-                        "Failed to hoist selected binary",
-                        Color::Red,
-                        true,
-                    )?;
-                }
-                Err(e.into())
-            }
-        }
-    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use serial_test::serial;
+    use crate::binaries::{Registration, RegistrationSource};
+    use crate::hoist::HoistOptions;
+    use crate::install::InstallOptions;
+    use crate::remove::NukeOptions;
+    use std::io::Read;
     use std::os::unix::prelude::OpenOptionsExt;
     use tempfile::TempDir;
 
-    fn setup_test(tempdir: &TempDir, t: &str) -> PathBuf {
+    /// Registers the project's binaries where they were built, bypassing the
+    /// content store.
+    pub(crate) fn install_linked(ctx: &HoistContext, project: &Path) {
+        let options = InstallOptions {
+            no_store: true,
+            ..Default::default()
+        };
+        HoistRegistry::install_with(ctx, Some(project), Vec::new(), options, false).unwrap();
+    }
+
+    #[allow(clippy::suspicious_open_options)]
+    pub(crate) fn setup_test(tempdir: &TempDir, t: &str) -> PathBuf {
         let test_dir = tempdir.path().join(t);
         std::fs::create_dir(&test_dir).unwrap();
-        std::env::set_current_dir(&test_dir).unwrap();
 
         let bash_file = test_dir.join(".bashrc");
         std::fs::File::create(&bash_file).unwrap();
@@ -415,23 +547,20 @@ mod tests {
             .unwrap();
         opts.sync_all().unwrap();
 
-        std::env::set_var("HOME", &test_dir);
-
         test_dir
     }
 
     #[test]
-    #[serial]
     fn test_setup() {
-        let original_home = std::env::var_os("HOME").unwrap();
         let tempdir = tempfile::tempdir().unwrap();
         let test_tempdir = setup_test(&tempdir, "test_setup");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
 
-        HoistRegistry::setup(false).unwrap();
+        HoistRegistry::setup_with(&ctx, false).unwrap();
 
-        assert_eq!(HoistRegistry::new().unwrap(), HoistRegistry::default());
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), HoistRegistry::default());
 
-        let hook_file = HoistRegistry::hook_identifier().unwrap();
+        let hook_file = ctx.hook_identifier();
         assert!(std::path::Path::new(&hook_file).exists());
         let mut file = std::fs::OpenOptions::new()
             .read(true)
@@ -452,119 +581,147 @@ mod tests {
         } else {
             assert_eq!(bash_file_contents, INSTALL_BASH_FUNCTION);
         }
-
-        std::env::set_current_dir(&original_home).unwrap();
-        std::env::set_var("HOME", original_home);
     }
 
     #[test]
-    #[serial]
-    fn test_install() {
-        let original_home = std::env::var_os("HOME").unwrap();
-        let tempdir = tempfile::tempdir().unwrap();
-        let test_tempdir = setup_test(&tempdir, "test_install");
-
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-
+    fn test_sorted_serialization() {
+        let names = ["zeta", "alpha", "mid", "beta", "alpha"];
+        let registry = |order: &mut dyn Iterator<Item = (usize, &&str)>| HoistRegistry {
+            binaries: order
+                .map(|(i, name)| {
+                    HoistedBinary::new(*name, PathBuf::from(format!("/p{}/{}", i, name)))
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let forward = toml::to_string(&registry(&mut names.iter().enumerate())).unwrap();
+        let backward = toml::to_string(&registry(&mut names.iter().enumerate().rev())).unwrap();
+        assert_eq!(forward, backward);
+        let locations: Vec<_> = forward
+            .lines()
+            .filter_map(|l| l.strip_prefix("location = "))
+            .collect();
         assert_eq!(
-            HoistRegistry::new().unwrap(),
-            HoistRegistry {
-                binaries: HashSet::from([
-                    HoistedBinary::new(
-                        "binary1".to_string(),
-                        test_tempdir
-                            .join("target/release/binary1")
-                            .canonicalize()
-                            .unwrap()
-                    ),
-                    HoistedBinary::new(
-                        "binary2".to_string(),
-                        test_tempdir
-                            .join("target/release/binary2")
-                            .canonicalize()
-                            .unwrap()
-                    ),
-                ])
-            }
+            locations,
+            [
+                "\"/p1/alpha\"",
+                "\"/p4/alpha\"",
+                "\"/p3/beta\"",
+                "\"/p2/mid\"",
+                "\"/p0/zeta\""
+            ]
         );
+    }
 
-        std::env::set_current_dir(&original_home).unwrap();
-        std::env::set_var("HOME", original_home);
+    #[test]
+    fn test_reregister() {
+        let mut registry = HoistRegistry::default();
+        let location = PathBuf::from("/p/target/debug/tool");
+        registry.insert(HoistedBinary {
+            profile: Some("debug".to_string()),
+            tags: vec!["old".to_string()],
+            hoist_count: 2,
+            registration: Some(Registration::now(RegistrationSource::Hook, "/p".into())),
+            ..HoistedBinary::new("tool", location.clone())
+        });
+        // The same build registered again, now with another profile and tags,
+        // replaces the entry instead of adding a second one.
+        registry.insert(HoistedBinary {
+            profile: Some("dev".to_string()),
+            tags: vec!["new".to_string()],
+            registration: Some(Registration::now(RegistrationSource::Manual, "/p".into())),
+            ..HoistedBinary::new("tool", location)
+        });
+        assert_eq!(registry.binaries.len(), 1);
+        let b = registry.latest("tool").unwrap();
+        assert_eq!(b.profile.as_deref(), Some("dev"));
+        assert_eq!(b.tags, vec!["new".to_string(), "old".to_string()]);
+        assert_eq!(b.hoist_count, 2);
+        assert_eq!(
+            b.registration.as_ref().map(|r| r.source),
+            Some(RegistrationSource::Manual)
+        );
     }
 
     #[test]
-    #[serial]
-    fn test_multiple_installs() {
-        let original_home = std::env::var_os("HOME").unwrap();
+    fn test_upgrade_pre_hook() {
         let tempdir = tempfile::tempdir().unwrap();
-        let test_tempdir = setup_test(&tempdir, "test_multiple_installs");
-
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_dir_with(&ctx, true).unwrap();
+        std::fs::write(ctx.hook_identifier(), "hook").unwrap();
+        let bashrc = tempdir.path().join(".bashrc");
+        std::fs::write(&bashrc, LEGACY_BASH_FUNCTION).unwrap();
+        HoistRegistry::create_pre_hook_with(&ctx, false, true).unwrap();
         assert_eq!(
-            HoistRegistry::new().unwrap(),
-            HoistRegistry {
-                binaries: HashSet::from([
-                    HoistedBinary::new(
-                        "binary1".to_string(),
-                        test_tempdir
-                            .join("target/release/binary1")
-                            .canonicalize()
-                            .unwrap()
-                    ),
-                    HoistedBinary::new(
-                        "binary2".to_string(),
-                        test_tempdir
-                            .join("target/release/binary2")
-                            .canonicalize()
-                            .unwrap()
-                    ),
-                ])
-            }
+            std::fs::read_to_string(&bashrc).unwrap(),
+            INSTALL_BASH_FUNCTION
         );
-
-        std::env::set_current_dir(&original_home).unwrap();
-        std::env::set_var("HOME", original_home);
     }
 
     #[test]
-    #[serial]
-    fn test_hoist() {
-        let original_home = std::env::var_os("HOME").unwrap();
+    fn test_load_recovers_corrupt_registry() {
         let tempdir = tempfile::tempdir().unwrap();
-        let test_tempdir = setup_test(&tempdir, "test_hoist");
-
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-
-        HoistRegistry::hoist(vec!["binary1".to_string()], false).unwrap();
-        HoistRegistry::hoist(vec!["binary1".to_string()], false).unwrap();
-
-        let binary1 = std::env::current_dir().unwrap().join("binary1");
-        assert!(std::path::Path::new(&binary1).exists());
-        let binary2 = std::env::current_dir().unwrap().join("binary2");
-        assert!(!std::path::Path::new(&binary2).exists());
-
-        std::env::set_current_dir(&original_home).unwrap();
-        std::env::set_var("HOME", original_home);
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        ctx.store().save(&registry).unwrap();
+        let mut toml = std::fs::read_to_string(ctx.registry_path()).unwrap();
+        toml.push_str("\n[[binaries]]\nname = \"b\"\n");
+        std::fs::write(ctx.registry_path(), &toml).unwrap();
+
+        assert!(HoistRegistry::load(&ctx.with_strict(true)).is_err());
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), registry);
+        let quarantined = ctx.hoist_dir().join("registry.toml.corrupt");
+        assert_eq!(std::fs::read_to_string(quarantined).unwrap(), toml);
+        assert_eq!(ctx.store().load().unwrap(), registry);
     }
 
     #[test]
-    #[serial]
-    fn test_nuke() {
-        let original_home = std::env::var_os("HOME").unwrap();
+    fn test_dry_run() {
         let tempdir = tempfile::tempdir().unwrap();
-        let test_tempdir = setup_test(&tempdir, "test_nuke");
+        let test_tempdir = setup_test(&tempdir, "test_dry_run");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
 
-        HoistRegistry::install(Some(&test_tempdir), Vec::new(), false).unwrap();
-
-        HoistRegistry::nuke(false).unwrap();
-
-        assert_eq!(HoistRegistry::new().unwrap(), HoistRegistry::default());
+        let dry_install = InstallOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        HoistRegistry::install_with(&ctx, Some(&test_tempdir), vec![], dry_install, false).unwrap();
+        assert!(!ctx.hoist_dir().exists());
+        HoistRegistry::register_paths(
+            &ctx,
+            vec![test_tempdir.join("target/release/binary1")],
+            None,
+            dry_install,
+            false,
+        )
+        .unwrap();
+        assert!(!ctx.hoist_dir().exists());
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            true,
+        )
+        .unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let dry_hoist = HoistOptions {
+            to: Some(PathBuf::from("bin")),
+            dry_run: true,
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec!["binary1".to_string()], dry_hoist, false).unwrap();
+        assert!(!test_tempdir.join("bin").exists());
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), registry);
 
-        std::env::set_current_dir(&original_home).unwrap();
-        std::env::set_var("HOME", original_home);
+        let dry_nuke = NukeOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        HoistRegistry::nuke_with(&ctx, dry_nuke, false).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), registry);
     }
 }
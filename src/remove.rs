@@ -0,0 +1,434 @@
+//! Remove
+//!
+//! Unregistering binaries with `cargo hoist remove`, and clearing the
+//! registry with `cargo hoist nuke`.
+
+use anyhow::Result;
+use inquire::Confirm;
+use is_terminal::IsTerminal;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// Options selecting what [HoistRegistry::nuke_with] removes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NukeOptions {
+    /// Only remove binaries whose name matches this glob pattern, e.g. `cargo-*`.
+    pub pattern: Option<String>,
+    /// Only remove binaries registered from projects under this path.
+    pub project: Option<PathBuf>,
+    /// Also remove pinned binaries.
+    pub force: bool,
+    /// Remove without prompting for confirmation.
+    pub yes: bool,
+    /// Print what would be removed without changing the registry.
+    pub dry_run: bool,
+}
+
+/// Options selecting what [HoistRegistry::remove] unregisters.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemoveOptions {
+    /// Remove binaries whose name matches any of these names, aliases,
+    /// `name@version` specs or glob patterns, e.g. `my-tool-*`.
+    pub patterns: Vec<String>,
+    /// Only remove binaries registered from projects under this path, or
+    /// without patterns, every binary registered from them.
+    pub project: Option<PathBuf>,
+    /// Also remove pinned binaries.
+    pub force: bool,
+    /// Print what would be removed without changing the registry.
+    pub dry_run: bool,
+}
+
+impl HoistRegistry {
+    /// Removes the binaries selected by the [RemoveOptions], whose project
+    /// path must already be resolved, along with the aliases of names left
+    /// without builds. Returns the removed binaries, and the pinned binaries
+    /// kept because `force` is not set.
+    pub fn remove_matching(
+        &mut self,
+        options: &RemoveOptions,
+    ) -> (Vec<HoistedBinary>, Vec<HoistedBinary>) {
+        let patterns: Vec<_> = options
+            .patterns
+            .iter()
+            .map(|p| self.resolve_alias(p))
+            .collect();
+        let (mut removed, mut pinned): (Vec<_>, Vec<_>) = self
+            .binaries
+            .iter()
+            .filter(|b| {
+                patterns.is_empty()
+                    || patterns
+                        .iter()
+                        .any(|p| b.matches(p) || crate::utils::glob_match(p, &b.name))
+            })
+            .filter(|b| {
+                options
+                    .project
+                    .as_ref()
+                    .is_none_or(|p| b.project().is_some_and(|bp| bp.starts_with(p)))
+            })
+            .cloned()
+            .partition(|b| options.force || !b.pinned);
+        for b in &removed {
+            self.binaries.remove(b);
+        }
+        let remaining: HashSet<_> = self.binaries.iter().map(|b| b.name.clone()).collect();
+        self.aliases.retain(|_, target| remaining.contains(target));
+        let by_identity = |a: &HoistedBinary, b: &HoistedBinary| {
+            (&a.name, &a.location).cmp(&(&b.name, &b.location))
+        };
+        removed.sort_by(by_identity);
+        pinned.sort_by(by_identity);
+        (removed, pinned)
+    }
+
+    /// Nukes the hoist toml registry, removing every binary without prompting.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::nuke_with`"
+    )]
+    #[instrument]
+    pub fn nuke(quiet: bool) -> Result<()> {
+        let options = NukeOptions {
+            yes: true,
+            ..Default::default()
+        };
+        HoistRegistry::nuke_with(&HoistContext::from_env()?, options, quiet)
+    }
+
+    /// Nukes the registry of the context.
+    /// This removes the binaries selected by the [NukeOptions], or every
+    /// binary, after taking a backup that `cargo hoist restore` can recover
+    /// the registry from. Pinned binaries are kept unless `force` is set, and
+    /// aliases are removed along with the last build they point at.
+    ///
+    /// Prompts for confirmation when run in a terminal, unless `yes` is set.
+    #[instrument]
+    pub fn nuke_with(ctx: &HoistContext, options: NukeOptions, quiet: bool) -> Result<()> {
+        if options.dry_run {
+            return HoistRegistry::nuke_dry_run(ctx, options, quiet);
+        }
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let _lock = HoistRegistry::lock(ctx)?;
+        let mut registry = HoistRegistry::load(ctx)?;
+        let selective = options.pattern.is_some() || options.project.is_some();
+        let (nuked, pinned) = registry.nuke_selection(ctx, &options);
+        if !quiet && !pinned.is_empty() {
+            HoistRegistry::print_color(
+                &format!(
+                    "Keeping {} pinned builds, pass --force to remove them",
+                    pinned.len()
+                ),
+                Color::Yellow,
+                true,
+            )?;
+        }
+        if nuked.is_empty() && (selective || registry.aliases.is_empty()) {
+            if !quiet && selective {
+                HoistRegistry::print_color("No registered binaries match", Color::Yellow, true)?;
+            }
+            return Ok(());
+        }
+        if !options.yes
+            && std::io::stdin().is_terminal()
+            && !Confirm::new(&format!("Remove {} registered builds?", nuked.len()))
+                .with_default(false)
+                .prompt()?
+        {
+            return Ok(());
+        }
+
+        let backup = crate::backup::backup(ctx, crate::backup::KEEP)?;
+        if !quiet {
+            HoistRegistry::print_color("Backed up the registry to ", Color::Green, false)?;
+            HoistRegistry::print_color(&backup.display().to_string(), Color::Cyan, true)?;
+        }
+        registry.remove_nuked(&nuked, selective);
+        if !quiet && selective {
+            let mut nuked = nuked;
+            nuked.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+            for b in &nuked {
+                HoistRegistry::print_color("Removed ", Color::Yellow, false)?;
+                HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(
+                    &format!(" ({})", b.location.display()),
+                    Color::Cyan,
+                    true,
+                )?;
+            }
+        }
+        registry.save(ctx)?;
+        Ok(())
+    }
+
+    /// Prints what [HoistRegistry::nuke_with] would remove.
+    fn nuke_dry_run(ctx: &HoistContext, options: NukeOptions, quiet: bool) -> Result<()> {
+        let mut registry = HoistRegistry::load_dry_run(ctx)?;
+        let selective = options.pattern.is_some() || options.project.is_some();
+        let (mut nuked, pinned) = registry.nuke_selection(ctx, &options);
+        let aliases = registry.aliases.clone();
+        registry.remove_nuked(&nuked, selective);
+        if quiet {
+            return Ok(());
+        }
+        nuked.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+        for b in &nuked {
+            HoistRegistry::print_color("Would remove ", Color::Yellow, false)?;
+            HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+            HoistRegistry::print_color(&format!(" ({})", b.location.display()), Color::Cyan, true)?;
+        }
+        for alias in aliases
+            .keys()
+            .filter(|a| !registry.aliases.contains_key(*a))
+        {
+            HoistRegistry::print_color("Would remove alias ", Color::Yellow, false)?;
+            HoistRegistry::print_color(alias, Color::Magenta, true)?;
+        }
+        if !pinned.is_empty() {
+            HoistRegistry::print_color(
+                &format!("Would keep {} pinned builds", pinned.len()),
+                Color::Yellow,
+                true,
+            )?;
+        }
+        if nuked.is_empty() && aliases == registry.aliases {
+            HoistRegistry::print_color("Nothing would be removed", Color::Green, true)?;
+        }
+        Ok(())
+    }
+
+    /// Splits the binaries selected by the [NukeOptions] into those removed
+    /// and the pinned ones kept.
+    fn nuke_selection(
+        &self,
+        ctx: &HoistContext,
+        options: &NukeOptions,
+    ) -> (Vec<HoistedBinary>, Vec<HoistedBinary>) {
+        let project = options.project.as_ref().map(|p| {
+            let p = ctx.resolve(p);
+            p.canonicalize().unwrap_or(p)
+        });
+        self.binaries
+            .iter()
+            .filter(|b| {
+                options
+                    .pattern
+                    .as_ref()
+                    .is_none_or(|p| crate::utils::glob_match(p, &b.name))
+            })
+            .filter(|b| {
+                project
+                    .as_ref()
+                    .is_none_or(|p| b.project().is_some_and(|bp| bp.starts_with(p)))
+            })
+            .cloned()
+            .partition(|b| options.force || !b.pinned)
+    }
+
+    /// Removes the `nuked` binaries, along with the aliases left pointing at
+    /// no binary. A `selective` nuke keeps aliases of binaries it didn't touch.
+    fn remove_nuked(&mut self, nuked: &[HoistedBinary], selective: bool) {
+        let nuked_names: HashSet<_> = nuked.iter().map(|b| b.name.clone()).collect();
+        for b in nuked {
+            self.binaries.remove(b);
+        }
+        let remaining: HashSet<_> = self.binaries.iter().map(|b| b.name.clone()).collect();
+        self.aliases.retain(|_, target| {
+            remaining.contains(target) || (selective && !nuked_names.contains(target))
+        });
+    }
+
+    /// Unregisters the binaries selected by the [RemoveOptions], see
+    /// [HoistRegistry::remove_matching]. Their stored copies are left for
+    /// `cargo hoist store gc`, and `cargo hoist undo` registers them again.
+    ///
+    /// Fails if no registered binary matches.
+    #[instrument(skip(ctx))]
+    pub fn remove(ctx: &HoistContext, options: RemoveOptions, quiet: bool) -> Result<()> {
+        let (_lock, mut registry) = HoistRegistry::load_for_update(ctx, options.dry_run, quiet)?;
+        let options = RemoveOptions {
+            project: options.project.as_ref().map(|p| {
+                let p = ctx.resolve(p);
+                p.canonicalize().unwrap_or(p)
+            }),
+            ..options
+        };
+        let (removed, pinned) = registry.remove_matching(&options);
+        if !quiet {
+            let verb = match options.dry_run {
+                true => "Would remove ",
+                false => "Removed ",
+            };
+            for b in &removed {
+                HoistRegistry::print_color(verb, Color::Yellow, false)?;
+                HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+                HoistRegistry::print_color(
+                    &format!(" ({})", b.location.display()),
+                    Color::Cyan,
+                    true,
+                )?;
+            }
+            if !pinned.is_empty() {
+                HoistRegistry::print_color(
+                    &format!(
+                        "Keeping {} pinned builds, pass --force to remove them",
+                        pinned.len()
+                    ),
+                    Color::Yellow,
+                    true,
+                )?;
+            }
+        }
+        if removed.is_empty() {
+            if !pinned.is_empty() {
+                return Ok(());
+            }
+            match &options.project {
+                Some(project) if options.patterns.is_empty() => {
+                    anyhow::bail!("No registered binaries were built in {}", project.display())
+                }
+                _ => anyhow::bail!(
+                    "No registered binaries match {}",
+                    options.patterns.join(", ")
+                ),
+            }
+        }
+        match options.dry_run {
+            true => Ok(()),
+            false => registry.save(ctx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{Registration, RegistrationSource};
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+
+    #[test]
+    fn test_remove() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join(".bashrc"), "").unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let built = |name: &str, project: &str| HoistedBinary {
+            registration: Some(Registration::now(
+                RegistrationSource::Manual,
+                tempdir.path().join(project),
+            )),
+            ..HoistedBinary::new(name, tempdir.path().join(project).join(name))
+        };
+        let mut registry = HoistRegistry::default();
+        registry.insert(built("my-tool-a", "tools"));
+        registry.insert(built("my-tool-b", "tools"));
+        registry.insert(built("other", "tools"));
+        registry.insert(HoistedBinary {
+            pinned: true,
+            ..built("kept", "tools")
+        });
+        registry.insert(built("app", "app"));
+        registry.aliases.insert("a".to_string(), "app".to_string());
+        registry
+            .aliases
+            .insert("o".to_string(), "other".to_string());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        registry.save(&ctx).unwrap();
+
+        let remove_with = |patterns: &[&str], project: Option<&str>, dry_run| {
+            let options = RemoveOptions {
+                patterns: patterns.iter().map(|p| p.to_string()).collect(),
+                project: project.map(PathBuf::from),
+                force: false,
+                dry_run,
+            };
+            HoistRegistry::remove(&ctx, options, true)
+        };
+        let remove = |patterns: &[&str], project| remove_with(patterns, project, false);
+        let names = || {
+            let mut names: Vec<_> = HoistRegistry::load(&ctx)
+                .unwrap()
+                .binaries
+                .into_iter()
+                .map(|b| b.name)
+                .collect();
+            names.sort();
+            names
+        };
+        remove_with(&["my-tool-*", "a"], None, true).unwrap();
+        assert_eq!(names().len(), 5);
+        assert!(remove_with(&["missing"], None, true).is_err());
+        remove(&["my-tool-*", "a"], None).unwrap();
+        assert_eq!(names(), ["kept", "other"]);
+        let aliases = HoistRegistry::load(&ctx).unwrap().aliases;
+        assert_eq!(aliases.keys().collect::<Vec<_>>(), ["o"]);
+        assert!(remove(&["missing"], None).is_err());
+
+        remove(&[], Some("tools")).unwrap();
+        assert_eq!(names(), ["kept"]);
+        assert!(HoistRegistry::load(&ctx).unwrap().aliases.is_empty());
+        assert!(remove(&[], Some("app")).is_err());
+    }
+
+    #[test]
+    fn test_nuke() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_nuke");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+
+        HoistRegistry::alias(&ctx, "two", "binary2", true).unwrap();
+        let nuke = |options: NukeOptions| HoistRegistry::nuke_with(&ctx, options, true).unwrap();
+        nuke(NukeOptions {
+            pattern: Some("*2".to_string()),
+            project: Some(test_tempdir.join("elsewhere")),
+            ..Default::default()
+        });
+        assert_eq!(HoistRegistry::load(&ctx).unwrap().binaries.len(), 2);
+        nuke(NukeOptions {
+            pattern: Some("*2".to_string()),
+            project: Some(test_tempdir.clone()),
+            ..Default::default()
+        });
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert!(registry.latest("binary2").is_none());
+        assert!(registry.aliases.is_empty());
+        crate::backup::restore(&ctx, None, true).unwrap();
+
+        HoistRegistry::pin(&ctx, "binary1", true).unwrap();
+        nuke(NukeOptions::default());
+        let kept = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(kept.binaries.len(), 1);
+        assert!(kept.latest("binary1").unwrap().pinned);
+
+        HoistRegistry::nuke_with(
+            &ctx,
+            NukeOptions {
+                force: true,
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(HoistRegistry::load(&ctx).unwrap(), HoistRegistry::default());
+        let backups = crate::backup::list(&ctx).unwrap();
+        assert_eq!(backups.len(), 3);
+        crate::backup::restore(&ctx, Some(&backups[0]), true).unwrap();
+        assert_eq!(HoistRegistry::load(&ctx).unwrap().binaries.len(), 2);
+    }
+}
@@ -0,0 +1,511 @@
+//! Software Bill of Materials
+//!
+//! Renders CycloneDX and SPDX json documents for a registered binary from its
+//! recorded `Cargo.lock` snapshot.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::registry::HoistRegistry;
+
+/// The SBOM document format.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 json
+    #[default]
+    #[value(name = "cyclonedx")]
+    CycloneDx,
+    /// SPDX 2.3 json
+    Spdx,
+}
+
+/// Everything known about a registered binary's dependency graph.
+#[derive(Debug, Clone)]
+pub struct Inventory<'a> {
+    /// The registered binary
+    pub binary: &'a HoistedBinary,
+    /// The sha256 digest of the binary
+    pub digest: String,
+    /// The locked packages reachable from the binary's package, root first
+    pub packages: Vec<&'a LockedPackage>,
+    /// The parsed lockfile snapshot
+    pub lockfile: &'a Lockfile,
+    /// The declared license of each package, if known
+    pub licenses: Vec<Option<String>>,
+}
+
+impl<'a> Inventory<'a> {
+    /// Builds the inventory for `binary` from its parsed lockfile snapshot.
+    pub fn new(
+        binary: &'a HoistedBinary,
+        lockfile: &'a Lockfile,
+        root: &str,
+        license: impl Fn(&LockedPackage) -> Option<String>,
+    ) -> Result<Self> {
+        let digest = crate::hash::hash_file(&binary.location, HashAlgorithm::Sha256)?;
+        let packages = lockfile.reachable(root);
+        let licenses = packages.iter().map(|p| license(p)).collect();
+        Ok(Self {
+            binary,
+            digest,
+            packages,
+            lockfile,
+            licenses,
+        })
+    }
+
+    /// The locked dependencies of `package` that are part of the inventory.
+    fn dependencies(&self, package: &LockedPackage) -> Vec<&'a LockedPackage> {
+        package
+            .dependencies
+            .iter()
+            .filter_map(|d| self.lockfile.resolve(d))
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDx {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: CycloneDxMetadata,
+    components: Vec<CycloneDxComponent>,
+    dependencies: Vec<CycloneDxDependency>,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxMetadata {
+    timestamp: String,
+    tools: Vec<CycloneDxTool>,
+    component: CycloneDxComponent,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxTool {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: String,
+    version: String,
+    purl: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<CycloneDxHash>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<CycloneDxLicense>,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxHash {
+    alg: &'static str,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxLicense {
+    expression: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxDependency {
+    #[serde(rename = "ref")]
+    reference: String,
+    depends_on: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Spdx {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    name: String,
+    version_info: String,
+    download_location: String,
+    license_concluded: &'static str,
+    license_declared: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<SpdxChecksum>,
+    external_refs: Vec<SpdxExternalRef>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum {
+    algorithm: &'static str,
+    checksum_value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+/// Renders the inventory as a CycloneDX 1.5 json document.
+pub fn cyclonedx(inventory: &Inventory<'_>, timestamp: &str) -> Result<String> {
+    let component = |(i, p): (usize, &&LockedPackage)| {
+        let mut hashes = vec![];
+        if let Some(checksum) = &p.checksum {
+            hashes.push(CycloneDxHash {
+                alg: "SHA-256",
+                content: checksum.clone(),
+            });
+        }
+        CycloneDxComponent {
+            kind: "library",
+            bom_ref: p.purl(),
+            name: p.name.clone(),
+            version: p.version.clone(),
+            purl: p.purl(),
+            hashes,
+            licenses: inventory.licenses[i]
+                .iter()
+                .map(|l| CycloneDxLicense {
+                    expression: l.clone(),
+                })
+                .collect(),
+        }
+    };
+    let mut components: Vec<_> = inventory
+        .packages
+        .iter()
+        .enumerate()
+        .map(component)
+        .collect();
+    let mut root = components.remove(0);
+    root.kind = "application";
+    root.hashes = vec![CycloneDxHash {
+        alg: "SHA-256",
+        content: inventory.digest.clone(),
+    }];
+    let dependencies = inventory
+        .packages
+        .iter()
+        .map(|p| CycloneDxDependency {
+            reference: p.purl(),
+            depends_on: inventory.dependencies(p).iter().map(|d| d.purl()).collect(),
+        })
+        .collect();
+    let bom = CycloneDx {
+        bom_format: "CycloneDX",
+        spec_version: "1.5",
+        version: 1,
+        metadata: CycloneDxMetadata {
+            timestamp: timestamp.to_string(),
+            tools: vec![CycloneDxTool {
+                name: env!("CARGO_PKG_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+            }],
+            component: root,
+        },
+        components,
+        dependencies,
+    };
+    crate::json::to_string_pretty(&bom)
+}
+
+/// Renders the inventory as an SPDX 2.3 json document.
+pub fn spdx(inventory: &Inventory<'_>, timestamp: &str) -> Result<String> {
+    let id = |p: &LockedPackage| {
+        let raw = format!("SPDXRef-Package-{}-{}", p.name, p.version);
+        raw.chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    true => c,
+                    false => '-',
+                },
+            )
+            .collect::<String>()
+    };
+    let packages = inventory
+        .packages
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let mut checksums = vec![];
+            if let Some(checksum) = &p.checksum {
+                checksums.push(SpdxChecksum {
+                    algorithm: "SHA256",
+                    checksum_value: checksum.clone(),
+                });
+            }
+            if i == 0 {
+                checksums.push(SpdxChecksum {
+                    algorithm: "SHA256",
+                    checksum_value: inventory.digest.clone(),
+                });
+            }
+            SpdxPackage {
+                spdx_id: id(p),
+                name: p.name.clone(),
+                version_info: p.version.clone(),
+                download_location: match p.is_crates_io() {
+                    true => format!(
+                        "https://crates.io/api/v1/crates/{}/{}/download",
+                        p.name, p.version
+                    ),
+                    false => "NOASSERTION".to_string(),
+                },
+                license_concluded: "NOASSERTION",
+                license_declared: inventory.licenses[i]
+                    .clone()
+                    .unwrap_or_else(|| "NOASSERTION".to_string()),
+                checksums,
+                external_refs: vec![SpdxExternalRef {
+                    reference_category: "PACKAGE-MANAGER",
+                    reference_type: "purl",
+                    reference_locator: p.purl(),
+                }],
+            }
+        })
+        .collect();
+    let mut relationships = vec![SpdxRelationship {
+        spdx_element_id: "SPDXRef-DOCUMENT".to_string(),
+        relationship_type: "DESCRIBES",
+        related_spdx_element: id(inventory.packages[0]),
+    }];
+    for p in &inventory.packages {
+        for d in inventory.dependencies(p) {
+            relationships.push(SpdxRelationship {
+                spdx_element_id: id(p),
+                relationship_type: "DEPENDS_ON",
+                related_spdx_element: id(d),
+            });
+        }
+    }
+    let doc = Spdx {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: inventory.binary.name.clone(),
+        document_namespace: format!(
+            "https://spdx.org/spdxdocs/{}-{}",
+            inventory.binary.name, inventory.digest
+        ),
+        creation_info: SpdxCreationInfo {
+            created: timestamp.to_string(),
+            creators: vec![format!(
+                "Tool: {}-{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )],
+        },
+        packages,
+        relationships,
+    };
+    crate::json::to_string_pretty(&doc)
+}
+
+/// Reads the declared license of a registry crate from the local cargo
+/// registry source cache, if it has been downloaded.
+pub fn cached_license(cargo_home: &Path, package: &LockedPackage) -> Option<String> {
+    let krate = format!("{}-{}", package.name, package.version);
+    std::fs::read_dir(cargo_home.join("registry/src"))
+        .ok()?
+        .flatten()
+        .map(|index| index.path().join(&krate).join("Cargo.toml"))
+        .find(|p| p.is_file())
+        .and_then(|p| manifest_license(&p))
+}
+
+/// Reads the `package.license` key of a `Cargo.toml`.
+fn manifest_license(manifest: &Path) -> Option<String> {
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(manifest).ok()?).ok()?;
+    manifest
+        .get("package")?
+        .get("license")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Prints or writes an SBOM for the latest registered build of `binary`.
+#[instrument(skip(binary, output))]
+pub fn sbom(
+    ctx: &HoistContext,
+    binary: impl AsRef<str>,
+    format: SbomFormat,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    HoistRegistry::setup_with(ctx, true)?;
+    let registry = HoistRegistry::load(ctx)?;
+    let binary = registry
+        .latest(binary.as_ref())
+        .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?;
+    let registration = binary.registration.as_ref();
+    let digest = registration
+        .and_then(|r| r.lockfile.as_ref())
+        .ok_or(anyhow::anyhow!(
+            "No Cargo.lock snapshot is recorded for {}, run `cargo hoist register` in its project to record one",
+            binary.name
+        ))?;
+    let lockfile = Lockfile::parse(&crate::lockfile::load(ctx, digest)?)?;
+
+    // The binary's package is the workspace member owning the bin target,
+    // falling back to a package of the same name.
+    let project = binary.project();
+    let root_manifest = project
+        .as_deref()
+        .and_then(|p| crate::metadata::Metadata::load(p).ok())
+        .and_then(|m| {
+            m.packages
+                .into_iter()
+                .find(|p| p.targets.iter().any(|t| t.name == binary.name))
+        });
+    let root = root_manifest
+        .as_ref()
+        .map(|p| p.name.clone())
+        .unwrap_or_else(|| binary.name.clone());
+    let root_license = binary
+        .license
+        .clone()
+        .or_else(|| root_manifest.as_ref().and_then(|p| p.license.clone()));
+
+    let cargo_home = ctx.cargo_home();
+    let inventory = Inventory::new(binary, &lockfile, &root, |p| match p.source {
+        None if p.name == root => root_license.clone(),
+        _ => cached_license(&cargo_home, p),
+    })?;
+    let timestamp = crate::time::to_rfc3339(std::time::SystemTime::now());
+    let document = match format {
+        SbomFormat::CycloneDx => cyclonedx(&inventory, &timestamp)?,
+        SbomFormat::Spdx => spdx(&inventory, &timestamp)?,
+    };
+    match output {
+        Some(path) => std::fs::write(path, document + "\n")?,
+        None => println!("{}", document),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["serde"]
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "abc"
+"#;
+
+    fn inventory<'a>(binary: &'a HoistedBinary, lockfile: &'a Lockfile) -> Inventory<'a> {
+        Inventory::new(binary, lockfile, "app", |p| match p.name.as_str() {
+            "serde" => Some("MIT OR Apache-2.0".to_string()),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cyclonedx() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let location = tempdir.path().join("app");
+        std::fs::write(&location, "").unwrap();
+        let binary = HoistedBinary::new("app", location);
+        let lockfile = Lockfile::parse(LOCK).unwrap();
+        let bom = cyclonedx(&inventory(&binary, &lockfile), "2023-10-14T09:30:00Z").unwrap();
+        let bom = crate::json::parse(&bom).unwrap();
+
+        assert_eq!(bom["bomFormat"].as_str(), Some("CycloneDX"));
+        let root = &bom["metadata"]["component"];
+        assert_eq!(root["type"].as_str(), Some("application"));
+        assert_eq!(
+            root["hashes"][0]["content"].as_str(),
+            Some(crate::hash::hash_bytes(b"", HashAlgorithm::Sha256).as_str())
+        );
+        let serde = &bom["components"][0];
+        assert_eq!(serde["purl"].as_str(), Some("pkg:cargo/serde@1.0.0"));
+        assert_eq!(
+            serde["licenses"][0]["expression"].as_str(),
+            Some("MIT OR Apache-2.0")
+        );
+        assert_eq!(
+            bom["dependencies"][0]["dependsOn"][0].as_str(),
+            Some("pkg:cargo/serde@1.0.0")
+        );
+    }
+
+    #[test]
+    fn test_spdx() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let location = tempdir.path().join("app");
+        std::fs::write(&location, "").unwrap();
+        let binary = HoistedBinary::new("app", location);
+        let lockfile = Lockfile::parse(LOCK).unwrap();
+        let doc = spdx(&inventory(&binary, &lockfile), "2023-10-14T09:30:00Z").unwrap();
+        let doc = crate::json::parse(&doc).unwrap();
+
+        assert_eq!(doc["spdxVersion"].as_str(), Some("SPDX-2.3"));
+        let serde = &doc["packages"][1];
+        assert_eq!(
+            serde["SPDXID"].as_str(),
+            Some("SPDXRef-Package-serde-1.0.0")
+        );
+        assert_eq!(serde["licenseDeclared"].as_str(), Some("MIT OR Apache-2.0"));
+        assert_eq!(
+            serde["downloadLocation"].as_str(),
+            Some("https://crates.io/api/v1/crates/serde/1.0.0/download")
+        );
+        assert_eq!(
+            doc["packages"][0]["licenseDeclared"].as_str(),
+            Some("NOASSERTION")
+        );
+        let depends = &doc["relationships"][1];
+        assert_eq!(depends["relationshipType"].as_str(), Some("DEPENDS_ON"));
+        assert_eq!(
+            depends["relatedSpdxElement"].as_str(),
+            Some("SPDXRef-Package-serde-1.0.0")
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! Schema
+//!
+//! JSON Schema documents describing the files hoist reads and writes, so
+//! editors and validators can check them without depending on hoist itself.
+
+use anyhow::Result;
+use toml::{Table, Value};
+
+/// The JSON Schema dialect the documents are written in.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A file format with a published schema.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SchemaTarget {
+    /// The `~/.hoist/registry.toml` registry
+    #[default]
+    Registry,
+    /// The `~/.hoist/config.toml` user config
+    Config,
+    /// A project's `hoist.toml` manifest
+    Manifest,
+}
+
+fn string(description: &str) -> Value {
+    typed("string", description)
+}
+
+fn integer(description: &str) -> Value {
+    let mut schema = typed("integer", description);
+    schema
+        .as_table_mut()
+        .unwrap()
+        .insert("minimum".to_string(), Value::Integer(0));
+    schema
+}
+
+fn typed(ty: &str, description: &str) -> Value {
+    let mut t = Table::new();
+    t.insert("type".to_string(), Value::String(ty.to_string()));
+    t.insert(
+        "description".to_string(),
+        Value::String(description.to_string()),
+    );
+    Value::Table(t)
+}
+
+fn enumeration(description: &str, variants: &[&str]) -> Value {
+    let mut schema = string(description);
+    schema.as_table_mut().unwrap().insert(
+        "enum".to_string(),
+        Value::Array(
+            variants
+                .iter()
+                .map(|v| Value::String(v.to_string()))
+                .collect(),
+        ),
+    );
+    schema
+}
+
+fn array(description: &str, items: Value) -> Value {
+    let mut schema = typed("array", description);
+    schema
+        .as_table_mut()
+        .unwrap()
+        .insert("items".to_string(), items);
+    schema
+}
+
+fn map(description: &str, values: Value) -> Value {
+    let mut schema = typed("object", description);
+    schema
+        .as_table_mut()
+        .unwrap()
+        .insert("additionalProperties".to_string(), values);
+    schema
+}
+
+fn reference(def: &str) -> Value {
+    let mut t = Table::new();
+    t.insert(
+        "$ref".to_string(),
+        Value::String(format!("#/$defs/{}", def)),
+    );
+    Value::Table(t)
+}
+
+fn object(description: &str, properties: Vec<(&str, Value)>, required: &[&str]) -> Value {
+    let mut schema = typed("object", description);
+    let t = schema.as_table_mut().unwrap();
+    t.insert(
+        "properties".to_string(),
+        Value::Table(
+            properties
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        ),
+    );
+    if !required.is_empty() {
+        t.insert(
+            "required".to_string(),
+            Value::Array(
+                required
+                    .iter()
+                    .map(|r| Value::String(r.to_string()))
+                    .collect(),
+            ),
+        );
+    }
+    schema
+}
+
+fn document(title: &str, root: Value, defs: Vec<(&str, Value)>) -> Value {
+    let Value::Table(mut t) = root else {
+        unreachable!("schema roots are objects");
+    };
+    t.insert("$schema".to_string(), Value::String(DIALECT.to_string()));
+    t.insert("title".to_string(), Value::String(title.to_string()));
+    if !defs.is_empty() {
+        t.insert(
+            "$defs".to_string(),
+            Value::Table(defs.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+        );
+    }
+    Value::Table(t)
+}
+
+/// Returns the schema document for the given [SchemaTarget].
+pub fn schema(target: SchemaTarget) -> Value {
+    match target {
+        SchemaTarget::Registry => document(
+            "cargo-hoist registry",
+            object(
+                "The global registry of cargo-built binaries.",
+                vec![
+                    (
+                        "version",
+                        integer("The registry format version, upgraded on load."),
+                    ),
+                    (
+                        "binaries",
+                        array("The registered binaries.", reference("binary")),
+                    ),
+                    (
+                        "aliases",
+                        map(
+                            "Alternative names for registered binaries.",
+                            string("The name of the aliased binary."),
+                        ),
+                    ),
+                ],
+                &[],
+            ),
+            vec![
+                (
+                    "binary",
+                    object(
+                        "A registered binary, identified by its name and location.",
+                        vec![
+                            ("name", string("The binary name.")),
+                            (
+                                "location",
+                                string("The absolute path to the built binary, or a `~/` home-relative path in exported registries."),
+                            ),
+                            (
+                                "origin",
+                                string("The path the binary was built at, when `location` is its copy in the content store."),
+                            ),
+                            (
+                                "kind",
+                                enumeration(
+                                    "The cargo target kind the binary was built from.",
+                                    &["bin", "test", "bench", "example"],
+                                ),
+                            ),
+                            ("version", string("The version of the source package.")),
+                            (
+                                "profile",
+                                string("The cargo profile the binary was built with."),
+                            ),
+                            (
+                                "triple",
+                                string("The target triple of a cross-compiled build."),
+                            ),
+                            (
+                                "features",
+                                array(
+                                    "The cargo features the binary was built with.",
+                                    string("A feature."),
+                                ),
+                            ),
+                            (
+                                "rustc",
+                                string("The version of rustc the binary was built with."),
+                            ),
+                            (
+                                "license",
+                                string("The SPDX license expression of the source package."),
+                            ),
+                            (
+                                "description",
+                                string("The description of the source package."),
+                            ),
+                            (
+                                "repository",
+                                string("The source repository url of the source package."),
+                            ),
+                            (
+                                "homepage",
+                                string("The homepage url of the source package."),
+                            ),
+                            (
+                                "tags",
+                                array(
+                                    "User-assigned tags grouping binaries by purpose.",
+                                    string("A tag."),
+                                ),
+                            ),
+                            (
+                                "pinned",
+                                typed("boolean", "Whether destructive commands keep the binary."),
+                            ),
+                            (
+                                "ttl",
+                                integer("How long the binary is kept after it was last used, in seconds."),
+                            ),
+                            (
+                                "libc",
+                                enumeration(
+                                    "The libc a Linux binary links against.",
+                                    &["glibc", "musl", "static"],
+                                ),
+                            ),
+                            (
+                                "min_glibc",
+                                string("The newest glibc symbol version the binary requires."),
+                            ),
+                            (
+                                "size",
+                                integer("The size of the binary in bytes when it was registered."),
+                            ),
+                            (
+                                "last_hoisted_at",
+                                integer("When the binary was last hoisted or executed, in seconds since the unix epoch."),
+                            ),
+                            (
+                                "hoist_count",
+                                integer("How many times the binary was hoisted."),
+                            ),
+                            ("registration", reference("registration")),
+                            (
+                                "sources",
+                                array(
+                                    "The other paths byte-identical builds of the binary were registered from.",
+                                    string("A build path."),
+                                ),
+                            ),
+                        ],
+                        &["name", "location"],
+                    ),
+                ),
+                (
+                    "registration",
+                    object(
+                        "Where and how the binary was registered.",
+                        vec![
+                            (
+                                "source",
+                                enumeration(
+                                    "What registered the binary.",
+                                    &["hook", "manual", "upload", "adopt", "bundle", "pull"],
+                                ),
+                            ),
+                            (
+                                "project",
+                                string("The project root the binary was registered from."),
+                            ),
+                            (
+                                "at",
+                                integer("The registration time, in seconds since the unix epoch."),
+                            ),
+                            (
+                                "lockfile",
+                                string("The sha256 digest of the snapshotted Cargo.lock."),
+                            ),
+                            (
+                                "commit",
+                                string("The git commit the project was checked out at."),
+                            ),
+                            (
+                                "dirty",
+                                typed("boolean", "Whether the project's git working tree had uncommitted changes."),
+                            ),
+                        ],
+                        &["source", "project", "at"],
+                    ),
+                ),
+            ],
+        ),
+        SchemaTarget::Config => document(
+            "cargo-hoist config",
+            object(
+                "The hoist user configuration.",
+                vec![
+                    ("jobs", integer("The maximum number of worker threads.")),
+                    (
+                        "default-action",
+                        enumeration(
+                            "The action run when no subcommand is given.",
+                            &["install", "list", "help"],
+                        ),
+                    ),
+                    (
+                        "keep",
+                        integer("The number of registered builds kept per binary name."),
+                    ),
+                    (
+                        "history",
+                        integer("The number of previous builds kept per binary when it is registered again from the same path."),
+                    ),
+                    (
+                        "store",
+                        enumeration("Where the registry is stored.", &["toml", "sqlite", "dir"]),
+                    ),
+                    (
+                        "portable",
+                        typed("boolean", "Whether the toml and dir stores write paths under the home directory as `~/...`."),
+                    ),
+                    (
+                        "ttl",
+                        string("How long binaries are kept after they were last used, e.g. `30d`."),
+                    ),
+                    (
+                        "strategy",
+                        enumeration(
+                            "How `hoist` places binaries into the working directory.",
+                            &["copy", "hardlink", "reflink", "symlink"],
+                        ),
+                    ),
+                    (
+                        "global-bin",
+                        string("The directory `hoist --global` places binaries into."),
+                    ),
+                    (
+                        "compress",
+                        typed("boolean", "Whether binaries copied into the content store are compressed with zstd."),
+                    ),
+                ],
+                &[],
+            ),
+            vec![],
+        ),
+        SchemaTarget::Manifest => document(
+            "cargo-hoist project manifest",
+            object(
+                "A project's hoist.toml manifest.",
+                vec![(
+                    "binaries",
+                    array(
+                        "The binaries the project expects to be hoisted into scope.",
+                        string("A registered binary name, optionally followed by `@` and a version requirement."),
+                    ),
+                )],
+                &[],
+            ),
+            vec![],
+        ),
+    }
+}
+
+/// Prints the JSON Schema document for the given [SchemaTarget].
+pub fn print_schema(target: SchemaTarget) -> Result<()> {
+    println!("{}", crate::json::to_string_pretty(&schema(target))?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::{HoistedBinary, Registration, RegistrationSource};
+    use crate::config::HoistConfig;
+
+    fn properties<'a>(schema: &'a Value, path: &[&str]) -> &'a Table {
+        let mut value = schema;
+        for key in path {
+            value = &value[key];
+        }
+        value["properties"].as_table().unwrap()
+    }
+
+    #[test]
+    fn test_schemas_cover_serialized_fields() {
+        let registry = schema(SchemaTarget::Registry);
+        assert_eq!(registry["$schema"].as_str(), Some(DIALECT));
+        let binary = HoistedBinary {
+            version: Some("0.1.0".to_string()),
+            profile: Some("release".to_string()),
+            triple: Some("x86_64-unknown-linux-musl".to_string()),
+            features: vec!["default".to_string()],
+            rustc: Some("1.80.0".to_string()),
+            license: Some("MIT".to_string()),
+            description: Some("A binary".to_string()),
+            repository: Some("https://example.com".to_string()),
+            homepage: Some("https://example.com".to_string()),
+            tags: vec!["tooling".to_string()],
+            pinned: true,
+            ttl: Some(1),
+            libc: Some(crate::elf::LibcFlavor::Glibc),
+            min_glibc: Some("2.34".to_string()),
+            size: Some(1),
+            last_hoisted_at: Some(1),
+            origin: Some("/p/target/debug/a".into()),
+            hoist_count: 1,
+            registration: Some(Registration {
+                lockfile: Some("00".to_string()),
+                commit: Some("00".to_string()),
+                dirty: true,
+                ..Registration::now(RegistrationSource::Hook, "/p".into())
+            }),
+            kind: crate::binaries::ArtifactKind::Test,
+            sources: vec!["/q/target/debug/a".into()],
+            ..HoistedBinary::new("a", "/p/target/debug/a".into())
+        };
+        let value = Value::try_from(&binary).unwrap();
+        let mut entries = crate::registry::HoistRegistry::default();
+        entries.aliases.insert("b".to_string(), "a".to_string());
+        entries.insert(binary.clone());
+        let registry_props = properties(&registry, &[]);
+        for key in Value::try_from(&entries)
+            .unwrap()
+            .as_table()
+            .unwrap()
+            .keys()
+        {
+            assert!(registry_props.contains_key(key), "missing {}", key);
+        }
+        let binary_props = properties(&registry, &["$defs", "binary"]);
+        for key in value.as_table().unwrap().keys() {
+            assert!(binary_props.contains_key(key), "missing {}", key);
+        }
+        let registration_props = properties(&registry, &["$defs", "registration"]);
+        for key in value["registration"].as_table().unwrap().keys() {
+            assert!(registration_props.contains_key(key), "missing {}", key);
+        }
+
+        let config = HoistConfig {
+            jobs: Some(1),
+            default_action: crate::config::DefaultAction::List,
+            keep: Some(1),
+            history: Some(1),
+            store: crate::config::StoreKind::Sqlite,
+            portable: true,
+            ttl: Some("30d".to_string()),
+            strategy: crate::binaries::CopyStrategy::Symlink,
+            global_bin: Some(std::path::PathBuf::from("~/.local/bin")),
+            compress: true,
+        };
+        let config_schema = schema(SchemaTarget::Config);
+        let config_props = properties(&config_schema, &[]);
+        for key in Value::try_from(&config).unwrap().as_table().unwrap().keys() {
+            assert!(config_props.contains_key(key), "missing {}", key);
+        }
+
+        let json = crate::json::to_string_pretty(&schema(SchemaTarget::Manifest)).unwrap();
+        assert_eq!(
+            crate::json::parse(&json).unwrap(),
+            schema(SchemaTarget::Manifest)
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Search
+//!
+//! Looking up registered binaries: finding and searching them, explaining
+//! where they came from, and opening their directories or repositories.
+
+use anyhow::Result;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Registration};
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+impl HoistRegistry {
+    /// Finds a given binary in the hoist registry toml.
+    #[deprecated(
+        note = "reads `HOME` from the process environment, use `HoistRegistry::find_with`"
+    )]
+    #[instrument(skip(binary))]
+    pub fn find(binary: impl AsRef<str>) -> Result<()> {
+        HoistRegistry::find_with(&HoistContext::from_env()?, binary)
+    }
+
+    /// Finds a given binary in the registry of the context.
+    #[instrument(skip(ctx, binary))]
+    pub fn find_with(ctx: &HoistContext, binary: impl AsRef<str>) -> Result<()> {
+        HoistRegistry::setup_with(ctx, false)?;
+        let binary = binary.as_ref();
+        let registry = HoistRegistry::select(ctx, &crate::store::Filter::name(binary))?;
+
+        // Find the binary in the registry.
+        let binary = registry
+            .binaries
+            .iter()
+            .find(|b| b.name == binary)
+            .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?;
+        HoistRegistry::print_color(&format!("{}: ", binary.display_name()), Color::Blue, false)?;
+        HoistRegistry::print_color(&binary.location.display().to_string(), Color::Cyan, true)?;
+        Ok(())
+    }
+
+    /// Searches every registered binary's name and metadata for `query`,
+    /// case-insensitively, printing the matches.
+    #[instrument(skip(query))]
+    pub fn search_all(ctx: &HoistContext, query: impl AsRef<str>) -> Result<()> {
+        HoistRegistry::setup_with(ctx, false)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let matches = registry.search(query.as_ref());
+        if matches.is_empty() {
+            anyhow::bail!("No registered binary matches {}", query.as_ref());
+        }
+        for binary in matches {
+            HoistRegistry::print_entry(binary, &registry.aliases_of(&binary.name), false)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the registered binaries whose name or metadata contains
+    /// `query`, case-insensitively.
+    ///
+    /// Exact name matches rank first, then partial name matches, then
+    /// matches on any other field.
+    pub fn search(&self, query: &str) -> Vec<&HoistedBinary> {
+        let query = query.to_lowercase();
+        let mut ranked: Vec<_> = self
+            .binaries
+            .iter()
+            .filter_map(|b| {
+                let name = b.name.to_lowercase();
+                let rank = if name == query {
+                    0
+                } else if name.contains(&query) {
+                    1
+                } else if b
+                    .search_fields()
+                    .iter()
+                    .any(|f| f.to_lowercase().contains(&query))
+                {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, b))
+            })
+            .collect();
+        ranked.sort_by(|(ra, a), (rb, b)| {
+            (ra, &a.name, &a.location).cmp(&(rb, &b.name, &b.location))
+        });
+        ranked.into_iter().map(|(_, b)| b).collect()
+    }
+
+    /// Explains where the registered binaries named `binary` came from and
+    /// which one `hoist` resolves to.
+    #[instrument(skip(binary))]
+    pub fn why(ctx: &HoistContext, binary: impl AsRef<str>) -> Result<()> {
+        HoistRegistry::setup_with(ctx, false)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let explanation = registry
+            .explain(&registry.resolve_alias(binary.as_ref()))
+            .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?;
+        println!("{}", explanation);
+        Ok(())
+    }
+
+    /// Renders the provenance of every registered binary named `name`, along
+    /// with how conflicts between them resolve. Returns [None] if no binary
+    /// with that name is registered.
+    pub fn explain(&self, name: &str) -> Option<String> {
+        let mut entries: Vec<_> = self.binaries.iter().filter(|b| b.name == name).collect();
+        if entries.is_empty() {
+            return None;
+        }
+        entries.sort_by(|a, b| a.location.cmp(&b.location));
+        let latest = self.latest(name);
+        let unknown = || "unknown".to_string();
+
+        let mut out = match entries.len() {
+            1 => format!("{}: 1 registered build\n", name),
+            n => format!("{}: {} registered builds\n", name, n),
+        };
+        for b in &entries {
+            let built = std::fs::metadata(&b.location)
+                .and_then(|m| m.modified())
+                .map(crate::time::to_rfc3339)
+                .unwrap_or_else(|_| "missing from disk".to_string());
+            let registered = b
+                .registration
+                .as_ref()
+                .map(|r| {
+                    format!(
+                        "{}, {}",
+                        r.source,
+                        crate::time::to_rfc3339(crate::time::from_unix(r.at))
+                    )
+                })
+                .unwrap_or_else(|| "unknown, recorded before provenance tracking".to_string());
+            let project = b
+                .project()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(unknown);
+            out.push_str(&format!("  {}\n", b.location.display()));
+            out.push_str(&format!("    kind:       {:?}\n", b.kind).to_lowercase());
+            if let Some(origin) = &b.origin {
+                out.push_str(&format!("    built at:   {}\n", origin.display()));
+            }
+            for source in &b.sources {
+                out.push_str(&format!("    also built: {}\n", source.display()));
+            }
+            match (&b.profile, &b.triple) {
+                (Some(p), Some(t)) => out.push_str(&format!("    profile:    {} ({})\n", p, t)),
+                (Some(p), None) => out.push_str(&format!("    profile:    {}\n", p)),
+                _ => {}
+            }
+            if !b.features.is_empty() {
+                out.push_str(&format!("    features:   {}\n", b.features.join(", ")));
+            }
+            if let Some(rustc) = &b.rustc {
+                out.push_str(&format!("    rustc:      {}\n", rustc));
+            }
+            if let Some(version) = &b.version {
+                out.push_str(&format!("    version:    {}\n", version));
+            }
+            out.push_str(&format!("    project:    {}\n", project));
+            out.push_str(&format!("    registered: {}\n", registered));
+            out.push_str(&format!("    built:      {}\n", built));
+            if let Some(at) = b.last_hoisted_at {
+                out.push_str(&format!(
+                    "    hoisted:    {} times, last {}\n",
+                    b.hoist_count,
+                    crate::time::to_rfc3339(crate::time::from_unix(at))
+                ));
+            }
+            if let Some(revision) = b.registration.as_ref().and_then(Registration::revision) {
+                out.push_str(&format!("    commit:     {}\n", revision));
+            }
+            if let Some(lockfile) = b.registration.as_ref().and_then(|r| r.lockfile.as_ref()) {
+                out.push_str(&format!("    lockfile:   {:.12}\n", lockfile));
+            }
+            if !b.tags.is_empty() {
+                out.push_str(&format!("    tags:       {}\n", b.tags.join(", ")));
+            }
+            if b.pinned {
+                out.push_str("    pinned:     yes\n");
+            }
+            if let Some(at) = b.expires_at(None) {
+                out.push_str(&format!(
+                    "    expires:    {}\n",
+                    crate::time::to_rfc3339(crate::time::from_unix(at))
+                ));
+            }
+            if let Some(repository) = &b.repository {
+                out.push_str(&format!("    repository: {}\n", repository));
+            }
+            if let Some(homepage) = &b.homepage {
+                out.push_str(&format!("    homepage:   {}\n", homepage));
+            }
+            match (&b.libc, &b.min_glibc) {
+                (Some(libc), Some(v)) => out.push_str(&format!("    libc:       {} {}\n", libc, v)),
+                (Some(libc), None) => out.push_str(&format!("    libc:       {}\n", libc)),
+                _ => {}
+            }
+        }
+
+        let latest = latest
+            .map(|b| b.location.display().to_string())
+            .unwrap_or_else(unknown);
+        match entries.len() {
+            1 => out.push_str(&format!("`hoist {}` copies {}", name, latest)),
+            _ => out.push_str(&format!(
+                "`hoist {}` prompts to choose between the conflicting builds when run in a terminal, \
+                 and copies every build otherwise.\n`exec` and `env` resolve to the latest build, {}",
+                name, latest
+            )),
+        }
+        Some(out)
+    }
+
+    /// Opens the directory containing a registered binary in the platform
+    /// file manager, or prints the directory if `print` is set.
+    #[instrument(skip(binary))]
+    pub fn open(ctx: &HoistContext, binary: impl AsRef<str>, print: bool) -> Result<()> {
+        HoistRegistry::setup_with(ctx, false)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let binary = registry
+            .latest(binary.as_ref())
+            .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?;
+        let dir = binary.location.parent().ok_or(anyhow::anyhow!(
+            "Failed to resolve the directory containing {}",
+            binary.location.display()
+        ))?;
+        match print {
+            true => println!("{}", dir.display()),
+            false => crate::opener::open(dir)?,
+        }
+        Ok(())
+    }
+
+    /// Opens the repository url of the latest registered build of `binary`,
+    /// falling back to its homepage, or prints it if `print` is set.
+    #[instrument(skip(binary))]
+    pub fn repo(ctx: &HoistContext, binary: impl AsRef<str>, print: bool) -> Result<()> {
+        HoistRegistry::setup_with(ctx, false)?;
+        let registry = HoistRegistry::load(ctx)?;
+        let binary = registry
+            .latest(binary.as_ref())
+            .ok_or(anyhow::anyhow!("Failed to find binary in hoist registry"))?;
+        let url = binary
+            .repository
+            .as_ref()
+            .or(binary.homepage.as_ref())
+            .ok_or(anyhow::anyhow!(
+                "{} declares no repository or homepage, re-register it to pick up manifest changes",
+                binary.name
+            ))?;
+        match print {
+            true => println!("{}", url),
+            false => crate::opener::open(url)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::RegistrationSource;
+    use crate::hash::HashAlgorithm;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_search() {
+        let mut registry = HoistRegistry {
+            binaries: HashSet::from([
+                HoistedBinary::new("kafka-cli", PathBuf::from("/p/target/release/kafka-cli")),
+                HoistedBinary::new("kafka", PathBuf::from("/q/target/release/kafka")),
+                HoistedBinary::new(
+                    "consumer",
+                    PathBuf::from("/kafka-tools/target/release/consumer"),
+                ),
+                HoistedBinary::new("unrelated", PathBuf::from("/r/target/release/unrelated")),
+            ]),
+            ..Default::default()
+        };
+        registry.insert(HoistedBinary {
+            license: Some("MIT".to_string()),
+            ..HoistedBinary::new("licensed", PathBuf::from("/s/target/release/licensed"))
+        });
+        let names: Vec<_> = registry
+            .search("KAFKA")
+            .into_iter()
+            .map(|b| b.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["kafka", "kafka-cli", "consumer"]);
+        assert_eq!(registry.search("mit")[0].name, "licensed");
+        assert!(registry.search("missing").is_empty());
+    }
+
+    #[test]
+    fn test_why() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_why");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+        std::fs::write(test_tempdir.join("Cargo.lock"), "version = 3\n").unwrap();
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions {
+                source: RegistrationSource::Hook,
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        let registration = registry.latest("binary1").unwrap().registration.clone();
+        let digest = crate::hash::hash_bytes(b"version = 3\n", HashAlgorithm::Sha256);
+        assert_eq!(
+            registration.map(|r| (r.source, r.project, r.lockfile)),
+            Some((
+                RegistrationSource::Hook,
+                test_tempdir.canonicalize().unwrap(),
+                Some(digest.clone())
+            ))
+        );
+        assert!(crate::lockfile::path(&ctx, &digest).exists());
+
+        let explanation = registry.explain("binary1").unwrap();
+        assert!(explanation.starts_with("binary1: 1 registered build\n"));
+        assert!(explanation.contains("    registered: hook, "));
+        assert!(explanation.contains(&format!("    lockfile:   {:.12}\n", digest)));
+        assert!(explanation.contains("`hoist binary1` copies "));
+        assert!(!explanation.contains("commit:"));
+
+        let mut built = registry.latest("binary1").cloned().unwrap();
+        built.registration = built.registration.map(|r| Registration {
+            commit: Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+            dirty: true,
+            ..r
+        });
+        built.features = vec!["default".to_string(), "tls".to_string()];
+        built.rustc = Some("1.80.0 (051478957 2024-07-21)".to_string());
+        registry.binaries.replace(built);
+        let explanation = registry.explain("binary1").unwrap();
+        assert!(explanation.contains("    commit:     0123456789ab-dirty\n"));
+        assert!(explanation.contains("    features:   default, tls\n"));
+        assert!(explanation.contains("    rustc:      1.80.0 (051478957 2024-07-21)\n"));
+
+        registry.insert(HoistedBinary::new(
+            "binary1",
+            test_tempdir.join("other/target/debug/binary1"),
+        ));
+        let explanation = registry.explain("binary1").unwrap();
+        assert!(explanation.starts_with("binary1: 2 registered builds\n"));
+        assert!(explanation.contains(&format!(
+            "    project:    {}\n",
+            test_tempdir.join("other").display()
+        )));
+        assert!(explanation.contains("    built:      missing from disk\n"));
+        assert!(explanation.contains("prompts to choose between the conflicting builds"));
+        assert!(registry.explain("binary3").is_none());
+    }
+
+    #[test]
+    fn test_open_print() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_open_print");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+
+        HoistRegistry::open(&ctx, "binary1", true).unwrap();
+        assert!(HoistRegistry::open(&ctx, "missing", true).is_err());
+    }
+}
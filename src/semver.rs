@@ -0,0 +1,322 @@
+//! Semver
+//!
+//! A minimal semantic version and cargo-style version requirement parser.
+
+use anyhow::Result;
+use std::cmp::Ordering;
+
+/// A semantic version, e.g. `1.2.3-beta.1+build`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Version {
+    /// The major version
+    pub major: u64,
+    /// The minor version
+    pub minor: u64,
+    /// The patch version
+    pub patch: u64,
+    /// The dot separated pre-release identifiers, empty for releases
+    pub pre: Vec<String>,
+}
+
+impl Version {
+    /// Creates a release version.
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: vec![],
+        }
+    }
+
+    /// Parses a version, ignoring any build metadata.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let s = s.split_once('+').map_or(s, |(v, _)| v);
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').map(str::to_string).collect()),
+            None => (s, vec![]),
+        };
+        let mut parts = core.split('.');
+        let mut next = || -> Result<u64> {
+            let part = parts
+                .next()
+                .ok_or(anyhow::anyhow!("invalid version: {}", s))?;
+            part.parse()
+                .map_err(|_| anyhow::anyhow!("invalid version: {}", s))
+        };
+        let version = Self {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+            pre,
+        };
+        if parts.next().is_some() {
+            anyhow::bail!("invalid version: {}", s);
+        }
+        Ok(version)
+    }
+
+    fn core(&self) -> (u64, u64, u64) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.core().cmp(&other.core()).then_with(|| {
+            match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_pre(&self.pre, &other.pre),
+            }
+        })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares pre-release identifiers, numeric identifiers sorting numerically
+/// and before alphanumeric ones.
+fn compare_pre(a: &[String], b: &[String]) -> Ordering {
+    for (x, y) in a.iter().zip(b) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// A comparison operator in a [VersionReq].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+    Wildcard,
+}
+
+/// A single comparator, e.g. `>= 1.2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    pre: Vec<String>,
+}
+
+impl Comparator {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (op, rest) = [
+            (">=", Op::GreaterEq),
+            ("<=", Op::LessEq),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("=", Op::Exact),
+            ("~", Op::Tilde),
+            ("^", Op::Caret),
+        ]
+        .iter()
+        .find(|(p, _)| s.starts_with(p))
+        .map(|(p, op)| (*op, s[p.len()..].trim()))
+        .unwrap_or((Op::Caret, s));
+        let rest = rest.split_once('+').map_or(rest, |(v, _)| v);
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').map(str::to_string).collect()),
+            None => (rest, vec![]),
+        };
+        let mut parts = core.split('.');
+        let invalid = || anyhow::anyhow!("invalid version requirement: {}", s);
+        let wildcard = |p: &str| matches!(p, "*" | "x" | "X");
+        let major = parts.next().ok_or_else(invalid)?;
+        if wildcard(major) {
+            return Ok(Self {
+                op: Op::Wildcard,
+                major: 0,
+                minor: None,
+                patch: None,
+                pre: vec![],
+            });
+        }
+        let major = major.parse().map_err(|_| invalid())?;
+        let component = |parts: &mut std::str::Split<'_, char>| -> Result<Option<u64>> {
+            match parts.next() {
+                None => Ok(None),
+                Some(p) if wildcard(p) => Ok(None),
+                Some(p) => Ok(Some(p.parse().map_err(|_| invalid())?)),
+            }
+        };
+        let minor = component(&mut parts)?;
+        let patch = match minor {
+            Some(_) => component(&mut parts)?,
+            None => None,
+        };
+        // `=1.2` and `=1` match any version with the given prefix, as `~` does.
+        let op = match (op, patch) {
+            (Op::Exact, None) => Op::Tilde,
+            (op, _) => op,
+        };
+        Ok(Self {
+            op,
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+
+    fn lower(&self) -> Version {
+        Version {
+            major: self.major,
+            minor: self.minor.unwrap_or(0),
+            patch: self.patch.unwrap_or(0),
+            pre: self.pre.clone(),
+        }
+    }
+
+    fn matches(&self, v: &Version) -> bool {
+        let lower = self.lower();
+        match self.op {
+            Op::Wildcard => true,
+            Op::Exact => *v == lower,
+            Op::GreaterEq => *v >= lower,
+            Op::Less => *v < lower,
+            Op::Greater => match (self.minor, self.patch) {
+                (None, _) => v.major > self.major,
+                (Some(minor), None) => (v.major, v.minor) > (self.major, minor),
+                _ => *v > lower,
+            },
+            Op::LessEq => match (self.minor, self.patch) {
+                (None, _) => v.major <= self.major,
+                (Some(minor), None) => (v.major, v.minor) <= (self.major, minor),
+                _ => *v <= lower,
+            },
+            Op::Tilde => {
+                *v >= lower
+                    && v.major == self.major
+                    && self.minor.is_none_or(|minor| v.minor == minor)
+            }
+            Op::Caret => {
+                if *v < lower {
+                    return false;
+                }
+                match (self.major, self.minor, self.patch) {
+                    (0, Some(0), Some(patch)) => v.core() == (0, 0, patch),
+                    (0, Some(minor), _) => v.major == 0 && v.minor == minor,
+                    (major, _, _) => v.major == major,
+                }
+            }
+        }
+    }
+}
+
+/// A cargo-style version requirement, e.g. `>= 1.2, < 2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+impl VersionReq {
+    /// Parses a comma separated list of comparators.
+    pub fn parse(s: &str) -> Result<Self> {
+        let comparators = s
+            .split(',')
+            .map(Comparator::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { comparators })
+    }
+
+    /// Returns if the version satisfies every comparator.
+    ///
+    /// As with cargo, pre-release versions only match comparators that name
+    /// a pre-release of the same `major.minor.patch`.
+    pub fn matches(&self, v: &Version) -> bool {
+        if !self.comparators.iter().all(|c| c.matches(v)) {
+            return false;
+        }
+        v.pre.is_empty()
+            || self
+                .comparators
+                .iter()
+                .any(|c| !c.pre.is_empty() && c.lower().core() == v.core())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(req: &str, v: &str) -> bool {
+        VersionReq::parse(req)
+            .unwrap()
+            .matches(&Version::parse(v).unwrap())
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0",
+            "1.0.1+build.5",
+            "1.10.0",
+        ];
+        let parsed: Vec<_> = versions
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        assert!(parsed.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(parsed[4].to_string(), "1.0.0-beta.11");
+        assert!(Version::parse("1.2").is_err());
+        assert!(Version::parse("1.2.3.4").is_err());
+    }
+
+    #[test]
+    fn test_version_req() {
+        assert!(matches("1.2.3", "1.9.0"));
+        assert!(!matches("1.2.3", "2.0.0"));
+        assert!(matches("^0.2.3", "0.2.9"));
+        assert!(!matches("^0.2.3", "0.3.0"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(matches("~1.2", "1.2.7"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches(">= 1.2, < 1.5", "1.4.9"));
+        assert!(!matches(">= 1.2, < 1.5", "1.5.0"));
+        assert!(matches("<= 1.2", "1.2.9"));
+        assert!(!matches("> 1.2", "1.2.9"));
+        assert!(matches("=1.2", "1.2.4"));
+        assert!(matches("*", "3.0.0"));
+        assert!(!matches(">= 1.0.0", "1.1.0-beta"));
+        assert!(matches(">= 1.1.0-alpha", "1.1.0-beta"));
+    }
+}
@@ -0,0 +1,496 @@
+//! Server
+//!
+//! Serves the registry and vault over HTTP so a team can share a binary
+//! catalog from a single build box.
+//!
+//! The vault at `~/.hoist/vault/` holds uploaded binaries, stored by the
+//! sha256 digest of their contents. Endpoints:
+//!
+//! - `GET /registry` returns the registry as json.
+//! - `GET /metrics` returns the [crate::metrics] of the server for Prometheus.
+//! - `GET /binaries/<name>` downloads the latest registered build of `name`.
+//! - `PUT /binaries/<name>` uploads a build of `name` into the vault and
+//!   registers it.
+//!
+//! When a token is configured, every request must carry it as an
+//! `Authorization: Bearer <token>` header. Without a token the server is
+//! read-only. Only authorized uploads may carry bodies of up to
+//! [MAX_UPLOAD] bytes, which are streamed into the vault as they arrive,
+//! other requests are held to [crate::http::MAX_BODY].
+//!
+//! `cargo hoist push` and `cargo hoist pull` are the matching clients,
+//! uploading registered binaries to a server and registering binaries
+//! downloaded from one.
+
+use anyhow::Result;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{HoistedBinary, Registration, RegistrationSource};
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::http::{BodyLimit, Request, Response};
+use crate::registry::HoistRegistry;
+
+/// The environment variable holding the server's bearer token.
+pub const TOKEN_ENV: &str = "HOIST_SERVER_TOKEN";
+
+/// The environment variable holding the server url for push and pull.
+pub const SERVER_ENV: &str = "HOIST_SERVER";
+
+/// The default address the server listens on.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+
+/// The largest binary accepted by an upload, in bytes.
+pub const MAX_UPLOAD: usize = 512 << 20;
+
+/// The directory uploaded binaries are stored in.
+pub fn vault_dir(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("vault")
+}
+
+/// A registry server.
+#[derive(Debug, Clone)]
+pub struct Server {
+    ctx: HoistContext,
+    token: Option<String>,
+}
+
+impl Server {
+    /// Creates a server for the registry in the context's home.
+    pub fn new(ctx: HoistContext, token: Option<String>) -> Self {
+        Self { ctx, token }
+    }
+
+    /// Returns if the request carries the configured token.
+    fn is_authorized(&self, request: &Request) -> bool {
+        match &self.token {
+            Some(token) => request
+                .bearer()
+                .is_some_and(|t| crate::http::secrets_match(t, token)),
+            None => false,
+        }
+    }
+
+    /// The largest body accepted for the request, which is only large
+    /// enough for a binary for authorized uploads, streamed into a temporary
+    /// file in the vault.
+    pub fn body_limit(&self, request: &Request) -> BodyLimit {
+        match request.method == "PUT" && self.is_authorized(request) {
+            true => BodyLimit::Spooled(
+                crate::content::temp_path(&vault_dir(&self.ctx).join("upload")),
+                MAX_UPLOAD,
+            ),
+            false => BodyLimit::Buffered(crate::http::MAX_BODY),
+        }
+    }
+
+    /// Handles a request, mapping failures to a `500` response.
+    #[instrument(skip(self, request), fields(method = %request.method, path = %request.path))]
+    pub fn handle(&self, request: &Request) -> Response {
+        if self.token.is_some() && !self.is_authorized(request) {
+            return Response::error(401);
+        }
+        self.route(request).unwrap_or_else(|e| {
+            crate::metrics::METRICS.record_error();
+            tracing::warn!("Failed to serve {}: {:?}", request.path, e);
+            Response::text(500, format!("{}\n", e))
+        })
+    }
+
+    fn route(&self, request: &Request) -> Result<Response> {
+        let segments: Vec<_> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["registry"]) => {
+                let registry = HoistRegistry::load(&self.ctx)?;
+                Ok(Response::json(crate::json::to_string_pretty(&registry)?))
+            }
+            ("GET", ["metrics"]) => {
+                let registry = HoistRegistry::load(&self.ctx)?;
+                let totals = crate::metrics::METRICS.flush(&self.ctx)?;
+                Ok(Response::new(
+                    200,
+                    "text/plain; version=0.0.4",
+                    totals.render(registry.binaries.len()),
+                ))
+            }
+            ("GET", ["binaries", name]) => {
+                let registry = HoistRegistry::load(&self.ctx)?;
+                match registry.latest(name) {
+                    Some(b) => match crate::content::materialize(&self.ctx, &b.location)
+                        .and_then(|path| Ok(std::fs::read(path)?))
+                    {
+                        Ok(bytes) => Ok(Response::new(200, "application/octet-stream", bytes)),
+                        Err(_) => Ok(Response::error(404)),
+                    },
+                    None => Ok(Response::error(404)),
+                }
+            }
+            ("PUT", ["binaries", name]) => match self.token {
+                Some(_) => self.upload(name, request),
+                None => Ok(Response::error(403)),
+            },
+            (_, ["registry"] | ["metrics"] | ["binaries", _]) => Ok(Response::error(405)),
+            _ => Ok(Response::error(404)),
+        }
+    }
+
+    /// Moves an uploaded binary, streamed into the vault, into place by its
+    /// digest and registers it.
+    fn upload(&self, name: &str, request: &Request) -> Result<Response> {
+        let Some(spooled) = request.spooled.as_ref().filter(|s| s.len > 0) else {
+            return Ok(Response::error(400));
+        };
+        if !crate::registry::is_valid_name(name) || name == "." || name == ".." {
+            return Ok(Response::error(400));
+        }
+        let digest = &spooled.sha256;
+        let dir = vault_dir(&self.ctx).join(digest);
+        let location = dir.join(name);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::set_permissions(&spooled.path, std::fs::Permissions::from_mode(0o755))?;
+        std::fs::rename(&spooled.path, &location)?;
+
+        let _lock = HoistRegistry::lock(&self.ctx)?;
+        let mut registry = HoistRegistry::load(&self.ctx)?;
+        registry.insert(HoistedBinary {
+            registration: Some(Registration::now(RegistrationSource::Upload, dir)),
+            size: Some(spooled.len),
+            ..HoistedBinary::new(name, location)
+        });
+        registry.save(&self.ctx)?;
+        crate::metrics::METRICS.record_registrations(1);
+        tracing::info!("Registered upload of {} ({:.12})", name, digest);
+        Ok(Response::text(201, format!("{}\n", digest)))
+    }
+}
+
+/// Serves the registry on `addr` until the process exits.
+///
+/// The token defaults to the [TOKEN_ENV] environment variable.
+#[instrument(skip(ctx, token))]
+pub fn serve(ctx: &HoistContext, addr: String, token: Option<String>, quiet: bool) -> Result<()> {
+    HoistRegistry::setup_with(ctx, quiet)?;
+    std::fs::create_dir_all(vault_dir(ctx))?;
+    let token = token
+        .or_else(|| std::env::var(TOKEN_ENV).ok())
+        .filter(|t| !t.is_empty());
+    if token.is_none() {
+        tracing::warn!(
+            "No token set with --token or {}, serving read-only",
+            TOKEN_ENV
+        );
+    }
+    if !quiet {
+        println!("Serving the hoist registry on http://{}", addr);
+    }
+    let server = Server::new(ctx.clone(), token);
+    let limits = server.clone();
+    crate::http::serve(
+        addr,
+        move |request| limits.body_limit(request),
+        move |request| server.handle(request),
+    )
+}
+
+/// The server url, `url` if given or else the [SERVER_ENV] environment
+/// variable.
+fn server_url(url: Option<String>) -> Result<String> {
+    url.or_else(|| std::env::var(SERVER_ENV).ok())
+        .filter(|u| !u.is_empty())
+        .map(|u| u.trim_end_matches('/').to_string())
+        .ok_or(anyhow::anyhow!(
+            "Name the server with --server or {}",
+            SERVER_ENV
+        ))
+}
+
+/// The token, `token` if given or else the [TOKEN_ENV] environment variable.
+fn client_token(token: Option<String>) -> Option<String> {
+    token
+        .or_else(|| std::env::var(TOKEN_ENV).ok())
+        .filter(|t| !t.is_empty())
+}
+
+/// Uploads the latest registered build of each named binary to the server
+/// at `url`, which defaults to the [SERVER_ENV] environment variable.
+///
+/// The token defaults to the [TOKEN_ENV] environment variable, and is
+/// required as servers without one are read-only.
+#[instrument(skip(ctx, token))]
+pub fn push(
+    ctx: &HoistContext,
+    url: Option<String>,
+    token: Option<String>,
+    names: Vec<String>,
+    quiet: bool,
+) -> Result<()> {
+    if names.is_empty() {
+        anyhow::bail!("Name the binaries to push");
+    }
+    let url = server_url(url)?;
+    let token = client_token(token).ok_or(anyhow::anyhow!(
+        "Pushing requires the server token, pass --token or set {}",
+        TOKEN_ENV
+    ))?;
+    let registry = HoistRegistry::load(ctx)?;
+    for name in &names {
+        let b = registry
+            .latest(name)
+            .ok_or(anyhow::anyhow!("{} is not in the hoist registry", name))?;
+        let bytes = std::fs::read(crate::content::materialize(ctx, &b.location)?)?;
+        let target = format!("{}/binaries/{}", url, crate::http::encode(name));
+        let (status, body) = crate::http::fetch("PUT", &target, Some(&token), &bytes)?;
+        if status != 201 {
+            anyhow::bail!(
+                "{} rejected {}: {} {}",
+                url,
+                name,
+                status,
+                String::from_utf8_lossy(&body).trim()
+            );
+        }
+        if !quiet {
+            HoistRegistry::print_color("Pushed ", Color::Green, false)?;
+            HoistRegistry::print_color(name, Color::Magenta, false)?;
+            HoistRegistry::print_color(&format!(" to {}", url), Color::Cyan, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Downloads the latest build of each named binary from the server at `url`
+/// into the content store and registers it.
+///
+/// The url and token default to the [SERVER_ENV] and [TOKEN_ENV] environment
+/// variables. Nothing is registered if any download fails.
+#[instrument(skip(ctx, token))]
+pub fn pull(
+    ctx: &HoistContext,
+    url: Option<String>,
+    token: Option<String>,
+    names: Vec<String>,
+    quiet: bool,
+) -> Result<()> {
+    if names.is_empty() {
+        anyhow::bail!("Name the binaries to pull");
+    }
+    let url = server_url(url)?;
+    let token = client_token(token);
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let mut pulled = Vec::with_capacity(names.len());
+    for name in &names {
+        if !crate::registry::is_valid_name(name) {
+            anyhow::bail!("Invalid binary name {:?}", name);
+        }
+        let source = format!("{}/binaries/{}", url, crate::http::encode(name));
+        let (status, bytes) = crate::http::fetch("GET", &source, token.as_deref(), b"")?;
+        if status != 200 || bytes.is_empty() {
+            anyhow::bail!(
+                "Failed to pull {} from {}: {} {}",
+                name,
+                url,
+                status,
+                crate::http::reason(status)
+            );
+        }
+        let digest = crate::hash::hash_bytes(&bytes, HashAlgorithm::Sha256);
+        let dir = crate::content::dir(ctx).join(&digest);
+        let location = dir.join(name);
+        if !location.exists() {
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(&location, &bytes)?;
+            std::fs::set_permissions(&location, std::fs::Permissions::from_mode(0o755))?;
+        }
+        pulled.push(HoistedBinary {
+            registration: Some(Registration::now(
+                RegistrationSource::Pull,
+                PathBuf::from(&url),
+            )),
+            size: Some(bytes.len() as u64),
+            ..HoistedBinary::new(name.as_str(), location)
+        });
+    }
+
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    for b in &pulled {
+        registry.insert(b.clone());
+    }
+    registry.save(ctx)?;
+    crate::metrics::METRICS.record_registrations(pulled.len());
+    if !quiet {
+        for b in &pulled {
+            HoistRegistry::print_color("Pulled ", Color::Green, false)?;
+            HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+            HoistRegistry::print_color(&format!(" from {}", url), Color::Cyan, true)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, token: Option<&str>, body: &[u8]) -> Request {
+        let mut request = Request::new(method, path);
+        if let Some(t) = token {
+            request
+                .headers
+                .insert("authorization".to_string(), format!("Bearer {}", t));
+        }
+        request.body = body.to_vec();
+        request
+    }
+
+    /// A request with the body spooled into the vault, as read for uploads.
+    fn upload(ctx: &HoistContext, path: &str, token: Option<&str>, body: &[u8]) -> Request {
+        let mut request = request("PUT", path, token, b"");
+        std::fs::create_dir_all(vault_dir(ctx)).unwrap();
+        let spooled = crate::content::temp_path(&vault_dir(ctx).join("upload"));
+        std::fs::write(&spooled, body).unwrap();
+        request.spooled = Some(crate::http::Spooled {
+            path: spooled,
+            len: body.len() as u64,
+            sha256: crate::hash::hash_bytes(body, HashAlgorithm::Sha256),
+        });
+        request
+    }
+
+    #[test]
+    fn test_server() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let server = Server::new(ctx.clone(), Some("s3cret".to_string()));
+
+        let get = |path: &str, token| server.handle(&request("GET", path, token, b""));
+        assert_eq!(get("/registry", None).status, 401);
+        assert_eq!(get("/registry", Some("wrong")).status, 401);
+        assert_eq!(get("/binaries/tool", Some("s3cret")).status, 404);
+        assert_eq!(get("/missing", Some("s3cret")).status, 404);
+
+        let put = |path: &str, body| server.handle(&upload(&ctx, path, Some("s3cret"), body));
+        assert_eq!(put("/binaries/tool", b"v1").status, 201);
+        assert_eq!(put("/binaries/..", b"v1").status, 400);
+        assert_eq!(put("/binaries/a@b", b"v1").status, 400);
+        assert_eq!(put("/binaries/tool", b"").status, 400);
+        assert_eq!(
+            server
+                .handle(&request("PUT", "/binaries/tool", Some("s3cret"), b"v1"))
+                .status,
+            400
+        );
+        assert_eq!(get("/binaries/tool", Some("s3cret")).body, b"v1");
+        let registry = String::from_utf8(get("/registry", Some("s3cret")).body).unwrap();
+        assert!(registry.contains("\"upload\""));
+        let metrics = String::from_utf8(get("/metrics", Some("s3cret")).body).unwrap();
+        assert!(metrics.contains("hoist_registry_binaries 1\n"));
+        let digest = crate::hash::hash_bytes(b"v1", HashAlgorithm::Sha256);
+        assert!(vault_dir(&ctx).join(digest).join("tool").exists());
+
+        let read_only = Server::new(ctx, None);
+        assert_eq!(
+            read_only
+                .handle(&request("PUT", "/binaries/tool", None, b"v2"))
+                .status,
+            403
+        );
+        assert_eq!(
+            read_only
+                .handle(&request("DELETE", "/registry", None, b""))
+                .status,
+            405
+        );
+    }
+
+    #[test]
+    fn test_body_limit() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let server = Server::new(ctx.clone(), Some("s3cret".to_string()));
+        let limit = |method, token| server.body_limit(&request(method, "/binaries/a", token, b""));
+        let buffered = BodyLimit::Buffered(crate::http::MAX_BODY);
+        assert!(matches!(
+            limit("PUT", Some("s3cret")),
+            BodyLimit::Spooled(path, MAX_UPLOAD) if path.starts_with(vault_dir(&ctx))
+        ));
+        assert_eq!(limit("PUT", Some("wrong")), buffered);
+        assert_eq!(limit("PUT", None), buffered);
+        assert_eq!(limit("GET", Some("s3cret")), buffered);
+    }
+
+    #[test]
+    fn test_push_and_pull() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let remote = HoistContext::new(tempdir.path().join("remote"), tempdir.path());
+        HoistRegistry::create_dir_with(&remote, true).unwrap();
+        HoistRegistry::create_registry_with(&remote, true).unwrap();
+        std::fs::create_dir_all(vault_dir(&remote)).unwrap();
+        let server = Server::new(remote.clone(), Some("s3cret".to_string()));
+        let limits = server.clone();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            crate::http::serve_listener(
+                listener,
+                move |r| limits.body_limit(r),
+                move |r| server.handle(r),
+            )
+        });
+
+        let local = HoistContext::new(tempdir.path().join("local"), tempdir.path());
+        HoistRegistry::create_registry_with(&local, true).unwrap();
+        std::fs::write(local.hook_identifier(), "").unwrap();
+        let build = tempdir.path().join("target/release/tool");
+        std::fs::create_dir_all(build.parent().unwrap()).unwrap();
+        std::fs::write(&build, "#!/bin/sh\necho tool\n").unwrap();
+        let mut registry = HoistRegistry::load(&local).unwrap();
+        registry.insert(HoistedBinary::new("tool", build));
+        registry.save(&local).unwrap();
+
+        let tool = vec!["tool".to_string()];
+        let token = Some("s3cret".to_string());
+        assert!(push(
+            &local,
+            Some(url.clone()),
+            Some("wrong".to_string()),
+            tool.clone(),
+            true
+        )
+        .is_err());
+        push(&local, Some(url.clone()), token.clone(), tool.clone(), true).unwrap();
+        assert!(HoistRegistry::load(&remote)
+            .unwrap()
+            .latest("tool")
+            .is_some());
+
+        let other = HoistContext::new(tempdir.path().join("other"), tempdir.path());
+        HoistRegistry::create_registry_with(&other, true).unwrap();
+        std::fs::write(other.hook_identifier(), "").unwrap();
+        assert!(pull(&other, Some(url.clone()), None, tool.clone(), true).is_err());
+        assert!(pull(
+            &other,
+            Some(url.clone()),
+            token.clone(),
+            vec!["missing".to_string()],
+            true
+        )
+        .is_err());
+        pull(&other, Some(url.clone()), token, tool, true).unwrap();
+        let registry = HoistRegistry::load(&other).unwrap();
+        let b = registry.latest("tool").unwrap();
+        assert!(b.location.starts_with(crate::content::dir(&other)));
+        assert_eq!(
+            std::fs::read_to_string(&b.location).unwrap(),
+            "#!/bin/sh\necho tool\n"
+        );
+        assert_eq!(
+            b.registration.as_ref().unwrap().source,
+            RegistrationSource::Pull
+        );
+    }
+}
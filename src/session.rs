@@ -0,0 +1,332 @@
+//! Session
+//!
+//! Session-scoped shim directories that follow the project a shell is in.
+//!
+//! When a shell enters a directory with a [crate::manifest::ProjectManifest],
+//! the shell hook asks cargo-hoist for an updated `PATH`. The manifest's
+//! binaries are linked into a per-session shim directory which is prepended
+//! to `PATH`, and dropped again once the shell leaves the project.
+//!
+//! `cargo hoist shell` places binaries into a temporary directory instead,
+//! prepended to the `PATH` of a subshell, to run them without hoisting them
+//! into the project.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+use crate::binaries::{CopyStrategy, HoistedBinary, Preserve};
+use crate::context::HoistContext;
+use crate::manifest::ProjectManifest;
+use crate::registry::HoistRegistry;
+
+/// The directory holding the session shim directories.
+pub fn sessions_dir(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("sessions")
+}
+
+/// The path to the session shim directory for the given session id.
+pub fn session_dir(ctx: &HoistContext, session: &str) -> PathBuf {
+    sessions_dir(ctx).join(session)
+}
+
+/// Returns if a process with the given pid is running.
+fn is_running(pid: libc::pid_t) -> bool {
+    // Signal 0 only checks that the process exists and may be signalled.
+    let res = unsafe { libc::kill(pid, 0) };
+    res == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Removes the shim directories of sessions, named by the pid of their
+/// shell, whose shell is no longer running, as a shell that exits inside a
+/// project never clears its directory. Returns the number removed.
+#[instrument(skip(ctx))]
+pub fn prune(ctx: &HoistContext) -> Result<usize> {
+    let Ok(entries) = std::fs::read_dir(sessions_dir(ctx)) else {
+        return Ok(0);
+    };
+    let mut pruned = 0;
+    for entry in entries.flatten() {
+        let pid = entry.file_name().to_str().and_then(|n| n.parse().ok());
+        if pid.is_some_and(|pid| pid > 0 && !is_running(pid)) {
+            clear(&entry.path())?;
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+/// Removes every occurrence of `dir` from a `PATH`-style string.
+pub fn strip_path(path: &str, dir: &Path) -> String {
+    std::env::split_paths(path)
+        .filter(|p| p != dir)
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Prepends `dir` to a `PATH`-style string.
+pub fn prepend_path(path: &str, dir: &Path) -> String {
+    match path.is_empty() {
+        true => dir.display().to_string(),
+        false => format!("{}:{}", dir.display(), path),
+    }
+}
+
+/// Materializes the session shim directory, replacing any previous contents
+/// with symlinks to the provided binaries.
+#[instrument(skip(binaries))]
+pub fn materialize(dir: &Path, binaries: &[HoistedBinary]) -> Result<()> {
+    clear(dir)?;
+    std::fs::create_dir_all(dir)?;
+    for b in binaries {
+        std::os::unix::fs::symlink(&b.location, dir.join(&b.name))?;
+    }
+    Ok(())
+}
+
+/// Removes the session shim directory if it exists.
+#[instrument]
+pub fn clear(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// The temporary directory `cargo hoist shell` places binaries into.
+pub fn shell_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("cargo-hoist-shell-{}", std::process::id()))
+}
+
+/// Places the latest registered builds of the named binaries, glob patterns
+/// or `name@version` specs into `dir`, returning the placed binaries.
+#[instrument(skip(ctx, names))]
+pub fn place(ctx: &HoistContext, names: &[String], dir: &Path) -> Result<Vec<HoistedBinary>> {
+    let mut registry = HoistRegistry::load(ctx)?;
+    registry.drop_expired(ctx)?;
+    let mut placed = Vec::with_capacity(names.len());
+    for spec in registry.expand_globs(names)? {
+        let latest = registry
+            .binaries
+            .iter()
+            .filter(|b| b.matches(&spec))
+            .max_by_key(|b| crate::registry::recency(b))
+            .ok_or(anyhow::anyhow!("{} is not in the hoist registry", spec))?;
+        placed.push(latest.clone());
+    }
+    std::fs::create_dir_all(dir)?;
+    for b in &placed {
+        b.hoist_to_dir(dir, CopyStrategy::Symlink, Preserve::Times)?;
+    }
+    Ok(placed)
+}
+
+/// Spawns `$SHELL` with the named registered binaries on its `PATH`, placed
+/// into a temporary directory that is removed once the shell exits.
+///
+/// With `print`, prints the line prepending the directory to `PATH` instead,
+/// for `eval`, leaving the directory to the system temp directory cleanup.
+#[instrument(skip(ctx, names))]
+pub fn shell(ctx: &HoistContext, names: Vec<String>, print: bool, quiet: bool) -> Result<()> {
+    if names.is_empty() {
+        anyhow::bail!("Name the binaries to put on the shell's PATH");
+    }
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let dir = shell_dir();
+    let placed = match place(ctx, &names, &dir) {
+        Ok(placed) => placed,
+        Err(e) => {
+            clear(&dir)?;
+            return Err(e);
+        }
+    };
+    let path = std::env::var("PATH").unwrap_or_default();
+    let path = prepend_path(&path, &dir);
+    if print {
+        println!("export PATH={}", shell_quote(&path));
+        return Ok(());
+    }
+
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    if !quiet {
+        let names: Vec<_> = placed.iter().map(HoistedBinary::display_name).collect();
+        HoistRegistry::print_color(
+            &format!(
+                "Started {} with {} on PATH, exit it to clean up",
+                shell,
+                names.join(", ")
+            ),
+            termcolor::Color::Green,
+            true,
+        )?;
+    }
+    let status = std::process::Command::new(&shell)
+        .env("PATH", &path)
+        .env("CARGO_HOIST_SHELL", &dir)
+        .status();
+    clear(&dir)?;
+    status.map_err(|e| anyhow::anyhow!("Failed to start {}: {}", shell, e))?;
+    Ok(())
+}
+
+/// Quotes a string for safe use in a posix shell.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r#"'\''"#))
+}
+
+impl HoistRegistry {
+    /// Prints the shell commands that scope the session shim directory to the
+    /// project manifest of the current working directory.
+    ///
+    /// Entering a project with a manifest links its binaries into the session
+    /// shim directory and prepends it to `PATH`. Leaving the project removes
+    /// the shim directory and strips it from `PATH` again. The directories of
+    /// sessions whose shell exited are pruned along the way.
+    ///
+    /// Runs on every prompt, so it never sets up the hoist directory or
+    /// installs the shell hook.
+    #[instrument(skip(session))]
+    pub fn env(ctx: &HoistContext, session: impl AsRef<str>) -> Result<()> {
+        if let Err(e) = prune(ctx) {
+            tracing::warn!("Failed to prune exited sessions: {}", e);
+        }
+        let dir = session_dir(ctx, session.as_ref());
+        let path = std::env::var("PATH").unwrap_or_default();
+        let mut path = strip_path(&path, &dir);
+
+        match ProjectManifest::find(&ctx.cwd) {
+            Some(m) => {
+                let manifest = ProjectManifest::load(&m)?;
+                let registry = HoistRegistry::load_dry_run(ctx)?;
+                let mut bins = Vec::with_capacity(manifest.binaries.len());
+                for (name, _) in manifest.requirements() {
+                    match registry.latest(name) {
+                        Some(b) => bins.push(b.clone()),
+                        None => tracing::warn!("{} is not in the hoist registry", name),
+                    }
+                }
+                materialize(&dir, &bins)?;
+                path = prepend_path(&path, &dir);
+            }
+            None => clear(&dir)?,
+        }
+
+        println!("export PATH={}", shell_quote(&path));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+    use std::os::unix::prelude::OpenOptionsExt;
+
+    #[test]
+    fn test_strip_and_prepend_path() {
+        let dir = PathBuf::from("/tmp/session");
+        let path = prepend_path("/usr/bin:/bin", &dir);
+        assert_eq!(path, "/tmp/session:/usr/bin:/bin");
+        assert_eq!(strip_path(&path, &dir), "/usr/bin:/bin");
+        assert_eq!(prepend_path("", &dir), "/tmp/session");
+    }
+
+    #[test]
+    fn test_materialize_and_clear() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let bin = tempdir.path().join("binary1");
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(&bin)
+            .unwrap();
+        let dir = tempdir.path().join("sessions/1");
+
+        materialize(&dir, &[HoistedBinary::new("binary1", bin.clone())]).unwrap();
+        assert_eq!(std::fs::read_link(dir.join("binary1")).unwrap(), bin);
+
+        clear(&dir).unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_prune() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let exited = child.id().to_string();
+        child.wait().unwrap();
+        let running = std::process::id().to_string();
+        for session in [exited.as_str(), running.as_str(), "named"] {
+            std::fs::create_dir_all(session_dir(&ctx, session)).unwrap();
+        }
+
+        assert_eq!(prune(&ctx).unwrap(), 1);
+        assert!(!session_dir(&ctx, &exited).exists());
+        assert!(session_dir(&ctx, &running).exists());
+        assert!(session_dir(&ctx, "named").exists());
+    }
+
+    #[test]
+    fn test_place() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        let bin = tempdir.path().join("tool");
+        std::fs::write(&bin, "tool").unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary::new("tool", bin.clone()));
+        registry.save(&ctx).unwrap();
+
+        let dir = tempdir.path().join("shell");
+        let placed = place(&ctx, &["to*".to_string()], &dir).unwrap();
+        assert_eq!(placed.len(), 1);
+        assert_eq!(std::fs::read_link(dir.join("tool")).unwrap(), bin);
+        assert!(place(&ctx, &["missing".to_string()], &dir).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r#"'it'\''s'"#);
+    }
+
+    #[test]
+    fn test_env() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_env");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        std::fs::write(
+            test_tempdir.join(crate::manifest::MANIFEST_FILE),
+            "binaries = [\"binary1\"]\n",
+        )
+        .unwrap();
+
+        HoistRegistry::env(&ctx, "1").unwrap();
+        let session = crate::session::session_dir(&ctx, "1");
+        assert!(session.join("binary1").exists());
+        assert!(!session.join("binary2").exists());
+
+        std::fs::remove_file(test_tempdir.join(crate::manifest::MANIFEST_FILE)).unwrap();
+        HoistRegistry::env(&ctx, "1").unwrap();
+        assert!(!session.exists());
+
+        let fresh = HoistContext::new(tempdir.path().join("fresh"), &test_tempdir);
+        HoistRegistry::env(&fresh, "1").unwrap();
+        assert!(!fresh.hook_identifier().exists());
+        assert!(!fresh.registry_path().exists());
+    }
+}
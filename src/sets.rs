@@ -0,0 +1,285 @@
+//! Binary Sets
+//!
+//! Named sets of binaries stored in `~/.hoist/sets.toml`, e.g. an `infra`
+//! set of `op-node` and `op-batcher`, so a whole environment can be hoisted
+//! or put on a subshell's PATH by its set name.
+//!
+//! Members are kept as given, so a set can name binaries, `name@version`
+//! specs or glob patterns, resolved against the registry when it is used.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::hoist::HoistOptions;
+use crate::registry::HoistRegistry;
+
+/// The named binary sets.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinarySets {
+    /// The members of each set, by set name
+    #[serde(flatten)]
+    pub sets: BTreeMap<String, Vec<String>>,
+}
+
+impl BinarySets {
+    /// The path of the sets file.
+    pub fn path(ctx: &HoistContext) -> PathBuf {
+        ctx.hoist_dir().join("sets.toml")
+    }
+
+    /// Loads the sets, which are empty if the sets file does not exist.
+    #[instrument(skip(ctx))]
+    pub fn load(ctx: &HoistContext) -> Result<Self> {
+        let path = BinarySets::path(ctx);
+        if !path.exists() {
+            return Ok(BinarySets::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the sets file.
+    #[instrument(skip(self, ctx))]
+    pub fn save(&self, ctx: &HoistContext) -> Result<()> {
+        std::fs::create_dir_all(ctx.hoist_dir())?;
+        std::fs::write(BinarySets::path(ctx), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The members of the set `name`.
+    pub fn members(&self, name: &str) -> Result<&[String]> {
+        self.sets
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or(anyhow::anyhow!("{} is not a binary set", name))
+    }
+}
+
+/// Updates the sets under the registry lock and prints the changed set.
+fn update(
+    ctx: &HoistContext,
+    name: &str,
+    quiet: bool,
+    f: impl FnOnce(&mut BinarySets) -> Result<()>,
+) -> Result<()> {
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut sets = BinarySets::load(ctx)?;
+    f(&mut sets)?;
+    sets.save(ctx)?;
+    if !quiet {
+        if let Some(members) = sets.sets.get(name) {
+            print_set(name, members)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints a set as `name: member, member`.
+fn print_set(name: &str, members: &[String]) -> Result<()> {
+    HoistRegistry::print_color(&format!("{}: ", name), Color::Blue, false)?;
+    HoistRegistry::print_color(&members.join(", "), Color::Magenta, true)
+}
+
+/// Adds `members` missing from `set`, keeping the order they are given in.
+fn extend(set: &mut Vec<String>, members: Vec<String>) {
+    for m in members {
+        if !set.contains(&m) {
+            set.push(m);
+        }
+    }
+}
+
+/// Creates the set `name` of `members`, replacing an existing set of that
+/// name with `force`.
+#[instrument(skip(ctx, members))]
+pub fn create(
+    ctx: &HoistContext,
+    name: &str,
+    members: Vec<String>,
+    force: bool,
+    quiet: bool,
+) -> Result<()> {
+    if !crate::registry::is_valid_name(name) {
+        anyhow::bail!("Invalid set name {:?}", name);
+    }
+    if members.is_empty() {
+        anyhow::bail!("Name the binaries of the set");
+    }
+    update(ctx, name, quiet, |sets| {
+        if sets.sets.contains_key(name) && !force {
+            anyhow::bail!(
+                "The set {} already exists, pass --force to replace it",
+                name
+            );
+        }
+        let mut set = Vec::with_capacity(members.len());
+        extend(&mut set, members);
+        sets.sets.insert(name.to_string(), set);
+        Ok(())
+    })
+}
+
+/// Adds `members` to the set `name`.
+#[instrument(skip(ctx, members))]
+pub fn add(ctx: &HoistContext, name: &str, members: Vec<String>, quiet: bool) -> Result<()> {
+    update(ctx, name, quiet, |sets| {
+        let set = sets
+            .sets
+            .get_mut(name)
+            .ok_or(anyhow::anyhow!("{} is not a binary set", name))?;
+        extend(set, members);
+        Ok(())
+    })
+}
+
+/// Removes `members` from the set `name`.
+///
+/// Fails if a member is not in the set, or the set would be left empty.
+#[instrument(skip(ctx, members))]
+pub fn remove(ctx: &HoistContext, name: &str, members: Vec<String>, quiet: bool) -> Result<()> {
+    update(ctx, name, quiet, |sets| {
+        let set = sets
+            .sets
+            .get_mut(name)
+            .ok_or(anyhow::anyhow!("{} is not a binary set", name))?;
+        if let Some(missing) = members.iter().find(|m| !set.contains(m)) {
+            anyhow::bail!("{} is not in the set {}", missing, name);
+        }
+        set.retain(|m| !members.contains(m));
+        if set.is_empty() {
+            anyhow::bail!(
+                "Removing every binary would leave the set {} empty, delete it instead",
+                name
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Deletes the set `name`.
+#[instrument(skip(ctx))]
+pub fn delete(ctx: &HoistContext, name: &str, quiet: bool) -> Result<()> {
+    update(ctx, name, quiet, |sets| match sets.sets.remove(name) {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("{} is not a binary set", name),
+    })?;
+    if !quiet {
+        HoistRegistry::print_color("Deleted the set ", Color::Green, false)?;
+        HoistRegistry::print_color(name, Color::Magenta, true)?;
+    }
+    Ok(())
+}
+
+/// Prints every set with its members.
+#[instrument(skip(ctx))]
+pub fn list(ctx: &HoistContext) -> Result<()> {
+    let sets = BinarySets::load(ctx)?;
+    if sets.sets.is_empty() {
+        return HoistRegistry::print_color(
+            "No binary sets, create one with `cargo hoist set create <name> <bins>`",
+            Color::Yellow,
+            true,
+        );
+    }
+    for (name, members) in &sets.sets {
+        print_set(name, members)?;
+    }
+    Ok(())
+}
+
+/// Hoists the latest registered build of every member of the set `name`,
+/// as `hoist` does with `options`.
+#[instrument(skip(ctx, options))]
+pub fn hoist(ctx: &HoistContext, name: &str, options: HoistOptions, quiet: bool) -> Result<()> {
+    let members = BinarySets::load(ctx)?.members(name)?.to_vec();
+    HoistRegistry::hoist_with(
+        ctx,
+        members,
+        HoistOptions {
+            latest: true,
+            ..options
+        },
+        quiet,
+    )
+}
+
+/// Starts a subshell with the members of the set `name` on its PATH, as
+/// `shell` does.
+#[instrument(skip(ctx))]
+pub fn shell(ctx: &HoistContext, name: &str, print: bool, quiet: bool) -> Result<()> {
+    let members = BinarySets::load(ctx)?.members(name)?.to_vec();
+    crate::session::shell(ctx, members, print, quiet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_sets() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        std::fs::write(ctx.hook_identifier(), "").unwrap();
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        create(&ctx, "infra", names(&["op-node", "op-node"]), false, true).unwrap();
+        assert!(create(&ctx, "infra", names(&["op-node"]), false, true).is_err());
+        assert!(create(&ctx, "bad name", names(&["op-node"]), false, true).is_err());
+        add(&ctx, "infra", names(&["op-batcher", "op-node"]), true).unwrap();
+        assert!(add(&ctx, "missing", names(&["a"]), true).is_err());
+        let sets = BinarySets::load(&ctx).unwrap();
+        assert_eq!(
+            sets.members("infra").unwrap(),
+            names(&["op-node", "op-batcher"])
+        );
+
+        assert!(remove(&ctx, "infra", names(&["op-proposer"]), true).is_err());
+        assert!(remove(&ctx, "infra", names(&["op-node", "op-batcher"]), true).is_err());
+        remove(&ctx, "infra", names(&["op-node"]), true).unwrap();
+        create(&ctx, "infra", names(&["op-geth"]), true, true).unwrap();
+        let contents = std::fs::read_to_string(BinarySets::path(&ctx)).unwrap();
+        assert_eq!(contents, "infra = [\"op-geth\"]\n");
+
+        delete(&ctx, "infra", true).unwrap();
+        assert!(delete(&ctx, "infra", true).is_err());
+        assert!(BinarySets::load(&ctx).unwrap().members("infra").is_err());
+    }
+
+    #[test]
+    fn test_hoist_set() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        std::fs::write(ctx.hook_identifier(), "").unwrap();
+        let bin = tempdir.path().join("build/tool");
+        std::fs::create_dir_all(bin.parent().unwrap()).unwrap();
+        std::fs::write(&bin, "#!/bin/sh\n").unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry
+            .binaries
+            .insert(crate::binaries::HoistedBinary::new("tool", bin));
+        registry.save(&ctx).unwrap();
+
+        create(&ctx, "dev", vec!["tool".to_string()], false, true).unwrap();
+        let dest = tempdir.path().join("dest");
+        hoist(
+            &ctx,
+            "dev",
+            HoistOptions {
+                to: Some(dest.clone()),
+                ..Default::default()
+            },
+            true,
+        )
+        .unwrap();
+        assert!(dest.join("tool").exists());
+        assert!(hoist(&ctx, "missing", HoistOptions::default(), true).is_err());
+    }
+}
@@ -1,10 +1,27 @@
 //! Shell Utilities
+//!
+//! Each supported shell implements [ShellHook], which owns everything hoist
+//! needs to know about that shell: how to detect it, the pre-cargo hook
+//! snippet, where its config file lives, and how to install or remove the
+//! hook from it. Supporting a new shell only requires a new [ShellHook],
+//! listed in [SHELLS].
 
 use anyhow::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// The bash function to install the hoist cargo pre-hook.
 pub const INSTALL_BASH_FUNCTION: &str = r#"
+function cargo() {
+    if ~/.cargo/bin/cargo hoist --help &>/dev/null; then
+      ~/.cargo/bin/cargo hoist --quiet install --hook
+    fi
+    ~/.cargo/bin/cargo "$@"
+}
+"#;
+
+/// The bash function installed by hoist versions that registered builds
+/// without marking them as triggered by the hook.
+pub const LEGACY_BASH_FUNCTION: &str = r#"
 function cargo() {
     if ~/.cargo/bin/cargo hoist --help &>/dev/null; then
       ~/.cargo/bin/cargo hoist --quiet install
@@ -13,39 +30,513 @@ function cargo() {
 }
 "#;
 
-/// The type of shell
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ShellType {
-    /// Zsh
-    Zsh,
-    /// Bash
-    Bash,
-    /// Other
-    Other,
+/// The fish function to install the hoist cargo pre-hook.
+pub const INSTALL_FISH_FUNCTION: &str = r#"
+function cargo --wraps cargo
+    if ~/.cargo/bin/cargo hoist --help &>/dev/null
+        ~/.cargo/bin/cargo hoist --quiet install --hook
+    end
+    ~/.cargo/bin/cargo $argv
+end
+"#;
+
+/// The nushell command to install the hoist cargo pre-hook.
+pub const INSTALL_NU_FUNCTION: &str = r#"
+def --wrapped cargo [...args] {
+    let cargo = ($env.HOME | path join ".cargo/bin/cargo")
+    if (^$cargo hoist --help | complete).exit_code == 0 {
+        ^$cargo hoist --quiet install --hook
+    }
+    ^$cargo ...$args
+}
+"#;
+
+/// The powershell function to install the hoist cargo pre-hook.
+pub const INSTALL_POWERSHELL_FUNCTION: &str = r#"
+function cargo {
+    $cargo = Join-Path $HOME ".cargo/bin/cargo"
+    & $cargo hoist --help *> $null
+    if ($LASTEXITCODE -eq 0) {
+        & $cargo hoist --quiet install --hook
+    }
+    & $cargo @args
+}
+"#;
+
+/// The elvish function to install the hoist cargo pre-hook.
+pub const INSTALL_ELVISH_FUNCTION: &str = r#"
+fn cargo {|@args|
+    var bin = $E:HOME/.cargo/bin/cargo
+    try {
+        $bin hoist --help > /dev/null 2>&1
+        $bin hoist --quiet install --hook
+    } catch { }
+    $bin $@args
+}
+"#;
+
+/// The xonsh alias to install the hoist cargo pre-hook.
+pub const INSTALL_XONSH_FUNCTION: &str = r#"
+def _cargo_hoist_cargo(args):
+    import os
+    cargo = os.path.expanduser("~/.cargo/bin/cargo")
+    if !(@(cargo) hoist --help):
+        ![@(cargo) hoist --quiet install --hook]
+    return ![@(cargo) @(args)].returncode
+
+aliases["cargo"] = _cargo_hoist_cargo
+"#;
+
+/// The bash hook that keeps the session shim directory in sync with the
+/// project manifest of the current working directory.
+pub const SESSION_BASH_HOOK: &str = r#"
+_cargo_hoist_session() {
+    if [[ "$PWD" != "${_CARGO_HOIST_LAST_PWD:-}" ]]; then
+        _CARGO_HOIST_LAST_PWD="$PWD"
+        eval "$(~/.cargo/bin/cargo hoist --quiet env --session $$)"
+    fi
+}
+if [[ ";${PROMPT_COMMAND:-};" != *";_cargo_hoist_session;"* ]]; then
+    PROMPT_COMMAND="_cargo_hoist_session${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#;
+
+/// The zsh hook that keeps the session shim directory in sync with the
+/// project manifest of the current working directory.
+pub const SESSION_ZSH_HOOK: &str = r#"
+_cargo_hoist_session() {
+    eval "$(~/.cargo/bin/cargo hoist --quiet env --session $$)"
 }
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _cargo_hoist_session
+_cargo_hoist_session
+"#;
+
+/// The integration between hoist and a single shell.
+///
+/// Only [ShellHook::name], [ShellHook::hook] and [ShellHook::config_file]
+/// are required. Detection defaults to matching the name against the shell
+/// binary, and installation, detection of an existing install and removal
+/// default to appending, searching for and stripping the hook snippet in the
+/// config file.
+pub trait ShellHook: std::fmt::Debug + Send + Sync {
+    /// The shell name, e.g. `bash`.
+    fn name(&self) -> &'static str;
+
+    /// Returns if the shell binary at `shell`, typically `$SHELL`, is this shell.
+    fn detect(&self, shell: &Path) -> bool {
+        shell.file_name().is_some_and(|n| n == self.name())
+    }
+
+    /// The snippet that runs `cargo hoist install` before every cargo invocation.
+    fn hook(&self) -> &'static str;
+
+    /// The snippets earlier versions of hoist installed in place of the hook.
+    fn legacy_hooks(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The snippet that keeps the session shim directory on `PATH`, if supported.
+    fn session_hook(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The path to the shell's config file within the `home` directory.
+    fn config_file(&self, home: &Path) -> PathBuf;
+
+    /// Returns if the hook is present in the shell's config file.
+    fn is_installed(&self, home: &Path) -> Result<bool> {
+        let config = self.config_file(home);
+        if !config.exists() {
+            return Ok(false);
+        }
+        Ok(std::fs::read_to_string(config)?.contains(self.hook()))
+    }
 
-/// Detect the type of shell a user is using.
+    /// Appends the hook to the shell's config file, which must already exist,
+    /// unless it is installed or an earlier version of it can be upgraded.
+    fn install(&self, home: &Path) -> Result<()> {
+        use std::io::Write;
+        let config = self.config_file(home);
+        if !config.exists() {
+            anyhow::bail!("{} does not exist", config.display());
+        }
+        if self.upgrade(home)? || self.is_installed(home)? {
+            return Ok(());
+        }
+        let mut file = std::fs::OpenOptions::new().append(true).open(config)?;
+        file.write_all(self.hook().as_bytes())?;
+        Ok(())
+    }
+
+    /// Replaces a hook installed by an earlier version of hoist in the shell's
+    /// config file with the current hook. Returns if one was replaced.
+    fn upgrade(&self, home: &Path) -> Result<bool> {
+        let config = self.config_file(home);
+        if !config.exists() {
+            return Ok(false);
+        }
+        let contents = std::fs::read_to_string(&config)?;
+        let Some(legacy) = self.legacy_hooks().iter().find(|h| contents.contains(**h)) else {
+            return Ok(false);
+        };
+        std::fs::write(config, contents.replacen(legacy, self.hook(), 1))?;
+        Ok(true)
+    }
+
+    /// Removes the hook from the shell's config file.
+    fn uninstall(&self, home: &Path) -> Result<()> {
+        if !self.is_installed(home)? {
+            return Ok(());
+        }
+        let config = self.config_file(home);
+        let contents = std::fs::read_to_string(&config)?;
+        std::fs::write(config, contents.replacen(self.hook(), "", 1))?;
+        Ok(())
+    }
+}
+
+/// The bash [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bash;
+
+impl ShellHook for Bash {
+    fn name(&self) -> &'static str {
+        "bash"
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_BASH_FUNCTION
+    }
+
+    fn legacy_hooks(&self) -> &'static [&'static str] {
+        &[LEGACY_BASH_FUNCTION]
+    }
+
+    fn session_hook(&self) -> Option<&'static str> {
+        Some(SESSION_BASH_HOOK)
+    }
+
+    fn config_file(&self, home: &Path) -> PathBuf {
+        home.join(".bashrc")
+    }
+}
+
+/// The zsh [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Zsh;
+
+impl ShellHook for Zsh {
+    fn name(&self) -> &'static str {
+        "zsh"
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_BASH_FUNCTION
+    }
+
+    fn legacy_hooks(&self) -> &'static [&'static str] {
+        &[LEGACY_BASH_FUNCTION]
+    }
+
+    fn session_hook(&self) -> Option<&'static str> {
+        Some(SESSION_ZSH_HOOK)
+    }
+
+    fn config_file(&self, home: &Path) -> PathBuf {
+        home.join(".zshrc")
+    }
+}
+
+/// The fish [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fish;
+
+impl ShellHook for Fish {
+    fn name(&self) -> &'static str {
+        "fish"
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_FISH_FUNCTION
+    }
+
+    fn config_file(&self, home: &Path) -> PathBuf {
+        home.join(".config/fish/config.fish")
+    }
+}
+
+/// The nushell [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Nu;
+
+impl ShellHook for Nu {
+    fn name(&self) -> &'static str {
+        "nu"
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_NU_FUNCTION
+    }
+
+    fn config_file(&self, home: &Path) -> PathBuf {
+        home.join(".config/nushell/config.nu")
+    }
+}
+
+/// The powershell [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PowerShell;
+
+impl ShellHook for PowerShell {
+    fn name(&self) -> &'static str {
+        "powershell"
+    }
+
+    fn detect(&self, shell: &Path) -> bool {
+        shell
+            .file_stem()
+            .is_some_and(|n| n == "pwsh" || n == "powershell")
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_POWERSHELL_FUNCTION
+    }
+
+    fn config_file(&self, home: &Path) -> PathBuf {
+        home.join(".config/powershell/Microsoft.PowerShell_profile.ps1")
+    }
+}
+
+/// The elvish [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Elvish;
+
+impl ShellHook for Elvish {
+    fn name(&self) -> &'static str {
+        "elvish"
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_ELVISH_FUNCTION
+    }
+
+    /// Prefers `~/.config/elvish/rc.elv`, falling back to the legacy
+    /// `~/.elvish/rc.elv` when only that one exists.
+    fn config_file(&self, home: &Path) -> PathBuf {
+        let legacy = home.join(".elvish/rc.elv");
+        let config = home.join(".config/elvish/rc.elv");
+        match !config.exists() && legacy.exists() {
+            true => legacy,
+            false => config,
+        }
+    }
+}
+
+/// The xonsh [ShellHook].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Xonsh;
+
+impl ShellHook for Xonsh {
+    fn name(&self) -> &'static str {
+        "xonsh"
+    }
+
+    fn hook(&self) -> &'static str {
+        INSTALL_XONSH_FUNCTION
+    }
+
+    fn config_file(&self, home: &Path) -> PathBuf {
+        home.join(".xonshrc")
+    }
+}
+
+/// The supported shells, in the order they are detected in.
+pub static SHELLS: &[&dyn ShellHook] = &[&Zsh, &Bash, &Fish, &Nu, &PowerShell, &Elvish, &Xonsh];
+
+/// A supported shell, looked up in [SHELLS].
+#[derive(Debug, Clone, Copy)]
+pub struct ShellType(&'static dyn ShellHook);
+
+impl ShellType {
+    /// The bash shell, which unknown shells fall back to.
+    pub const BASH: ShellType = ShellType(&Bash);
+
+    /// Returns the shell named `name`, e.g. `bash`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        SHELLS
+            .iter()
+            .find(|s| s.name() == name)
+            .map(|s| ShellType(*s))
+    }
+
+    /// Returns the shell the shell binary at `shell` is.
+    pub fn detect(shell: &Path) -> Option<Self> {
+        SHELLS
+            .iter()
+            .find(|s| s.detect(shell))
+            .map(|s| ShellType(*s))
+    }
+
+    /// Returns the [ShellHook] for the shell type.
+    pub fn hook(&self) -> &'static dyn ShellHook {
+        self.0
+    }
+}
+
+impl PartialEq for ShellType {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.name() == other.0.name()
+    }
+}
+
+impl Eq for ShellType {}
+
+impl clap::ValueEnum for ShellType {
+    fn value_variants<'a>() -> &'a [Self] {
+        static VARIANTS: std::sync::OnceLock<Vec<ShellType>> = std::sync::OnceLock::new();
+        VARIANTS.get_or_init(|| SHELLS.iter().map(|s| ShellType(*s)).collect())
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.0.name()))
+    }
+}
+
+/// Detect the type of shell a user is using from `SHELL`, falling back to
+/// bash when it is unset or names a shell hoist does not support.
 pub fn detect_shell() -> Result<ShellType> {
-    if let Ok(shell_path) = std::env::var("SHELL") {
-        if shell_path.contains("zsh") {
-            Ok(ShellType::Zsh)
-        } else if shell_path.contains("bash") {
-            Ok(ShellType::Bash)
-        } else {
-            Ok(ShellType::Other)
+    let Ok(shell_path) = std::env::var("SHELL") else {
+        return Ok(ShellType::BASH);
+    };
+    match ShellType::detect(Path::new(&shell_path)) {
+        Some(shell) => Ok(shell),
+        None => {
+            tracing::warn!("Unrecognized shell {}, falling back to bash", shell_path);
+            Ok(ShellType::BASH)
         }
-    } else {
-        // default to bash for now
-        Ok(ShellType::Bash)
-        // Err(anyhow::anyhow!("Unable to determine the user's shell."))
     }
 }
 
 /// Helper to get the path to the user's shell config file.
+#[deprecated(note = "reads `HOME` from the process environment, use `ShellHook::config_file`")]
 pub fn get_shell_config_file(shell_type: ShellType) -> Result<PathBuf> {
     let home_dir = std::env::var("HOME")?;
-    match shell_type {
-        ShellType::Zsh => Ok(PathBuf::from(format!("{}/.zshrc", home_dir))),
-        _ => Ok(PathBuf::from(format!("{}/.bashrc", home_dir))),
+    Ok(shell_type.hook().config_file(Path::new(&home_dir)))
+}
+
+/// Returns the session hook snippet for the given shell type.
+pub fn session_hook(shell_type: ShellType) -> Result<&'static str> {
+    let hook = shell_type.hook();
+    hook.session_hook().ok_or(anyhow::anyhow!(
+        "session hooks are not supported for {}",
+        hook.name()
+    ))
+}
+
+/// Prints the session hook for the given shell, detecting the user's shell
+/// when none is provided.
+pub fn print_session_hook(shell_type: Option<ShellType>) -> Result<()> {
+    let shell_type = match shell_type {
+        Some(s) => s,
+        None => detect_shell()?,
+    };
+    print!("{}", session_hook(shell_type)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert!(Zsh.detect(Path::new("/bin/zsh")));
+        assert!(Nu.detect(Path::new("/usr/local/bin/nu")));
+        assert!(!Nu.detect(Path::new("/usr/local/bin/nushell-wrapper")));
+        assert!(PowerShell.detect(Path::new("/opt/microsoft/powershell/pwsh")));
+        assert!(PowerShell.detect(Path::new("/usr/bin/powershell")));
+        assert!(Elvish.detect(Path::new("/usr/bin/elvish")));
+        assert!(Xonsh.detect(Path::new("/home/me/.local/bin/xonsh")));
+    }
+
+    #[test]
+    fn test_shell_type() {
+        use clap::ValueEnum;
+        assert_eq!(ShellType::from_name("fish").unwrap().hook().name(), "fish");
+        assert!(ShellType::from_name("cmd").is_none());
+        assert_eq!(
+            ShellType::detect(Path::new("/usr/bin/pwsh")),
+            ShellType::from_name("powershell")
+        );
+        assert!(ShellType::detect(Path::new("/bin/tcsh")).is_none());
+        assert_eq!(
+            ShellType::from_str("nu", false).unwrap().hook().name(),
+            "nu"
+        );
+        assert_eq!(ShellType::value_variants().len(), SHELLS.len());
+    }
+
+    #[test]
+    fn test_elvish_config_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let home = tempdir.path();
+        assert_eq!(Elvish.config_file(home), home.join(".config/elvish/rc.elv"));
+
+        std::fs::create_dir_all(home.join(".elvish")).unwrap();
+        std::fs::write(home.join(".elvish/rc.elv"), "").unwrap();
+        assert_eq!(Elvish.config_file(home), home.join(".elvish/rc.elv"));
+
+        std::fs::create_dir_all(home.join(".config/elvish")).unwrap();
+        std::fs::write(home.join(".config/elvish/rc.elv"), "").unwrap();
+        assert_eq!(Elvish.config_file(home), home.join(".config/elvish/rc.elv"));
+    }
+
+    #[test]
+    fn test_install_uninstall() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let home = tempdir.path();
+        assert!(Fish.install(home).is_err());
+
+        let config = Fish.config_file(home);
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(&config, "set -x EDITOR vim\n").unwrap();
+        Fish.install(home).unwrap();
+        Fish.install(home).unwrap();
+        assert!(Fish.is_installed(home).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            format!("set -x EDITOR vim\n{}", INSTALL_FISH_FUNCTION)
+        );
+
+        Fish.uninstall(home).unwrap();
+        assert!(!Fish.upgrade(home).unwrap());
+        assert!(!Fish.is_installed(home).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            "set -x EDITOR vim\n"
+        );
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let home = tempdir.path();
+        assert!(!Bash.upgrade(home).unwrap());
+
+        let config = Bash.config_file(home);
+        std::fs::write(
+            &config,
+            format!("alias ll='ls -l'\n{}", LEGACY_BASH_FUNCTION),
+        )
+        .unwrap();
+        assert!(!Bash.is_installed(home).unwrap());
+        assert!(Bash.upgrade(home).unwrap());
+        assert!(Bash.is_installed(home).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            format!("alias ll='ls -l'\n{}", INSTALL_BASH_FUNCTION)
+        );
+        assert!(!Bash.upgrade(home).unwrap());
     }
 }
@@ -0,0 +1,197 @@
+//! Shims
+//!
+//! The managed shim directory, `~/.hoist/bin`.
+//!
+//! Unlike the shims `hoist --shim` writes, which run the build they were
+//! written for, the shims in the managed directory run their binary through
+//! `cargo hoist exec` by name, so each invocation resolves the latest
+//! registered build. With the directory on `PATH`, rebuilt binaries are
+//! runnable everywhere without hoisting them into each project.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+/// The line following the shebang of every shim written by cargo-hoist.
+const MARKER: &str = "# Generated by cargo-hoist.";
+
+/// The path to the managed shim directory.
+pub fn shim_dir(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("bin")
+}
+
+/// The posix shell line prepending `dir` to `PATH`.
+pub fn export_line(dir: &Path) -> String {
+    format!(
+        "export PATH={}:\"$PATH\"",
+        crate::session::shell_quote(&dir.display().to_string())
+    )
+}
+
+/// The posix shell shim that runs the latest registered build of `name`
+/// through `cargo hoist exec`.
+pub fn sh_shim(name: &str) -> String {
+    format!(
+        "#!/bin/sh\n{}\nexec \"${{CARGO_HOIST:-cargo-hoist}}\" hoist exec {} -- \"$@\"\n",
+        MARKER,
+        crate::session::shell_quote(name),
+    )
+}
+
+/// Returns if the file at `path` is a shim written by cargo-hoist.
+pub fn is_shim(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .is_ok_and(|s| s.starts_with("#!/bin/sh\n") && s.lines().nth(1) == Some(MARKER))
+}
+
+/// Prints the line adding the managed shim directory to `PATH`.
+pub fn print_path(ctx: &HoistContext) -> Result<()> {
+    println!("{}", export_line(&shim_dir(ctx)));
+    Ok(())
+}
+
+/// Writes shims for the named registered binaries, or with `all`, every
+/// registered binary, into the managed shim directory.
+#[instrument(skip(ctx, names))]
+pub fn shim(ctx: &HoistContext, names: Vec<String>, all: bool, quiet: bool) -> Result<()> {
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    registry.drop_expired(ctx)?;
+    let mut names = match all {
+        true => registry
+            .binaries
+            .iter()
+            .filter(|b| b.kind.is_bin())
+            .map(|b| b.name.clone())
+            .collect(),
+        false => names,
+    };
+    names.sort();
+    names.dedup();
+    if let Some(missing) = names
+        .iter()
+        .find(|n| registry.latest(&registry.resolve_alias(n)).is_none())
+    {
+        anyhow::bail!("{} is not in the hoist registry", missing);
+    }
+
+    let dir = shim_dir(ctx);
+    std::fs::create_dir_all(&dir)?;
+    for name in &names {
+        write_shim(&dir, name)?;
+        if !quiet {
+            HoistRegistry::print_color("Shimmed ", Color::Green, false)?;
+            HoistRegistry::print_color(name, Color::Magenta, true)?;
+        }
+    }
+    let path = std::env::var("PATH").unwrap_or_default();
+    if !quiet && !names.is_empty() && !std::env::split_paths(&path).any(|p| p == dir) {
+        HoistRegistry::print_color(
+            "Add `eval \"$(cargo hoist path)\"` to your shell config to put the shims on PATH",
+            Color::Yellow,
+            true,
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes the shims of the named binaries, or with `all`, every shim, from
+/// the managed shim directory.
+#[instrument(skip(ctx, names))]
+pub fn unshim(ctx: &HoistContext, names: Vec<String>, all: bool, quiet: bool) -> Result<()> {
+    let dir = shim_dir(ctx);
+    let shims = match all {
+        true => match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| is_shim(p))
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+        false => {
+            let mut shims = Vec::with_capacity(names.len());
+            for name in &names {
+                let path = dir.join(name);
+                if !is_shim(&path) {
+                    anyhow::bail!("{} is not shimmed", name);
+                }
+                shims.push(path);
+            }
+            shims
+        }
+    };
+    for path in shims {
+        std::fs::remove_file(&path)?;
+        if !quiet {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            HoistRegistry::print_color("Unshimmed ", Color::Green, false)?;
+            HoistRegistry::print_color(&name, Color::Magenta, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the shim for `name` into `dir`.
+fn write_shim(dir: &Path, name: &str) -> Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o755)
+        .open(dir.join(name))?;
+    std::io::Write::write_all(&mut file, sh_shim(name).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binaries::HoistedBinary;
+
+    #[test]
+    fn test_shim_and_unshim() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        std::fs::write(ctx.hook_identifier(), "hook").unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary::new("tool", tempdir.path().join("tool")));
+        registry.insert(HoistedBinary::new("other", tempdir.path().join("other")));
+        registry.save(&ctx).unwrap();
+
+        assert!(shim(&ctx, vec!["missing".to_string()], false, true).is_err());
+        shim(&ctx, vec!["tool".to_string()], false, true).unwrap();
+        let dir = shim_dir(&ctx);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("tool")).unwrap(),
+            sh_shim("tool")
+        );
+        assert!(sh_shim("tool").contains("hoist exec 'tool' --"));
+        assert!(!dir.join("other").exists());
+
+        shim(&ctx, vec![], true, true).unwrap();
+        assert!(is_shim(&dir.join("other")));
+        std::fs::write(dir.join("mine"), "#!/bin/sh\necho mine\n").unwrap();
+        assert!(unshim(&ctx, vec!["mine".to_string()], false, true).is_err());
+
+        unshim(&ctx, vec!["tool".to_string()], false, true).unwrap();
+        assert!(!dir.join("tool").exists());
+        unshim(&ctx, vec![], true, true).unwrap();
+        assert!(!dir.join("other").exists());
+        assert!(dir.join("mine").exists());
+    }
+
+    #[test]
+    fn test_export_line() {
+        assert_eq!(
+            export_line(Path::new("/home/u/.hoist/bin")),
+            "export PATH='/home/u/.hoist/bin':\"$PATH\""
+        );
+    }
+}
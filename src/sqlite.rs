@@ -0,0 +1,455 @@
+//! Sqlite
+//!
+//! A [RegistryStore] backed by a SQLite database, for registries too large to
+//! re-parse and rewrite as TOML on every command.
+//!
+//! Entries are keyed by name and location, with indexes on profile and tags,
+//! so [RegistryStore::select] lookups only read the matching rows. Saving
+//! diffs the registry against the stored rows and only writes the entries
+//! that changed.
+//!
+//! Links against the system `libsqlite3` through a minimal binding of its C
+//! API, enabled by the `sqlite` feature.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+use crate::store::{Filter, RegistryLock, RegistryStore, TomlStore};
+
+#[allow(non_camel_case_types)]
+type sqlite3 = c_void;
+#[allow(non_camel_case_types)]
+type sqlite3_stmt = c_void;
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+const SQLITE_OPEN_FULLMUTEX: c_int = 0x10000;
+/// Makes sqlite copy bound text before the bind call returns.
+const SQLITE_TRANSIENT: isize = -1;
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open_v2(
+        filename: *const c_char,
+        db: *mut *mut sqlite3,
+        flags: c_int,
+        vfs: *const c_char,
+    ) -> c_int;
+    fn sqlite3_close(db: *mut sqlite3) -> c_int;
+    fn sqlite3_errmsg(db: *mut sqlite3) -> *const c_char;
+    fn sqlite3_busy_timeout(db: *mut sqlite3, ms: c_int) -> c_int;
+    fn sqlite3_prepare_v2(
+        db: *mut sqlite3,
+        sql: *const c_char,
+        len: c_int,
+        stmt: *mut *mut sqlite3_stmt,
+        tail: *mut *const c_char,
+    ) -> c_int;
+    fn sqlite3_bind_text(
+        stmt: *mut sqlite3_stmt,
+        index: c_int,
+        text: *const c_char,
+        len: c_int,
+        destructor: isize,
+    ) -> c_int;
+    fn sqlite3_bind_null(stmt: *mut sqlite3_stmt, index: c_int) -> c_int;
+    fn sqlite3_step(stmt: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_column_text(stmt: *mut sqlite3_stmt, col: c_int) -> *const u8;
+    fn sqlite3_column_bytes(stmt: *mut sqlite3_stmt, col: c_int) -> c_int;
+    fn sqlite3_finalize(stmt: *mut sqlite3_stmt) -> c_int;
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS binaries (
+    name TEXT NOT NULL,
+    location TEXT NOT NULL,
+    profile TEXT,
+    entry TEXT NOT NULL,
+    PRIMARY KEY (name, location)
+);
+CREATE INDEX IF NOT EXISTS binaries_profile ON binaries (profile);
+CREATE TABLE IF NOT EXISTS tags (
+    tag TEXT NOT NULL,
+    name TEXT NOT NULL,
+    location TEXT NOT NULL,
+    PRIMARY KEY (tag, name, location)
+);
+CREATE TABLE IF NOT EXISTS aliases (
+    alias TEXT PRIMARY KEY,
+    name TEXT NOT NULL
+);
+";
+
+/// An open database connection.
+#[derive(Debug)]
+struct Connection {
+    db: *mut sqlite3,
+}
+
+// SAFETY: connections are opened in serialized mode, so sqlite synchronizes
+// every call on the handle internally.
+unsafe impl Send for Connection {}
+
+impl Connection {
+    fn open(path: &Path) -> Result<Self> {
+        let filename = CString::new(path.to_string_lossy().as_bytes())?;
+        let mut db = std::ptr::null_mut();
+        let flags = SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE | SQLITE_OPEN_FULLMUTEX;
+        // SAFETY: the filename is nul-terminated and `db` is a valid out pointer.
+        let rc = unsafe { sqlite3_open_v2(filename.as_ptr(), &mut db, flags, std::ptr::null()) };
+        // sqlite allocates a handle carrying the error message even on failure.
+        let connection = Self { db };
+        if rc != SQLITE_OK {
+            anyhow::bail!("Failed to open {}: {}", path.display(), connection.error());
+        }
+        // SAFETY: the handle is open.
+        unsafe { sqlite3_busy_timeout(db, 5_000) };
+        Ok(connection)
+    }
+
+    fn error(&self) -> String {
+        if self.db.is_null() {
+            return "out of memory".to_string();
+        }
+        // SAFETY: the handle is valid and sqlite returns a nul-terminated message.
+        unsafe { CStr::from_ptr(sqlite3_errmsg(self.db)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Runs each `;`-separated statement of `sql`.
+    fn execute_batch(&self, sql: &str) -> Result<()> {
+        for statement in sql.split(';').filter(|s| !s.trim().is_empty()) {
+            self.execute(statement, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Runs a statement binding `params` to its `?` placeholders.
+    fn execute(&self, sql: &str, params: &[Option<&str>]) -> Result<()> {
+        self.query(sql, params, |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Runs a query binding `params` to its `?` placeholders, mapping each
+    /// row with `f`.
+    fn query<T>(
+        &self,
+        sql: &str,
+        params: &[Option<&str>],
+        mut f: impl FnMut(&Row<'_>) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let statement = Statement::prepare(self, sql)?;
+        for (i, param) in params.iter().enumerate() {
+            statement.bind(i as c_int + 1, *param)?;
+        }
+        let mut rows = vec![];
+        loop {
+            // SAFETY: the statement is prepared and its params are bound.
+            match unsafe { sqlite3_step(statement.stmt) } {
+                SQLITE_ROW => rows.push(f(&Row(&statement))?),
+                SQLITE_DONE => return Ok(rows),
+                _ => anyhow::bail!("Failed to run {:?}: {}", sql.trim(), self.error()),
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: every statement is finalized before the connection drops.
+        unsafe { sqlite3_close(self.db) };
+    }
+}
+
+/// A prepared statement, finalized when dropped.
+struct Statement<'a> {
+    connection: &'a Connection,
+    stmt: *mut sqlite3_stmt,
+}
+
+impl<'a> Statement<'a> {
+    fn prepare(connection: &'a Connection, sql: &str) -> Result<Self> {
+        let sql = CString::new(sql)?;
+        let mut stmt = std::ptr::null_mut();
+        // SAFETY: the handle is open, the sql is nul-terminated and `stmt` is
+        // a valid out pointer.
+        let rc = unsafe {
+            sqlite3_prepare_v2(
+                connection.db,
+                sql.as_ptr(),
+                -1,
+                &mut stmt,
+                std::ptr::null_mut(),
+            )
+        };
+        if rc != SQLITE_OK {
+            anyhow::bail!("Failed to prepare {:?}: {}", sql, connection.error());
+        }
+        Ok(Self { connection, stmt })
+    }
+
+    fn bind(&self, index: c_int, value: Option<&str>) -> Result<()> {
+        // SAFETY: the statement is prepared, and sqlite copies transient text.
+        let rc = unsafe {
+            match value {
+                Some(v) => sqlite3_bind_text(
+                    self.stmt,
+                    index,
+                    v.as_ptr() as *const c_char,
+                    v.len() as c_int,
+                    SQLITE_TRANSIENT,
+                ),
+                None => sqlite3_bind_null(self.stmt, index),
+            }
+        };
+        if rc != SQLITE_OK {
+            anyhow::bail!("Failed to bind parameter: {}", self.connection.error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the statement was prepared and is finalized exactly once.
+        unsafe { sqlite3_finalize(self.stmt) };
+    }
+}
+
+/// The current row of a stepped [Statement].
+struct Row<'a>(&'a Statement<'a>);
+
+impl Row<'_> {
+    fn text(&self, col: c_int) -> String {
+        // SAFETY: the statement is positioned on a row. sqlite owns the text
+        // until the next step, and it is copied out before then.
+        unsafe {
+            let text = sqlite3_column_text(self.0.stmt, col);
+            if text.is_null() {
+                return String::new();
+            }
+            let len = sqlite3_column_bytes(self.0.stmt, col) as usize;
+            String::from_utf8_lossy(std::slice::from_raw_parts(text, len)).into_owned()
+        }
+    }
+}
+
+/// A [RegistryStore] backed by a SQLite database.
+#[derive(Debug)]
+pub struct SqliteStore {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it and its tables if needed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let connection = Connection::open(&path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            path,
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// The database path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Loads the aliases, and the binaries matching the `where` clause.
+    fn select_where(&self, clause: &str, params: &[Option<&str>]) -> Result<HoistRegistry> {
+        let connection = self.connection();
+        let sql = format!("SELECT entry FROM binaries {}", clause);
+        let binaries = connection.query(&sql, params, |row| {
+            Ok(toml::from_str::<HoistedBinary>(&row.text(0))?)
+        })?;
+        let aliases = connection.query("SELECT alias, name FROM aliases", &[], |row| {
+            Ok((row.text(0), row.text(1)))
+        })?;
+        Ok(HoistRegistry {
+            binaries: binaries.into_iter().collect(),
+            aliases: aliases.into_iter().collect(),
+            ..Default::default()
+        })
+    }
+}
+
+impl RegistryStore for SqliteStore {
+    fn load(&self) -> Result<HoistRegistry> {
+        self.select_where("", &[])
+    }
+
+    fn select(&self, filter: &Filter) -> Result<HoistRegistry> {
+        let mut clauses = vec![];
+        let mut params = vec![];
+        if let Some(name) = &filter.name {
+            clauses.push("name = ?");
+            params.push(Some(name.as_str()));
+        }
+        if let Some(profile) = &filter.profile {
+            clauses.push("profile = ?");
+            params.push(Some(profile.as_str()));
+        }
+        if let Some(tag) = &filter.tag {
+            clauses.push("(name, location) IN (SELECT name, location FROM tags WHERE tag = ?)");
+            params.push(Some(tag.as_str()));
+        }
+        let clause = match clauses.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", clauses.join(" AND ")),
+        };
+        self.select_where(&clause, &params)
+    }
+
+    fn save(&self, registry: &HoistRegistry) -> Result<()> {
+        let connection = self.connection();
+        connection.execute("BEGIN IMMEDIATE", &[])?;
+        let result = (|| {
+            let stored: HashMap<(String, String), String> = connection
+                .query("SELECT name, location, entry FROM binaries", &[], |row| {
+                    Ok(((row.text(0), row.text(1)), row.text(2)))
+                })?
+                .into_iter()
+                .collect();
+            let mut kept = std::collections::HashSet::new();
+            for binary in &registry.binaries {
+                let key = (
+                    binary.name.clone(),
+                    binary.location.to_string_lossy().into_owned(),
+                );
+                let entry = toml::to_string(binary)?;
+                if stored.get(&key) != Some(&entry) {
+                    let (name, location) = (Some(key.0.as_str()), Some(key.1.as_str()));
+                    connection.execute(
+                        "INSERT OR REPLACE INTO binaries (name, location, profile, entry) VALUES (?, ?, ?, ?)",
+                        &[name, location, binary.profile.as_deref(), Some(&entry)],
+                    )?;
+                    connection.execute(
+                        "DELETE FROM tags WHERE name = ? AND location = ?",
+                        &[name, location],
+                    )?;
+                    for tag in &binary.tags {
+                        connection.execute(
+                            "INSERT INTO tags (tag, name, location) VALUES (?, ?, ?)",
+                            &[Some(tag), name, location],
+                        )?;
+                    }
+                }
+                kept.insert(key);
+            }
+            for (name, location) in stored.keys().filter(|k| !kept.contains(*k)) {
+                let params = [Some(name.as_str()), Some(location.as_str())];
+                connection.execute(
+                    "DELETE FROM binaries WHERE name = ? AND location = ?",
+                    &params,
+                )?;
+                connection.execute("DELETE FROM tags WHERE name = ? AND location = ?", &params)?;
+            }
+            connection.execute("DELETE FROM aliases", &[])?;
+            for (alias, name) in &registry.aliases {
+                connection.execute(
+                    "INSERT INTO aliases (alias, name) VALUES (?, ?)",
+                    &[Some(alias), Some(name)],
+                )?;
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => connection.execute("COMMIT", &[]),
+            Err(e) => {
+                let _ = connection.execute("ROLLBACK", &[]);
+                Err(e)
+            }
+        }
+    }
+
+    fn lock(&self) -> Result<RegistryLock> {
+        crate::store::flock(&self.path.with_extension("lock"))
+    }
+}
+
+/// The path to the SQLite registry database, `~/.hoist/registry.db`.
+pub fn database_path(ctx: &HoistContext) -> PathBuf {
+    ctx.hoist_dir().join("registry.db")
+}
+
+/// Opens the context's SQLite registry database, importing the TOML registry
+/// file when the database is first created.
+pub fn open(ctx: &HoistContext) -> Result<SqliteStore> {
+    let path = database_path(ctx);
+    let created = !path.exists();
+    std::fs::create_dir_all(ctx.hoist_dir())?;
+    let store = SqliteStore::open(&path)?;
+    let toml = TomlStore::new(ctx.registry_path());
+    if created && toml.path().exists() {
+        let registry = toml.load()?;
+        tracing::info!(
+            "Importing {} binaries from {}",
+            registry.binaries.len(),
+            toml.path().display()
+        );
+        store.save(&registry)?;
+    }
+    Ok(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_sqlite_store() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary {
+            profile: Some("release".to_string()),
+            tags: vec!["infra".to_string()],
+            ..HoistedBinary::new("a", PathBuf::from("/p/target/release/a"))
+        });
+        registry.insert(HoistedBinary::new("b", PathBuf::from("/p/target/debug/b")));
+        registry.aliases.insert("c".to_string(), "a".to_string());
+        std::fs::create_dir_all(ctx.hoist_dir()).unwrap();
+        TomlStore::new(ctx.registry_path()).save(&registry).unwrap();
+
+        let store = open(&ctx).unwrap();
+        assert_eq!(store.load().unwrap(), registry);
+        let tagged = Filter {
+            tag: Some("infra".to_string()),
+            ..Default::default()
+        };
+        let selected = store.select(&tagged).unwrap();
+        assert_eq!(selected.binaries.len(), 1);
+        assert_eq!(selected.aliases, registry.aliases);
+        assert_eq!(store.select(&Filter::name("b")).unwrap().binaries.len(), 1);
+
+        let ctx = ctx.with_store(Arc::new(store));
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.binaries.retain(|b| b.name != "b");
+        registry.save(&ctx).unwrap();
+        let reopened = SqliteStore::open(database_path(&ctx)).unwrap();
+        assert_eq!(reopened.load().unwrap(), registry);
+        assert!(reopened
+            .select(&Filter::name("b"))
+            .unwrap()
+            .binaries
+            .is_empty());
+    }
+}
@@ -0,0 +1,577 @@
+//! Store
+//!
+//! Pluggable persistence for the [HoistRegistry].
+//!
+//! [HoistRegistry::load] and [HoistRegistry::save] go through the
+//! [RegistryStore] of the [crate::context::HoistContext], which defaults to
+//! the `~/.hoist/registry.toml` file. Library users can back the registry
+//! with their own store, or a [MemoryStore] in tests.
+
+use anyhow::Result;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::binaries::HoistedBinary;
+use crate::registry::HoistRegistry;
+
+/// Narrows the entries loaded by [RegistryStore::select].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// Only binaries with this name
+    pub name: Option<String>,
+    /// Only builds with this cargo profile, e.g. `release`
+    pub profile: Option<String>,
+    /// Only binaries with this tag
+    pub tag: Option<String>,
+}
+
+impl Filter {
+    /// A filter selecting the binaries named `name`.
+    pub fn name(name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns if the binary passes the filter.
+    pub fn matches(&self, binary: &HoistedBinary) -> bool {
+        self.name.as_ref().is_none_or(|n| &binary.name == n)
+            && self
+                .profile
+                .as_ref()
+                .is_none_or(|p| binary.profile.as_ref() == Some(p))
+            && self.tag.as_ref().is_none_or(|t| binary.has_tag(t))
+    }
+}
+
+/// Persists a [HoistRegistry].
+pub trait RegistryStore: std::fmt::Debug + Send + Sync {
+    /// Loads the registry.
+    fn load(&self) -> Result<HoistRegistry>;
+
+    /// Loads the registry entries passing `filter`, along with every alias.
+    ///
+    /// Stores with indexes can override this to avoid loading the whole registry.
+    fn select(&self, filter: &Filter) -> Result<HoistRegistry> {
+        let mut registry = self.load()?;
+        registry.binaries.retain(|b| filter.matches(b));
+        Ok(registry)
+    }
+
+    /// Replaces the stored registry.
+    fn save(&self, registry: &HoistRegistry) -> Result<()>;
+
+    /// Takes an exclusive lock on the registry, held until the returned
+    /// guard is dropped, blocking until any other holder releases it.
+    fn lock(&self) -> Result<RegistryLock>;
+
+    /// Creates an empty registry if none is stored yet.
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Salvages what it can of a registry that failed to load, setting the
+    /// corrupt original aside. Returns [None] if the store can't recover.
+    fn recover(&self) -> Result<Option<Recovery>> {
+        Ok(None)
+    }
+}
+
+/// A registry salvaged by [RegistryStore::recover].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovery {
+    /// The salvaged registry, now stored in place of the corrupt one
+    pub registry: HoistRegistry,
+    /// Where the corrupt registry was moved
+    pub quarantined: PathBuf,
+    /// The parts of the corrupt registry that were dropped
+    pub dropped: Vec<crate::migrate::Dropped>,
+}
+
+/// A held [RegistryStore::lock], released when dropped.
+#[must_use = "the registry is unlocked when the guard is dropped"]
+pub struct RegistryLock(#[allow(dead_code)] Box<dyn Send>);
+
+impl RegistryLock {
+    /// Wraps a guard that releases the lock when dropped.
+    pub fn new(guard: impl Send + 'static) -> Self {
+        Self(Box::new(guard))
+    }
+}
+
+impl std::fmt::Debug for RegistryLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RegistryLock")
+    }
+}
+
+/// Takes an exclusive advisory `flock` on the file at `path`, creating it if
+/// needed. Closing the file when the guard drops releases the lock.
+pub fn flock(path: &Path) -> Result<RegistryLock> {
+    use std::os::unix::io::AsRawFd;
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    // SAFETY: the descriptor is owned by `file`, which outlives the call.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(RegistryLock::new(file))
+}
+
+/// The default store, a TOML file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TomlStore {
+    path: PathBuf,
+    root: Option<PathBuf>,
+    home: Option<PathBuf>,
+}
+
+impl TomlStore {
+    /// Creates a store over the TOML file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            root: None,
+            home: None,
+        }
+    }
+
+    /// Stores paths under `home` as `~/...`, expanded against it again on
+    /// load, so the file works under another user's home directory.
+    pub fn with_home(self, home: impl Into<PathBuf>) -> Self {
+        Self {
+            home: Some(home.into()),
+            ..self
+        }
+    }
+
+    /// Stores paths under `root` relative to it, so the file can be
+    /// committed along with the project at `root`.
+    pub fn relative_to(self, root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: Some(root.into()),
+            ..self
+        }
+    }
+
+    /// The registry file path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The lock file guarding the registry file, `registry.lock` next to it.
+    pub fn lock_path(&self) -> PathBuf {
+        self.path.with_extension("lock")
+    }
+}
+
+impl TomlStore {
+    /// The path a corrupt registry file is moved to, `registry.toml.corrupt`.
+    pub fn quarantine_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".corrupt");
+        self.path.with_file_name(name)
+    }
+
+    /// Resolves the paths of a registry as read from the file.
+    fn expand_paths(&self, mut registry: HoistRegistry) -> HoistRegistry {
+        if let Some(home) = &self.home {
+            registry = crate::export::localize(&registry, home);
+        }
+        match &self.root {
+            Some(root) => crate::export::map_paths(&registry, |p| match p == Path::new(".") {
+                true => root.clone(),
+                false => root.join(p),
+            }),
+            None => registry,
+        }
+    }
+}
+
+impl RegistryStore for TomlStore {
+    fn load(&self) -> Result<HoistRegistry> {
+        let registry_toml = std::fs::read_to_string(&self.path)?;
+        Ok(self.expand_paths(crate::migrate::parse(&registry_toml)?))
+    }
+
+    /// Writes the registry to a temporary file and renames it over the
+    /// registry file, so readers never see a partially written registry.
+    fn save(&self, registry: &HoistRegistry) -> Result<()> {
+        let portable;
+        let registry = match &self.home {
+            Some(home) => {
+                portable = crate::export::portable(registry, home);
+                &portable
+            }
+            None => registry,
+        };
+        let toml = match &self.root {
+            Some(root) => toml::to_string(&crate::export::map_paths(registry, |p| {
+                match p.strip_prefix(root) {
+                    Ok(rest) if rest.as_os_str().is_empty() => PathBuf::from("."),
+                    Ok(rest) => rest.to_path_buf(),
+                    Err(_) => p.to_path_buf(),
+                }
+            }))?,
+            None => toml::to_string(registry)?,
+        };
+        let tmp = self
+            .path
+            .with_extension(format!("toml.{}.tmp", std::process::id()));
+        let mut f = std::fs::File::create(&tmp)?;
+        f.write_all(toml.as_bytes())?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<RegistryLock> {
+        flock(&self.lock_path())
+    }
+
+    fn init(&self) -> Result<()> {
+        if !self.path.exists() {
+            self.save(&HoistRegistry::default())?;
+        }
+        Ok(())
+    }
+
+    /// Salvages the valid entries of the registry file with
+    /// [crate::migrate::salvage], moving the file to
+    /// [TomlStore::quarantine_path] and saving the salvaged registry.
+    fn recover(&self) -> Result<Option<Recovery>> {
+        let registry_toml = std::fs::read_to_string(&self.path)?;
+        let (registry, dropped) = crate::migrate::salvage(&registry_toml)?;
+        let registry = self.expand_paths(registry);
+        let quarantined = self.quarantine_path();
+        std::fs::rename(&self.path, &quarantined)?;
+        self.save(&registry)?;
+        Ok(Some(Recovery {
+            registry,
+            quarantined,
+            dropped,
+        }))
+    }
+}
+
+/// A store keeping each binary in its own small TOML file in a directory,
+/// `~/.hoist/registry.d/` by default, with the aliases in `aliases.toml`.
+///
+/// Registries synced between machines through git or syncthing merge
+/// cleanly in this layout, where a single registry file would conflict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirStore {
+    dir: PathBuf,
+    home: Option<PathBuf>,
+}
+
+/// The file in a [DirStore] directory holding the registry version and aliases.
+const ALIASES_FILE: &str = "aliases.toml";
+
+impl DirStore {
+    /// Creates a store over the directory at `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            home: None,
+        }
+    }
+
+    /// Stores paths under `home` as `~/...`, as [TomlStore::with_home] does.
+    pub fn with_home(self, home: impl Into<PathBuf>) -> Self {
+        Self {
+            home: Some(home.into()),
+            ..self
+        }
+    }
+
+    /// Opens the context's `registry.d` directory, importing the TOML
+    /// registry into it when the directory is created.
+    pub fn open(ctx: &crate::context::HoistContext) -> Result<Self> {
+        let store = Self::new(ctx.hoist_dir().join("registry.d"));
+        let toml = TomlStore::new(ctx.registry_path());
+        if !store.dir.exists() && toml.path().exists() {
+            let registry = toml.load()?;
+            tracing::info!(
+                "Importing {} binaries from {}",
+                registry.binaries.len(),
+                toml.path().display()
+            );
+            store.save(&registry)?;
+        }
+        Ok(store)
+    }
+
+    /// The registry directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The file holding `binary`, named after it and a digest of its
+    /// location, so the same entry keeps the same file on every save.
+    pub fn entry_path(&self, binary: &HoistedBinary) -> PathBuf {
+        let digest = crate::hash::hash_bytes(
+            binary.location.to_string_lossy().as_bytes(),
+            crate::hash::HashAlgorithm::Sha256,
+        );
+        let name = binary.name.replace(['/', '\\'], "_");
+        self.dir.join(format!("{}-{:.12}.toml", name, digest))
+    }
+
+    /// The entry files in the directory.
+    fn entry_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "toml")
+                && path.file_name().is_some_and(|n| n != ALIASES_FILE)
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Writes `contents` to `path` through a temporary file, unless it
+    /// already holds them.
+    fn write(path: &Path, contents: &str) -> Result<()> {
+        if std::fs::read_to_string(path).is_ok_and(|c| c == contents) {
+            return Ok(());
+        }
+        let tmp = path.with_extension(format!("toml.{}.tmp", std::process::id()));
+        let mut f = std::fs::File::create(&tmp)?;
+        f.write_all(contents.as_bytes())?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl RegistryStore for DirStore {
+    /// Assembles the registry from the entry files, migrating it as a
+    /// whole, as is done for a single registry file.
+    fn load(&self) -> Result<HoistRegistry> {
+        let mut table = match std::fs::read_to_string(self.dir.join(ALIASES_FILE)) {
+            Ok(aliases) => toml::from_str(&aliases)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && self.dir.is_dir() => {
+                toml::Table::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let binaries = self
+            .entry_paths()?
+            .iter()
+            .map(|path| {
+                let entry = std::fs::read_to_string(path)?;
+                toml::from_str(&entry)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+            })
+            .collect::<Result<Vec<toml::Value>>>()?;
+        if !binaries.is_empty() {
+            table.insert("binaries".to_string(), toml::Value::Array(binaries));
+        }
+        let registry = crate::migrate::from_table(table)?;
+        Ok(match &self.home {
+            Some(home) => crate::export::localize(&registry, home),
+            None => registry,
+        })
+    }
+
+    /// Writes the changed entries and removes those no longer registered.
+    /// Each file is replaced atomically, but a save is not atomic as a whole.
+    fn save(&self, registry: &HoistRegistry) -> Result<()> {
+        let portable;
+        let registry = match &self.home {
+            Some(home) => {
+                portable = crate::export::portable(registry, home);
+                &portable
+            }
+            None => registry,
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        let mut kept = std::collections::HashSet::new();
+        for binary in &registry.binaries {
+            let path = self.entry_path(binary);
+            Self::write(&path, &toml::to_string(binary)?)?;
+            kept.insert(path);
+        }
+        for path in self.entry_paths()? {
+            if !kept.contains(&path) {
+                std::fs::remove_file(path)?;
+            }
+        }
+        let aliases = HoistRegistry {
+            binaries: Default::default(),
+            ..registry.clone()
+        };
+        Self::write(&self.dir.join(ALIASES_FILE), &toml::to_string(&aliases)?)
+    }
+
+    /// Locks `registry.lock` next to the directory, the lock file of the
+    /// TOML registry.
+    fn lock(&self) -> Result<RegistryLock> {
+        flock(&self.dir.with_extension("lock"))
+    }
+
+    fn init(&self) -> Result<()> {
+        if !self.dir.exists() {
+            self.save(&HoistRegistry::default())?;
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory store, for tests and embedding hoist without touching disk.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    registry: Mutex<HoistRegistry>,
+    locked: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl MemoryStore {
+    /// Creates a store holding `registry`.
+    pub fn new(registry: HoistRegistry) -> Self {
+        Self {
+            registry: Mutex::new(registry),
+            ..Default::default()
+        }
+    }
+}
+
+/// Releases a [MemoryStore] lock when dropped.
+struct MemoryLock(Arc<(Mutex<bool>, Condvar)>);
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        let (locked, released) = &*self.0;
+        *locked.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        released.notify_one();
+    }
+}
+
+impl RegistryStore for MemoryStore {
+    fn load(&self) -> Result<HoistRegistry> {
+        Ok(self
+            .registry
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn save(&self, registry: &HoistRegistry) -> Result<()> {
+        *self.registry.lock().unwrap_or_else(|e| e.into_inner()) = registry.clone();
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<RegistryLock> {
+        let (locked, released) = &*self.locked;
+        let mut held = locked.lock().unwrap_or_else(|e| e.into_inner());
+        while *held {
+            held = released.wait(held).unwrap_or_else(|e| e.into_inner());
+        }
+        *held = true;
+        Ok(RegistryLock::new(MemoryLock(Arc::clone(&self.locked))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::HoistContext;
+
+    #[test]
+    fn test_memory_store() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MemoryStore::default());
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path()).with_store(store.clone());
+
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        registry.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        registry.save(&ctx).unwrap();
+        assert_eq!(store.load().unwrap().binaries.len(), 1);
+        assert!(!ctx.registry_path().exists());
+
+        let lock = store.lock().unwrap();
+        let waiter = {
+            let store = store.clone();
+            std::thread::spawn(move || store.lock().map(|_| ()))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+        drop(lock);
+        waiter.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_toml_store() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let store = TomlStore::new(tempdir.path().join("registry.toml"));
+        assert!(store.load().is_err());
+        store.init().unwrap();
+        assert_eq!(store.load().unwrap(), HoistRegistry::default());
+
+        let lock = store.lock().unwrap();
+        assert!(store.lock_path().exists());
+        drop(lock);
+        let _relocked = store.lock().unwrap();
+
+        let root = tempdir.path().join("project");
+        let local = TomlStore::new(tempdir.path().join("local.toml")).relative_to(&root);
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary::new("a", root.join("bin/a")));
+        registry.insert(HoistedBinary::new("b", PathBuf::from("/opt/b")));
+        local.save(&registry).unwrap();
+        let toml = std::fs::read_to_string(tempdir.path().join("local.toml")).unwrap();
+        assert!(toml.contains("location = \"bin/a\""));
+        assert!(toml.contains("location = \"/opt/b\""));
+        assert_eq!(local.load().unwrap(), registry);
+
+        let home = tempdir.path().join("home");
+        let portable = TomlStore::new(tempdir.path().join("portable.toml")).with_home(&home);
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary::new("a", home.join("p/a")));
+        portable.save(&registry).unwrap();
+        let toml = std::fs::read_to_string(portable.path()).unwrap();
+        assert!(toml.contains("location = \"~/p/a\""));
+        assert_eq!(portable.load().unwrap(), registry);
+    }
+
+    #[test]
+    fn test_dir_store() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path());
+        std::fs::create_dir_all(ctx.hoist_dir()).unwrap();
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary::new("a", PathBuf::from("/p/a")));
+        TomlStore::new(ctx.registry_path()).save(&registry).unwrap();
+
+        let store = DirStore::open(&ctx).unwrap();
+        assert_eq!(store.load().unwrap(), registry);
+        registry.insert(HoistedBinary::new("b", PathBuf::from("/p/b")));
+        registry.aliases.insert("x".to_string(), "b".to_string());
+        store.save(&registry).unwrap();
+        let a = store.entry_path(&HoistedBinary::new("a", PathBuf::from("/p/a")));
+        assert!(a.exists());
+        assert_eq!(store.entry_paths().unwrap().len(), 2);
+        assert_eq!(store.load().unwrap(), registry);
+
+        registry
+            .binaries
+            .remove(&HoistedBinary::new("a", PathBuf::from("/p/a")));
+        store.save(&registry).unwrap();
+        assert!(!a.exists());
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded, registry);
+        assert_eq!(loaded.aliases.get("x").map(String::as_str), Some("b"));
+        let _lock = store.lock().unwrap();
+        assert!(DirStore::new(tempdir.path().join("missing"))
+            .load()
+            .is_err());
+    }
+}
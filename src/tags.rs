@@ -0,0 +1,169 @@
+//! Tags
+//!
+//! Tags and pins, which are assigned per binary name, to every registered
+//! build of it.
+
+use anyhow::Result;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::context::HoistContext;
+use crate::registry::HoistRegistry;
+
+impl HoistRegistry {
+    /// Adds tags to every registered build of a binary.
+    #[instrument(skip(ctx))]
+    pub fn tag(ctx: &HoistContext, binary: &str, tags: Vec<String>, quiet: bool) -> Result<()> {
+        HoistRegistry::retag(ctx, binary, quiet, |b| {
+            for tag in &tags {
+                if !b.has_tag(tag) {
+                    b.tags.push(tag.clone());
+                }
+            }
+        })
+    }
+
+    /// Removes tags from every registered build of a binary.
+    #[instrument(skip(ctx))]
+    pub fn untag(ctx: &HoistContext, binary: &str, tags: Vec<String>, quiet: bool) -> Result<()> {
+        HoistRegistry::retag(ctx, binary, quiet, |b| b.tags.retain(|t| !tags.contains(t)))
+    }
+
+    /// Updates the tags of every registered build of a binary and prints them.
+    fn retag(
+        ctx: &HoistContext,
+        binary: &str,
+        quiet: bool,
+        update: impl Fn(&mut HoistedBinary),
+    ) -> Result<()> {
+        let builds = HoistRegistry::update_builds(ctx, binary, quiet, |b| {
+            update(b);
+            b.tags.sort();
+        })?;
+        if !quiet {
+            HoistRegistry::print_color(&format!("{}: ", binary), Color::Blue, false)?;
+            HoistRegistry::print_color(&builds[0].tags.join(", "), Color::Cyan, true)?;
+        }
+        Ok(())
+    }
+
+    /// Pins every registered build of a binary, so `prune`, `gc` and `nuke`
+    /// keep them.
+    #[instrument(skip(ctx))]
+    pub fn pin(ctx: &HoistContext, binary: &str, quiet: bool) -> Result<()> {
+        HoistRegistry::update_builds(ctx, binary, quiet, |b| b.pinned = true)?;
+        if !quiet {
+            HoistRegistry::print_color("Pinned ", Color::Green, false)?;
+            HoistRegistry::print_color(binary, Color::Magenta, true)?;
+        }
+        Ok(())
+    }
+
+    /// Unpins every registered build of a binary.
+    #[instrument(skip(ctx))]
+    pub fn unpin(ctx: &HoistContext, binary: &str, quiet: bool) -> Result<()> {
+        HoistRegistry::update_builds(ctx, binary, quiet, |b| b.pinned = false)?;
+        if !quiet {
+            HoistRegistry::print_color("Unpinned ", Color::Green, false)?;
+            HoistRegistry::print_color(binary, Color::Magenta, true)?;
+        }
+        Ok(())
+    }
+
+    /// Applies `update` to every registered build of a binary, returning the
+    /// updated builds.
+    fn update_builds(
+        ctx: &HoistContext,
+        binary: &str,
+        quiet: bool,
+        update: impl Fn(&mut HoistedBinary),
+    ) -> Result<Vec<HoistedBinary>> {
+        HoistRegistry::setup_with(ctx, quiet)?;
+        let _lock = HoistRegistry::lock(ctx)?;
+        let mut registry = HoistRegistry::load(ctx)?;
+        let mut builds: Vec<_> = registry
+            .binaries
+            .iter()
+            .filter(|b| b.name == binary)
+            .cloned()
+            .collect();
+        if builds.is_empty() {
+            anyhow::bail!("Failed to find binary {} in hoist registry", binary);
+        }
+        for b in &mut builds {
+            update(b);
+            registry.binaries.replace(b.clone());
+        }
+        registry.save(ctx)?;
+        Ok(builds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hoist::HoistOptions;
+    use crate::install::InstallOptions;
+    use crate::registry::tests::setup_test;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_tags() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let test_tempdir = setup_test(&tempdir, "test_tags");
+        let ctx = HoistContext::new(&test_tempdir, &test_tempdir);
+
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        let tags = vec!["tooling".to_string(), "infra".to_string()];
+        HoistRegistry::tag(&ctx, "binary1", tags, false).unwrap();
+        assert!(HoistRegistry::tag(&ctx, "missing", vec!["x".to_string()], false).is_err());
+
+        // Tags survive re-registration and carry over to new builds of the binary.
+        HoistRegistry::install_with(
+            &ctx,
+            Some(&test_tempdir),
+            Vec::new(),
+            InstallOptions::default(),
+            false,
+        )
+        .unwrap();
+        let mut registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(
+            registry.latest("binary1").unwrap().tags,
+            ["infra", "tooling"]
+        );
+        registry.insert(HoistedBinary::new(
+            "binary1",
+            PathBuf::from("/other/binary1"),
+        ));
+        assert!(registry
+            .binaries
+            .iter()
+            .all(|b| b.name != "binary1" || b.has_tag("infra")));
+
+        let tagged = HoistOptions {
+            tag: Some("tooling".to_string()),
+            ..Default::default()
+        };
+        HoistRegistry::hoist_with(&ctx, vec![], tagged, false).unwrap();
+        assert!(test_tempdir.join("binary1").exists());
+        assert!(!test_tempdir.join("binary2").exists());
+
+        HoistRegistry::untag(&ctx, "binary1", vec!["tooling".to_string()], false).unwrap();
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        assert_eq!(registry.latest("binary1").unwrap().tags, ["infra"]);
+        let tagged = HoistOptions {
+            tag: Some("tooling".to_string()),
+            ..Default::default()
+        };
+        assert!(HoistRegistry::hoist_with(&ctx, vec![], tagged, false).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Time
+//!
+//! Lightweight timestamp helpers.
+
+use anyhow::Result;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Formats a [SystemTime] as an RFC 3339 UTC timestamp, e.g. `2023-10-14T09:30:00Z`.
+pub fn to_rfc3339(t: SystemTime) -> String {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    let (y, m, d) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        y,
+        m,
+        d,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// The current time, in seconds since the unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Returns the [SystemTime] `secs` seconds after the unix epoch.
+pub fn from_unix(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// Parses a duration made of `<number><unit>` parts, e.g. `90m` or `1d12h`.
+///
+/// Units are `s`, `m`, `h`, `d` and `w`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let mut secs = 0u64;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        anyhow::bail!("empty duration");
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            anyhow::bail!("invalid duration {:?}, expected e.g. 24h or 7d", s);
+        }
+        let n: u64 = rest[..digits].parse()?;
+        let unit = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 3600,
+            Some('d') => 86_400,
+            Some('w') => 604_800,
+            _ => anyhow::bail!(
+                "invalid duration {:?}, expected a unit of s, m, h, d or w",
+                s
+            ),
+        };
+        secs = secs.saturating_add(n.saturating_mul(unit));
+        rest = &rest[digits + 1..];
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Parses a UTC `YYYY-MM-DD` date or `YYYY-MM-DDTHH:MM:SSZ` timestamp.
+pub fn parse_date(s: &str) -> Result<SystemTime> {
+    let invalid = || anyhow::anyhow!("invalid date {:?}, expected YYYY-MM-DD", s);
+    let (date, time) = s.trim().split_once('T').unwrap_or((s.trim(), ""));
+    let mut parts = date.splitn(3, '-');
+    let mut next = || -> Result<i64> { Ok(parts.next().ok_or_else(invalid)?.parse()?) };
+    let (y, m, d) = (next()?, next()?, next()?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return Err(invalid());
+    }
+    let mut secs = days_from_civil(y, m as u32, d as u32) * 86_400;
+    if !time.is_empty() {
+        let time = time.strip_suffix('Z').ok_or_else(invalid)?;
+        let mut parts = time.splitn(3, ':');
+        for unit in [3600, 60, 1] {
+            secs += parts.next().ok_or_else(invalid)?.parse::<i64>()? * unit;
+        }
+    }
+    let secs = u64::try_from(secs).map_err(|_| invalid())?;
+    Ok(from_unix(secs))
+}
+
+/// Parses a `--changed-since` style bound, either a duration before `now`
+/// such as `24h`, or a date.
+pub fn parse_since_at(s: &str, now: SystemTime) -> Result<SystemTime> {
+    match s.contains('-') {
+        true => parse_date(s),
+        false => Ok(now.checked_sub(parse_duration(s)?).unwrap_or(UNIX_EPOCH)),
+    }
+}
+
+/// Parses a duration before now, such as `24h`, or a date.
+pub fn parse_since(s: &str) -> Result<SystemTime> {
+    parse_since_at(s, SystemTime::now())
+}
+
+/// Converts a civil date into days since the unix epoch.
+///
+/// See Howard Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let m = m as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Converts days since the unix epoch into a `(year, month, day)` civil date.
+///
+/// See Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rfc3339() {
+        assert_eq!(to_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(to_rfc3339(from_unix(951_782_400)), "2000-02-29T00:00:00Z");
+        assert_eq!(to_rfc3339(from_unix(1_697_275_800)), "2023-10-14T09:30:00Z");
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_duration("90m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(
+            parse_duration("1d12h").unwrap(),
+            Duration::from_secs(129_600)
+        );
+        assert!(parse_duration("24").is_err());
+        assert!(parse_duration("h").is_err());
+        assert_eq!(parse_date("2000-02-29").unwrap(), from_unix(951_782_400));
+        assert_eq!(
+            parse_date("2023-10-14T09:30:00Z").unwrap(),
+            from_unix(1_697_275_800)
+        );
+        assert!(parse_date("2023-13-01").is_err());
+        let now = from_unix(1_000_000);
+        assert_eq!(parse_since_at("1w", now).unwrap(), from_unix(395_200));
+        assert_eq!(
+            parse_since_at("1970-01-02", now).unwrap(),
+            from_unix(86_400)
+        );
+    }
+}
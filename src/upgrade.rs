@@ -0,0 +1,441 @@
+//! Upgrade
+//!
+//! Rebuilds registered binaries whose source project changed since they were built.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use termcolor::Color;
+use tracing::instrument;
+
+use crate::binaries::{ArtifactKind, HoistedBinary, Registration, RegistrationSource};
+use crate::context::HoistContext;
+use crate::hash::HashAlgorithm;
+use crate::project::Project;
+use crate::registry::HoistRegistry;
+
+/// A set of stale binaries rebuilt by a single `cargo build` invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildGroup {
+    /// The project root to build in
+    pub project: PathBuf,
+    /// The build profile, e.g. `debug` or `release`
+    pub profile: String,
+    /// The target triple, for cross-compiled builds
+    pub triple: Option<String>,
+    /// The binaries to rebuild
+    pub binaries: Vec<HoistedBinary>,
+}
+
+impl BuildGroup {
+    /// The `cargo build` arguments that rebuild every binary in the group.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = vec!["build".to_string()];
+        match self.profile.as_str() {
+            "debug" => {}
+            "release" => args.push("--release".to_string()),
+            p => args.extend(["--profile".to_string(), p.to_string()]),
+        }
+        if let Some(triple) = &self.triple {
+            args.extend(["--target".to_string(), triple.clone()]);
+        }
+        for b in &self.binaries {
+            let flag = match b.kind {
+                ArtifactKind::Bin => "--bin",
+                ArtifactKind::Test => "--test",
+                ArtifactKind::Bench => "--bench",
+                ArtifactKind::Example => "--example",
+            };
+            args.extend([flag.to_string(), b.name.clone()]);
+        }
+        args
+    }
+
+    /// The path `cargo build` writes the group's build of `binary` to, in the
+    /// target directory of the project as cargo resolves it in the context.
+    ///
+    /// Test and bench executables keep the file name they were built with, as
+    /// cargo reuses the hash suffix for the same target and profile.
+    pub fn output(&self, ctx: &HoistContext, binary: &HoistedBinary) -> PathBuf {
+        let mut dir = Project::new(ctx, &self.project).target_dir();
+        if let Some(triple) = &self.triple {
+            dir.push(triple);
+        }
+        dir.push(&self.profile);
+        match binary.kind {
+            ArtifactKind::Bin => dir.join(&binary.name),
+            ArtifactKind::Example => dir.join("examples").join(&binary.name),
+            ArtifactKind::Test | ArtifactKind::Bench => {
+                let file = binary.built_path().file_name();
+                dir.join("deps").join(file.unwrap_or(binary.name.as_ref()))
+            }
+        }
+    }
+
+    /// Runs `cargo build` for the group, returning cargo's stderr on failure.
+    #[instrument(skip(self))]
+    pub fn build(&self) -> Result<()> {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let output = std::process::Command::new(cargo)
+            .args(self.cargo_args())
+            .current_dir(&self.project)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the profile and optional target triple a binary was built with,
+/// read from the `target/[<triple>/]<profile>/` path it was built at.
+pub fn build_config(binary: &HoistedBinary) -> Option<(String, Option<String>)> {
+    let mut dir = binary.built_path().parent()?;
+    if matches!(binary.kind, ArtifactKind::Test | ArtifactKind::Bench)
+        || dir.file_name().is_some_and(|n| n == "examples")
+    {
+        dir = dir.parent()?;
+    }
+    let profile = dir.file_name()?.to_str()?.to_string();
+    let parent = dir.parent()?;
+    let triple = match parent.file_name()?.to_str()? {
+        "target" => None,
+        t => Some(t.to_string()),
+    };
+    Some((profile, triple))
+}
+
+/// The modification time and sha256 digest of the file at `path`, if it
+/// exists.
+fn fingerprint(path: &Path) -> Option<(SystemTime, String)> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let digest = crate::hash::hash_file(path, HashAlgorithm::Sha256).ok()?;
+    Some((modified, digest))
+}
+
+/// Runs `build` for the group, returning the fresh output of each of its
+/// binaries, in order.
+///
+/// Fails if an output is missing or left unchanged by the build, e.g. when
+/// cargo wrote it somewhere else, so a stale build is never re-registered as
+/// an upgrade.
+fn rebuild(
+    ctx: &HoistContext,
+    group: &BuildGroup,
+    build: impl Fn(&BuildGroup) -> Result<()>,
+) -> Result<Vec<PathBuf>> {
+    let outputs: Vec<_> = group
+        .binaries
+        .iter()
+        .map(|b| group.output(ctx, b))
+        .collect();
+    let before: Vec<_> = outputs.iter().map(|p| fingerprint(p)).collect();
+    build(group)?;
+    for (path, before) in outputs.iter().zip(before) {
+        match fingerprint(path) {
+            None => anyhow::bail!("cargo build did not produce {}", path.display()),
+            Some(after) if Some(&after) == before.as_ref() => {
+                anyhow::bail!("cargo build left {} unchanged", path.display())
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(outputs)
+}
+
+/// Returns the most recent modification time of the project's sources,
+/// skipping `target` and hidden directories.
+pub fn newest_source(root: &Path) -> Option<SystemTime> {
+    let mut newest = None;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if name != "target" && !name.to_string_lossy().starts_with('.') {
+                    pending.push(entry.path());
+                }
+                continue;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            newest = newest.max(modified);
+        }
+    }
+    newest
+}
+
+/// Returns if the binary is missing or older than its project's sources.
+pub fn is_stale(binary: &HoistedBinary) -> bool {
+    let Some(project) = binary.project() else {
+        return false;
+    };
+    let built = std::fs::metadata(binary.built_path())
+        .and_then(|m| m.modified())
+        .ok();
+    match (built, newest_source(&project)) {
+        (None, _) => true,
+        (Some(built), Some(source)) => source > built,
+        (Some(_), None) => false,
+    }
+}
+
+/// Groups stale binaries by project, profile and target triple so each
+/// group is rebuilt with one cargo invocation.
+pub fn stale_groups<'a>(binaries: impl IntoIterator<Item = &'a HoistedBinary>) -> Vec<BuildGroup> {
+    let mut groups: Vec<BuildGroup> = vec![];
+    for b in binaries.into_iter().filter(|b| is_stale(b)) {
+        let (Some(project), Some((profile, triple))) = (b.project(), build_config(b)) else {
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|g| g.project == project && g.profile == profile && g.triple == triple)
+        {
+            Some(g) => g.binaries.push(b.clone()),
+            None => groups.push(BuildGroup {
+                project,
+                profile,
+                triple,
+                binaries: vec![b.clone()],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| (&a.project, &a.profile).cmp(&(&b.project, &b.profile)));
+    groups
+}
+
+/// Rebuilds and re-registers stale registered binaries.
+///
+/// Rebuilds the named `binaries`, or every registered binary when `all` is
+/// set. Independent projects are built in parallel, bounded by `--jobs`.
+#[instrument(skip(binaries))]
+pub fn upgrade(ctx: &HoistContext, binaries: Vec<String>, all: bool, quiet: bool) -> Result<()> {
+    upgrade_with(ctx, binaries, all, quiet, BuildGroup::build)
+}
+
+/// Upgrades stale binaries as [upgrade] does, rebuilding each group with
+/// `build`.
+fn upgrade_with(
+    ctx: &HoistContext,
+    binaries: Vec<String>,
+    all: bool,
+    quiet: bool,
+    build: impl Fn(&BuildGroup) -> Result<()> + Sync,
+) -> Result<()> {
+    if binaries.is_empty() && !all {
+        anyhow::bail!("Provide binaries to upgrade, or pass --all");
+    }
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    // Previous builds are history, only the current build of each is rebuilt.
+    registry.trim_history(0);
+    let candidates = registry
+        .binaries
+        .iter()
+        .filter(|b| all || binaries.contains(&b.name));
+    let groups = stale_groups(candidates);
+    let total: usize = groups.iter().map(|g| g.binaries.len()).sum();
+    let checked = registry
+        .binaries
+        .iter()
+        .filter(|b| all || binaries.contains(&b.name))
+        .count();
+
+    let results = crate::parallel::try_map(groups, |g| {
+        let res = rebuild(ctx, &g, &build);
+        Ok((g, res))
+    })?;
+
+    // Builds can take minutes, so only lock the registry to merge the results
+    // into a fresh copy of it.
+    let _lock = HoistRegistry::lock(ctx)?;
+    let mut registry = HoistRegistry::load(ctx)?;
+    let mut failed = 0;
+    for (group, res) in &results {
+        match res {
+            Ok(outputs) => {
+                let rustc = crate::fingerprint::rustc_version(&group.project);
+                let registration =
+                    Registration::snapshot(ctx, RegistrationSource::Manual, group.project.clone());
+                for (b, output) in group.binaries.iter().zip(outputs) {
+                    let stored = b.origin.is_some();
+                    let mut rebuilt = HoistedBinary {
+                        location: output.clone(),
+                        origin: None,
+                        registration: Some(registration.clone()),
+                        ..b.clone()
+                    };
+                    rebuilt.record_compilation(rustc.as_deref());
+                    // Copy the fresh build next to the stored copy of the old one.
+                    if stored {
+                        crate::content::store(ctx, &mut rebuilt)?;
+                    }
+                    rebuilt.record_size();
+                    registry.insert(rebuilt);
+                    if !quiet {
+                        HoistRegistry::print_color("Upgraded ", Color::Green, false)?;
+                        HoistRegistry::print_color(&b.name, Color::Magenta, false)?;
+                        HoistRegistry::print_color(
+                            &format!(" ({})", output.display()),
+                            Color::Cyan,
+                            true,
+                        )?;
+                    }
+                }
+            }
+            Err(e) => {
+                failed += group.binaries.len();
+                if !quiet {
+                    let names: Vec<_> = group.binaries.iter().map(|b| b.name.as_str()).collect();
+                    HoistRegistry::print_color(
+                        &format!(
+                            "Failed to rebuild {} in {}: {}",
+                            names.join(", "),
+                            group.project.display(),
+                            e
+                        ),
+                        Color::Red,
+                        true,
+                    )?;
+                }
+            }
+        }
+    }
+    registry.apply_retention(ctx)?;
+    registry.save(ctx)?;
+
+    if !quiet {
+        HoistRegistry::print_color(
+            &format!(
+                "{} upgraded, {} failed, {} up to date",
+                total - failed,
+                failed,
+                checked - total
+            ),
+            Color::White,
+            true,
+        )?;
+    }
+    match failed {
+        0 => Ok(()),
+        n => anyhow::bail!("{} binaries failed to rebuild", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::InstallOptions;
+
+    #[test]
+    fn test_upgrade() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        let ctx = HoistContext::new(root, root);
+        HoistRegistry::create_registry_with(&ctx, true).unwrap();
+        std::fs::write(ctx.hook_identifier(), "").unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        let target = root.join("target/debug/demo");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::write(&target, "old").unwrap();
+        std::fs::set_permissions(&target, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let old = SystemTime::now() - std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&target)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        HoistRegistry::install_with(&ctx, Some(root), vec![], InstallOptions::default(), true)
+            .unwrap();
+
+        assert!(upgrade(&ctx, vec![], false, true).is_err());
+        let noop = |_: &BuildGroup| Ok(());
+        assert!(upgrade_with(&ctx, vec![], true, true, noop).is_err());
+        let build = |g: &BuildGroup| {
+            assert_eq!(g.cargo_args(), vec!["build", "--bin", "demo"]);
+            for b in &g.binaries {
+                std::fs::write(g.output(&ctx, b), "new")?;
+            }
+            Ok(())
+        };
+        upgrade_with(&ctx, vec![], true, true, build).unwrap();
+        let built = std::fs::metadata(&target).unwrap().modified().unwrap();
+        assert!(built > old);
+        assert!(!is_stale(&HoistedBinary::new("demo", target.clone())));
+        let registry = HoistRegistry::load(&ctx).unwrap();
+        let b = registry.latest("demo").unwrap();
+        assert_eq!(std::fs::read_to_string(&b.location).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_build_config() {
+        let b = HoistedBinary::new("a", PathBuf::from("/p/target/release/a"));
+        assert_eq!(build_config(&b), Some(("release".to_string(), None)));
+        let b = HoistedBinary::new(
+            "a",
+            PathBuf::from("/p/target/x86_64-unknown-linux-musl/dist/examples/a"),
+        );
+        assert_eq!(
+            build_config(&b),
+            Some((
+                "dist".to_string(),
+                Some("x86_64-unknown-linux-musl".to_string())
+            ))
+        );
+        let b = HoistedBinary {
+            kind: ArtifactKind::Test,
+            ..HoistedBinary::new(
+                "it",
+                PathBuf::from("/p/target/debug/deps/it-0123456789abcdef"),
+            )
+        };
+        assert_eq!(build_config(&b), Some(("debug".to_string(), None)));
+    }
+
+    #[test]
+    fn test_stale_groups() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target/release")).unwrap();
+        std::fs::write(root.join("target/release/fresh"), "").unwrap();
+        let old = SystemTime::now() - std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(root.join("target/release/fresh"))
+            .unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+        std::fs::write(root.join("target/release/stale"), "").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(root.join("target/release/stale"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let bins = [
+            HoistedBinary::new("fresh", root.join("target/release/fresh")),
+            HoistedBinary::new("stale", root.join("target/release/stale")),
+            HoistedBinary::new("missing", root.join("target/release/missing")),
+        ];
+        let groups = stale_groups(&bins);
+        assert_eq!(groups.len(), 1);
+        let names: Vec<_> = groups[0].binaries.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["stale", "missing"]);
+        assert_eq!(
+            groups[0].cargo_args(),
+            vec!["build", "--release", "--bin", "stale", "--bin", "missing"]
+        );
+    }
+}
@@ -1,5 +1,7 @@
-//! Utilities for working with [Vec] and [std::collections::HashSet].
+//! Utilities for working with [Vec] and [std::collections::HashSet], for
+//! reading lists from stdin, and for formatting values for display.
 use std::hash::Hash;
+use std::io::BufRead;
 
 /// Helper function to merge two optional string vectors and dedup any duplicate entries.
 pub fn merge_and_dedup_vecs<T: Eq + Hash + Clone + Ord>(
@@ -17,3 +19,125 @@ pub fn merge_and_dedup_vecs<T: Eq + Hash + Clone + Ord>(
     merged.dedup();
     merged
 }
+
+/// Reads newline-separated entries, trimming whitespace and skipping blank lines.
+pub fn read_lines(reader: impl BufRead) -> std::io::Result<Vec<String>> {
+    let mut lines = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    Ok(lines)
+}
+
+/// Splits `-` entries out of `values`, reading the lines of `stdin` in their
+/// place, or whenever `read` is set.
+///
+/// Returns the remaining values and the lines read, or [None] if stdin was not read.
+pub fn split_stdin(
+    values: Vec<String>,
+    read: bool,
+    stdin: impl BufRead,
+) -> std::io::Result<(Vec<String>, Option<Vec<String>>)> {
+    let dash = values.iter().any(|v| v == "-");
+    let values = values.into_iter().filter(|v| v != "-").collect();
+    match read || dash {
+        true => Ok((values, Some(read_lines(stdin)?))),
+        false => Ok((values, None)),
+    }
+}
+
+/// Matches `text` against a glob `pattern`, where `*` matches any run of
+/// characters and `?` any single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<_>, Vec<_>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` and the text it was matched up to.
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns if `s` is a glob pattern for [glob_match], containing a `*` or `?`.
+pub fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Formats a byte count for display using binary units, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_dedup_vecs() {
+        let merged = merge_and_dedup_vecs(Some(vec!["b", "a"]), Some(vec!["a", "c"]));
+        assert_eq!(merged, vec!["a", "b", "c"]);
+        assert!(merge_and_dedup_vecs::<String>(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_split_stdin() {
+        let input = "  a\n\nb/c \n".as_bytes();
+        let values = vec!["x".to_string(), "-".to_string()];
+        let (values, lines) = split_stdin(values, false, input).unwrap();
+        assert_eq!(values, vec!["x"]);
+        assert_eq!(lines, Some(vec!["a".to_string(), "b/c".to_string()]));
+        let (_, lines) = split_stdin(vec![], false, input).unwrap();
+        assert_eq!(lines, None);
+        let (_, lines) = split_stdin(vec![], true, "".as_bytes()).unwrap();
+        assert_eq!(lines, Some(vec![]));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("cargo-*", "cargo-hoist"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("b?n*y", "binary"));
+        assert!(glob_match("*a*a", "banana"));
+        assert!(!glob_match("cargo-*", "hoist"));
+        assert!(!glob_match("bin", "binary"));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -0,0 +1,449 @@
+//! Web
+//!
+//! A local web dashboard over the registry, for browsing and searching
+//! registered binaries and hoisting or removing them without the CLI.
+//!
+//! Listing and searching go through the same [HoistRegistry] queries as
+//! `cargo hoist list` and `cargo hoist search --all-fields`.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+use crate::binaries::HoistedBinary;
+use crate::config::HoistConfig;
+use crate::context::HoistContext;
+use crate::hoist::{HoistOptions, OverwritePolicy};
+use crate::http::{encode, Request, Response};
+use crate::registry::HoistRegistry;
+
+/// The default address the dashboard listens on.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:7879";
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem auto;max-width:64rem;padding:0 1rem}\
+table{border-collapse:collapse;width:100%}td,th{border-bottom:1px solid #ddd;padding:.4rem;text-align:left;vertical-align:top}\
+code{word-break:break-all}pre{background:#f6f6f6;padding:.5rem;white-space:pre-wrap}\
+form{display:inline}.message{background:#eef6ee;padding:.5rem}";
+
+/// Escapes text for html element content and attribute values.
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns if `url` is safe to link to, i.e. an `http` or `https` url.
+fn is_web_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Generates a random token, hex encoded, to embed in the dashboard's forms.
+fn csrf_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The local web dashboard.
+#[derive(Debug)]
+pub struct Dashboard {
+    ctx: HoistContext,
+    addr: SocketAddr,
+    csrf: String,
+}
+
+impl Dashboard {
+    /// Creates a dashboard over the registry in the context's home, served
+    /// on the loopback address `addr`. Binaries are hoisted into the
+    /// context's working directory by default.
+    pub fn new(ctx: HoistContext, addr: SocketAddr) -> Result<Self> {
+        Ok(Self {
+            ctx,
+            addr,
+            csrf: csrf_token()?,
+        })
+    }
+
+    /// Returns if the request was sent to the dashboard's own address, so
+    /// that pages of other sites can't reach it by rebinding their DNS.
+    fn is_own_host(&self, host: &str) -> bool {
+        host == self.addr.to_string() || host == format!("localhost:{}", self.addr.port())
+    }
+
+    /// Handles a request, mapping failures to a `500` response.
+    #[instrument(skip(self, request), fields(method = %request.method, path = %request.path))]
+    pub fn handle(&self, request: &Request) -> Response {
+        let host = request.header("host").unwrap_or_default();
+        if !self.is_own_host(host) {
+            return Response::error(403);
+        }
+        // Forms only post from the dashboard itself, with the token of the
+        // page, so reject cross-site posts.
+        if request.method == "POST" {
+            let origin = request.header("origin");
+            let form = request.form();
+            let token = form.get("csrf").map(String::as_str).unwrap_or_default();
+            if origin != Some(format!("http://{}", host).as_str())
+                || !crate::http::secrets_match(token, &self.csrf)
+            {
+                return Response::error(403);
+            }
+        }
+        self.route(request).unwrap_or_else(|e| {
+            #[cfg(feature = "server")]
+            crate::metrics::METRICS.record_error();
+            tracing::warn!("Failed to serve {}: {:?}", request.path, e);
+            Response::text(500, format!("{}\n", e))
+        })
+    }
+
+    fn route(&self, request: &Request) -> Result<Response> {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") => {
+                let query = request.query.get("q").map(String::as_str).unwrap_or("");
+                let message = request.query.get("message").map(String::as_str);
+                Ok(Response::html(self.index(query, message)?))
+            }
+            ("POST", "/hoist") => self.hoist(&request.form()),
+            ("POST", "/remove") => self.remove(&request.form()),
+            (_, "/" | "/hoist" | "/remove") => Ok(Response::error(405)),
+            _ => Ok(Response::error(404)),
+        }
+    }
+
+    /// Renders the registry listing, filtered by the search query when given.
+    pub fn index(&self, query: &str, message: Option<&str>) -> Result<String> {
+        let registry = HoistRegistry::load(&self.ctx)?;
+        let binaries = match query.trim() {
+            "" => {
+                let mut all: Vec<_> = registry.binaries.iter().collect();
+                all.sort_by(|a, b| (&a.name, &a.location).cmp(&(&b.name, &b.location)));
+                all
+            }
+            q => registry.search(q),
+        };
+
+        let mut html = format!(
+            "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>cargo-hoist</title>\
+             <style>{}</style></head><body>\n<h1>cargo-hoist</h1>\n",
+            STYLE
+        );
+        if let Some(message) = message {
+            html.push_str(&format!("<p class=\"message\">{}</p>\n", escape(message)));
+        }
+        html.push_str(&format!(
+            "<form method=\"get\" action=\"/\"><input name=\"q\" value=\"{}\" placeholder=\"Search\" autofocus> \
+             <button>Search</button></form>\n",
+            escape(query)
+        ));
+        html.push_str(&format!(
+            "<p>{} of {} registered binaries</p>\n",
+            binaries.len(),
+            registry.binaries.len()
+        ));
+        html.push_str(
+            "<table>\n<tr><th>Name</th><th>Location</th><th>License</th><th>Actions</th></tr>\n",
+        );
+        let targets = self.targets()?;
+        for b in binaries {
+            html.push_str(&self.row(&registry, b, &targets));
+        }
+        html.push_str("</table>\n</body></html>\n");
+        Ok(html)
+    }
+
+    fn row(&self, registry: &HoistRegistry, b: &HoistedBinary, targets: &[PathBuf]) -> String {
+        let hidden = format!(
+            "<input type=\"hidden\" name=\"csrf\" value=\"{}\">\
+             <input type=\"hidden\" name=\"name\" value=\"{}\">\
+             <input type=\"hidden\" name=\"location\" value=\"{}\">",
+            escape(&self.csrf),
+            escape(&b.name),
+            escape(&b.location.display().to_string())
+        );
+        let mut details = String::new();
+        if let Some(description) = &b.description {
+            details.push_str(&format!("<p>{}</p>", escape(description)));
+        }
+        match b.repository.as_ref().or(b.homepage.as_ref()) {
+            Some(url) if is_web_url(url) => details.push_str(&format!(
+                "<p><a href=\"{}\" rel=\"noreferrer\">{}</a></p>",
+                escape(url),
+                escape(url)
+            )),
+            Some(url) => details.push_str(&format!("<p>{}</p>", escape(url))),
+            None => {}
+        }
+        if let Some(explanation) = registry.explain(&b.name) {
+            details.push_str(&format!("<pre>{}</pre>", escape(&explanation)));
+        }
+        format!(
+            "<tr><td>{}</td><td><code>{}</code><details><summary>Details</summary>{}</details></td>\
+             <td>{}</td><td>\
+             <form method=\"post\" action=\"/hoist\">{}<select name=\"dir\">{}</select> \
+             <label><input type=\"checkbox\" name=\"replace\"> Replace</label> \
+             <button>Hoist</button></form> \
+             <form method=\"post\" action=\"/remove\">{}<button>Remove</button></form></td></tr>\n",
+            escape(&b.name),
+            escape(&b.location.display().to_string()),
+            details,
+            escape(b.license.as_deref().unwrap_or("")),
+            hidden,
+            targets
+                .iter()
+                .map(|t| {
+                    let t = escape(&t.display().to_string());
+                    format!("<option value=\"{}\">{}</option>", t, t)
+                })
+                .collect::<String>(),
+            hidden
+        )
+    }
+
+    /// The directories binaries can be hoisted into from the dashboard: the
+    /// working directory and the global bin directory of the user config.
+    fn targets(&self) -> Result<Vec<PathBuf>> {
+        let mut targets = vec![self.ctx.cwd.clone()];
+        let global = HoistConfig::load(&self.ctx)?.global_bin(&self.ctx);
+        if global != self.ctx.cwd {
+            targets.push(global);
+        }
+        Ok(targets)
+    }
+
+    /// Finds the registered binary named by the form's `name` and `location`.
+    fn entry(registry: &HoistRegistry, form: &HashMap<String, String>) -> Option<HoistedBinary> {
+        let name = form.get("name")?;
+        let location = Path::new(form.get("location")?);
+        registry
+            .binaries
+            .iter()
+            .find(|b| &b.name == name && b.location == location)
+            .cloned()
+    }
+
+    fn hoist(&self, form: &HashMap<String, String>) -> Result<Response> {
+        let registry = HoistRegistry::load(&self.ctx)?;
+        let Some(binary) = Dashboard::entry(&registry, form) else {
+            return Ok(Response::error(404));
+        };
+        let dir = match form.get("dir") {
+            Some(d) => PathBuf::from(d),
+            None => self.ctx.cwd.clone(),
+        };
+        if !self.targets()?.contains(&dir) {
+            return Ok(Response::text(
+                400,
+                format!("{} is not a hoist target\n", dir.display()),
+            ));
+        }
+        let overwrite = match form.contains_key("replace") {
+            true => OverwritePolicy::Force,
+            false => OverwritePolicy::NoClobber,
+        };
+        let clobbered = overwrite == OverwritePolicy::NoClobber
+            && binary
+                .change_summary(&dir)
+                .ok()
+                .flatten()
+                .is_some_and(|s| !s.is_unchanged());
+        let options = HoistOptions {
+            location: Some(binary.location.clone()),
+            to: Some(dir.clone()),
+            latest: true,
+            overwrite,
+            ..Default::default()
+        };
+        let message =
+            match HoistRegistry::hoist_with(&self.ctx, vec![binary.name.clone()], options, true) {
+                Ok(()) if clobbered => format!(
+                    "Skipped {}, a different file exists in {}",
+                    binary.name,
+                    dir.display()
+                ),
+                Ok(()) => format!("Hoisted {} into {}", binary.name, dir.display()),
+                Err(e) => {
+                    #[cfg(feature = "server")]
+                    crate::metrics::METRICS.record_error();
+                    format!("Failed to hoist {}: {}", binary.name, e)
+                }
+            };
+        Ok(Response::redirect(format!(
+            "/?message={}",
+            encode(&message)
+        )))
+    }
+
+    fn remove(&self, form: &HashMap<String, String>) -> Result<Response> {
+        let _lock = HoistRegistry::lock(&self.ctx)?;
+        let mut registry = HoistRegistry::load(&self.ctx)?;
+        let Some(binary) = Dashboard::entry(&registry, form) else {
+            return Ok(Response::error(404));
+        };
+        registry.binaries.remove(&binary);
+        registry.save(&self.ctx)?;
+        let message = format!("Removed {} ({})", binary.name, binary.location.display());
+        Ok(Response::redirect(format!(
+            "/?message={}",
+            encode(&message)
+        )))
+    }
+}
+
+/// Serves the dashboard on the loopback address `addr` until the process
+/// exits.
+#[instrument(skip(ctx))]
+pub fn serve(ctx: &HoistContext, addr: String, quiet: bool) -> Result<()> {
+    let Some(addr) = addr.to_socket_addrs()?.next() else {
+        anyhow::bail!("{} does not resolve to an address", addr);
+    };
+    if !addr.ip().is_loopback() {
+        anyhow::bail!(
+            "The dashboard only listens on loopback addresses, not {}",
+            addr
+        );
+    }
+    HoistRegistry::setup_with(ctx, quiet)?;
+    let listener = TcpListener::bind(addr)?;
+    let dashboard = Dashboard::new(ctx.clone(), listener.local_addr()?)?;
+    if !quiet {
+        println!("Serving the hoist dashboard on http://{}", dashboard.addr);
+    }
+    crate::http::serve_listener(
+        listener,
+        |_| crate::http::BodyLimit::Buffered(crate::http::MAX_BODY),
+        move |request| dashboard.handle(request),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "127.0.0.1:7879";
+
+    fn get(target: &str, host: &str) -> Request {
+        let mut request = Request::new("GET", target);
+        request.headers.insert("host".to_string(), host.to_string());
+        request
+    }
+
+    fn post(path: &str, form: &str, origin: Option<&str>) -> Request {
+        let mut request = Request::new("POST", path);
+        request.headers.insert("host".to_string(), ADDR.to_string());
+        if let Some(o) = origin {
+            request.headers.insert("origin".to_string(), o.to_string());
+        }
+        request.body = form.as_bytes().to_vec();
+        request
+    }
+
+    #[test]
+    fn test_dashboard() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let ctx = HoistContext::new(tempdir.path(), tempdir.path().join("work"));
+        std::fs::create_dir_all(&ctx.cwd).unwrap();
+        let location = tempdir.path().join("target/release/<tool>");
+        std::fs::create_dir_all(location.parent().unwrap()).unwrap();
+        std::fs::write(&location, "build").unwrap();
+        let mut registry = HoistRegistry::default();
+        registry.insert(HoistedBinary {
+            license: Some("MIT".to_string()),
+            repository: Some("javascript:alert(1)".to_string()),
+            ..HoistedBinary::new("<tool>", location.clone())
+        });
+        registry.insert(HoistedBinary {
+            homepage: Some("https://example.com".to_string()),
+            ..HoistedBinary::new("other", PathBuf::from("/p/other"))
+        });
+        HoistRegistry::create_dir_with(&ctx, true).unwrap();
+        registry.save(&ctx).unwrap();
+        std::fs::write(ctx.hook_identifier(), "").unwrap();
+        let dashboard = Dashboard::new(ctx.clone(), ADDR.parse().unwrap()).unwrap();
+
+        assert_eq!(dashboard.handle(&get("/", "evil.example")).status, 403);
+        assert_eq!(dashboard.handle(&get("/", "localhost:7879")).status, 200);
+        let index = dashboard.handle(&get("/?q=tool", ADDR));
+        let html = String::from_utf8(index.body).unwrap();
+        assert!(html.contains("1 of 2 registered binaries"));
+        assert!(html.contains("<td>&lt;tool&gt;</td>"));
+        assert!(!html.contains("<tool>"));
+        assert!(html.contains(&dashboard.csrf));
+        assert!(html.contains("<p>javascript:alert(1)</p>"));
+        assert!(!html.contains("href=\"javascript:"));
+        let index = dashboard.handle(&get("/?q=other", ADDR));
+        let html = String::from_utf8(index.body).unwrap();
+        assert!(html.contains("<a href=\"https://example.com\""));
+
+        let form = |dir: &Path, csrf: &str| {
+            format!(
+                "csrf={}&name={}&location={}&dir={}",
+                csrf,
+                encode("<tool>"),
+                encode(&location.display().to_string()),
+                encode(&dir.display().to_string())
+            )
+        };
+        let origin = Some("http://127.0.0.1:7879");
+        let valid = form(&ctx.cwd, &dashboard.csrf);
+        let outside = form(&tempdir.path().join("out"), &dashboard.csrf);
+        assert_eq!(
+            dashboard.handle(&post("/hoist", &outside, origin)).status,
+            400
+        );
+        let on_path = std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())
+            .find(|d| d.is_dir())
+            .map(|d| form(&d, &dashboard.csrf));
+        if let Some(on_path) = on_path {
+            assert_eq!(
+                dashboard.handle(&post("/hoist", &on_path, origin)).status,
+                400
+            );
+        }
+        let hoisted = dashboard.handle(&post("/hoist", &valid, origin));
+        assert_eq!(hoisted.status, 303);
+        assert_eq!(std::fs::read(ctx.cwd.join("<tool>")).unwrap(), b"build");
+
+        std::fs::write(ctx.cwd.join("<tool>"), "mine").unwrap();
+        dashboard.handle(&post("/hoist", &valid, origin));
+        assert_eq!(std::fs::read(ctx.cwd.join("<tool>")).unwrap(), b"mine");
+        let replace = format!("{}&replace=on", valid);
+        dashboard.handle(&post("/hoist", &replace, origin));
+        assert_eq!(std::fs::read(ctx.cwd.join("<tool>")).unwrap(), b"build");
+
+        let forged = form(&ctx.cwd, "0");
+        assert_eq!(
+            dashboard.handle(&post("/remove", &forged, origin)).status,
+            403
+        );
+        assert_eq!(
+            dashboard
+                .handle(&post("/remove", &valid, Some("http://evil.example")))
+                .status,
+            403
+        );
+        assert_eq!(dashboard.handle(&post("/remove", &valid, None)).status, 403);
+        assert_eq!(
+            dashboard.handle(&post("/remove", &valid, origin)).status,
+            303
+        );
+        assert_eq!(HoistRegistry::load(&ctx).unwrap().binaries.len(), 1);
+        assert_eq!(
+            dashboard.handle(&post("/remove", &valid, origin)).status,
+            404
+        );
+    }
+}